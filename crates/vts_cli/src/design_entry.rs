@@ -1,10 +1,18 @@
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
 use std::path::{Path, PathBuf};
 
 use clap::Subcommand;
 use thiserror::Error;
 
 use vts_abc::{Abc, BlifLutMapper};
-use vts_core::interchange::yosys::Design;
+use vts_core::interchange::yosys::{flatten, Design};
+use vts_core::interchange::{aiger, blif, rtlil};
+use vts_core::ir::export;
+use vts_core::ir::graph::{self, Graph, NodeKind};
+use vts_core::ir::lutmap::{self, LutMapConfig, LutMapMode};
+use vts_core::ir::ops::Lut;
+use vts_core::ir::visualize::{self, dot, graphml, VisualizeConfig};
 use vts_yosys::{Command as YosysCmd, FileFormat, Yosys};
 
 const GITHUB_REPO_ISSUES: &str = "https://github.com/rikushoney/vts/issues";
@@ -15,16 +23,40 @@ pub(super) enum Error {
     FileNotFound(PathBuf),
     #[error("unknown input file format")]
     UnknownFileFormat,
+    #[error("unsupported output file format")]
+    UnsupportedOutputFormat,
+    #[error("LUTs of {0} inputs are not supported")]
+    UnsupportedLutSize(usize),
+    #[error(r#"no node named "{0}""#)]
+    UnknownNode(String),
     #[error(transparent)]
     Abc(#[from] vts_abc::Error),
     #[error(transparent)]
+    Aiger(#[from] aiger::Error),
+    #[error(transparent)]
+    Blif(#[from] blif::Error),
+    #[error(transparent)]
+    Export(#[from] export::Error),
+    #[error(transparent)]
+    Flatten(#[from] flatten::Error),
+    #[error(transparent)]
+    Graph(#[from] graph::YosysError),
+    #[error(transparent)]
     IO(#[from] std::io::Error),
+    #[error(transparent)]
+    Json(#[from] serde_json::Error),
+    #[error(transparent)]
+    LutMap(#[from] lutmap::Error),
+    #[error(transparent)]
+    Levels(#[from] graph::Error),
     #[error(
         "piping requires Abc and Yosys to support reading from memory (see {}/2)",
         GITHUB_REPO_ISSUES
     )]
     RequiresAbcYosysReadString,
     #[error(transparent)]
+    Rtlil(#[from] rtlil::Error),
+    #[error(transparent)]
     Yosys(#[from] vts_yosys::Error),
     #[error(transparent)]
     YosysNetlist(#[from] vts_core::interchange::yosys::Error),
@@ -44,6 +76,25 @@ pub(super) enum Command {
         output_filename: Option<PathBuf>,
         #[arg(short = 'k', default_value_t = 4)]
         k_lut: usize,
+        /// Map for depth only, without recovering area.
+        #[arg(long)]
+        depth: bool,
+    },
+    /// Export a design as a Graphviz DOT or GraphML graph.
+    View {
+        input_filename: PathBuf,
+        /// The output file, GraphML if it ends in `.graphml` and DOT otherwise.
+        #[arg(short = 'o')]
+        output_filename: Option<PathBuf>,
+        /// Highlight the node of this name.
+        #[arg(long, value_name = "NAME")]
+        highlight: Vec<String>,
+        /// Highlight a critical path.
+        #[arg(long)]
+        critical_path: bool,
+        /// Highlight the splitters.
+        #[arg(long)]
+        splitters: bool,
     },
 }
 
@@ -55,9 +106,9 @@ fn check_file_is_not_pipe(filename: &Path) -> Result<()> {
     }
 }
 
-fn check_file_exists_and_guess_format(filename: &PathBuf) -> Result<FileFormat> {
+fn check_file_exists_and_guess_format(filename: &Path) -> Result<FileFormat> {
     if !filename.exists() {
-        return Err(Error::FileNotFound(filename.clone()));
+        return Err(Error::FileNotFound(filename.to_path_buf()));
     }
     FileFormat::guess(filename).map_err(|_| Error::UnknownFileFormat)
 }
@@ -65,9 +116,20 @@ fn check_file_exists_and_guess_format(filename: &PathBuf) -> Result<FileFormat>
 fn check(input_filename: &PathBuf) -> Result<()> {
     check_file_is_not_pipe(input_filename)?;
     let input_format = check_file_exists_and_guess_format(input_filename)?;
-    if input_format == FileFormat::Json {
-        let _design = Design::from_file(input_filename)?;
-        return Ok(());
+    match input_format {
+        FileFormat::Aiger => {
+            let _graph = aiger::from_file(input_filename)?;
+            return Ok(());
+        }
+        FileFormat::Json => {
+            let _design = Design::from_file(input_filename)?;
+            return Ok(());
+        }
+        FileFormat::Rtlil => {
+            let _design = rtlil::from_file(input_filename)?;
+            return Ok(());
+        }
+        _ => {}
     }
     let yosys = Yosys::new()?;
     let mut cmd = YosysCmd::new();
@@ -81,7 +143,7 @@ fn check(input_filename: &PathBuf) -> Result<()> {
         FileFormat::Blif => {
             cmd.read_blif(input_filename);
         }
-        FileFormat::Json => {
+        FileFormat::Aiger | FileFormat::Json | FileFormat::Rtlil => {
             // NOTE: Handled above to prevent unnecessary `Yosys` instance
             // creation.
             unreachable!()
@@ -91,19 +153,170 @@ fn check(input_filename: &PathBuf) -> Result<()> {
     Ok(())
 }
 
-fn lutmap(input_filename: &PathBuf, output_filename: &PathBuf, k_lut: usize) -> Result<()> {
+/// Read a design from JSON or RTLIL, or through Yosys from Verilog.
+fn read_design(input_filename: &Path, input_format: FileFormat) -> Result<Design> {
+    let mut cmd = YosysCmd::new();
+    match input_format {
+        FileFormat::Json => return Ok(Design::from_file(input_filename)?),
+        FileFormat::Rtlil => return Ok(rtlil::from_file(input_filename)?),
+        FileFormat::Verilog => {
+            cmd.read_verilog(input_filename);
+        }
+        FileFormat::SV => {
+            cmd.read_sv(input_filename);
+        }
+        FileFormat::Aiger | FileFormat::Blif => {
+            // NOTE: AIGER and BLIF are read into a graph directly.
+            unreachable!()
+        }
+    }
+    // NOTE: Yosys can only write the design to a file, which is removed when
+    // `json_file` is dropped.
+    let json_file = tempfile::Builder::new()
+        .prefix("vts-")
+        .suffix(".json")
+        .tempfile()?;
+    let yosys = Yosys::new()?;
+    // NOTE: `techmap` lowers the cells that cannot be blasted into gates, and
+    // word-level flip-flops into single-bit ones.
+    cmd.proc()
+        .flatten()
+        .opt()
+        .techmap()
+        .opt()
+        .write_json(json_file.path())
+        .execute(&yosys)?;
+    Ok(Design::from_file(json_file.path())?)
+}
+
+/// A writer to `output_filename`, or to stdout for `-`.
+fn create_writer(output_filename: &Path) -> Result<BufWriter<Box<dyn Write>>> {
+    let writer: Box<dyn Write> = if matches!(output_filename.to_str(), Some("-")) {
+        Box::new(io::stdout().lock())
+    } else {
+        Box::new(File::create(output_filename)?)
+    };
+    Ok(BufWriter::new(writer))
+}
+
+/// Read the graph of the top module of a design in any supported format,
+/// with its name.
+fn read_graph(input_filename: &Path) -> Result<(String, Graph)> {
     check_file_is_not_pipe(input_filename)?;
-    check_file_is_not_pipe(output_filename)?;
+    let stem = || {
+        input_filename
+            .file_stem()
+            .map_or("top".into(), |stem| stem.to_string_lossy().to_string())
+    };
     let input_format = check_file_exists_and_guess_format(input_filename)?;
     match input_format {
-        FileFormat::Blif => {
-            let abc = Abc::new()?;
-            BlifLutMapper::new(input_filename, k_lut).run(&abc, output_filename)?;
-        }
-        _ => {
-            todo!()
+        FileFormat::Aiger => return Ok((stem(), aiger::from_file(input_filename)?)),
+        FileFormat::Blif => return Ok((stem(), blif::from_file(input_filename)?)),
+        _ => {}
+    }
+    let design = read_design(input_filename, input_format)?;
+    let top = design.top_module()?.to_string();
+    let graph = Graph::try_from(design.flatten(Some(top.as_str()))?)?;
+    Ok((top, graph))
+}
+
+fn write_graph(graph: &Graph, name: &str, output_filename: &Path) -> Result<()> {
+    let output_format = match output_filename.to_str() {
+        Some("-") => FileFormat::Json,
+        _ => FileFormat::guess(output_filename).map_err(|_| Error::UnsupportedOutputFormat)?,
+    };
+    let mut writer = create_writer(output_filename)?;
+    match output_format {
+        FileFormat::Json => {
+            serde_json::to_writer_pretty(&mut writer, &export::to_design(graph, name)?)?;
+            writeln!(writer)?;
         }
+        FileFormat::Blif => blif::write(graph, name, &mut writer)?,
+        _ => return Err(Error::UnsupportedOutputFormat),
+    }
+    writer.flush()?;
+    Ok(())
+}
+
+fn lutmap(
+    input_filename: &PathBuf,
+    output_filename: &Path,
+    k_lut: usize,
+    depth: bool,
+) -> Result<()> {
+    check_file_is_not_pipe(input_filename)?;
+    // NOTE: Other formats are mapped natively.
+    if FileFormat::guess(input_filename).ok() == Some(FileFormat::Blif) {
+        check_file_exists_and_guess_format(input_filename)?;
+        check_file_is_not_pipe(output_filename)?;
+        let abc = Abc::new()?;
+        BlifLutMapper::new(input_filename, k_lut).run(&abc, output_filename)?;
+        return Ok(());
+    }
+    if !(2..=Lut::MAX_INPUTS).contains(&k_lut) {
+        return Err(Error::UnsupportedLutSize(k_lut));
+    }
+    let (top, mut graph) = read_graph(input_filename)?;
+    let mode = if depth {
+        LutMapMode::Depth
+    } else {
+        LutMapMode::Area
+    };
+    let stats = lutmap::map_luts(
+        &mut graph,
+        &LutMapConfig {
+            mode,
+            ..LutMapConfig::new(k_lut)
+        },
+    )?;
+    tracing::info!(
+        "mapped {top} to {} LUTs of depth {}",
+        stats.luts,
+        stats.depth
+    );
+    write_graph(&graph, &top, output_filename)
+}
+
+fn view(
+    input_filename: &Path,
+    output_filename: &Path,
+    highlight: &[String],
+    critical_path: bool,
+    splitters: bool,
+) -> Result<()> {
+    let (name, graph) = read_graph(input_filename)?;
+    let mut config = VisualizeConfig::default();
+    for node in highlight {
+        let node = graph
+            .find(node)
+            .ok_or_else(|| Error::UnknownNode(node.clone()))?;
+        config.highlight.insert(node);
     }
+    if critical_path {
+        config = config.with_highlight(visualize::critical_path(&graph)?);
+    }
+    if splitters {
+        config = config.with_highlight(
+            graph
+                .node_ids()
+                .filter(|node| matches!(graph.node(*node).kind, NodeKind::Splitter)),
+        );
+    }
+    let graphml = match output_filename
+        .extension()
+        .and_then(|extension| extension.to_str())
+    {
+        Some("graphml") => true,
+        Some("dot" | "gv") | None => false,
+        Some(_) => return Err(Error::UnsupportedOutputFormat),
+    };
+    let mut writer = create_writer(output_filename)?;
+    if graphml {
+        graphml::write(&graph, &name, &config, &mut writer)?;
+    } else {
+        dot::write(&graph, &name, &config, &mut writer)?;
+    }
+    writer.flush()?;
     Ok(())
 }
 
@@ -112,6 +325,7 @@ impl Command {
         match self {
             Self::Check { .. } => "check",
             Self::LutMap { .. } => "lutmap",
+            Self::View { .. } => "view",
         }
     }
 
@@ -122,11 +336,68 @@ impl Command {
                 input_filename,
                 output_filename,
                 k_lut,
+                depth,
             } => lutmap(
                 input_filename,
                 output_filename.as_ref().unwrap_or(&PathBuf::from("-")),
                 *k_lut,
+                *depth,
+            ),
+            Self::View {
+                input_filename,
+                output_filename,
+                highlight,
+                critical_path,
+                splitters,
+            } => view(
+                input_filename,
+                output_filename.as_ref().unwrap_or(&PathBuf::from("-")),
+                highlight,
+                *critical_path,
+                *splitters,
             ),
         }
     }
 }
+
+#[cfg(test)]
+#[serial_test::serial]
+mod tests {
+    use vts_core::ir::sim;
+
+    use super::*;
+
+    fn example(filename: &str) -> PathBuf {
+        Path::new(env!("CARGO_MANIFEST_DIR"))
+            .join("../../examples")
+            .join(filename)
+    }
+
+    #[test]
+    fn test_read_graph_sv() {
+        let (top, graph) = read_graph(&example("sr/sr.sv")).unwrap();
+        assert_eq!(top, "sr");
+        let flip_flops = graph
+            .nodes()
+            .filter(|node| matches!(node.kind, NodeKind::Seq(_)))
+            .count();
+        assert_eq!(flip_flops, 4);
+        let (_, simplemap) = read_graph(&example("sr/sr_simplemap.json")).unwrap();
+        let config = sim::CompareConfig::default();
+        assert_eq!(sim::compare(&graph, &simplemap, &config).unwrap(), None);
+    }
+
+    #[test]
+    fn test_read_graph_aiger() {
+        let filename = example("sr/sr.aig");
+        assert_eq!(FileFormat::guess(&filename).unwrap(), FileFormat::Aiger);
+        check(&filename).unwrap();
+        let (top, graph) = read_graph(&filename).unwrap();
+        assert_eq!(top, "sr");
+        let latches = graph
+            .nodes()
+            .filter(|node| matches!(node.kind, NodeKind::Seq(_)))
+            .count();
+        assert_eq!(latches, 5);
+    }
+}
@@ -37,5 +37,9 @@ impl Command {
 }
 
 fn main() -> Result<()> {
+    // NOTE: Logs go to stderr so they don't mix with designs written to stdout.
+    tracing_subscriber::fmt()
+        .with_writer(std::io::stderr)
+        .init();
     Cli::parse().command.run()
 }
@@ -687,7 +687,7 @@ impl Concat {
             sink_last_i,
         );
 
-        for (source_port_i, part) in zip(0.., splits.into_iter()) {
+        for (source_port_i, part) in zip(0.., splits) {
             let source_pins = source.pins.clone_masked(part.source_pins);
 
             let source_component = source
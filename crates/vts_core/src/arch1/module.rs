@@ -43,7 +43,7 @@ impl Module {
         self.name = ustr(name);
     }
 
-    pub fn components(&self) -> ComponentIter {
+    pub fn components(&self) -> ComponentIter<'_> {
         ComponentIter {
             module: self,
             iter: self.components.keys(),
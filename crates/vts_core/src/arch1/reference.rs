@@ -361,6 +361,8 @@ impl<'a, 'm> Resolve<'a, 'm> for ComponentWeakRef {
             builder.set_alias(alias);
         }
 
+        builder.set_n_instances(self.n_instances);
+
         Ok(builder.finish().map_err(linker::Error::from)?.unbind())
     }
 }
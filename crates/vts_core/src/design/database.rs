@@ -1,10 +1,593 @@
+use std::iter;
+
+use fnv::FnvHashMap;
+use serde::{Deserialize, Serialize};
+use slotmap::{new_key_type, SlotMap};
+use thiserror::Error;
+use ustr::{ustr, Ustr};
+
+use crate::arch1::ComponentClass;
+use crate::device::{
+    database::{BelBucketId, BelId, Database as Device, PipId},
+    resources::PinDirection,
+};
+use crate::interchange::yosys::{
+    self,
+    constids::{internal_cells, std_cells},
+    flatten, PortDirection, SignalBit,
+};
+
+/// The cell type of the cells driving the top-level input ports.
+pub const INPAD: &str = "$__inpad";
+/// The output pin of an [INPAD].
+pub const INPAD_PIN: &str = "Y";
+/// The cell type of the cells driven by the top-level output ports.
+pub const OUTPAD: &str = "$__outpad";
+/// The input pin of an [OUTPAD].
+pub const OUTPAD_PIN: &str = "A";
+
+#[derive(Debug, Error)]
+pub enum Error {
+    #[error(transparent)]
+    Top(#[from] flatten::Error),
+    #[error(r#"port "{port}" of "{cell}" is bidirectional or has no direction"#)]
+    UnsupportedPortDirection { cell: String, port: String },
+    #[error(r#"net "{0}" has multiple drivers"#)]
+    MultipleDrivers(String),
+    #[error(r#"no bel bucket supports cell type "{ty}" of "{cell}""#)]
+    UnsupportedCellType { cell: String, ty: String },
+    #[error(r#"duplicate cell name "{0}""#)]
+    DuplicateCell(String),
+    #[error(r#"duplicate net name "{0}""#)]
+    DuplicateNet(String),
+}
+
+pub type Result<T> = std::result::Result<T, Error>;
+
+new_key_type! {
+    pub struct CellId;
+    pub struct NetId;
+}
+
+/// The cell types implemented by every [ComponentClass].
+pub const CLASS_CELL_TYPES: [(ComponentClass, &[&str]); 2] = [
+    (ComponentClass::Lut, &[internal_cells::LUT]),
+    (
+        ComponentClass::Latch,
+        &[
+            internal_cells::DFF,
+            internal_cells::FF,
+            std_cells::DFF_P,
+            std_cells::DFF_N,
+        ],
+    ),
+];
+
+/// The cell types implemented by `class`.
+pub fn class_cell_types(class: ComponentClass) -> &'static [&'static str] {
+    CLASS_CELL_TYPES
+        .iter()
+        .find_map(|&(other, cell_types)| (other == class).then_some(cell_types))
+        .unwrap_or_default()
+}
+
+/// The [ComponentClass] implementing cells of type `ty`, if any.
+pub fn cell_class(ty: &str) -> Option<ComponentClass> {
+    CLASS_CELL_TYPES
+        .iter()
+        .find_map(|&(class, cell_types)| cell_types.contains(&ty).then_some(class))
+}
+
+/// A pin of a [Cell], optionally connected to a [Net].
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct CellPin {
+    name: Ustr,
+    direction: PinDirection,
+    net: Option<NetId>,
+}
+
+impl CellPin {
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn direction(&self) -> PinDirection {
+        self.direction
+    }
+
+    pub fn net(&self) -> Option<NetId> {
+        self.net
+    }
+}
+
+/// A reference to a [CellPin] of a [Cell].
+#[derive(Clone, Copy, Debug, Deserialize, Eq, Hash, PartialEq, Serialize)]
+pub struct PinRef {
+    pub cell: CellId,
+    pub pin: Ustr,
+}
+
+/// An instance of a technology-mapped cell.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct Cell {
+    name: Ustr,
+    ty: Ustr,
+    class: Option<ComponentClass>,
+    pins: Vec<CellPin>,
+    parameters: FnvHashMap<String, String>,
+    attributes: FnvHashMap<String, String>,
+    bucket: Option<BelBucketId>,
+    bel: Option<BelId>,
+}
+
+impl Cell {
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn ty(&self) -> &str {
+        &self.ty
+    }
+
+    pub fn class(&self) -> Option<ComponentClass> {
+        self.class
+    }
+
+    pub fn pins(&self) -> &[CellPin] {
+        &self.pins
+    }
+
+    pub fn find_pin(&self, name: &str) -> Option<&CellPin> {
+        self.pins.iter().find(|pin| pin.name == name)
+    }
+
+    pub fn parameters(&self) -> &FnvHashMap<String, String> {
+        &self.parameters
+    }
+
+    pub fn attributes(&self) -> &FnvHashMap<String, String> {
+        &self.attributes
+    }
+
+    /// The [BelBucket](crate::device::resources::BelBucket) the cell is bound
+    /// to by [Database::bind_buckets].
+    pub fn bucket(&self) -> Option<BelBucketId> {
+        self.bucket
+    }
+
+    /// The [Bel](crate::device::resources::Bel) the cell is placed on.
+    pub fn bel(&self) -> Option<BelId> {
+        self.bel
+    }
+}
+
+/// A connection from a driving [CellPin] to zero or more sink [CellPin]'s.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct Net {
+    name: Ustr,
+    driver: Option<PinRef>,
+    sinks: Vec<PinRef>,
+    pips: Vec<PipId>,
+}
+
+impl Net {
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn driver(&self) -> Option<PinRef> {
+        self.driver
+    }
+
+    pub fn sinks(&self) -> &[PinRef] {
+        &self.sinks
+    }
+
+    /// The [Pip](crate::device::resources::Pip)'s used to route the net.
+    pub fn pips(&self) -> &[PipId] {
+        &self.pips
+    }
+}
+
 /// A `database` for design entry resources.
-#[derive(Clone, Debug, Default)]
-pub struct Database {}
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+pub struct Database {
+    name: Ustr,
+    cells: SlotMap<CellId, Cell>,
+    nets: SlotMap<NetId, Net>,
+    cell_names: FnvHashMap<Ustr, CellId>,
+    net_names: FnvHashMap<Ustr, NetId>,
+}
 
 impl Database {
     /// Create a new empty database.
-    pub fn new() -> Self {
-        Self::default()
+    pub fn new(name: &str) -> Self {
+        Self {
+            name: ustr(name),
+            ..Self::default()
+        }
+    }
+
+    /// Build the database from the [top module](yosys::Design::top_module) of
+    /// a Yosys [Design](yosys::Design).
+    ///
+    /// Every bit of the top-level ports becomes an [INPAD] or [OUTPAD] cell.
+    /// Cell pins are named after the cell port, with the bit index appended
+    /// for ports wider than a single bit, e.g. `A[0]`. Constant connections
+    /// are dropped. The names of pads and nets are built the same way, and
+    /// given the least suffix `_n` that makes them unique if they are taken.
+    pub fn from_yosys(design: &yosys::Design) -> Result<Self> {
+        let name = design.top_module()?;
+        let top = &design.modules[name];
+        let mut database = Self::new(name);
+        let mut net_names = FnvHashMap::<usize, String>::default();
+        let mut netnames: Vec<_> = top.netnames.iter().collect();
+        netnames.sort_by_key(|(name, netname)| (netname.hide_name, *name));
+        for (name, netname) in netnames {
+            for (i, bit) in netname.bits.iter().enumerate() {
+                if let SignalBit::Ref(bit) = bit {
+                    net_names
+                        .entry(*bit)
+                        .or_insert_with(|| pin_name(name, i, netname.bits.len()));
+                }
+            }
+        }
+        let mut nets = FnvHashMap::<usize, NetId>::default();
+        let mut net = |database: &mut Self, bit: usize| -> Result<NetId> {
+            if let Some(net) = nets.get(&bit) {
+                return Ok(*net);
+            }
+            let name = net_names.remove(&bit).unwrap_or_else(|| format!("${bit}"));
+            let name = unique_name(&name, |name| database.find_net(name).is_some());
+            let net = database.add_net(&name)?;
+            nets.insert(bit, net);
+            Ok(net)
+        };
+        let mut ports: Vec<_> = top.ports.iter().collect();
+        ports.sort_by_key(|(name, _)| *name);
+        for (name, port) in ports {
+            let (ty, pin, direction) = match port.direction {
+                PortDirection::Input => (INPAD, INPAD_PIN, PinDirection::Output),
+                PortDirection::Output => (OUTPAD, OUTPAD_PIN, PinDirection::Input),
+                PortDirection::InOut => {
+                    return Err(Error::UnsupportedPortDirection {
+                        cell: database.name.to_string(),
+                        port: name.clone(),
+                    })
+                }
+            };
+            for (i, bit) in port.bits.iter().enumerate() {
+                // NOTE: Pads are named after their port, which may also name
+                // a cell of the design.
+                let name = unique_name(&pin_name(name, i, port.bits.len()), |name| {
+                    top.cells.contains_key(name) || database.find_cell(name).is_some()
+                });
+                let cell = database.add_cell(&name, ty)?;
+                database.add_pin(cell, pin, direction);
+                let net = net(&mut database, *bit)?;
+                database.connect(cell, pin, net)?;
+            }
+        }
+        let mut cells: Vec<_> = top.cells.iter().collect();
+        cells.sort_by_key(|(name, _)| *name);
+        for (name, yosys_cell) in cells {
+            let cell = database.add_cell(name, &yosys_cell.ty)?;
+            database.cells[cell].parameters = yosys_cell.parameters.clone();
+            database.cells[cell].attributes = yosys_cell.attributes.clone();
+            let mut connections: Vec<_> = yosys_cell.connections.iter().collect();
+            connections.sort_by_key(|(port, _)| *port);
+            for (port, bits) in connections {
+                let direction = match yosys_cell.port_directions.get(port) {
+                    Some(PortDirection::Input) => PinDirection::Input,
+                    Some(PortDirection::Output) => PinDirection::Output,
+                    _ => {
+                        return Err(Error::UnsupportedPortDirection {
+                            cell: name.clone(),
+                            port: port.clone(),
+                        })
+                    }
+                };
+                for (i, bit) in bits.iter().enumerate() {
+                    let pin = pin_name(port, i, bits.len());
+                    database.add_pin(cell, &pin, direction);
+                    if let SignalBit::Ref(bit) = bit {
+                        let net = net(&mut database, *bit)?;
+                        database.connect(cell, &pin, net)?;
+                    }
+                }
+            }
+        }
+        Ok(database)
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Add a cell of type `ty`, named `name`, which must be unique.
+    pub fn add_cell(&mut self, name: &str, ty: &str) -> Result<CellId> {
+        let name = ustr(name);
+        if self.cell_names.contains_key(&name) {
+            return Err(Error::DuplicateCell(name.to_string()));
+        }
+        let id = self.cells.insert(Cell {
+            name,
+            ty: ustr(ty),
+            class: cell_class(ty),
+            pins: Vec::new(),
+            parameters: FnvHashMap::default(),
+            attributes: FnvHashMap::default(),
+            bucket: None,
+            bel: None,
+        });
+        self.cell_names.insert(name, id);
+        Ok(id)
+    }
+
+    pub fn add_pin(&mut self, cell: CellId, name: &str, direction: PinDirection) {
+        self.cells[cell].pins.push(CellPin {
+            name: ustr(name),
+            direction,
+            net: None,
+        });
+    }
+
+    /// Add a net named `name`, which must be unique.
+    pub fn add_net(&mut self, name: &str) -> Result<NetId> {
+        let name = ustr(name);
+        if self.net_names.contains_key(&name) {
+            return Err(Error::DuplicateNet(name.to_string()));
+        }
+        let id = self.nets.insert(Net {
+            name,
+            driver: None,
+            sinks: Vec::new(),
+            pips: Vec::new(),
+        });
+        self.net_names.insert(name, id);
+        Ok(id)
+    }
+
+    /// Connect `pin` of `cell` to `net`, as its driver for output pins or as a
+    /// sink for input pins.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `cell` has no pin named `pin`.
+    pub fn connect(&mut self, cell: CellId, pin: &str, net: NetId) -> Result<()> {
+        let cell_pin = self.cells[cell]
+            .pins
+            .iter_mut()
+            .find(|other| other.name == pin)
+            .expect("cell should have pin");
+        cell_pin.net = Some(net);
+        let pin = PinRef {
+            cell,
+            pin: cell_pin.name,
+        };
+        let net = &mut self.nets[net];
+        match cell_pin.direction {
+            PinDirection::Output => {
+                if net.driver.replace(pin).is_some() {
+                    return Err(Error::MultipleDrivers(net.name.to_string()));
+                }
+            }
+            PinDirection::Input => net.sinks.push(pin),
+        }
+        Ok(())
+    }
+
+    pub fn cell(&self, cell: CellId) -> &Cell {
+        &self.cells[cell]
+    }
+
+    pub fn cells(&self) -> impl Iterator<Item = (CellId, &Cell)> + '_ {
+        self.cells.iter()
+    }
+
+    pub fn find_cell(&self, name: &str) -> Option<CellId> {
+        self.cell_names.get(&ustr(name)).copied()
+    }
+
+    pub fn n_cells(&self) -> usize {
+        self.cells.len()
+    }
+
+    pub fn net(&self, net: NetId) -> &Net {
+        &self.nets[net]
+    }
+
+    pub fn nets(&self) -> impl Iterator<Item = (NetId, &Net)> + '_ {
+        self.nets.iter()
+    }
+
+    pub fn find_net(&self, name: &str) -> Option<NetId> {
+        self.net_names.get(&ustr(name)).copied()
+    }
+
+    pub fn n_nets(&self) -> usize {
+        self.nets.len()
+    }
+
+    /// Bind every cell to the [BelBucket](crate::device::resources::BelBucket)
+    /// of `device` supporting its type.
+    pub fn bind_buckets(&mut self, device: &Device) -> Result<()> {
+        for cell in self.cells.values_mut() {
+            let bucket =
+                device
+                    .cell_type_bucket(&cell.ty)
+                    .ok_or_else(|| Error::UnsupportedCellType {
+                        cell: cell.name.to_string(),
+                        ty: cell.ty.to_string(),
+                    })?;
+            cell.bucket = Some(bucket);
+        }
+        Ok(())
+    }
+
+    /// Annotate `cell` with the [Bel](crate::device::resources::Bel) it is placed on.
+    pub fn place_cell(&mut self, cell: CellId, bel: BelId) {
+        self.cells[cell].bel = Some(bel);
+    }
+
+    /// Annotate `net` with the [Pip](crate::device::resources::Pip)'s it is routed through.
+    pub fn route_net<P>(&mut self, net: NetId, pips: P)
+    where
+        P: IntoIterator<Item = PipId>,
+    {
+        self.nets[net].pips = pips.into_iter().collect();
+    }
+
+    pub fn clear_placement(&mut self) {
+        for cell in self.cells.values_mut() {
+            cell.bel = None;
+        }
+    }
+
+    pub fn clear_routing(&mut self) {
+        for net in self.nets.values_mut() {
+            net.pips.clear();
+        }
+    }
+}
+
+fn pin_name(port: &str, index: usize, width: usize) -> String {
+    if width == 1 {
+        port.to_string()
+    } else {
+        format!("{port}[{index}]")
+    }
+}
+
+/// `name`, or `name_n` with the least `n` that is not `taken`.
+fn unique_name<F>(name: &str, taken: F) -> String
+where
+    F: Fn(&str) -> bool,
+{
+    iter::once(name.to_string())
+        .chain((1..).map(|n| format!("{name}_{n}")))
+        .find(|name| !taken(name))
+        .expect("a unique name should exist")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const DESIGN: &str = r#"{
+  "creator": "test",
+  "modules": {
+    "top": {
+      "attributes": { "top": "00000000000000000000000000000001" },
+      "ports": {
+        "a": { "direction": "input", "bits": [ 2, 3 ] },
+        "y": { "direction": "output", "bits": [ 4 ] }
+      },
+      "cells": {
+        "lut": {
+          "hide_name": 0,
+          "type": "$lut",
+          "parameters": { "LUT": "0111", "WIDTH": "00000000000000000000000000000010" },
+          "attributes": { "src": "top.v:3" },
+          "port_directions": { "A": "input", "Y": "output" },
+          "connections": { "A": [ 2, 3 ], "Y": [ 4 ] }
+        },
+        "dff": {
+          "hide_name": 1,
+          "type": "$_DFF_P_",
+          "parameters": {},
+          "attributes": {},
+          "port_directions": { "C": "input", "D": "input", "Q": "output" },
+          "connections": { "C": [ "0" ], "D": [ 4 ], "Q": [ 5 ] }
+        }
+      },
+      "netnames": {
+        "a": { "hide_name": 0, "bits": [ 2, 3 ], "attributes": {} },
+        "y": { "hide_name": 0, "bits": [ 4 ], "attributes": {} }
+      }
+    }
+  }
+}"#;
+
+    #[test]
+    fn test_from_yosys() {
+        let design: yosys::Design = DESIGN.parse().unwrap();
+        let database = Database::from_yosys(&design).unwrap();
+        assert_eq!(database.name(), "top");
+        assert_eq!(database.n_cells(), 5);
+        let lut = database.find_cell("lut").unwrap();
+        let cell = database.cell(lut);
+        assert_eq!(cell.class(), Some(ComponentClass::Lut));
+        assert_eq!(cell.parameters()["LUT"], "0111");
+        assert_eq!(cell.attributes()["src"], "top.v:3");
+        let names: Vec<_> = cell.pins().iter().map(|pin| pin.name()).collect();
+        assert_eq!(names, ["A[0]", "A[1]", "Y"]);
+
+        let y = database.net(database.find_net("y").unwrap());
+        assert_eq!(y.driver().unwrap().cell, lut);
+        assert_eq!(y.sinks().len(), 2);
+        let a = database.net(database.find_net("a[1]").unwrap());
+        assert_eq!(
+            a.driver().unwrap().cell,
+            database.find_cell("a[1]").unwrap()
+        );
+
+        let dff = database.cell(database.find_cell("dff").unwrap());
+        assert_eq!(dff.class(), Some(ComponentClass::Latch));
+        assert_eq!(dff.find_pin("C").unwrap().net(), None);
+        let q = database.net(dff.find_pin("Q").unwrap().net().unwrap());
+        assert_eq!(q.name(), "$5");
+        assert!(q.sinks().is_empty());
+    }
+
+    #[test]
+    fn test_name_collisions() {
+        let mut design: yosys::Design = DESIGN.parse().unwrap();
+        let top = design.modules.get_mut("top").unwrap();
+        let dff = top.cells.remove("dff").unwrap();
+        top.cells.insert("y".to_string(), dff);
+        let mut netname = top.netnames["y"].clone();
+        netname.bits = vec![SignalBit::Ref(5)];
+        top.netnames.insert("a[1]".to_string(), netname);
+        let database = Database::from_yosys(&design).unwrap();
+
+        let dff = database.find_cell("y").unwrap();
+        assert_eq!(database.cell(dff).ty(), std_cells::DFF_P);
+        let pad = database.find_cell("y_1").unwrap();
+        assert_eq!(database.cell(pad).ty(), OUTPAD);
+        let a = database.net(database.find_net("a[1]").unwrap());
+        assert_eq!(
+            a.driver().unwrap().cell,
+            database.find_cell("a[1]").unwrap()
+        );
+        let q = database.find_net("a[1]_1").unwrap();
+        assert_eq!(database.net(q).driver().unwrap().cell, dff);
+    }
+
+    #[test]
+    fn test_multiple_drivers() {
+        let mut database = Database::new("top");
+        let net = database.add_net("n").unwrap();
+        for name in ["a", "b"] {
+            let cell = database.add_cell(name, INPAD).unwrap();
+            database.add_pin(cell, INPAD_PIN, PinDirection::Output);
+            let result = database.connect(cell, INPAD_PIN, net);
+            assert_eq!(name == "b", result.is_err());
+        }
+    }
+
+    #[test]
+    fn test_duplicate_names() {
+        let mut database = Database::new("top");
+        let cell = database.add_cell("y", INPAD).unwrap();
+        assert!(matches!(
+            database.add_cell("y", "$_NOT_"),
+            Err(Error::DuplicateCell(name)) if name == "y"
+        ));
+        assert_eq!(database.find_cell("y"), Some(cell));
+        assert_eq!(database.n_cells(), 1);
+        let net = database.add_net("y").unwrap();
+        assert!(matches!(database.add_net("y"), Err(Error::DuplicateNet(_))));
+        assert_eq!(database.find_net("y"), Some(net));
     }
 }
@@ -1,6 +1,23 @@
-use slotmap::{new_key_type, SlotMap};
+use std::fmt;
 
-use super::resources::{Bel, BelBucket, Group, Pip, Wire};
+use fnv::FnvHashMap;
+use slotmap::{new_key_type, Key, SlotMap};
+use thiserror::Error;
+use ustr::Ustr;
+
+use super::resources::{Bel, BelBucket, BelPinRef, Group, Location, Pip, Wire};
+
+#[derive(Debug, Error)]
+pub enum Error {
+    #[error(r#"duplicate {kind} name "{name}""#)]
+    DuplicateName { kind: &'static str, name: String },
+    #[error(r#"cell type "{cell_type}" already belongs to bel bucket "{bucket}""#)]
+    DuplicateCellType { cell_type: String, bucket: String },
+    #[error("{kind} {id} does not belong to the database")]
+    ForeignId { kind: &'static str, id: String },
+}
+
+pub type Result<T> = std::result::Result<T, Error>;
 
 new_key_type! {
     pub struct BelId;
@@ -11,6 +28,9 @@ new_key_type! {
 }
 
 /// A `database` for device resources.
+///
+/// Resources are looked up by name, so names must be unique within each kind
+/// of resource.
 #[derive(Clone, Debug, Default)]
 pub struct Database {
     bels: SlotMap<BelId, Bel>,
@@ -18,6 +38,13 @@ pub struct Database {
     pips: SlotMap<PipId, Pip>,
     groups: SlotMap<GroupId, Group>,
     buckets: SlotMap<BelBucketId, BelBucket>,
+    bel_names: FnvHashMap<Ustr, BelId>,
+    wire_names: FnvHashMap<Ustr, WireId>,
+    pip_names: FnvHashMap<Ustr, PipId>,
+    group_names: FnvHashMap<Ustr, GroupId>,
+    bucket_names: FnvHashMap<Ustr, BelBucketId>,
+    cell_type_buckets: FnvHashMap<Ustr, BelBucketId>,
+    bel_locations: FnvHashMap<Location, Vec<BelId>>,
 }
 
 impl Database {
@@ -25,4 +52,308 @@ impl Database {
     pub fn new() -> Self {
         Self::default()
     }
+
+    /// Add a [BelBucket] to the database.
+    ///
+    /// A cell type may only belong to a single bucket.
+    pub fn add_bucket(&mut self, bucket: BelBucket) -> Result<BelBucketId> {
+        let name = check_name(&self.bucket_names, "bel bucket", bucket.name())?;
+        let cell_types: Vec<Ustr> = bucket.cell_types().map(Ustr::from).collect();
+        for cell_type in &cell_types {
+            if let Some(other) = self.cell_type_buckets.get(cell_type) {
+                return Err(Error::DuplicateCellType {
+                    cell_type: cell_type.to_string(),
+                    bucket: self.buckets[*other].name().to_string(),
+                });
+            }
+        }
+        let id = self.buckets.insert(bucket);
+        self.bucket_names.insert(name, id);
+        for cell_type in cell_types {
+            self.cell_type_buckets.insert(cell_type, id);
+        }
+        Ok(id)
+    }
+
+    /// Add a [Bel] to the database, register it with its [BelBucket] and bind
+    /// its pins to their [Wire]'s.
+    pub fn add_bel(&mut self, bel: Bel) -> Result<BelId> {
+        let name = check_name(&self.bel_names, "bel", bel.name())?;
+        let (bucket, location) = (bel.bucket(), bel.location());
+        check_id(&self.buckets, "bel bucket", bucket)?;
+        for pin in bel.pins() {
+            check_id(&self.wires, "wire", pin.wire())?;
+        }
+        let pins: Vec<_> = bel
+            .pins()
+            .iter()
+            .map(|pin| (pin.name().into(), pin.wire()))
+            .collect();
+        let id = self.bels.insert(bel);
+        self.buckets[bucket].bels.push(id);
+        self.bel_names.insert(name, id);
+        self.bel_locations.entry(location).or_default().push(id);
+        for (pin, wire) in pins {
+            self.wires[wire].bel_pins.push(BelPinRef { bel: id, pin });
+        }
+        Ok(id)
+    }
+
+    pub fn bel(&self, bel: BelId) -> &Bel {
+        &self.bels[bel]
+    }
+
+    pub fn bels(&self) -> impl Iterator<Item = (BelId, &Bel)> + '_ {
+        self.bels.iter()
+    }
+
+    pub fn find_bel(&self, name: &str) -> Option<BelId> {
+        self.bel_names.get(&Ustr::from(name)).copied()
+    }
+
+    /// The [Bel]'s at `location`.
+    pub fn bels_at(&self, location: Location) -> &[BelId] {
+        self.bel_locations
+            .get(&location)
+            .map(Vec::as_slice)
+            .unwrap_or_default()
+    }
+
+    /// The [Wire] bound to `pin` of `bel`.
+    pub fn bel_pin_wire(&self, bel: BelId, pin: &str) -> Option<WireId> {
+        self.bels[bel].find_pin(pin).map(|pin| pin.wire())
+    }
+
+    /// Add a [Wire] to the database.
+    pub fn add_wire(&mut self, wire: Wire) -> Result<WireId> {
+        let name = check_name(&self.wire_names, "wire", wire.name())?;
+        let id = self.wires.insert(wire);
+        self.wire_names.insert(name, id);
+        Ok(id)
+    }
+
+    /// Add a [Pip] to the database and connect it to its source and sink [Wire]'s.
+    pub fn add_pip(&mut self, pip: Pip) -> Result<PipId> {
+        let name = check_name(&self.pip_names, "pip", pip.name())?;
+        let (source, sink) = (pip.source(), pip.sink());
+        check_id(&self.wires, "wire", source)?;
+        check_id(&self.wires, "wire", sink)?;
+        let id = self.pips.insert(pip);
+        self.pip_names.insert(name, id);
+        self.wires[source].downhill.push(id);
+        self.wires[sink].uphill.push(id);
+        Ok(id)
+    }
+
+    pub fn wire(&self, wire: WireId) -> &Wire {
+        &self.wires[wire]
+    }
+
+    pub fn wires(&self) -> impl Iterator<Item = (WireId, &Wire)> + '_ {
+        self.wires.iter()
+    }
+
+    pub fn contains_wire(&self, wire: WireId) -> bool {
+        self.wires.contains_key(wire)
+    }
+
+    pub fn find_wire(&self, name: &str) -> Option<WireId> {
+        self.wire_names.get(&Ustr::from(name)).copied()
+    }
+
+    pub fn pip(&self, pip: PipId) -> &Pip {
+        &self.pips[pip]
+    }
+
+    pub fn pips(&self) -> impl Iterator<Item = (PipId, &Pip)> + '_ {
+        self.pips.iter()
+    }
+
+    pub fn find_pip(&self, name: &str) -> Option<PipId> {
+        self.pip_names.get(&Ustr::from(name)).copied()
+    }
+
+    /// Add a [Group] to the database.
+    pub fn add_group(&mut self, group: Group) -> Result<GroupId> {
+        let name = check_name(&self.group_names, "group", group.name())?;
+        let id = self.groups.insert(group);
+        self.group_names.insert(name, id);
+        Ok(id)
+    }
+
+    pub fn group(&self, group: GroupId) -> &Group {
+        &self.groups[group]
+    }
+
+    pub fn groups(&self) -> impl Iterator<Item = (GroupId, &Group)> + '_ {
+        self.groups.iter()
+    }
+
+    pub fn find_group(&self, name: &str) -> Option<GroupId> {
+        self.group_names.get(&Ustr::from(name)).copied()
+    }
+
+    pub fn bucket(&self, bucket: BelBucketId) -> &BelBucket {
+        &self.buckets[bucket]
+    }
+
+    pub fn buckets(&self) -> impl Iterator<Item = (BelBucketId, &BelBucket)> + '_ {
+        self.buckets.iter()
+    }
+
+    pub fn find_bucket(&self, name: &str) -> Option<BelBucketId> {
+        self.bucket_names.get(&Ustr::from(name)).copied()
+    }
+
+    /// The [BelBucket] whose [Bel]'s can host cells of type `cell_type`.
+    pub fn cell_type_bucket(&self, cell_type: &str) -> Option<BelBucketId> {
+        self.cell_type_buckets.get(&Ustr::from(cell_type)).copied()
+    }
+}
+
+/// `name` as a key of `names`, unless it is already taken.
+fn check_name<K>(names: &FnvHashMap<Ustr, K>, kind: &'static str, name: &str) -> Result<Ustr> {
+    let name = Ustr::from(name);
+    if names.contains_key(&name) {
+        return Err(Error::DuplicateName {
+            kind,
+            name: name.to_string(),
+        });
+    }
+    Ok(name)
+}
+
+/// An error unless `id` is a key of `items`.
+///
+/// An id of another database is only caught if no resource of this
+/// database has the same key.
+fn check_id<K: Key + fmt::Debug, V>(
+    items: &SlotMap<K, V>,
+    kind: &'static str,
+    id: K,
+) -> Result<()> {
+    if !items.contains_key(id) {
+        return Err(Error::ForeignId {
+            kind,
+            id: format!("{id:?}"),
+        });
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::device::resources::{GroupItem, PinDirection};
+
+    #[test]
+    fn test_queries() {
+        let mut device = Database::new();
+        let lut = device.add_bucket(BelBucket::new("LUT", ["$lut"])).unwrap();
+        let a = device.add_wire(Wire::new("a")).unwrap();
+        let y = device.add_wire(Wire::new("y")).unwrap();
+        let mut bel = Bel::new("lut0", Location::new(1, 2), lut);
+        bel.add_pin("A", PinDirection::Input, a).unwrap();
+        bel.add_pin("Y", PinDirection::Output, y).unwrap();
+        let bel = device.add_bel(bel).unwrap();
+        let pip = device
+            .add_pip(Pip::new("y->a", y, a).with_delay(10.0))
+            .unwrap();
+        let group = device
+            .add_group(Group::new("tile", [GroupItem::Bel(bel)]))
+            .unwrap();
+
+        assert_eq!(device.find_bel("lut0"), Some(bel));
+        assert_eq!(device.bels_at(Location::new(1, 2)), [bel]);
+        assert!(device.bels_at(Location::new(0, 0)).is_empty());
+        assert_eq!(device.bel_pin_wire(bel, "Y"), Some(y));
+        assert_eq!(device.bel_pin_wire(bel, "B"), None);
+        assert_eq!(device.find_wire("a"), Some(a));
+        assert_eq!(
+            device.wire(a).bel_pins()[0],
+            BelPinRef {
+                bel,
+                pin: "A".into()
+            }
+        );
+        assert_eq!(device.wire(a).uphill(), [pip]);
+        assert_eq!(device.wire(y).downhill(), [pip]);
+        assert_eq!(device.find_pip("y->a"), Some(pip));
+        assert_eq!(device.pip(pip).delay(), 10.0);
+        assert_eq!(device.find_group("tile"), Some(group));
+        assert_eq!(device.group(group).items(), [GroupItem::Bel(bel)]);
+        assert_eq!(device.find_bucket("LUT"), Some(lut));
+        assert_eq!(device.cell_type_bucket("$lut"), Some(lut));
+        assert_eq!(device.cell_type_bucket("$_DFF_P_"), None);
+    }
+
+    #[test]
+    fn test_duplicates() {
+        let mut device = Database::new();
+        let lut = device.add_bucket(BelBucket::new("LUT", ["$lut"])).unwrap();
+        assert!(matches!(
+            device.add_bucket(BelBucket::new("LUT", ["$_DFF_P_"])),
+            Err(Error::DuplicateName {
+                kind: "bel bucket",
+                ..
+            })
+        ));
+        assert!(matches!(
+            device.add_bucket(BelBucket::new("LUT2", ["$lut"])),
+            Err(Error::DuplicateCellType { bucket, .. }) if bucket == "LUT"
+        ));
+        assert_eq!(device.find_bucket("LUT2"), None);
+        let a = device.add_wire(Wire::new("a")).unwrap();
+        assert!(device.add_wire(Wire::new("a")).is_err());
+        device
+            .add_bel(Bel::new("lut0", Location::new(0, 0), lut))
+            .unwrap();
+        assert!(device
+            .add_bel(Bel::new("lut0", Location::new(1, 0), lut))
+            .is_err());
+        assert_eq!(device.bucket(lut).bels().len(), 1);
+        device.add_pip(Pip::new("a->a", a, a)).unwrap();
+        assert!(device.add_pip(Pip::new("a->a", a, a)).is_err());
+        assert_eq!(device.wire(a).downhill().len(), 1);
+
+        let mut bel = Bel::new("lut1", Location::new(1, 0), lut);
+        bel.add_pin("A", PinDirection::Input, a).unwrap();
+        assert!(matches!(
+            bel.add_pin("A", PinDirection::Output, a),
+            Err(Error::DuplicateName {
+                kind: "bel pin",
+                ..
+            })
+        ));
+        assert_eq!(bel.pins().len(), 1);
+    }
+
+    #[test]
+    fn test_foreign_ids() {
+        let mut other = Database::new();
+        let bucket = other.add_bucket(BelBucket::new("IO", ["$io"])).unwrap();
+        let wire = other.add_wire(Wire::new("w")).unwrap();
+
+        let mut device = Database::new();
+        assert!(matches!(
+            device.add_bel(Bel::new("io0", Location::new(0, 0), bucket)),
+            Err(Error::ForeignId {
+                kind: "bel bucket",
+                ..
+            })
+        ));
+        let lut = device.add_bucket(BelBucket::new("LUT", ["$lut"])).unwrap();
+        let mut bel = Bel::new("lut0", Location::new(0, 0), lut);
+        bel.add_pin("A", PinDirection::Input, wire).unwrap();
+        assert!(matches!(
+            device.add_bel(bel),
+            Err(Error::ForeignId { kind: "wire", .. })
+        ));
+        assert!(matches!(
+            device.add_pip(Pip::new("w->w", wire, wire)),
+            Err(Error::ForeignId { kind: "wire", .. })
+        ));
+        assert_eq!(device.bels().count(), 0);
+        assert_eq!(device.pips().count(), 0);
+    }
 }
@@ -0,0 +1,485 @@
+//! Device generation from an [arch1](crate::arch1) architecture description.
+//!
+//! The top-level component of the architecture is tiled on a `width` by
+//! `height` grid, surrounded by a ring of IO tiles. Every tile has a routing
+//! channel of `channel_width` tracks, connected to the tracks of its
+//! neighbouring tiles and to every pin of the tile.
+//!
+//! Within a tile, component references are unrolled into instances and
+//! connections into [Pip]'s between the [Wire]'s of the connected pins:
+//! - [ConnectionKind::Direct] connects the `j`-th source pin to the `j`-th sink pin.
+//! - [ConnectionKind::Complete] connects every source pin to every sink pin.
+//! - [ConnectionKind::Mux] makes every sink pin a multiplexer selecting any
+//!   source pin. As [Pip]'s, this is the same fan-in as a complete connection.
+//!
+//! Every tile is a [Bel] of a bucket named after the top-level component,
+//! every instance of a [ComponentClass] is a [Bel] of a bucket named after its
+//! class.
+
+use fnv::FnvHashMap;
+use thiserror::Error;
+
+use super::database::{self, BelBucketId, Database, WireId};
+use super::resources::{Bel, BelBucket, Location, PinDirection, Pip, Wire};
+use crate::arch1::{
+    connection::ComponentRefs, module::ComponentRefId, port::PortPins, Component, ComponentClass,
+    ConnectionKind, Module, PortKind,
+};
+use crate::design::database::{class_cell_types, INPAD, INPAD_PIN, OUTPAD, OUTPAD_PIN};
+
+#[derive(Debug, Error)]
+pub enum Error {
+    #[error(transparent)]
+    Device(#[from] database::Error),
+    #[error(r#"undefined tile component "{0}""#)]
+    UndefinedTile(String),
+    #[error("architecture has no top-level component")]
+    NoTopComponent,
+    #[error(r#"architecture has multiple top-level components ("{0}" and "{1}")"#)]
+    AmbiguousTopComponent(String, String),
+    #[error(r#"connection "{connection}" connects {sources} source pins to {sinks} sink pins"#)]
+    WidthMismatch {
+        connection: String,
+        sources: usize,
+        sinks: usize,
+    },
+}
+
+pub type Result<T> = std::result::Result<T, Error>;
+
+/// The name of the [BelBucket] of the IO [Bel]'s.
+pub const IO_BUCKET: &str = "IO";
+
+#[derive(Clone, Debug)]
+pub struct DeviceConfig {
+    pub width: u32,
+    pub height: u32,
+    /// The number of routing tracks per tile.
+    pub channel_width: u32,
+    /// The number of IO [Bel]'s per IO tile.
+    pub io_capacity: u32,
+    /// The component to be tiled. Defaults to the only component not
+    /// referenced by any other component.
+    pub tile: Option<String>,
+}
+
+impl DeviceConfig {
+    pub fn new(width: u32, height: u32) -> Self {
+        Self {
+            width,
+            height,
+            channel_width: 8,
+            io_capacity: 2,
+            tile: None,
+        }
+    }
+}
+
+/// The bucket name and cell types of the [Bel]'s of a [ComponentClass].
+pub fn class_bucket(class: ComponentClass) -> (&'static str, &'static [&'static str]) {
+    match class {
+        ComponentClass::Lut => ("LUT", class_cell_types(class)),
+        ComponentClass::Latch => ("LATCH", class_cell_types(class)),
+    }
+}
+
+/// The component named `tile`, or the only component of `arch` not referenced
+/// by any other component.
+pub(crate) fn find_tile<'m>(arch: &'m Module, tile: Option<&str>) -> Result<Component<'m>> {
+    if let Some(tile) = tile {
+        return arch
+            .find_component(tile)
+            .ok_or_else(|| Error::UndefinedTile(tile.to_string()));
+    }
+    let mut roots = arch.components().filter(|component| {
+        !arch.components().any(|parent| {
+            parent
+                .references()
+                .any(|reference| reference.component().unbind() == component.unbind())
+        })
+    });
+    let root = roots.next().ok_or(Error::NoTopComponent)?;
+    if let Some(other) = roots.next() {
+        return Err(Error::AmbiguousTopComponent(
+            root.name().to_string(),
+            other.name().to_string(),
+        ));
+    }
+    Ok(root)
+}
+
+/// The `(source, sink)` index pairs connected by a connection of `kind`
+/// between `n_sources` source pins and `n_sinks` sink pins.
+///
+/// Returns [None] if a [ConnectionKind::Direct] connection has mismatched widths.
+pub(crate) fn connection_pairs(
+    kind: ConnectionKind,
+    n_sources: usize,
+    n_sinks: usize,
+) -> Option<Vec<(usize, usize)>> {
+    match kind {
+        ConnectionKind::Direct => {
+            (n_sources == n_sinks).then(|| (0..n_sources).map(|j| (j, j)).collect())
+        }
+        ConnectionKind::Complete | ConnectionKind::Mux => Some(
+            (0..n_sources)
+                .flat_map(|j| (0..n_sinks).map(move |k| (j, k)))
+                .collect(),
+        ),
+    }
+}
+
+pub(crate) fn indexed(name: &str, index: u32, width: u32) -> String {
+    if width == 1 {
+        name.to_string()
+    } else {
+        format!("{name}[{index}]")
+    }
+}
+
+/// The wires of the port pins of a single component instance.
+#[derive(Default)]
+struct InstanceWires(FnvHashMap<(String, u32), WireId>);
+
+impl InstanceWires {
+    fn pin(&self, port: &str, index: u32) -> WireId {
+        self.0[&(port.to_string(), index)]
+    }
+}
+
+struct Generator<'m> {
+    arch: &'m Module,
+    device: Database,
+    buckets: FnvHashMap<&'static str, BelBucketId>,
+}
+
+impl<'m> Generator<'m> {
+    fn add_pip(&mut self, source: WireId, sink: WireId) -> Result<()> {
+        let name = format!(
+            "{}->{}",
+            self.device.wire(source).name(),
+            self.device.wire(sink).name()
+        );
+        self.device.add_pip(Pip::new(&name, source, sink))?;
+        Ok(())
+    }
+
+    fn class_bucket(&mut self, class: ComponentClass) -> Result<BelBucketId> {
+        let (name, cell_types) = class_bucket(class);
+        if let Some(bucket) = self.buckets.get(name) {
+            return Ok(*bucket);
+        }
+        let bucket = self
+            .device
+            .add_bucket(BelBucket::new(name, cell_types.iter().copied()))?;
+        self.buckets.insert(name, bucket);
+        Ok(bucket)
+    }
+
+    /// Unroll an instance of `component` named `path`, returning the wires of its ports.
+    fn add_instance(
+        &mut self,
+        component: Component<'m>,
+        path: &str,
+        location: Location,
+    ) -> Result<InstanceWires> {
+        let mut wires = InstanceWires::default();
+        for port in component.ports() {
+            for i in 0..port.n_pins() {
+                let name = format!("{path}.{}", indexed(port.name(), i, port.n_pins()));
+                let wire = self.device.add_wire(Wire::new(&name))?;
+                wires.0.insert((port.name().to_string(), i), wire);
+            }
+        }
+        if let Some(class) = component.class() {
+            let bucket = self.class_bucket(class)?;
+            let mut bel = Bel::new(path, location, bucket);
+            add_bel_pins(&mut bel, component, &wires)?;
+            self.device.add_bel(bel)?;
+        }
+        let mut children = FnvHashMap::default();
+        for reference in component.references() {
+            let n_instances = reference.n_instances();
+            let instances = (0..n_instances)
+                .map(|k| {
+                    let name = indexed(reference.alias_or_name(), k, n_instances);
+                    self.add_instance(reference.component(), &format!("{path}.{name}"), location)
+                })
+                .collect::<Result<Vec<_>>>()?;
+            children.insert(reference.unbind(), instances);
+        }
+        for connection in component.connections() {
+            let sources = self.connection_wires(
+                &wires,
+                &children,
+                connection.source_component(),
+                connection.source_pins(),
+            );
+            let sinks = self.connection_wires(
+                &wires,
+                &children,
+                connection.sink_component(),
+                connection.sink_pins(),
+            );
+            let pairs = connection_pairs(connection.kind(), sources.len(), sinks.len())
+                .ok_or_else(|| Error::WidthMismatch {
+                    connection: connection.source_name_or_default().to_string(),
+                    sources: sources.len(),
+                    sinks: sinks.len(),
+                })?;
+            for (j, k) in pairs {
+                self.add_pip(sources[j], sinks[k])?;
+            }
+        }
+        Ok(wires)
+    }
+
+    fn connection_wires(
+        &self,
+        wires: &InstanceWires,
+        children: &FnvHashMap<ComponentRefId, Vec<InstanceWires>>,
+        references: Option<&ComponentRefs>,
+        pins: &PortPins,
+    ) -> Vec<WireId> {
+        let port = pins.port(self.arch);
+        let range = pins.range(self.arch);
+        match references {
+            Some(references) => references
+                .range(self.arch)
+                .flat_map(|k| {
+                    let instance = &children[&references.id()][k as usize];
+                    range.clone().map(move |i| instance.pin(port.name(), i))
+                })
+                .collect(),
+            None => range.map(|i| wires.pin(port.name(), i)).collect(),
+        }
+    }
+}
+
+fn add_bel_pins(bel: &mut Bel, component: Component<'_>, wires: &InstanceWires) -> Result<()> {
+    for port in component.ports() {
+        let direction = match port.kind() {
+            PortKind::Input => PinDirection::Input,
+            PortKind::Output => PinDirection::Output,
+        };
+        for i in 0..port.n_pins() {
+            let name = indexed(port.name(), i, port.n_pins());
+            bel.add_pin(&name, direction, wires.pin(port.name(), i))?;
+        }
+    }
+    Ok(())
+}
+
+/// Generate a device by tiling the top-level component of `arch`.
+pub fn generate(arch: &Module, config: &DeviceConfig) -> Result<Database> {
+    let tile = find_tile(arch, config.tile.as_deref())?;
+    let mut generator = Generator {
+        arch,
+        device: Database::new(),
+        buckets: FnvHashMap::default(),
+    };
+    let tile_bucket = generator
+        .device
+        .add_bucket(BelBucket::new(tile.name(), [tile.name()]))?;
+    let io_bucket = generator
+        .device
+        .add_bucket(BelBucket::new(IO_BUCKET, [INPAD, OUTPAD]))?;
+    let (width, height) = (config.width, config.height);
+    let mut tracks = FnvHashMap::<Location, Vec<WireId>>::default();
+    for x in 0..width + 2 {
+        for y in 0..height + 2 {
+            let is_io = x == 0 || y == 0 || x == width + 1 || y == height + 1;
+            let is_corner = (x == 0 || x == width + 1) && (y == 0 || y == height + 1);
+            if is_corner {
+                continue;
+            }
+            let location = Location::new(x, y);
+            let prefix = format!("x{x}y{y}");
+            let channel: Vec<_> = (0..config.channel_width)
+                .map(|t| {
+                    let name = format!("{prefix}.track[{t}]");
+                    generator.device.add_wire(Wire::new(&name))
+                })
+                .collect::<database::Result<_>>()?;
+            // NOTE: Every pin of the tile connects to every track of its channel.
+            let connect_pin = |generator: &mut Generator, wire, direction| {
+                for &track in &channel {
+                    match direction {
+                        PinDirection::Input => generator.add_pip(track, wire)?,
+                        PinDirection::Output => generator.add_pip(wire, track)?,
+                    }
+                }
+                Ok::<_, Error>(())
+            };
+            if is_io {
+                for k in 0..config.io_capacity {
+                    let name = format!("{prefix}.io[{k}]");
+                    let mut bel = Bel::new(&name, location, io_bucket);
+                    for (pin, direction) in [
+                        (OUTPAD_PIN, PinDirection::Input),
+                        (INPAD_PIN, PinDirection::Output),
+                    ] {
+                        let wire = generator
+                            .device
+                            .add_wire(Wire::new(&format!("{name}.{pin}")))?;
+                        bel.add_pin(pin, direction, wire)?;
+                        connect_pin(&mut generator, wire, direction)?;
+                    }
+                    generator.device.add_bel(bel)?;
+                }
+            } else {
+                let wires = generator.add_instance(tile, &prefix, location)?;
+                let mut bel = Bel::new(&prefix, location, tile_bucket);
+                add_bel_pins(&mut bel, tile, &wires)?;
+                for pin in bel.pins() {
+                    connect_pin(&mut generator, pin.wire(), pin.direction())?;
+                }
+                generator.device.add_bel(bel)?;
+            }
+            tracks.insert(location, channel);
+        }
+    }
+    let mut locations: Vec<_> = tracks.keys().copied().collect();
+    locations.sort();
+    for location in locations {
+        for neighbour in [
+            Location::new(location.x + 1, location.y),
+            Location::new(location.x, location.y + 1),
+        ] {
+            let Some(other) = tracks.get(&neighbour) else {
+                continue;
+            };
+            for (&a, &b) in tracks[&location].iter().zip(other) {
+                generator.add_pip(a, b)?;
+                generator.add_pip(b, a)?;
+            }
+        }
+    }
+    Ok(generator.device)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::arch1::yaml;
+    use crate::design::database::cell_class;
+    use crate::interchange::yosys::constids::{internal_cells, std_cells};
+
+    const ARCH: &str = r#"
+name: test
+components:
+  lut:
+    class: LUT
+    ports:
+      in: { kind: INPUT, n_pins: 2, class: LUT_IN }
+      out: { kind: OUTPUT, class: LUT_OUT }
+  clb:
+    ports:
+      I: { kind: INPUT, n_pins: 4 }
+      O: { kind: OUTPUT, n_pins: 2 }
+    references:
+      - { component: lut, n_instances: 2 }
+    connections:
+      - kind: COMPLETE
+        source: { port: I }
+        sink: { reference: lut, port: in }
+      - kind: DIRECT
+        source: { reference: lut, port: out }
+        sink: { port: O }
+"#;
+
+    #[test]
+    fn test_generate() {
+        let arch = yaml::from_str(ARCH).unwrap();
+        let device = generate(&arch, &DeviceConfig::new(2, 1)).unwrap();
+        let clb = device.find_bucket("clb").unwrap();
+        assert_eq!(device.bucket(clb).bels().len(), 2);
+        let lut = device.cell_type_bucket(internal_cells::LUT).unwrap();
+        assert_eq!(device.bucket(lut).bels().len(), 4);
+        // 2 * 2 + 2 * 1 IO tiles.
+        let io = device.find_bucket(IO_BUCKET).unwrap();
+        assert_eq!(device.bucket(io).bels().len(), 6 * 2);
+
+        let bel = device.find_bel("x1y1.lut[1]").unwrap();
+        assert_eq!(device.bel(bel).location(), Location::new(1, 1));
+        let input = device.bel_pin_wire(bel, "in[0]").unwrap();
+        assert_eq!(device.wire(input).uphill().len(), 4);
+        let output = device.bel_pin_wire(bel, "out").unwrap();
+        let pip = device.wire(output).downhill()[0];
+        assert_eq!(device.wire(device.pip(pip).sink()).name(), "x1y1.O[1]");
+
+        let site = device.find_bel("x1y1").unwrap();
+        let tile_input = device.bel_pin_wire(site, "I[0]").unwrap();
+        assert_eq!(device.wire(tile_input).uphill().len(), 8);
+        let track = device.find_wire("x1y1.track[0]").unwrap();
+        // 4 neighbours, 2 tile outputs.
+        assert_eq!(device.wire(track).uphill().len(), 6);
+    }
+
+    #[test]
+    fn test_class_buckets() {
+        let arch = yaml::from_str(
+            r#"
+name: test
+components:
+  ff:
+    class: LATCH
+    ports:
+      D: { kind: INPUT }
+      Q: { kind: OUTPUT }
+  clb:
+    ports:
+      I: { kind: INPUT, n_pins: 3 }
+      O: { kind: OUTPUT }
+    references:
+      - { component: ff, n_instances: 2 }
+    connections:
+      - kind: MUX
+        source: { port: I }
+        sink: { reference: ff, port: D }
+      - kind: MUX
+        source: { reference: ff, port: Q }
+        sink: { port: O }
+"#,
+        )
+        .unwrap();
+        let device = generate(&arch, &DeviceConfig::new(1, 1)).unwrap();
+        let latch = device.find_bucket("LATCH").unwrap();
+        for ty in [
+            internal_cells::DFF,
+            internal_cells::FF,
+            std_cells::DFF_P,
+            std_cells::DFF_N,
+        ] {
+            assert_eq!(cell_class(ty), Some(ComponentClass::Latch));
+            assert_eq!(device.cell_type_bucket(ty), Some(latch));
+        }
+
+        // NOTE: Every sink of a mux selects any of its sources.
+        for k in 0..2 {
+            let bel = device.find_bel(&format!("x1y1.ff[{k}]")).unwrap();
+            let input = device.bel_pin_wire(bel, "D").unwrap();
+            assert_eq!(device.wire(input).uphill().len(), 3);
+        }
+        let output = device.find_wire("x1y1.O").unwrap();
+        assert_eq!(device.wire(output).uphill().len(), 2);
+    }
+
+    #[test]
+    fn test_errors() {
+        let arch = yaml::from_str(ARCH).unwrap();
+        let config = DeviceConfig {
+            tile: Some("dsp".to_string()),
+            ..DeviceConfig::new(1, 1)
+        };
+        assert!(matches!(
+            generate(&arch, &config),
+            Err(Error::UndefinedTile(_))
+        ));
+        let arch = yaml::from_str(&ARCH.replace("n_pins: 2 }", "n_pins: 3 }")).unwrap();
+        assert!(matches!(
+            generate(&arch, &DeviceConfig::new(1, 1)),
+            Err(Error::WidthMismatch { .. })
+        ));
+    }
+}
@@ -1,27 +1,205 @@
-use ustr::Ustr;
+use serde::{Deserialize, Serialize};
+use ustr::{ustr, Ustr};
 
-use super::database::{BelId, GroupId, PipId, WireId};
+use super::database::{BelBucketId, BelId, Error, GroupId, PipId, Result, WireId};
+
+/// A tile coordinate on the device grid.
+#[derive(Clone, Copy, Debug, Default, Eq, Hash, Ord, PartialEq, PartialOrd)]
+pub struct Location {
+    pub x: u32,
+    pub y: u32,
+}
+
+impl Location {
+    pub fn new(x: u32, y: u32) -> Self {
+        Self { x, y }
+    }
+
+    /// The manhattan distance between two locations.
+    pub fn distance(&self, other: Location) -> u32 {
+        self.x.abs_diff(other.x) + self.y.abs_diff(other.y)
+    }
+}
+
+#[derive(Clone, Copy, Debug, Deserialize, Eq, Hash, PartialEq, Serialize)]
+pub enum PinDirection {
+    Input,
+    Output,
+}
+
+/// A pin of a [Bel], bound to a [Wire].
+#[derive(Clone, Debug)]
+pub struct BelPin {
+    name: Ustr,
+    direction: PinDirection,
+    wire: WireId,
+}
+
+impl BelPin {
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn direction(&self) -> PinDirection {
+        self.direction
+    }
+
+    pub fn wire(&self) -> WireId {
+        self.wire
+    }
+}
 
 /// A basic element.
 #[derive(Clone, Debug)]
 pub struct Bel {
     name: Ustr,
+    location: Location,
+    bucket: BelBucketId,
+    pins: Vec<BelPin>,
+}
+
+impl Bel {
+    pub fn new(name: &str, location: Location, bucket: BelBucketId) -> Self {
+        Self {
+            name: ustr(name),
+            location,
+            bucket,
+            pins: Vec::new(),
+        }
+    }
+
+    /// Add a pin bound to `wire`.
+    ///
+    /// Pin names must be unique within the bel.
+    pub fn add_pin(&mut self, name: &str, direction: PinDirection, wire: WireId) -> Result<()> {
+        if self.find_pin(name).is_some() {
+            return Err(Error::DuplicateName {
+                kind: "bel pin",
+                name: name.to_string(),
+            });
+        }
+        self.pins.push(BelPin {
+            name: ustr(name),
+            direction,
+            wire,
+        });
+        Ok(())
+    }
+
+    pub fn pins(&self) -> &[BelPin] {
+        &self.pins
+    }
+
+    pub fn find_pin(&self, name: &str) -> Option<&BelPin> {
+        self.pins.iter().find(|pin| pin.name == name)
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn location(&self) -> Location {
+        self.location
+    }
+
+    pub fn bucket(&self) -> BelBucketId {
+        self.bucket
+    }
+}
+
+/// A reference to a [BelPin] of a [Bel] in the [Database](super::database::Database).
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub struct BelPinRef {
+    pub bel: BelId,
+    pub pin: Ustr,
 }
 
 /// A physical connection between [Pip]'s and/or [Bel] pins.
 #[derive(Clone, Debug)]
 pub struct Wire {
     name: Ustr,
+    pub(super) uphill: Vec<PipId>,
+    pub(super) downhill: Vec<PipId>,
+    pub(super) bel_pins: Vec<BelPinRef>,
+}
+
+impl Wire {
+    pub fn new(name: &str) -> Self {
+        Self {
+            name: ustr(name),
+            uphill: Vec::new(),
+            downhill: Vec::new(),
+            bel_pins: Vec::new(),
+        }
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// The [Pip]'s driving this wire.
+    pub fn uphill(&self) -> &[PipId] {
+        &self.uphill
+    }
+
+    /// The [Pip]'s driven by this wire.
+    pub fn downhill(&self) -> &[PipId] {
+        &self.downhill
+    }
+
+    /// The [Bel] pins bound to this wire.
+    pub fn bel_pins(&self) -> &[BelPinRef] {
+        &self.bel_pins
+    }
 }
 
 /// A programmable interconnect point.
 #[derive(Clone, Debug)]
 pub struct Pip {
     name: Ustr,
+    source: WireId,
+    sink: WireId,
+    delay: f64,
+}
+
+impl Pip {
+    pub fn new(name: &str, source: WireId, sink: WireId) -> Self {
+        Self {
+            name: ustr(name),
+            source,
+            sink,
+            delay: 0.0,
+        }
+    }
+
+    /// Set the intrinsic delay of the pip.
+    pub fn with_delay(mut self, delay: f64) -> Self {
+        self.delay = delay;
+        self
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// The [Wire] driving this pip.
+    pub fn source(&self) -> WireId {
+        self.source
+    }
+
+    /// The [Wire] driven by this pip.
+    pub fn sink(&self) -> WireId {
+        self.sink
+    }
+
+    /// The intrinsic delay of the pip in picoseconds.
+    pub fn delay(&self) -> f64 {
+        self.delay
+    }
 }
 
 /// An item within a [Group].
-#[derive(Clone, Debug)]
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
 pub enum GroupItem {
     Bel(BelId),
     Pip(PipId),
@@ -36,6 +214,56 @@ pub struct Group {
     items: Vec<GroupItem>,
 }
 
+impl Group {
+    pub fn new<I>(name: &str, items: I) -> Self
+    where
+        I: IntoIterator<Item = GroupItem>,
+    {
+        Self {
+            name: ustr(name),
+            items: items.into_iter().collect(),
+        }
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn items(&self) -> &[GroupItem] {
+        &self.items
+    }
+}
+
 /// A collection of [Bel]'s and `cell types`.
 #[derive(Clone, Debug)]
-pub struct BelBucket;
+pub struct BelBucket {
+    name: Ustr,
+    cell_types: Vec<Ustr>,
+    pub(super) bels: Vec<BelId>,
+}
+
+impl BelBucket {
+    pub fn new<'a, C>(name: &str, cell_types: C) -> Self
+    where
+        C: IntoIterator<Item = &'a str>,
+    {
+        Self {
+            name: ustr(name),
+            cell_types: cell_types.into_iter().map(ustr).collect(),
+            bels: Vec::new(),
+        }
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// The `cell types` that can be placed on the [Bel]'s in this bucket.
+    pub fn cell_types(&self) -> impl Iterator<Item = &str> + '_ {
+        self.cell_types.iter().map(|ty| ty.as_str())
+    }
+
+    pub fn bels(&self) -> &[BelId] {
+        &self.bels
+    }
+}
@@ -0,0 +1,738 @@
+//! An end-to-end flow from a Yosys [Design](yosys::Design), or a synthesized
+//! [Database](design::Database), to a placed and routed device.
+
+use std::error::Error as StdError;
+use std::fmt;
+
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+use crate::design::database as design;
+use crate::device::database::{Database, PipId};
+use crate::interchange::yosys;
+use crate::ir::export;
+use crate::ir::graph::Graph;
+use crate::ir::lutmap::{self, LutMapConfig};
+use crate::pack::{self, CellPacker, Packer};
+use crate::place::{
+    anneal::AnnealState,
+    netlist::{BlockPin, NetId, Netlist},
+    placement::Placement,
+    Placer,
+};
+use crate::route::{
+    pathfinder::{PathFinderState, RouteNet},
+    Router,
+};
+use crate::{place, route, Flow};
+
+type BoxedError = Box<dyn StdError + Send + Sync>;
+
+#[derive(Debug, Error)]
+pub enum Error {
+    #[error("synthesis failed: {0}")]
+    Synth(BoxedError),
+    #[error("packing failed: {0}")]
+    Pack(#[from] pack::Error),
+    #[error(r#"bel "{bel}" has no pin "{pin}""#)]
+    UndefinedBelPin { bel: String, pin: String },
+    #[error("placement is incomplete")]
+    IncompletePlacement,
+    #[error("placement failed: {0}")]
+    Place(BoxedError),
+    #[error("routing failed: {0}")]
+    Route(BoxedError),
+}
+
+pub type Result<T> = std::result::Result<T, Error>;
+
+/// A stage of the flow.
+#[derive(Clone, Copy, Debug, Deserialize, Eq, Hash, Ord, PartialEq, PartialOrd, Serialize)]
+pub enum Stage {
+    Synth,
+    Pack,
+    Place,
+    Route,
+    Done,
+}
+
+impl Stage {
+    pub fn next(self) -> Self {
+        match self {
+            Self::Synth => Self::Pack,
+            Self::Pack => Self::Place,
+            Self::Place => Self::Route,
+            Self::Route | Self::Done => Self::Done,
+        }
+    }
+}
+
+impl fmt::Display for Stage {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::Synth => "synth",
+            Self::Pack => "pack",
+            Self::Place => "place",
+            Self::Route => "route",
+            Self::Done => "done",
+        })
+    }
+}
+
+fn synth_err<E: Into<BoxedError>>(err: E) -> Error {
+    Error::Synth(err.into())
+}
+
+/// The design entry of a flow starting at [Stage::Synth].
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct Synthesis {
+    pub design: yosys::Design,
+    /// Map the logic of the top module to LUTs. Designs already mapped by
+    /// Yosys are loaded as they are when [None].
+    pub lut_map: Option<LutMapConfig>,
+}
+
+impl Synthesis {
+    pub fn new(design: yosys::Design) -> Self {
+        Self {
+            design,
+            lut_map: None,
+        }
+    }
+
+    /// Map the logic of the top module to LUTs of `config`.
+    pub fn with_lut_map(mut self, config: LutMapConfig) -> Self {
+        self.lut_map = Some(config);
+        self
+    }
+
+    fn run(&self) -> Result<design::Database> {
+        let Some(config) = &self.lut_map else {
+            return design::Database::from_yosys(&self.design).map_err(synth_err);
+        };
+        let top = self.design.top_module().map_err(synth_err)?;
+        let module = self.design.flatten(Some(top)).map_err(synth_err)?;
+        let mut graph = Graph::try_from(module).map_err(synth_err)?;
+        lutmap::map_luts(&mut graph, config).map_err(synth_err)?;
+        let design = export::to_design(&graph, top).map_err(synth_err)?;
+        design::Database::from_yosys(&design).map_err(synth_err)
+    }
+}
+
+/// A placer `state` that can be driven by a [FlowDriver].
+pub trait FlowPlaceState: place::State + Clone {
+    /// Create the initial state of the placer from a packed [Netlist].
+    fn from_netlist(device: &Database, netlist: Netlist) -> Result<Self>;
+
+    fn placement(&self) -> &Placement;
+
+    fn is_done(&self) -> bool;
+}
+
+impl FlowPlaceState for AnnealState {
+    fn from_netlist(device: &Database, netlist: Netlist) -> Result<Self> {
+        AnnealState::new(device, netlist).map_err(|err| Error::Place(err.into()))
+    }
+
+    fn placement(&self) -> &Placement {
+        self.placement()
+    }
+
+    fn is_done(&self) -> bool {
+        self.is_done()
+    }
+}
+
+/// A router `state` that can be driven by a [FlowDriver].
+pub trait FlowRouteState: route::State + Clone {
+    /// Create the initial state of the router from a placed [Netlist].
+    fn from_placement(device: &Database, netlist: &Netlist, placement: &Placement) -> Result<Self>;
+
+    /// The [Pip](crate::device::resources::Pip)'s routing `net`.
+    fn pips(&self, net: NetId) -> Vec<PipId>;
+
+    fn is_done(&self) -> bool;
+}
+
+impl FlowRouteState for PathFinderState {
+    fn from_placement(device: &Database, netlist: &Netlist, placement: &Placement) -> Result<Self> {
+        let pin_wire = |pin: BlockPin| {
+            let bel = placement.bel(pin.block).ok_or(Error::IncompletePlacement)?;
+            device
+                .bel_pin_wire(bel, &pin.pin)
+                .ok_or_else(|| Error::UndefinedBelPin {
+                    bel: device.bel(bel).name().to_string(),
+                    pin: pin.pin.to_string(),
+                })
+        };
+        let nets = netlist
+            .nets()
+            .map(|(_, net)| {
+                let source = pin_wire(net.driver())?;
+                let sinks = net
+                    .sinks()
+                    .iter()
+                    .map(|pin| pin_wire(*pin))
+                    .collect::<Result<Vec<_>>>()?;
+                Ok(RouteNet::new(net.name(), source, sinks))
+            })
+            .collect::<Result<Vec<_>>>()?;
+        Ok(PathFinderState::new(nets))
+    }
+
+    fn pips(&self, net: NetId) -> Vec<PipId> {
+        self.route(net.index()).pips().collect()
+    }
+
+    fn is_done(&self) -> bool {
+        self.is_done()
+    }
+}
+
+/// The intermediate results of a [FlowDriver], from which the flow can be
+/// resumed.
+///
+/// Checkpoints include the [Packer] of the flow. They can be saved with serde,
+/// for example as JSON, if the packer can, and refer to the resources of the
+/// device by id, so they can only be resumed on the device they were created
+/// with.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct Checkpoint<PS, RS, K = CellPacker> {
+    stage: Stage,
+    synthesis: Option<Synthesis>,
+    design: design::Database,
+    netlist: Option<Netlist>,
+    place_state: Option<PS>,
+    route_state: Option<RS>,
+    packer: K,
+}
+
+impl<PS, RS, K> Checkpoint<PS, RS, K> {
+    /// The next stage to be run after resuming.
+    pub fn stage(&self) -> Stage {
+        self.stage
+    }
+}
+
+/// Drives a design through synthesis, packing, placement and routing using
+/// any [Packer], [Placer] and [Router] operating on a device [Database].
+pub struct FlowDriver<'a, PS, P, RS, R, K = CellPacker> {
+    device: &'a Database,
+    synthesis: Option<Synthesis>,
+    design: design::Database,
+    packer: K,
+    placer: P,
+    router: R,
+    stage: Stage,
+    netlist: Option<Netlist>,
+    place_state: Option<PS>,
+    route_state: Option<RS>,
+}
+
+impl<'a, PS, P, RS, R> FlowDriver<'a, PS, P, RS, R>
+where
+    PS: FlowPlaceState,
+    P: Placer<PS, Ctx = Database>,
+    P::Err: StdError + Send + Sync + 'static,
+    RS: FlowRouteState,
+    R: Router<RS, Ctx = Database>,
+    R::Err: StdError + Send + Sync + 'static,
+{
+    /// Create a flow packing every cell into its own block with a [CellPacker].
+    pub fn new(device: &'a Database, design: design::Database, placer: P, router: R) -> Self {
+        Self {
+            device,
+            synthesis: None,
+            design,
+            packer: CellPacker,
+            placer,
+            router,
+            stage: Stage::Pack,
+            netlist: None,
+            place_state: None,
+            route_state: None,
+        }
+    }
+
+    /// Create a flow starting at [Stage::Synth], which loads the design from
+    /// `synthesis`.
+    pub fn from_yosys(device: &'a Database, synthesis: Synthesis, placer: P, router: R) -> Self {
+        Self {
+            synthesis: Some(synthesis),
+            stage: Stage::Synth,
+            ..Self::new(device, design::Database::default(), placer, router)
+        }
+    }
+}
+
+impl<'a, PS, P, RS, R, K> FlowDriver<'a, PS, P, RS, R, K>
+where
+    PS: FlowPlaceState,
+    P: Placer<PS, Ctx = Database>,
+    P::Err: StdError + Send + Sync + 'static,
+    RS: FlowRouteState,
+    R: Router<RS, Ctx = Database>,
+    R::Err: StdError + Send + Sync + 'static,
+    K: Packer,
+{
+    /// Resume the flow from a [Checkpoint], with the packer it was saved with.
+    pub fn resume(
+        device: &'a Database,
+        placer: P,
+        router: R,
+        checkpoint: Checkpoint<PS, RS, K>,
+    ) -> Self {
+        Self {
+            device,
+            synthesis: checkpoint.synthesis,
+            design: checkpoint.design,
+            packer: checkpoint.packer,
+            placer,
+            router,
+            stage: checkpoint.stage,
+            netlist: checkpoint.netlist,
+            place_state: checkpoint.place_state,
+            route_state: checkpoint.route_state,
+        }
+    }
+
+    /// Pack the design with `packer` instead.
+    pub fn with_packer<L: Packer>(self, packer: L) -> FlowDriver<'a, PS, P, RS, R, L> {
+        FlowDriver {
+            device: self.device,
+            synthesis: self.synthesis,
+            design: self.design,
+            packer,
+            placer: self.placer,
+            router: self.router,
+            stage: self.stage,
+            netlist: self.netlist,
+            place_state: self.place_state,
+            route_state: self.route_state,
+        }
+    }
+
+    pub fn packer(&self) -> &K {
+        &self.packer
+    }
+
+    /// Save the intermediate results of the stages run so far.
+    pub fn checkpoint(&self) -> Checkpoint<PS, RS, K>
+    where
+        K: Clone,
+    {
+        Checkpoint {
+            stage: self.stage,
+            synthesis: self.synthesis.clone(),
+            design: self.design.clone(),
+            netlist: self.netlist.clone(),
+            place_state: self.place_state.clone(),
+            route_state: self.route_state.clone(),
+            packer: self.packer.clone(),
+        }
+    }
+
+    /// Rewind the flow to `stage`, discarding the results of it and every
+    /// later stage.
+    ///
+    /// Flows without a [Synthesis] are rewound to [Stage::Pack] at the
+    /// earliest.
+    pub fn rewind(&mut self, stage: Stage) {
+        let stage = match self.synthesis {
+            Some(_) => stage,
+            None => stage.max(Stage::Pack),
+        };
+        if stage >= self.stage {
+            return;
+        }
+        if stage <= Stage::Route {
+            self.route_state = None;
+            self.design.clear_routing();
+        }
+        if stage <= Stage::Place {
+            self.place_state = None;
+            self.design.clear_placement();
+        }
+        if stage <= Stage::Pack {
+            self.netlist = None;
+        }
+        if stage <= Stage::Synth {
+            self.design = design::Database::default();
+        }
+        self.stage = stage;
+    }
+
+    /// The design, annotated with the results of the stages run so far.
+    pub fn design(&self) -> &design::Database {
+        &self.design
+    }
+
+    pub fn into_design(self) -> design::Database {
+        self.design
+    }
+
+    /// The packed netlist, available once the [Stage::Pack] has been run.
+    pub fn netlist(&self) -> Option<&Netlist> {
+        self.netlist.as_ref()
+    }
+
+    /// The final placer state, available once [Stage::Place] has been run.
+    pub fn place_state(&self) -> Option<&PS> {
+        self.place_state.as_ref()
+    }
+
+    /// The final router state, available once [Stage::Route] has been run.
+    pub fn route_state(&self) -> Option<&RS> {
+        self.route_state.as_ref()
+    }
+
+    fn place(&mut self) -> Result<PS> {
+        let netlist = self.netlist.clone().expect("design should be packed");
+        let mut state = PS::from_netlist(self.device, netlist)?;
+        let place_err = |err: P::Err| Error::Place(err.into());
+        self.placer
+            .setup(self.device, state.clone())
+            .map_err(place_err)?;
+        while !state.is_done() {
+            state = self.placer.step(self.device, state).map_err(place_err)?;
+        }
+        Ok(state)
+    }
+
+    fn route(&mut self) -> Result<RS> {
+        let netlist = self.netlist.as_ref().expect("design should be packed");
+        let placement = self
+            .place_state
+            .as_ref()
+            .expect("design should be placed")
+            .placement();
+        let mut state = RS::from_placement(self.device, netlist, placement)?;
+        let route_err = |err: R::Err| Error::Route(err.into());
+        self.router
+            .setup(self.device, state.clone())
+            .map_err(route_err)?;
+        while !state.is_done() {
+            state = self.router.step(self.device, state).map_err(route_err)?;
+        }
+        Ok(state)
+    }
+}
+
+impl<PS, P, RS, R, K> Flow<PS, P, RS, R> for FlowDriver<'_, PS, P, RS, R, K>
+where
+    PS: FlowPlaceState,
+    P: Placer<PS, Ctx = Database>,
+    P::Err: StdError + Send + Sync + 'static,
+    RS: FlowRouteState,
+    R: Router<RS, Ctx = Database>,
+    R::Err: StdError + Send + Sync + 'static,
+    K: Packer,
+{
+    type Err = Error;
+
+    fn stage(&self) -> Stage {
+        self.stage
+    }
+
+    fn run_stage(&mut self) -> Result<Stage> {
+        match self.stage {
+            Stage::Synth => {
+                let synthesis = self
+                    .synthesis
+                    .as_ref()
+                    .expect("flow should have a synthesis");
+                self.design = synthesis.run()?;
+            }
+            Stage::Pack => {
+                let netlist = self.packer.pack(self.device, &mut self.design)?;
+                self.netlist = Some(netlist);
+            }
+            Stage::Place => {
+                let state = self.place()?;
+                let netlist = self.netlist.as_ref().expect("design should be packed");
+                for (block, bel) in state.placement().iter() {
+                    for cell in netlist.block(block).cells() {
+                        self.design.place_cell(*cell, bel);
+                    }
+                }
+                self.place_state = Some(state);
+            }
+            Stage::Route => {
+                let state = self.route()?;
+                let netlist = self.netlist.as_ref().expect("design should be packed");
+                for (id, net) in netlist.nets() {
+                    if let Some(net) = net.design_net() {
+                        self.design.route_net(net, state.pips(id));
+                    }
+                }
+                self.route_state = Some(state);
+            }
+            Stage::Done => {}
+        }
+        self.stage = self.stage.next();
+        Ok(self.stage)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::design::database::{INPAD, OUTPAD};
+    use crate::device::resources::{Bel, BelBucket, Location, PinDirection, Pip, Wire};
+    use crate::interchange::yosys::constids::internal_cells;
+    use crate::place::anneal::{AnnealConfig, AnnealingPlacer};
+    use crate::route::pathfinder::{PathFinderConfig, PathFinderRouter};
+
+    type TestDriver<'a> =
+        FlowDriver<'a, AnnealState, AnnealingPlacer, PathFinderState, PathFinderRouter>;
+
+    /// A device with every output pin connected to every input pin.
+    fn crossbar_device() -> Database {
+        let mut device = Database::new();
+        let io = device
+            .add_bucket(BelBucket::new("IO", [INPAD, OUTPAD]))
+            .unwrap();
+        let logic = device
+            .add_bucket(BelBucket::new("LOGIC", ["$_AND_", "$_NOT_"]))
+            .unwrap();
+        let lut = device
+            .add_bucket(BelBucket::new("LUT", [internal_cells::LUT]))
+            .unwrap();
+        let mut inputs = Vec::new();
+        let mut outputs = Vec::new();
+        for x in 0..3 {
+            for (bucket, pins) in [
+                (io, &["A", "Y"][..]),
+                (logic, &["A", "B", "Y"][..]),
+                (lut, &["A[0]", "A[1]", "Y"][..]),
+            ] {
+                let name = format!("{}{x}", device.bucket(bucket).name());
+                let mut bel = Bel::new(&name, Location::new(x, 0), bucket);
+                for pin in pins {
+                    let wire = device
+                        .add_wire(Wire::new(&format!("{name}.{pin}")))
+                        .unwrap();
+                    if *pin == "Y" {
+                        bel.add_pin(pin, PinDirection::Output, wire).unwrap();
+                        outputs.push(wire);
+                    } else {
+                        bel.add_pin(pin, PinDirection::Input, wire).unwrap();
+                        inputs.push(wire);
+                    }
+                }
+                device.add_bel(bel).unwrap();
+            }
+        }
+        for &source in &outputs {
+            for &sink in &inputs {
+                let name = format!(
+                    "{}->{}",
+                    device.wire(source).name(),
+                    device.wire(sink).name()
+                );
+                device.add_pip(Pip::new(&name, source, sink)).unwrap();
+            }
+        }
+        device
+    }
+
+    const NAND: &str = r#"{
+  "creator": "test",
+  "modules": {
+    "nand": {
+      "attributes": { "top": "00000000000000000000000000000001" },
+      "ports": {
+        "a": { "direction": "input", "bits": [ 2 ] },
+        "b": { "direction": "input", "bits": [ 3 ] },
+        "y": { "direction": "output", "bits": [ 4 ] }
+      },
+      "cells": {
+        "and": {
+          "hide_name": 0,
+          "type": "$_AND_",
+          "parameters": {},
+          "attributes": {},
+          "port_directions": { "A": "input", "B": "input", "Y": "output" },
+          "connections": { "A": [ 2 ], "B": [ 3 ], "Y": [ 5 ] }
+        },
+        "not": {
+          "hide_name": 0,
+          "type": "$_NOT_",
+          "parameters": {},
+          "attributes": {},
+          "port_directions": { "A": "input", "Y": "output" },
+          "connections": { "A": [ 5 ], "Y": [ 4 ] }
+        }
+      },
+      "netnames": {
+        "a": { "hide_name": 0, "bits": [ 2 ], "attributes": {} },
+        "b": { "hide_name": 0, "bits": [ 3 ], "attributes": {} },
+        "y": { "hide_name": 0, "bits": [ 4 ], "attributes": {} }
+      }
+    }
+  }
+}"#;
+
+    fn design() -> design::Database {
+        design::Database::from_yosys(&NAND.parse().unwrap()).unwrap()
+    }
+
+    fn driver(device: &Database) -> TestDriver<'_> {
+        FlowDriver::new(
+            device,
+            design(),
+            AnnealingPlacer::new(AnnealConfig::default()).unwrap(),
+            PathFinderRouter::new(PathFinderConfig::default()),
+        )
+    }
+
+    #[test]
+    fn test_pack() {
+        let device = crossbar_device();
+        let mut design = design();
+        let netlist = CellPacker.pack(&device, &mut design).unwrap();
+        assert_eq!(netlist.n_blocks(), 5);
+        let names: Vec<_> = netlist.nets().map(|(_, net)| net.name()).collect();
+        assert_eq!(names, ["a", "b", "y", "$5"]);
+        let (_, net) = netlist.nets().nth(3).unwrap();
+        let and = netlist.find_block("and").unwrap();
+        assert_eq!(net.driver().block, and);
+        assert_eq!(net.design_net(), design.find_net("$5"));
+        assert_eq!(
+            netlist.block(and).cells(),
+            [design.find_cell("and").unwrap()]
+        );
+        assert_eq!(net.sinks()[0].pin, "A");
+        let mut design = design::Database::new("top");
+        design.add_cell("or", "$_OR_").unwrap();
+        assert!(matches!(
+            CellPacker.pack(&device, &mut design),
+            Err(pack::Error::Design(
+                design::Error::UnsupportedCellType { .. }
+            ))
+        ));
+    }
+
+    #[test]
+    fn test_run() {
+        let device = crossbar_device();
+        let mut flow = driver(&device);
+        assert_eq!(flow.run_stage().unwrap(), Stage::Place);
+        assert!(flow.netlist().is_some());
+        flow.run().unwrap();
+        assert_eq!(flow.stage(), Stage::Done);
+        let placement = flow.place_state().unwrap().placement();
+        assert!(placement.is_legal(&device, flow.netlist().unwrap()));
+        let routing = flow.route_state().unwrap();
+        assert!(routing.is_done());
+        assert!(routing.routes().all(|(_, route)| route.pips().count() > 0));
+        let design = flow.into_design();
+        assert!(design.cells().all(|(_, cell)| cell.bel().is_some()));
+        assert!(design.nets().all(|(_, net)| !net.pips().is_empty()));
+    }
+
+    #[test]
+    fn test_synth() {
+        let device = crossbar_device();
+        let synth_driver = |synthesis| {
+            TestDriver::from_yosys(
+                &device,
+                synthesis,
+                AnnealingPlacer::new(AnnealConfig::default()).unwrap(),
+                PathFinderRouter::new(PathFinderConfig::default()),
+            )
+        };
+        let synthesis = Synthesis::new(NAND.parse().unwrap());
+        let mut flow = synth_driver(synthesis.clone());
+        assert_eq!(flow.stage(), Stage::Synth);
+        assert_eq!(flow.run_stage().unwrap(), Stage::Pack);
+        assert_eq!(flow.design().cells().count(), design().cells().count());
+
+        // NOTE: The NAND is mapped to a single LUT.
+        let mut flow = synth_driver(synthesis.with_lut_map(LutMapConfig::new(2)));
+        flow.run().unwrap();
+        let types: Vec<_> = flow.design().cells().map(|(_, cell)| cell.ty()).collect();
+        assert_eq!(types.len(), 4);
+        assert_eq!(
+            types
+                .iter()
+                .filter(|ty| **ty == internal_cells::LUT)
+                .count(),
+            1
+        );
+        assert!(flow.route_state().unwrap().is_done());
+
+        let json = serde_json::to_string(&flow.checkpoint()).unwrap();
+        let checkpoint: Checkpoint<AnnealState, PathFinderState> =
+            serde_json::from_str(&json).unwrap();
+        let mut resumed = FlowDriver::resume(
+            &device,
+            AnnealingPlacer::new(AnnealConfig::default()).unwrap(),
+            PathFinderRouter::new(PathFinderConfig::default()),
+            checkpoint,
+        );
+        resumed.rewind(Stage::Synth);
+        assert_eq!(resumed.stage(), Stage::Synth);
+        assert_eq!(resumed.design().cells().count(), 0);
+        resumed.run().unwrap();
+        assert_eq!(resumed.design().cells().count(), 4);
+
+        // NOTE: Without a synthesis, the design cannot be reloaded.
+        let mut flow = driver(&device);
+        flow.run().unwrap();
+        flow.rewind(Stage::Synth);
+        assert_eq!(flow.stage(), Stage::Pack);
+    }
+
+    #[test]
+    fn test_synth_error() {
+        let device = crossbar_device();
+        let mut design: yosys::Design = NAND.parse().unwrap();
+        design.modules.clear();
+        let mut flow = TestDriver::from_yosys(
+            &device,
+            Synthesis::new(design).with_lut_map(LutMapConfig::new(2)),
+            AnnealingPlacer::new(AnnealConfig::default()).unwrap(),
+            PathFinderRouter::new(PathFinderConfig::default()),
+        );
+        assert!(matches!(flow.run_stage(), Err(Error::Synth(_))));
+    }
+
+    #[test]
+    fn test_resume() {
+        let device = crossbar_device();
+        let mut flow = driver(&device);
+        flow.run_stage().unwrap();
+        flow.run_stage().unwrap();
+        let json = serde_json::to_string(&flow.checkpoint()).unwrap();
+        let checkpoint: Checkpoint<AnnealState, PathFinderState> =
+            serde_json::from_str(&json).unwrap();
+        assert_eq!(checkpoint.stage(), Stage::Route);
+        assert_eq!(
+            checkpoint
+                .place_state
+                .as_ref()
+                .unwrap()
+                .placement()
+                .iter()
+                .count(),
+            5
+        );
+        let mut resumed = FlowDriver::resume(
+            &device,
+            AnnealingPlacer::new(AnnealConfig::default()).unwrap(),
+            PathFinderRouter::new(PathFinderConfig::default()),
+            checkpoint,
+        );
+        assert_eq!(resumed.run_stage().unwrap(), Stage::Done);
+        assert!(resumed.route_state().unwrap().is_done());
+        resumed.rewind(Stage::Place);
+        assert_eq!(resumed.stage(), Stage::Place);
+        assert!(resumed.netlist().is_some());
+        assert!(resumed.place_state().is_none() && resumed.route_state().is_none());
+        assert!(resumed
+            .design()
+            .cells()
+            .all(|(_, cell)| cell.bel().is_none()));
+    }
+}
@@ -0,0 +1,669 @@
+//! AIGER serialization.
+//!
+//! Both the ASCII (`aag`) and binary (`aig`) formats are supported, with
+//! latches, their initial values and the symbol table. AND's are read as
+//! [BinaryOp::And] nodes, complemented literals as [UnaryOp::Not] nodes and
+//! latches as [SeqOp::Ff] nodes. Latches without an initial value are read
+//! without the [INIT] attribute.
+//!
+//! References:
+//! - https://fmv.jku.at/aiger/FORMAT.aiger
+
+use std::collections::HashMap;
+use std::fs;
+use std::io::{self, Read, Write};
+use std::path::Path;
+
+use thiserror::Error;
+
+use super::blif;
+use crate::ir::export::{self, cell_type};
+use crate::ir::graph::{self, Edge, Graph, Node, NodeData, NodeKind, INIT};
+use crate::ir::ops::{AnyOp, BinaryOp, ConstOp, SeqOp, UnaryOp};
+
+#[derive(Debug, Error)]
+pub enum Error {
+    #[error(transparent)]
+    IO(#[from] io::Error),
+    #[error("invalid AIGER: {0}")]
+    Syntax(String),
+    #[error("literal {0} is not defined")]
+    UndefinedLiteral(usize),
+    #[error(r#""{0}" not supported"#)]
+    Unsupported(String),
+    #[error(transparent)]
+    Graph(#[from] graph::Error),
+    #[error(transparent)]
+    Export(#[from] export::Error),
+}
+
+pub type Result<T> = std::result::Result<T, Error>;
+
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub enum Format {
+    Ascii,
+    #[default]
+    Binary,
+}
+
+fn syntax_error<T>(message: impl Into<String>) -> Result<T> {
+    Err(Error::Syntax(message.into()))
+}
+
+struct Cursor<'a> {
+    bytes: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Cursor<'a> {
+    fn line(&mut self) -> Result<Option<&'a str>> {
+        if self.pos >= self.bytes.len() {
+            return Ok(None);
+        }
+        let rest = &self.bytes[self.pos..];
+        let end = rest.iter().position(|b| *b == b'\n').unwrap_or(rest.len());
+        self.pos += end + 1;
+        match std::str::from_utf8(&rest[..end]) {
+            Ok(line) => Ok(Some(line.trim_end_matches('\r'))),
+            Err(_) => syntax_error("line should be UTF-8"),
+        }
+    }
+
+    fn numbers(&mut self, min: usize, max: usize) -> Result<Vec<usize>> {
+        let Some(line) = self.line()? else {
+            return syntax_error("unexpected end of file");
+        };
+        let numbers = line
+            .split_whitespace()
+            .map(|number| number.parse())
+            .collect::<std::result::Result<Vec<usize>, _>>();
+        match numbers {
+            Ok(numbers) if (min..=max).contains(&numbers.len()) => Ok(numbers),
+            _ => syntax_error(format!(r#"invalid line "{line}""#)),
+        }
+    }
+
+    fn varint(&mut self) -> Result<usize> {
+        let mut value = 0;
+        for shift in (0..usize::BITS).step_by(7) {
+            let Some(&byte) = self.bytes.get(self.pos) else {
+                return syntax_error("unexpected end of file");
+            };
+            self.pos += 1;
+            value |= usize::from(byte & 0x7f) << shift;
+            if byte & 0x80 == 0 {
+                return Ok(value);
+            }
+        }
+        syntax_error("invalid delta")
+    }
+}
+
+fn write_varint(bytes: &mut Vec<u8>, mut value: usize) {
+    while value >= 0x80 {
+        bytes.push((value & 0x7f) as u8 | 0x80);
+        value >>= 7;
+    }
+    bytes.push(value as u8);
+}
+
+struct Latch {
+    literal: usize,
+    next: usize,
+    init: Option<bool>,
+}
+
+/// Maps the literals of an AIG to the nodes of a graph.
+struct Literals {
+    drivers: Vec<Option<Node>>,
+    nots: HashMap<usize, Node>,
+    consts: [Option<Node>; 2],
+}
+
+impl Literals {
+    fn define(&mut self, literal: usize, node: Node) -> Result<()> {
+        match self.drivers.get_mut(literal / 2) {
+            Some(driver @ None) if literal.is_multiple_of(2) && literal > 0 => {
+                *driver = Some(node);
+                Ok(())
+            }
+            _ => syntax_error(format!("invalid definition of literal {literal}")),
+        }
+    }
+
+    fn node(&mut self, graph: &mut Graph, literal: usize) -> Result<Node> {
+        if literal < 2 {
+            let k = if literal == 1 {
+                ConstOp::Unit
+            } else {
+                ConstOp::Zero
+            };
+            return Ok(*self.consts[literal].get_or_insert_with(|| graph.add_const(k)));
+        }
+        let Some(&Some(node)) = self.drivers.get(literal / 2) else {
+            return Err(Error::UndefinedLiteral(literal));
+        };
+        if literal.is_multiple_of(2) {
+            return Ok(node);
+        }
+        Ok(*self.nots.entry(literal / 2).or_insert_with(|| {
+            let not = graph.add_node(NodeData::new_op(UnaryOp::Not));
+            graph.add_edge(Edge {
+                source: node,
+                sink: not,
+            });
+            not
+        }))
+    }
+}
+
+/// Parse the ASCII or binary AIGER `bytes`.
+pub fn parse(bytes: &[u8]) -> Result<Graph> {
+    let mut cursor = Cursor { bytes, pos: 0 };
+    let Some(header) = cursor.line()? else {
+        return syntax_error("missing header");
+    };
+    let (format, counts) = match header.split_once(' ') {
+        Some(("aag", counts)) => (Format::Ascii, counts),
+        Some(("aig", counts)) => (Format::Binary, counts),
+        _ => return syntax_error(format!(r#"invalid header "{header}""#)),
+    };
+    let counts = Cursor {
+        bytes: counts.as_bytes(),
+        pos: 0,
+    }
+    .numbers(5, 9)?;
+    if counts[5..].iter().any(|count| *count > 0) {
+        return Err(Error::Unsupported(header.to_string()));
+    }
+    let [max_var, n_inputs, n_latches, n_outputs, n_ands] = counts[..5] else {
+        unreachable!("header should have 5 counts");
+    };
+
+    let mut inputs = Vec::with_capacity(n_inputs);
+    for i in 0..n_inputs {
+        inputs.push(match format {
+            Format::Ascii => cursor.numbers(1, 1)?[0],
+            Format::Binary => 2 * (i + 1),
+        });
+    }
+    let mut latches = Vec::with_capacity(n_latches);
+    for i in 0..n_latches {
+        let (literal, line) = match format {
+            Format::Ascii => {
+                let line = cursor.numbers(2, 3)?;
+                (line[0], line[1..].to_vec())
+            }
+            Format::Binary => (2 * (n_inputs + i + 1), cursor.numbers(1, 2)?),
+        };
+        let init = match line.get(1) {
+            None | Some(0) => Some(false),
+            Some(1) => Some(true),
+            Some(init) if *init == literal => None,
+            Some(init) => return syntax_error(format!("invalid initial value {init}")),
+        };
+        latches.push(Latch {
+            literal,
+            next: line[0],
+            init,
+        });
+    }
+    let mut outputs = Vec::with_capacity(n_outputs);
+    for _ in 0..n_outputs {
+        outputs.push(cursor.numbers(1, 1)?[0]);
+    }
+    let mut ands = Vec::with_capacity(n_ands);
+    for i in 0..n_ands {
+        ands.push(match format {
+            Format::Ascii => {
+                let line = cursor.numbers(3, 3)?;
+                [line[0], line[1], line[2]]
+            }
+            Format::Binary => {
+                let lhs = 2 * (n_inputs + n_latches + i + 1);
+                let rhs0 = lhs.checked_sub(cursor.varint()?);
+                let rhs1 = rhs0.and_then(|rhs0| rhs0.checked_sub(cursor.varint().ok()?));
+                let (Some(rhs0), Some(rhs1)) = (rhs0, rhs1) else {
+                    return syntax_error(format!("invalid AND {lhs}"));
+                };
+                [lhs, rhs0, rhs1]
+            }
+        });
+    }
+    let mut symbols = HashMap::new();
+    while let Some(line) = cursor.line()? {
+        if line == "c" {
+            break;
+        }
+        let symbol = line.split_once(' ').and_then(|(position, name)| {
+            let (kind, position) = position.split_at_checked(1)?;
+            Some(((kind, position.parse::<usize>().ok()?), name))
+        });
+        match symbol {
+            Some((key, name)) => symbols.insert(key, name),
+            None if line.is_empty() => continue,
+            None => return syntax_error(format!(r#"invalid symbol "{line}""#)),
+        };
+    }
+    let names = |kind: &str, count: usize| -> Vec<String> {
+        (0..count)
+            .map(|i| match symbols.get(&(kind, i)) {
+                Some(name) => name.to_string(),
+                None => format!("{kind}{i}"),
+            })
+            .collect()
+    };
+
+    let mut graph = Graph::default();
+    let mut literals = Literals {
+        drivers: vec![None; max_var + 1],
+        nots: HashMap::new(),
+        consts: [None; 2],
+    };
+    let input_names = names("i", n_inputs);
+    let input_names: Vec<_> = input_names.iter().map(String::as_str).collect();
+    let mut literal = inputs.iter();
+    for (name, nets) in blif::ports(&input_names) {
+        for node in graph.add_input(&name, nets.len()) {
+            literals.define(*literal.next().expect("input should exist"), node)?;
+        }
+    }
+    let output_names = names("o", n_outputs);
+    let output_names: Vec<_> = output_names.iter().map(String::as_str).collect();
+    let mut sinks = Vec::with_capacity(n_outputs);
+    for (name, nets) in blif::ports(&output_names) {
+        sinks.extend(graph.add_output(&name, nets.len()));
+    }
+    let mut latch_nodes = Vec::with_capacity(n_latches);
+    for (i, latch) in latches.iter().enumerate() {
+        let mut data = NodeData::new_seq(SeqOp::Ff);
+        if let Some(name) = symbols.get(&("l", i)) {
+            data = data.with_name(name);
+        }
+        if let Some(init) = latch.init {
+            data.attributes
+                .insert(INIT.to_string(), u8::from(init).to_string());
+        }
+        let node = graph.add_node(data);
+        literals.define(latch.literal, node)?;
+        latch_nodes.push(node);
+    }
+    let mut and_nodes = Vec::with_capacity(n_ands);
+    for [lhs, ..] in &ands {
+        let node = graph.add_node(NodeData::new_op(BinaryOp::And));
+        literals.define(*lhs, node)?;
+        and_nodes.push(node);
+    }
+
+    for (latch, sink) in latches.iter().zip(latch_nodes) {
+        let source = literals.node(&mut graph, latch.next)?;
+        graph.add_edge(Edge { source, sink });
+    }
+    for ([_, rhs0, rhs1], sink) in ands.into_iter().zip(and_nodes) {
+        for rhs in [rhs0, rhs1] {
+            let source = literals.node(&mut graph, rhs)?;
+            graph.add_edge(Edge { source, sink });
+        }
+    }
+    for (output, sink) in outputs.into_iter().zip(sinks) {
+        let source = literals.node(&mut graph, output)?;
+        graph.add_edge(Edge { source, sink });
+    }
+    Ok(graph)
+}
+
+pub fn from_reader<R>(mut reader: R) -> Result<Graph>
+where
+    R: Read,
+{
+    let mut bytes = Vec::new();
+    reader.read_to_end(&mut bytes)?;
+    parse(&bytes)
+}
+
+pub fn from_file<P>(path: P) -> Result<Graph>
+where
+    P: AsRef<Path>,
+{
+    parse(&fs::read(path)?)
+}
+
+/// A structurally hashed AIG under construction.
+struct Aig {
+    n_vars: usize,
+    ands: Vec<(usize, usize)>,
+    hash: HashMap<(usize, usize), usize>,
+}
+
+impl Aig {
+    fn and(&mut self, a: usize, b: usize) -> usize {
+        let (a, b) = (a.max(b), a.min(b));
+        match (a, b) {
+            (_, 0) => 0,
+            (a, 1) => a,
+            (a, b) if a == b => a,
+            (a, b) if a == b ^ 1 => 0,
+            _ => *self.hash.entry((a, b)).or_insert_with(|| {
+                self.n_vars += 1;
+                self.ands.push((a, b));
+                2 * self.n_vars
+            }),
+        }
+    }
+
+    fn or(&mut self, a: usize, b: usize) -> usize {
+        self.and(a ^ 1, b ^ 1) ^ 1
+    }
+
+    fn mux(&mut self, s: usize, t: usize, e: usize) -> usize {
+        match (t, e) {
+            (t, e) if t == e => t,
+            (1, e) => self.or(s, e),
+            (0, e) => self.and(s ^ 1, e),
+            (t, 0) => self.and(s, t),
+            (t, 1) => self.or(s ^ 1, t),
+            (t, e) => {
+                let t = self.and(s, t);
+                let e = self.and(s ^ 1, e);
+                self.or(t, e)
+            }
+        }
+    }
+
+    /// The literal of `op` applied to `inputs`, expanding the cofactors of
+    /// its inputs from last to first with `values[..n_inputs]` fixed.
+    fn cofactor(
+        &mut self,
+        op: &AnyOp,
+        inputs: &[usize],
+        values: &mut [bool],
+        n_inputs: usize,
+    ) -> usize {
+        let Some(i) = n_inputs.checked_sub(1) else {
+            return usize::from(op.eval(values));
+        };
+        values[i] = true;
+        let t = self.cofactor(op, inputs, values, i);
+        values[i] = false;
+        let e = self.cofactor(op, inputs, values, i);
+        self.mux(inputs[i], t, e)
+    }
+
+    fn op(&mut self, op: &AnyOp, inputs: &[usize]) -> usize {
+        let mut values = vec![false; inputs.len()];
+        self.cofactor(op, inputs, &mut values, inputs.len())
+    }
+}
+
+fn fanins(graph: &Graph, node: Node, expected: usize) -> Result<&[Node]> {
+    let fanins = graph.fanins(node);
+    if fanins.len() != expected {
+        return Err(export::Error::Arity {
+            node,
+            expected,
+            found: fanins.len(),
+        }
+        .into());
+    }
+    Ok(fanins)
+}
+
+/// Write `graph` to `writer` as an AIGER file in `format`.
+///
+/// Gates are decomposed into AND's and complemented literals, splitters are
+/// removed and [SeqOp::Ff] nodes are written as latches. Other nodes are not
+/// supported.
+pub fn write<W>(graph: &Graph, format: Format, mut writer: W) -> Result<()>
+where
+    W: Write,
+{
+    let of_kind = |kind: fn(&NodeKind) -> bool| -> Vec<Node> {
+        graph
+            .node_ids()
+            .filter(|node| kind(&graph.node(*node).kind))
+            .collect()
+    };
+    let inputs = of_kind(|kind| matches!(kind, NodeKind::Source));
+    let latches = of_kind(|kind| matches!(kind, NodeKind::Seq(SeqOp::Ff)));
+    let outputs = of_kind(|kind| matches!(kind, NodeKind::Sink));
+
+    let mut literals = vec![None; graph.n_nodes()];
+    for (i, node) in inputs.iter().chain(&latches).enumerate() {
+        literals[node.index()] = Some(2 * (i + 1));
+    }
+    let literal = |literals: &[Option<usize>], node: Node| {
+        literals[node.index()].expect("fanins should precede their sinks")
+    };
+    let mut aig = Aig {
+        n_vars: inputs.len() + latches.len(),
+        ands: Vec::new(),
+        hash: HashMap::new(),
+    };
+    for node in graph.topological_order()? {
+        match graph.node(node).kind {
+            NodeKind::Source | NodeKind::Seq(SeqOp::Ff) | NodeKind::Sink => {}
+            NodeKind::Gate(op) => {
+                let inputs: Vec<_> = fanins(graph, node, op.arity())?
+                    .iter()
+                    .map(|fanin| literal(&literals, *fanin))
+                    .collect();
+                literals[node.index()] = Some(aig.op(&op, &inputs));
+            }
+            NodeKind::Splitter => {
+                literals[node.index()] = Some(literal(&literals, fanins(graph, node, 1)?[0]));
+            }
+            ref kind => {
+                let ty = cell_type(kind).map_or_else(|| format!("{kind:?}"), |(ty, ..)| ty);
+                return Err(Error::Unsupported(ty));
+            }
+        }
+    }
+    let next = |node: Node| -> Result<usize> { Ok(literal(&literals, fanins(graph, node, 1)?[0])) };
+
+    let mut bytes = Vec::new();
+    let header = match format {
+        Format::Ascii => "aag",
+        Format::Binary => "aig",
+    };
+    writeln!(
+        bytes,
+        "{header} {} {} {} {} {}",
+        aig.n_vars,
+        inputs.len(),
+        latches.len(),
+        outputs.len(),
+        aig.ands.len()
+    )?;
+    if format == Format::Ascii {
+        for node in &inputs {
+            writeln!(bytes, "{}", literal(&literals, *node))?;
+        }
+    }
+    for node in &latches {
+        if format == Format::Ascii {
+            write!(bytes, "{} ", literal(&literals, *node))?;
+        }
+        write!(bytes, "{}", next(*node)?)?;
+        // NOTE: Latches are initialized to 0 by default.
+        match graph.node(*node).attributes.get(INIT).map(String::as_str) {
+            Some("1") => write!(bytes, " 1")?,
+            Some(_) => {}
+            None => write!(bytes, " {}", literal(&literals, *node))?,
+        }
+        writeln!(bytes)?;
+    }
+    for node in &outputs {
+        writeln!(bytes, "{}", next(*node)?)?;
+    }
+    let n_defined = inputs.len() + latches.len();
+    for (i, (rhs0, rhs1)) in aig.ands.iter().enumerate() {
+        let lhs = 2 * (n_defined + i + 1);
+        match format {
+            Format::Ascii => writeln!(bytes, "{lhs} {rhs0} {rhs1}")?,
+            Format::Binary => {
+                write_varint(&mut bytes, lhs - rhs0);
+                write_varint(&mut bytes, rhs0 - rhs1);
+            }
+        }
+    }
+    for (kind, nodes) in [("i", &inputs), ("l", &latches), ("o", &outputs)] {
+        for (i, node) in nodes.iter().enumerate() {
+            if let Some(name) = graph.node(*node).name {
+                writeln!(bytes, "{kind}{i} {name}")?;
+            }
+        }
+    }
+    writer.write_all(&bytes)?;
+    Ok(())
+}
+
+/// The AIGER file of `graph` in `format`.
+pub fn to_bytes(graph: &Graph, format: Format) -> Result<Vec<u8>> {
+    let mut bytes = Vec::new();
+    write(graph, format, &mut bytes)?;
+    Ok(bytes)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::interchange::yosys;
+    use crate::ir::testing::{bits, eval};
+
+    fn example(json: &str, module: &str) -> Graph {
+        let design: yosys::Design = json.parse().unwrap();
+        Graph::try_from(design.modules[module].clone()).unwrap()
+    }
+
+    fn count(graph: &Graph, kind: fn(&NodeKind) -> bool) -> usize {
+        graph.nodes().filter(|node| kind(&node.kind)).count()
+    }
+
+    #[test]
+    fn test_parse_ascii() {
+        // NOTE: A toggle flip-flop with an enable and a reset.
+        let graph = parse(
+            b"aag 7 2 1 2 4\n2\n4\n6 14 1\n6\n7\n8 3 6\n10 2 7\n12 9 11\n14 4 12\ni0 enable\ni1 reset\nl0 q\no0 Q\nc\ncomment\n",
+        )
+        .unwrap();
+        let ports: Vec<_> = graph.ports().iter().map(|port| port.name()).collect();
+        assert_eq!(ports, ["enable", "reset", "Q", "o1"]);
+        let q = graph.find("q").unwrap();
+        assert!(matches!(graph.node(q).kind, NodeKind::Seq(SeqOp::Ff)));
+        assert_eq!(graph.node(q).attributes[INIT], "1");
+        assert_eq!(
+            count(&graph, |kind| matches!(
+                kind,
+                NodeKind::Gate(AnyOp::Binary(BinaryOp::And))
+            )),
+            4
+        );
+        // NOTE: Literals 3, 7, 9 and 11 are complemented.
+        assert_eq!(
+            count(&graph, |kind| matches!(
+                kind,
+                NodeKind::Gate(AnyOp::Unary(UnaryOp::Not))
+            )),
+            4
+        );
+    }
+
+    #[test]
+    fn test_parse_binary() {
+        let graph = parse(include_bytes!("../../../../examples/alu/add4.aig")).unwrap();
+        let add4 = example(
+            include_str!("../../../../examples/alu/add4_simplemap.json"),
+            "add4",
+        );
+        assert_eq!(graph.ports().len(), 14);
+        for x in 0..1 << 9 {
+            let inputs: Vec<_> = bits(x, 9).collect();
+            // NOTE: The file has no symbol table and lists `S` before `Cout`.
+            let mut outputs = eval(&add4, &inputs);
+            outputs.rotate_left(1);
+            assert_eq!(eval(&graph, &inputs), outputs, "{inputs:?}");
+        }
+
+        let graph = parse(include_bytes!("../../../../examples/sr/sr.aig")).unwrap();
+        assert_eq!(
+            count(&graph, |kind| matches!(kind, NodeKind::Seq(SeqOp::Ff))),
+            5
+        );
+        assert!(graph
+            .nodes()
+            .filter(|node| matches!(node.kind, NodeKind::Seq(_)))
+            .all(|node| node.attributes[INIT] == "0"));
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let add4 = example(
+            include_str!("../../../../examples/alu/add4_simplemap.json"),
+            "add4",
+        );
+        let sr = parse(include_bytes!("../../../../examples/sr/sr.aig")).unwrap();
+        for graph in [add4, sr] {
+            let n_inputs = count(&graph, |kind| matches!(kind, NodeKind::Source));
+            for format in [Format::Ascii, Format::Binary] {
+                let copy = parse(&to_bytes(&graph, format).unwrap()).unwrap();
+                let names = |graph: &Graph| {
+                    graph
+                        .ports()
+                        .iter()
+                        .map(|port| (port.name().to_string(), port.width()))
+                        .collect::<Vec<_>>()
+                };
+                assert_eq!(names(&copy), names(&graph));
+                for x in 0..1 << n_inputs {
+                    let inputs: Vec<_> = bits(x, n_inputs).collect();
+                    assert_eq!(eval(&copy, &inputs), eval(&graph, &inputs));
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_write() {
+        let mut graph = Graph::default();
+        let inputs: Vec<_> = graph.add_input("a", 2).collect();
+        let xor = graph.add_node(NodeData::new_op(BinaryOp::Xor));
+        let q = graph.add_node(NodeData::new_seq(SeqOp::Ff).with_name("q"));
+        let outputs: Vec<_> = graph.add_output("y", 2).collect();
+        graph.add_edges([
+            Edge {
+                source: inputs[0],
+                sink: xor,
+            },
+            Edge {
+                source: inputs[1],
+                sink: xor,
+            },
+            Edge {
+                source: xor,
+                sink: q,
+            },
+            Edge {
+                source: xor,
+                sink: outputs[0],
+            },
+            Edge {
+                source: q,
+                sink: outputs[1],
+            },
+        ]);
+        let aag = String::from_utf8(to_bytes(&graph, Format::Ascii).unwrap()).unwrap();
+        assert_eq!(
+            aag,
+            "aag 6 2 1 2 3\n2\n4\n6 13 6\n13\n6\n8 4 3\n10 5 2\n12 11 9\ni0 a[0]\ni1 a[1]\nl0 q\no0 y[0]\no1 y[1]\n"
+        );
+
+        let sr = example(
+            include_str!("../../../../examples/sr/sr_simplemap.json"),
+            "sr",
+        );
+        assert!(matches!(
+            to_bytes(&sr, Format::Binary),
+            Err(Error::Unsupported(ty)) if ty.starts_with("$_SDFF")
+        ));
+    }
+}
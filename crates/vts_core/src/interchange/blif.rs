@@ -0,0 +1,685 @@
+//! BLIF netlist serialization.
+//!
+//! Files of a single `.model` are read, as `.subckt`s of other models are not
+//! resolved. Every `.names` is read as a
+//! [Lut] of up to [Lut::MAX_INPUTS] inputs, or as a constant. `.subckt`s are
+//! read as the gate-level cells of `Graph: TryFrom<yosys::Module>`.
+//!
+//! References:
+//! - https://people.eecs.berkeley.edu/~alanmi/publications/other/blif.pdf
+
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::io::{self, Read, Write};
+use std::path::Path;
+
+use thiserror::Error;
+
+use crate::ir::export::{self, cell_type};
+use crate::ir::graph::{self, Edge, Graph, Node, NodeData, NodeKind};
+use crate::ir::ops::{AnyOp, ConstOp, FlipFlop, Latch, Lut, Polarity, SeqOp};
+
+#[derive(Debug, Error)]
+pub enum Error {
+    #[error(transparent)]
+    IO(#[from] io::Error),
+    #[error("line {line}: {message}")]
+    Syntax { line: usize, message: String },
+    #[error(r#"net "{0}" is not driven"#)]
+    UndrivenNet(String),
+    #[error(r#"net "{0}" is driven more than once"#)]
+    MultipleDrivers(String),
+    #[error(r#""{0}" not supported"#)]
+    Unsupported(String),
+    #[error(transparent)]
+    Export(#[from] export::Error),
+}
+
+pub type Result<T> = std::result::Result<T, Error>;
+
+pub use crate::ir::graph::INIT;
+
+fn syntax_error<T>(line: usize, message: impl Into<String>) -> Result<T> {
+    Err(Error::Syntax {
+        line,
+        message: message.into(),
+    })
+}
+
+/// The tokens of every logical line of `text`, with the number of its first
+/// line, joining continued lines and dropping comments.
+fn lines(text: &str) -> Vec<(usize, Vec<&str>)> {
+    let mut lines = Vec::new();
+    let mut current: Option<(usize, Vec<&str>)> = None;
+    for (i, line) in text.lines().enumerate() {
+        let line = line.split('#').next().unwrap_or_default();
+        let (line, continued) = match line.trim_end().strip_suffix('\\') {
+            Some(line) => (line, true),
+            None => (line, false),
+        };
+        let (_, tokens) = current.get_or_insert_with(|| (i + 1, Vec::new()));
+        tokens.extend(line.split_whitespace());
+        if !continued {
+            let line = current.take().expect("line should be started");
+            if !line.1.is_empty() {
+                lines.push(line);
+            }
+        }
+    }
+    lines.extend(current.filter(|(_, tokens)| !tokens.is_empty()));
+    lines
+}
+
+/// The base name and bit index of a net named `base[bit]`.
+fn split_bit(name: &str) -> Option<(&str, usize)> {
+    let (base, bit) = name.strip_suffix(']')?.rsplit_once('[')?;
+    Some((base, bit.parse().ok()?))
+}
+
+/// Group `names` into ports, merging runs of at least two bits `base[0]`,
+/// `base[1]`, ... into a port `base`.
+pub(super) fn ports<'a>(names: &[&'a str]) -> Vec<(String, Vec<&'a str>)> {
+    let mut ports: Vec<(String, Vec<&str>)> = Vec::new();
+    for &name in names {
+        if let (Some((base, bit)), Some((port, bits))) = (split_bit(name), ports.last_mut()) {
+            let continues = bits
+                .first()
+                .and_then(|first| split_bit(first))
+                .is_some_and(|(first, _)| first == base && bit == bits.len());
+            if continues {
+                *port = base.to_string();
+                bits.push(name);
+                continue;
+            }
+        }
+        ports.push((name.to_string(), vec![name]));
+    }
+    ports
+}
+
+/// The truth table of a `.names` cover of `n_inputs`.
+fn cover_table(line: usize, n_inputs: usize, cover: &[(usize, Vec<&str>)]) -> Result<Lut> {
+    let Some(lut) = Lut::new(n_inputs, 0) else {
+        return Err(Error::Unsupported(format!("{n_inputs}-input .names")));
+    };
+    let mut on_set = None;
+    let mut table = 0u64;
+    for (line, tokens) in cover {
+        let (plane, output) = match tokens[..] {
+            [plane, output] => (plane, output),
+            _ => return syntax_error(*line, "expected input plane and output"),
+        };
+        if plane.len() != n_inputs {
+            return syntax_error(*line, format!("expected {n_inputs} inputs"));
+        }
+        let output = match output {
+            "1" => true,
+            "0" => false,
+            _ => return syntax_error(*line, format!(r#"invalid output "{output}""#)),
+        };
+        if *on_set.get_or_insert(output) != output {
+            return syntax_error(*line, "cover should only have one output value");
+        }
+        for minterm in 0..1usize << n_inputs {
+            let mut matches = true;
+            for (i, value) in plane.chars().enumerate() {
+                let bit = minterm >> i & 1 == 1;
+                matches &= match value {
+                    '1' => bit,
+                    '0' => !bit,
+                    '-' => true,
+                    _ => return syntax_error(*line, format!("invalid input value '{value}'")),
+                };
+            }
+            if matches {
+                table |= 1 << minterm;
+            }
+        }
+    }
+    if on_set == Some(false) {
+        table = !table;
+    }
+    Lut::new(lut.n_inputs(), table).map_or_else(|| syntax_error(line, "invalid cover"), Ok)
+}
+
+/// A gate of a model, with the nets driving its inputs.
+struct Gate<'a> {
+    data: NodeData,
+    inputs: Vec<&'a str>,
+    output: &'a str,
+}
+
+fn names<'a>(line: usize, nets: &[&'a str], cover: &[(usize, Vec<&str>)]) -> Result<Gate<'a>> {
+    let Some((&output, inputs)) = nets.split_last() else {
+        return syntax_error(line, ".names should have an output");
+    };
+    let data = if inputs.is_empty() {
+        let value = match cover {
+            [] => false,
+            [(_, tokens)] if tokens[..] == ["1"] => true,
+            [(_, tokens)] if tokens[..] == ["0"] => false,
+            _ => return syntax_error(line, "invalid constant cover"),
+        };
+        NodeData::new_op(if value { ConstOp::Unit } else { ConstOp::Zero })
+    } else {
+        NodeData::new_op(cover_table(line, inputs.len(), cover)?)
+    };
+    Ok(Gate {
+        data,
+        inputs: inputs.to_vec(),
+        output,
+    })
+}
+
+fn latch<'a>(line: usize, tokens: &[&'a str]) -> Result<Gate<'a>> {
+    let (input, output, control, init) = match *tokens {
+        [input, output] => (input, output, None, None),
+        [input, output, init] => (input, output, None, Some(init)),
+        [input, output, ty, control] => (input, output, Some((ty, control)), None),
+        [input, output, ty, control, init] => (input, output, Some((ty, control)), Some(init)),
+        _ => return syntax_error(line, "invalid .latch"),
+    };
+    let (op, inputs) = match control {
+        None | Some((_, "NIL")) => (SeqOp::Ff, vec![input]),
+        Some((ty, control)) => {
+            let op = match ty {
+                "re" => SeqOp::FlipFlop(FlipFlop {
+                    clock: Polarity::Positive,
+                    enable: None,
+                    reset: None,
+                }),
+                "fe" => SeqOp::FlipFlop(FlipFlop {
+                    clock: Polarity::Negative,
+                    enable: None,
+                    reset: None,
+                }),
+                "ah" => SeqOp::Latch(Latch {
+                    enable: Polarity::Positive,
+                    reset: None,
+                }),
+                "al" => SeqOp::Latch(Latch {
+                    enable: Polarity::Negative,
+                    reset: None,
+                }),
+                _ => return Err(Error::Unsupported(format!(r#""{ty}" latches"#))),
+            };
+            (op, vec![input, control])
+        }
+    };
+    let mut data = NodeData::new_seq(op);
+    match init {
+        Some(init @ ("0" | "1")) => {
+            data.attributes.insert(INIT.to_string(), init.to_string());
+        }
+        Some("2" | "3") | None => {}
+        Some(init) => return syntax_error(line, format!(r#"invalid initial value "{init}""#)),
+    }
+    Ok(Gate {
+        data,
+        inputs,
+        output,
+    })
+}
+
+fn subckt<'a>(line: usize, tokens: &[&'a str]) -> Result<Gate<'a>> {
+    let Some((&model, pins)) = tokens.split_first() else {
+        return syntax_error(line, ".subckt should have a model");
+    };
+    let Some((kind, ports, output)) = graph::cell_kind(model) else {
+        return Err(Error::Unsupported(format!("subcircuit {model}")));
+    };
+    let mut connections = HashMap::new();
+    for pin in pins {
+        let Some((formal, actual)) = pin.split_once('=') else {
+            return syntax_error(line, format!(r#"invalid pin "{pin}""#));
+        };
+        connections.insert(formal, actual);
+    }
+    let pin = |port: &str| match connections.get(port) {
+        Some(net) => Ok(*net),
+        None => syntax_error(line, format!(r#"subcircuit should connect "{port}""#)),
+    };
+    let inputs = ports.iter().map(|port| pin(port)).collect::<Result<_>>()?;
+    Ok(Gate {
+        data: NodeData::from(kind),
+        inputs,
+        output: pin(output)?,
+    })
+}
+
+/// Parse the single model of the BLIF `text`.
+pub fn parse(text: &str) -> Result<Graph> {
+    let lines = lines(text);
+    let mut lines = lines.iter().peekable();
+    let mut inputs = Vec::new();
+    let mut outputs = Vec::new();
+    let mut gates = Vec::new();
+    let mut model = None;
+    while let Some((line, tokens)) = lines.next() {
+        let (line, (&command, args)) = (*line, tokens.split_first().expect("line has tokens"));
+        if model.is_none() && command != ".model" {
+            return syntax_error(line, "expected .model");
+        }
+        match command {
+            ".model" if model.is_none() => model = Some(args.first().copied().unwrap_or_default()),
+            ".inputs" => inputs.extend_from_slice(args),
+            ".outputs" => outputs.extend_from_slice(args),
+            ".names" => {
+                let mut cover = Vec::new();
+                while let Some((line, tokens)) =
+                    lines.next_if(|(_, tokens)| !tokens[0].starts_with('.'))
+                {
+                    cover.push((*line, tokens.clone()));
+                }
+                gates.push(names(line, args, &cover)?);
+            }
+            ".latch" => gates.push(latch(line, args)?),
+            ".subckt" => gates.push(subckt(line, args)?),
+            ".model" => return Err(Error::Unsupported("multiple models".to_string())),
+            ".end" => break,
+            command if command.starts_with('.') => {
+                return Err(Error::Unsupported(command.to_string()));
+            }
+            _ => return syntax_error(line, format!(r#"unexpected "{command}""#)),
+        }
+    }
+    if let Some((line, tokens)) = lines.next() {
+        return match tokens[0] {
+            ".model" => Err(Error::Unsupported("multiple models".to_string())),
+            _ => syntax_error(*line, "expected end of file after .end"),
+        };
+    }
+
+    let mut graph = Graph::default();
+    let mut drivers = HashMap::new();
+    for (name, nets) in ports(&inputs) {
+        let nodes = graph.add_input(&name, nets.len());
+        for (net, node) in nets.into_iter().zip(nodes) {
+            if drivers.insert(net, node).is_some() {
+                return Err(Error::MultipleDrivers(net.to_string()));
+            }
+        }
+    }
+    let mut sinks = Vec::new();
+    for (name, nets) in ports(&outputs) {
+        let nodes = graph.add_output(&name, nets.len());
+        sinks.extend(nets.into_iter().zip(nodes));
+    }
+    // NOTE: Gates are named after the net they drive, unless it is an output
+    // and the name is taken by its sink.
+    let outputs: HashSet<_> = sinks.iter().map(|(net, _)| *net).collect();
+    let mut nodes = Vec::with_capacity(gates.len());
+    for gate in &gates {
+        let mut data = gate.data.clone();
+        if !outputs.contains(gate.output) {
+            data = data.with_name(gate.output);
+        }
+        let node = graph.add_node(data);
+        if drivers.insert(gate.output, node).is_some() {
+            return Err(Error::MultipleDrivers(gate.output.to_string()));
+        }
+        nodes.push(node);
+    }
+    let driver = |net: &str| {
+        drivers
+            .get(net)
+            .copied()
+            .ok_or_else(|| Error::UndrivenNet(net.to_string()))
+    };
+    for (gate, sink) in gates.iter().zip(nodes) {
+        for input in &gate.inputs {
+            graph.add_edge(Edge {
+                source: driver(input)?,
+                sink,
+            });
+        }
+    }
+    for (net, sink) in sinks {
+        graph.add_edge(Edge {
+            source: driver(net)?,
+            sink,
+        });
+    }
+    Ok(graph)
+}
+
+pub fn from_reader<R>(mut reader: R) -> Result<Graph>
+where
+    R: Read,
+{
+    let mut text = String::new();
+    reader.read_to_string(&mut text)?;
+    parse(&text)
+}
+
+pub fn from_file<P>(path: P) -> Result<Graph>
+where
+    P: AsRef<Path>,
+{
+    parse(&fs::read_to_string(path)?)
+}
+
+/// `candidate` if it is not `used`, and a name derived from `node` otherwise.
+fn unique_name(used: &mut HashSet<String>, node: Node, candidate: Option<&str>) -> String {
+    match candidate {
+        Some(name) if used.insert(name.to_string()) => name.to_string(),
+        _ => {
+            let mut name = format!("n{}", node.index());
+            while !used.insert(name.clone()) {
+                name.insert(0, '$');
+            }
+            name
+        }
+    }
+}
+
+/// The names of the nets driven by every node of `graph`, and of its sinks.
+///
+/// A node driving an output is named after the first output it drives, so
+/// that the output needs no buffer.
+fn net_names(graph: &Graph) -> Vec<String> {
+    let is_port = |node: &Node| matches!(graph.node(*node).kind, NodeKind::Source | NodeKind::Sink);
+    let mut used = HashSet::new();
+    let mut names = vec![String::new(); graph.n_nodes()];
+    for node in graph.node_ids().filter(is_port) {
+        names[node.index()] = unique_name(&mut used, node, graph.node(node).name.as_deref());
+    }
+    let mut driven = HashSet::new();
+    for node in graph.node_ids().filter(|node| !is_port(node)) {
+        let output = graph
+            .sinks(node)
+            .iter()
+            .find(|sink| matches!(graph.node(**sink).kind, NodeKind::Sink));
+        names[node.index()] = match output {
+            Some(output) if driven.insert(*output) => names[output.index()].clone(),
+            _ => unique_name(&mut used, node, graph.node(node).name.as_deref()),
+        };
+    }
+    names
+}
+
+/// Write `graph` to `writer` as the BLIF model `model`.
+pub fn write<W>(graph: &Graph, model: &str, mut writer: W) -> Result<()>
+where
+    W: Write,
+{
+    let arity = |node: Node, expected: usize| {
+        let found = graph.fanins(node).len();
+        if found == expected {
+            Ok(())
+        } else {
+            Err(export::Error::Arity {
+                node,
+                expected,
+                found,
+            })
+        }
+    };
+    let names = net_names(graph);
+    let net = |node: Node| names[node.index()].as_str();
+    let ports = |kind: fn(&NodeKind) -> bool| {
+        graph
+            .node_ids()
+            .filter(|node| kind(&graph.node(*node).kind))
+            .map(net)
+            .collect::<Vec<_>>()
+            .join(" ")
+    };
+    writeln!(writer, ".model {model}")?;
+    writeln!(
+        writer,
+        ".inputs {}",
+        ports(|kind| matches!(kind, NodeKind::Source))
+    )?;
+    writeln!(
+        writer,
+        ".outputs {}",
+        ports(|kind| matches!(kind, NodeKind::Sink))
+    )?;
+    for node in graph.node_ids() {
+        let data = graph.node(node);
+        let fanins: Vec<_> = graph.fanins(node).iter().map(|fanin| net(*fanin)).collect();
+        let init = data.attributes.get(INIT).map_or("", String::as_str);
+        match data.kind {
+            NodeKind::Source => {}
+            NodeKind::Sink => {
+                arity(node, 1)?;
+                // NOTE: Outputs driven by another output or by an input are
+                // driven through a buffer.
+                if fanins[0] != net(node) {
+                    writeln!(writer, ".names {} {}\n1 1", fanins[0], net(node))?;
+                }
+            }
+            NodeKind::Gate(AnyOp::Const(k)) => {
+                writeln!(writer, ".names {}", net(node))?;
+                if k == ConstOp::Unit {
+                    writeln!(writer, "1")?;
+                }
+            }
+            NodeKind::Gate(op) => {
+                let n_inputs = op.arity();
+                arity(node, n_inputs)?;
+                writeln!(writer, ".names {} {}", fanins.join(" "), net(node))?;
+                for minterm in 0..1usize << n_inputs {
+                    let inputs: Vec<_> = (0..n_inputs).map(|i| minterm >> i & 1 == 1).collect();
+                    if op.eval(&inputs) {
+                        let plane: String = inputs
+                            .iter()
+                            .map(|input| if *input { '1' } else { '0' })
+                            .collect();
+                        writeln!(writer, "{plane} 1")?;
+                    }
+                }
+            }
+            NodeKind::Seq(SeqOp::Ff) => {
+                arity(node, 1)?;
+                writeln!(writer, ".latch {} {} {init}", fanins[0], net(node))?;
+            }
+            NodeKind::Seq(
+                op @ (SeqOp::FlipFlop(FlipFlop {
+                    enable: None,
+                    reset: None,
+                    ..
+                })
+                | SeqOp::Latch(Latch { reset: None, .. })),
+            ) => {
+                arity(node, 2)?;
+                let ty = match op {
+                    SeqOp::FlipFlop(FlipFlop {
+                        clock: Polarity::Positive,
+                        ..
+                    }) => "re",
+                    SeqOp::FlipFlop(_) => "fe",
+                    SeqOp::Latch(Latch {
+                        enable: Polarity::Positive,
+                        ..
+                    }) => "ah",
+                    _ => "al",
+                };
+                writeln!(
+                    writer,
+                    ".latch {} {} {ty} {} {init}",
+                    fanins[0],
+                    net(node),
+                    fanins[1]
+                )?;
+            }
+            ref kind => {
+                let (ty, inputs, output) = cell_type(kind).expect("node should be a cell");
+                arity(node, inputs.len())?;
+                let pins: Vec<_> = inputs
+                    .iter()
+                    .zip(&fanins)
+                    .map(|(port, net)| format!("{port}={net}"))
+                    .collect();
+                writeln!(
+                    writer,
+                    ".subckt {ty} {} {output}={}",
+                    pins.join(" "),
+                    net(node)
+                )?;
+            }
+        }
+    }
+    writeln!(writer, ".end")?;
+    Ok(())
+}
+
+/// The BLIF model `model` of `graph`.
+pub fn to_string(graph: &Graph, model: &str) -> Result<String> {
+    let mut bytes = Vec::new();
+    write(graph, model, &mut bytes)?;
+    Ok(String::from_utf8(bytes).expect("BLIF should be UTF-8"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::interchange::yosys;
+    use crate::ir::testing::{bits, eval, value};
+
+    const FULL_ADDER: &str = r#"
+# full adder
+.model fa
+.inputs a b \
+  cin
+.outputs s cout
+.names a b cin s
+100 1
+010 1
+001 1
+111 1
+.names a b cin cout
+00- 0
+0-0 0
+-00 0
+.end
+"#;
+
+    #[test]
+    fn test_parse() {
+        let graph = parse(FULL_ADDER).unwrap();
+        let luts = graph
+            .nodes()
+            .filter(|node| matches!(node.kind, NodeKind::Gate(AnyOp::Lut(_))))
+            .count();
+        assert_eq!(luts, 2);
+        for x in 0..8 {
+            let inputs: Vec<_> = bits(x, 3).collect();
+            let sum = inputs.iter().filter(|bit| **bit).count() as u64;
+            assert_eq!(value(&eval(&graph, &inputs)), sum, "{inputs:?}");
+        }
+    }
+
+    #[test]
+    fn test_sequential() {
+        let graph = parse(
+            r#"
+.model seq
+.inputs d clk en
+.outputs q[0] q[1] q[2] one
+.latch d q[0] re clk 1
+.latch d q[1] 0
+.subckt $_DFFE_PP_ D=d C=clk E=en Q=q[2]
+.names one
+1
+.end
+"#,
+        )
+        .unwrap();
+        let ports: Vec<_> = graph
+            .ports()
+            .iter()
+            .map(|port| (port.name(), port.width()))
+            .collect();
+        assert_eq!(
+            ports,
+            [("d", 1), ("clk", 1), ("en", 1), ("q", 3), ("one", 1)]
+        );
+        let driver = |graph: &Graph, name: &str| graph.fanins(graph.find(name).unwrap())[0];
+        let q0 = driver(&graph, "q[0]");
+        let NodeKind::Seq(SeqOp::FlipFlop(flip_flop)) = graph.node(q0).kind else {
+            panic!("q[0] should be driven by a flip-flop");
+        };
+        assert_eq!(flip_flop.clock, Polarity::Positive);
+        assert_eq!(graph.node(q0).attributes[INIT], "1");
+        assert_eq!(
+            graph.fanins(q0),
+            [graph.find("d").unwrap(), graph.find("clk").unwrap()]
+        );
+        let q1 = driver(&graph, "q[1]");
+        assert!(matches!(graph.node(q1).kind, NodeKind::Seq(SeqOp::Ff)));
+        assert_eq!(graph.node(q1).attributes[INIT], "0");
+        let q2 = driver(&graph, "q[2]");
+        assert_eq!(graph.fanins(q2).len(), 3);
+        assert!(eval(&graph, &[true, true, true])[3]);
+
+        let copy = parse(&to_string(&graph, "seq").unwrap()).unwrap();
+        assert_eq!(copy.n_nodes(), graph.n_nodes());
+        assert_eq!(copy.edges().count(), graph.edges().count());
+        let q0 = driver(&copy, "q[0]");
+        assert_eq!(copy.node(q0).attributes[INIT], "1");
+    }
+
+    #[test]
+    fn test_round_trip() {
+        for (json, module) in [
+            (
+                include_str!("../../../../examples/alu/add4_simplemap.json"),
+                "add4",
+            ),
+            (
+                include_str!("../../../../examples/alu/add4_flowmap.json"),
+                "add4",
+            ),
+            (
+                include_str!("../../../../examples/sr/sr_simplemap.json"),
+                "sr",
+            ),
+        ] {
+            let design: yosys::Design = json.parse().unwrap();
+            let graph = Graph::try_from(design.modules[module].clone()).unwrap();
+            let blif = to_string(&graph, module).unwrap();
+            let copy = parse(&blif).unwrap();
+            assert_eq!(copy.n_nodes(), graph.n_nodes(), "{module}");
+            assert_eq!(copy.edges().count(), graph.edges().count(), "{module}");
+            let ports = |graph: &Graph| {
+                graph
+                    .ports()
+                    .iter()
+                    .map(|port| (port.name().to_string(), port.width()))
+                    .collect::<Vec<_>>()
+            };
+            assert_eq!(ports(&copy), ports(&graph), "{module}");
+            let n_inputs = graph.ports().iter().map(|port| port.width()).sum::<usize>();
+            for x in 0..1 << n_inputs.min(10) {
+                let inputs: Vec<_> = bits(x, n_inputs).collect();
+                assert_eq!(eval(&copy, &inputs), eval(&graph, &inputs), "{module}");
+            }
+        }
+    }
+
+    #[test]
+    fn test_errors() {
+        assert!(matches!(
+            parse(".model m\n.outputs y\n.names a y\n1 1\n.end"),
+            Err(Error::UndrivenNet(net)) if net == "a"
+        ));
+        assert!(matches!(
+            parse(".model m\n.inputs a\n.outputs y\n.names a y\n1 1\n.names a y\n0 1\n.end"),
+            Err(Error::MultipleDrivers(net)) if net == "y"
+        ));
+        assert!(matches!(
+            parse(".model m\n.inputs a\n.outputs y\n.names a y\n11 1\n.end"),
+            Err(Error::Syntax { line: 5, .. })
+        ));
+        assert!(matches!(
+            parse(".model m\n.inputs a\n.outputs y\n.subckt top A=a Y=y\n.end"),
+            Err(Error::Unsupported(_))
+        ));
+        assert!(matches!(
+            parse(".model m\n.outputs y\n.names y\n.end\n.model n\n.outputs z\n.names z\n.end"),
+            Err(Error::Unsupported(message)) if message == "multiple models"
+        ));
+    }
+}
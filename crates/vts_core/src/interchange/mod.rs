@@ -1 +1,4 @@
+pub mod aiger;
+pub mod blif;
+pub mod rtlil;
 pub mod yosys;
@@ -0,0 +1,931 @@
+//! Yosys RTLIL text frontend.
+//!
+//! RTLIL is read into the same [Design] as Yosys JSON netlists, numbering
+//! signal bits the way `write_json` does, so that both dumps of a design
+//! compare equal. Processes are not supported and should be removed with the
+//! `proc` pass before writing RTLIL.
+//!
+//! References:
+//! - https://yosyshq.readthedocs.io/projects/yosys/en/latest/yosys_internals/formats/rtlil_text.html
+//! - https://github.com/YosysHQ/yosys/blob/1eaf4e0/frontends/rtlil/rtlil_parser.y
+
+use fnv::FnvHashMap;
+use thiserror::Error;
+
+use std::collections::HashMap;
+use std::fs;
+use std::io::Read;
+use std::iter::Peekable;
+use std::path::Path;
+use std::str::Chars;
+use std::vec::IntoIter;
+
+use super::yosys::{
+    Cell, ConstBit, Design, Memory, Module, NetName, Port, PortDirection, SignalBit,
+};
+
+#[derive(Debug, Error)]
+pub enum Error {
+    #[error(transparent)]
+    IO(#[from] std::io::Error),
+    #[error("line {line}: {message}")]
+    Syntax { line: usize, message: String },
+    #[error(r#""{0}" not supported"#)]
+    Unsupported(String),
+}
+
+pub type Result<T> = std::result::Result<T, Error>;
+
+#[derive(Clone, Debug, PartialEq)]
+enum Token {
+    /// An identifier, including its `\` or `$` prefix.
+    Id(String),
+    Keyword(String),
+    Int(i64),
+    /// The bits of a sized constant, most significant first.
+    Bits(String),
+    Str(String),
+    Punct(char),
+}
+
+impl Token {
+    fn describe(&self) -> String {
+        match self {
+            Token::Id(id) | Token::Keyword(id) | Token::Bits(id) => id.clone(),
+            Token::Int(value) => value.to_string(),
+            Token::Str(value) => format!("{value:?}"),
+            Token::Punct(c) => c.to_string(),
+        }
+    }
+}
+
+fn syntax_error<T>(line: usize, message: impl Into<String>) -> Result<T> {
+    Err(Error::Syntax {
+        line,
+        message: message.into(),
+    })
+}
+
+fn tokenize(line: usize, text: &str) -> Result<Vec<Token>> {
+    let mut tokens = Vec::new();
+    let mut chars = text.chars().peekable();
+    fn take_while(chars: &mut Peekable<Chars>, accept: impl Fn(char) -> bool) -> String {
+        let mut word = String::new();
+        while let Some(c) = chars.next_if(|c| accept(*c)) {
+            word.push(c);
+        }
+        word
+    }
+    while let Some(&c) = chars.peek() {
+        match c {
+            c if c.is_whitespace() => {
+                chars.next();
+            }
+            '#' => break,
+            '\\' | '$' => tokens.push(Token::Id(take_while(&mut chars, |c| !c.is_whitespace()))),
+            '"' => {
+                chars.next();
+                let mut value = String::new();
+                loop {
+                    match chars.next() {
+                        Some('"') => break,
+                        Some('\\') => match chars.next() {
+                            Some('n') => value.push('\n'),
+                            Some('t') => value.push('\t'),
+                            Some(c @ '0'..='7') => {
+                                let mut code = c.to_digit(8).expect("digit should be octal");
+                                for _ in 0..2 {
+                                    match chars.next_if(|c| c.is_digit(8)) {
+                                        Some(c) => code = code * 8 + c.to_digit(8).unwrap_or(0),
+                                        None => break,
+                                    }
+                                }
+                                value.push(char::from_u32(code).unwrap_or_default());
+                            }
+                            Some(c) => value.push(c),
+                            None => return syntax_error(line, "unterminated string"),
+                        },
+                        Some(c) => value.push(c),
+                        None => return syntax_error(line, "unterminated string"),
+                    }
+                }
+                tokens.push(Token::Str(value));
+            }
+            '{' | '}' | '[' | ']' | ':' => {
+                chars.next();
+                tokens.push(Token::Punct(c));
+            }
+            '-' | '0'..='9' => {
+                let number = take_while(&mut chars, |c| c == '-' || c.is_ascii_digit());
+                let Ok(value) = number.parse() else {
+                    return syntax_error(line, format!(r#"invalid number "{number}""#));
+                };
+                if chars.next_if_eq(&'\'').is_some() {
+                    let Ok(width) = usize::try_from(value) else {
+                        return syntax_error(line, format!("invalid width {value}"));
+                    };
+                    let bits = take_while(&mut chars, |c| "01xzm-".contains(c));
+                    // NOTE: Short constants are extended by their most
+                    // significant bit, with 1's extended by 0's.
+                    let extension = match bits.chars().next() {
+                        Some('1') => '0',
+                        Some(c) => c,
+                        None => 'x',
+                    };
+                    let padding = width.saturating_sub(bits.len());
+                    let bits: String = std::iter::repeat_n(extension, padding)
+                        .chain(bits.chars().skip(bits.len().saturating_sub(width)))
+                        .collect();
+                    tokens.push(Token::Bits(bits));
+                } else {
+                    tokens.push(Token::Int(value));
+                }
+            }
+            c if c.is_ascii_alphabetic() => tokens
+                .push(Token::Keyword(take_while(&mut chars, |c| {
+                    c.is_ascii_alphanumeric() || c == '_'
+                }))),
+            c => return syntax_error(line, format!("unexpected '{c}'")),
+        }
+    }
+    Ok(tokens)
+}
+
+/// The tokens of a single statement.
+struct Statement {
+    line: usize,
+    tokens: Peekable<IntoIter<Token>>,
+}
+
+impl Statement {
+    fn next(&mut self) -> Result<Token> {
+        match self.tokens.next() {
+            Some(token) => Ok(token),
+            None => syntax_error(self.line, "unexpected end of line"),
+        }
+    }
+
+    fn keyword(&mut self) -> Result<String> {
+        match self.next()? {
+            Token::Keyword(keyword) => Ok(keyword),
+            token => syntax_error(
+                self.line,
+                format!(r#"expected keyword, found "{}""#, token.describe()),
+            ),
+        }
+    }
+
+    fn id(&mut self) -> Result<String> {
+        match self.next()? {
+            Token::Id(id) => Ok(id),
+            token => syntax_error(
+                self.line,
+                format!(r#"expected identifier, found "{}""#, token.describe()),
+            ),
+        }
+    }
+
+    fn int(&mut self) -> Result<i64> {
+        match self.next()? {
+            Token::Int(value) => Ok(value),
+            token => syntax_error(
+                self.line,
+                format!(r#"expected integer, found "{}""#, token.describe()),
+            ),
+        }
+    }
+
+    fn usize(&mut self) -> Result<usize> {
+        let value = self.int()?;
+        usize::try_from(value).or_else(|_| {
+            syntax_error(
+                self.line,
+                format!("expected unsigned integer, found {value}"),
+            )
+        })
+    }
+
+    fn punct(&mut self, punct: char) -> Result<()> {
+        match self.next()? {
+            Token::Punct(c) if c == punct => Ok(()),
+            token => syntax_error(
+                self.line,
+                format!(r#"expected '{punct}', found "{}""#, token.describe()),
+            ),
+        }
+    }
+
+    /// The JSON encoding of a constant, as written by `write_json`.
+    fn constant(&mut self) -> Result<String> {
+        match self.next()? {
+            Token::Int(value) => Ok(format!("{:032b}", value as i32)),
+            Token::Bits(bits) => Ok(bits),
+            // NOTE: Strings that could be mistaken for bits are padded with a space.
+            Token::Str(mut value) => {
+                let bits = value.trim_end_matches(' ');
+                if bits.chars().all(|c| "01xz".contains(c)) {
+                    value.push(' ');
+                }
+                Ok(value)
+            }
+            token => syntax_error(
+                self.line,
+                format!(r#"expected constant, found "{}""#, token.describe()),
+            ),
+        }
+    }
+
+    fn end(&mut self) -> Result<()> {
+        match self.tokens.next() {
+            Some(token) => syntax_error(self.line, format!(r#"unexpected "{}""#, token.describe())),
+            None => Ok(()),
+        }
+    }
+}
+
+/// The JSON name of an RTLIL identifier.
+fn unescape(id: &str) -> String {
+    id.strip_prefix('\\').unwrap_or(id).to_string()
+}
+
+fn hide_name(id: &str) -> usize {
+    usize::from(id.starts_with('$'))
+}
+
+struct Wire {
+    id: String,
+    attributes: FnvHashMap<String, String>,
+    /// The index of the first bit of the wire.
+    start: usize,
+    width: usize,
+    offset: usize,
+    upto: bool,
+    signed: bool,
+    port: Option<(usize, PortDirection)>,
+}
+
+#[derive(Clone, Copy)]
+enum Bit {
+    Wire(usize),
+    Const(ConstBit),
+}
+
+struct RtlilCell {
+    id: String,
+    ty: String,
+    parameters: FnvHashMap<String, String>,
+    attributes: FnvHashMap<String, String>,
+    connections: Vec<(String, Vec<Bit>)>,
+}
+
+/// A parsed RTLIL module, before its signals are numbered.
+#[derive(Default)]
+struct RtlilModule {
+    attributes: FnvHashMap<String, String>,
+    parameter_default_values: FnvHashMap<String, String>,
+    wires: Vec<Wire>,
+    wire_ids: HashMap<String, usize>,
+    cells: Vec<RtlilCell>,
+    memories: Vec<(String, Memory)>,
+    connections: Vec<(Vec<Bit>, Vec<Bit>)>,
+    n_bits: usize,
+}
+
+impl RtlilModule {
+    fn wire(&self, statement: &Statement, id: &str) -> Result<&Wire> {
+        match self.wire_ids.get(id) {
+            Some(wire) => Ok(&self.wires[*wire]),
+            None => syntax_error(statement.line, format!(r#"wire "{id}" is not defined"#)),
+        }
+    }
+
+    /// The bits of a signal, least significant first.
+    fn sigspec(&self, statement: &mut Statement) -> Result<Vec<Bit>> {
+        let bits_of = |bits: &str| -> Vec<Bit> {
+            bits.chars()
+                .rev()
+                .map(|c| {
+                    Bit::Const(match c {
+                        '0' => ConstBit::_0,
+                        '1' => ConstBit::_1,
+                        'z' => ConstBit::Z,
+                        _ => ConstBit::X,
+                    })
+                })
+                .collect()
+        };
+        match statement.next()? {
+            Token::Int(value) => Ok(bits_of(&format!("{:032b}", value as i32))),
+            Token::Bits(bits) => Ok(bits_of(&bits)),
+            Token::Punct('{') => {
+                let mut parts = Vec::new();
+                while statement.tokens.next_if_eq(&Token::Punct('}')).is_none() {
+                    parts.push(self.sigspec(statement)?);
+                }
+                // NOTE: Concatenations list their most significant part first.
+                Ok(parts.into_iter().rev().flatten().collect())
+            }
+            Token::Id(id) => {
+                let wire = self.wire(statement, &id)?;
+                let index = |statement: &mut Statement| -> Result<usize> {
+                    let index = statement.usize()?;
+                    match index.checked_sub(wire.offset) {
+                        Some(i) if i < wire.width => {
+                            Ok(if wire.upto { wire.width - 1 - i } else { i })
+                        }
+                        _ => syntax_error(
+                            statement.line,
+                            format!(r#"bit {index} of "{id}" out of range"#),
+                        ),
+                    }
+                };
+                let (mut lsb, mut msb) = (0, wire.width);
+                if statement.tokens.next_if_eq(&Token::Punct('[')).is_some() {
+                    let hi = index(statement)?;
+                    let lo = match statement.tokens.next_if_eq(&Token::Punct(':')) {
+                        Some(_) => index(statement)?,
+                        None => hi,
+                    };
+                    statement.punct(']')?;
+                    (lsb, msb) = (hi.min(lo), hi.max(lo) + 1);
+                }
+                Ok((wire.start + lsb..wire.start + msb)
+                    .map(Bit::Wire)
+                    .collect())
+            }
+            token => syntax_error(
+                statement.line,
+                format!(r#"expected signal, found "{}""#, token.describe()),
+            ),
+        }
+    }
+}
+
+struct Parser {
+    statements: Peekable<IntoIter<Statement>>,
+    attributes: FnvHashMap<String, String>,
+}
+
+impl Parser {
+    fn next(&mut self) -> Option<(Statement, String)> {
+        let mut statement = self.statements.next()?;
+        let keyword = statement.keyword();
+        Some((statement, keyword.unwrap_or_default()))
+    }
+
+    fn attribute(&mut self, statement: &mut Statement) -> Result<()> {
+        let key = unescape(&statement.id()?);
+        let value = statement.constant()?;
+        statement.end()?;
+        self.attributes.insert(key, value);
+        Ok(())
+    }
+
+    fn module(&mut self, line: usize) -> Result<RtlilModule> {
+        let mut module = RtlilModule {
+            attributes: std::mem::take(&mut self.attributes),
+            ..Default::default()
+        };
+        while let Some((mut statement, keyword)) = self.next() {
+            match keyword.as_str() {
+                "attribute" => self.attribute(&mut statement)?,
+                "parameter" => {
+                    let id = unescape(&statement.id()?);
+                    while let Some(Token::Keyword(_)) = statement.tokens.peek() {
+                        statement.next()?;
+                    }
+                    if statement.tokens.peek().is_some() {
+                        let value = statement.constant()?;
+                        module.parameter_default_values.insert(id, value);
+                    }
+                    statement.end()?;
+                }
+                "wire" => {
+                    let mut wire = Wire {
+                        id: String::new(),
+                        attributes: std::mem::take(&mut self.attributes),
+                        start: module.n_bits,
+                        width: 1,
+                        offset: 0,
+                        upto: false,
+                        signed: false,
+                        port: None,
+                    };
+                    loop {
+                        match statement.next()? {
+                            Token::Id(id) => {
+                                wire.id = id;
+                                break;
+                            }
+                            Token::Keyword(option) => match option.as_str() {
+                                "width" => wire.width = statement.usize()?,
+                                "offset" => wire.offset = statement.usize()?,
+                                "upto" => wire.upto = true,
+                                "signed" => wire.signed = true,
+                                "input" => {
+                                    wire.port = Some((statement.usize()?, PortDirection::Input))
+                                }
+                                "output" => {
+                                    wire.port = Some((statement.usize()?, PortDirection::Output))
+                                }
+                                "inout" => {
+                                    wire.port = Some((statement.usize()?, PortDirection::InOut))
+                                }
+                                _ => {
+                                    return syntax_error(
+                                        statement.line,
+                                        format!(r#"unknown wire option "{option}""#),
+                                    )
+                                }
+                            },
+                            token => {
+                                return syntax_error(
+                                    statement.line,
+                                    format!(r#"unexpected "{}""#, token.describe()),
+                                )
+                            }
+                        }
+                    }
+                    statement.end()?;
+                    module.n_bits += wire.width;
+                    module.wire_ids.insert(wire.id.clone(), module.wires.len());
+                    module.wires.push(wire);
+                }
+                "memory" => {
+                    let mut memory = Memory {
+                        hide_name: 0,
+                        attributes: std::mem::take(&mut self.attributes),
+                        width: 1,
+                        start_offset: 0,
+                        size: 0,
+                    };
+                    let id = loop {
+                        match statement.next()? {
+                            Token::Id(id) => break id,
+                            Token::Keyword(option) => match option.as_str() {
+                                "width" => memory.width = statement.usize()?,
+                                "size" => memory.size = statement.usize()?,
+                                "offset" => memory.start_offset = statement.usize()?,
+                                _ => {
+                                    return syntax_error(
+                                        statement.line,
+                                        format!(r#"unknown memory option "{option}""#),
+                                    )
+                                }
+                            },
+                            token => {
+                                return syntax_error(
+                                    statement.line,
+                                    format!(r#"unexpected "{}""#, token.describe()),
+                                )
+                            }
+                        }
+                    };
+                    statement.end()?;
+                    memory.hide_name = hide_name(&id);
+                    module.memories.push((id, memory));
+                }
+                "cell" => {
+                    let ty = statement.id()?;
+                    let id = statement.id()?;
+                    statement.end()?;
+                    let cell = self.cell(&module, ty, id)?;
+                    module.cells.push(cell);
+                }
+                "connect" => {
+                    let lhs = module.sigspec(&mut statement)?;
+                    let rhs = module.sigspec(&mut statement)?;
+                    statement.end()?;
+                    if lhs.len() != rhs.len() {
+                        return syntax_error(
+                            statement.line,
+                            "connected signals should have the same width",
+                        );
+                    }
+                    module.connections.push((lhs, rhs));
+                }
+                "process" => {
+                    return Err(Error::Unsupported(format!("process {}", statement.id()?)))
+                }
+                "end" => return Ok(module),
+                _ => return syntax_error(statement.line, format!(r#"unexpected "{keyword}""#)),
+            }
+        }
+        syntax_error(line, "module should end")
+    }
+
+    fn cell(&mut self, module: &RtlilModule, ty: String, id: String) -> Result<RtlilCell> {
+        let mut cell = RtlilCell {
+            id,
+            ty,
+            parameters: FnvHashMap::default(),
+            attributes: std::mem::take(&mut self.attributes),
+            connections: Vec::new(),
+        };
+        while let Some((mut statement, keyword)) = self.next() {
+            match keyword.as_str() {
+                "parameter" => {
+                    let mut token = statement.next()?;
+                    while let Token::Keyword(_) = token {
+                        token = statement.next()?;
+                    }
+                    let Token::Id(id) = token else {
+                        return syntax_error(statement.line, "expected parameter name");
+                    };
+                    let value = statement.constant()?;
+                    statement.end()?;
+                    cell.parameters.insert(unescape(&id), value);
+                }
+                "connect" => {
+                    let port = statement.id()?;
+                    let bits = module.sigspec(&mut statement)?;
+                    statement.end()?;
+                    cell.connections.push((port, bits));
+                }
+                "end" => return Ok(cell),
+                _ => return syntax_error(statement.line, format!(r#"unexpected "{keyword}""#)),
+            }
+        }
+        Err(Error::Unsupported(format!("cell {} should end", cell.id)))
+    }
+}
+
+/// The output ports of the internal cell `ty`.
+fn output_ports(ty: &str) -> &'static [&'static str] {
+    match ty {
+        "$alu" => &["X", "Y", "CO"],
+        "$fa" => &["X", "Y"],
+        "$lcu" => &["CO"],
+        "$fsm" => &["CTRL_OUT"],
+        "$mem" | "$mem_v2" => &["RD_DATA"],
+        "$memrd" | "$memrd_v2" => &["DATA"],
+        "$memwr" | "$memwr_v2" | "$meminit" | "$meminit_v2" => &[],
+        _ => &["Y", "Q"],
+    }
+}
+
+/// The union-find of the connected bits of a module, numbering every bit the
+/// first time it is seen.
+struct SigMap {
+    parents: Vec<usize>,
+    consts: Vec<Option<ConstBit>>,
+    ids: Vec<Option<usize>>,
+    next_id: usize,
+}
+
+impl SigMap {
+    fn new(module: &RtlilModule) -> Self {
+        let mut sigmap = Self {
+            parents: (0..module.n_bits).collect(),
+            consts: vec![None; module.n_bits],
+            ids: vec![None; module.n_bits],
+            next_id: 2,
+        };
+        for (lhs, rhs) in &module.connections {
+            for (a, b) in lhs.iter().zip(rhs) {
+                match (*a, *b) {
+                    (Bit::Wire(a), Bit::Wire(b)) => {
+                        let (a, b) = (sigmap.find(a), sigmap.find(b));
+                        sigmap.parents[a] = b;
+                        sigmap.consts[b] = sigmap.consts[b].or(sigmap.consts[a]);
+                    }
+                    (Bit::Wire(a), Bit::Const(k)) | (Bit::Const(k), Bit::Wire(a)) => {
+                        let a = sigmap.find(a);
+                        sigmap.consts[a] = Some(k);
+                    }
+                    (Bit::Const(_), Bit::Const(_)) => {}
+                }
+            }
+        }
+        sigmap
+    }
+
+    fn find(&mut self, mut bit: usize) -> usize {
+        while self.parents[bit] != bit {
+            self.parents[bit] = self.parents[self.parents[bit]];
+            bit = self.parents[bit];
+        }
+        bit
+    }
+
+    fn get(&mut self, bit: Bit) -> SignalBit {
+        match bit {
+            Bit::Const(k) => SignalBit::Const(k),
+            Bit::Wire(bit) => {
+                let bit = self.find(bit);
+                if let Some(k) = &self.consts[bit] {
+                    return SignalBit::Const(*k);
+                }
+                SignalBit::Ref(*self.ids[bit].get_or_insert_with(|| {
+                    self.next_id += 1;
+                    self.next_id - 1
+                }))
+            }
+        }
+    }
+
+    fn bits(&mut self, bits: &[Bit]) -> Vec<SignalBit> {
+        bits.iter().map(|bit| self.get(*bit)).collect()
+    }
+
+    fn wire(&mut self, wire: &Wire) -> Vec<SignalBit> {
+        (wire.start..wire.start + wire.width)
+            .map(|bit| self.get(Bit::Wire(bit)))
+            .collect()
+    }
+}
+
+/// The port directions of every module of a design.
+type Interfaces = HashMap<String, HashMap<String, PortDirection>>;
+
+fn convert(module: RtlilModule, interfaces: &Interfaces) -> Result<Module> {
+    let mut sigmap = SigMap::new(&module);
+
+    let mut ports: Vec<_> = module
+        .wires
+        .iter()
+        .filter(|wire| wire.port.is_some())
+        .collect();
+    ports.sort_by_key(|wire| wire.port.as_ref().map(|(id, _)| *id));
+    let mut json_ports = FnvHashMap::default();
+    for wire in ports {
+        let bits = sigmap
+            .wire(wire)
+            .into_iter()
+            .map(|bit| match bit {
+                SignalBit::Ref(bit) => Ok(bit),
+                SignalBit::Const(_) => Err(Error::Unsupported(format!(
+                    "constant bits of port {}",
+                    wire.id
+                ))),
+            })
+            .collect::<Result<_>>()?;
+        let (_, direction) = wire.port.clone().expect("port should have a direction");
+        json_ports.insert(
+            unescape(&wire.id),
+            Port {
+                direction,
+                bits,
+                offset: wire.offset,
+                upto: usize::from(wire.upto),
+                signed: usize::from(wire.signed),
+            },
+        );
+    }
+
+    let mut cells = module.cells;
+    // NOTE: Like `write_json`, scope information is not kept.
+    cells.retain(|cell| cell.ty != "$scopeinfo");
+    cells.sort_by(|a, b| a.id.cmp(&b.id));
+    let mut json_cells = FnvHashMap::default();
+    for mut cell in cells {
+        cell.connections.sort_by(|a, b| a.0.cmp(&b.0));
+        let mut port_directions = FnvHashMap::default();
+        let mut connections = FnvHashMap::default();
+        let interface = interfaces.get(&cell.ty);
+        for (port, bits) in &cell.connections {
+            let name = unescape(port);
+            let direction = match interface {
+                Some(interface) => interface.get(&name).cloned(),
+                None if cell.ty.starts_with('$') => {
+                    Some(if output_ports(&cell.ty).contains(&name.as_str()) {
+                        PortDirection::Output
+                    } else {
+                        PortDirection::Input
+                    })
+                }
+                None => None,
+            };
+            if let Some(direction) = direction {
+                port_directions.insert(name.clone(), direction);
+            }
+            connections.insert(name, sigmap.bits(bits));
+        }
+        json_cells.insert(
+            unescape(&cell.id),
+            Cell {
+                hide_name: hide_name(&cell.id),
+                ty: unescape(&cell.ty),
+                parameters: cell.parameters,
+                attributes: cell.attributes,
+                port_directions,
+                connections,
+            },
+        );
+    }
+
+    let mut wires = module.wires;
+    wires.sort_by(|a, b| a.id.cmp(&b.id));
+    let mut netnames = FnvHashMap::default();
+    for wire in wires {
+        let bits = sigmap.wire(&wire);
+        netnames.insert(
+            unescape(&wire.id),
+            NetName {
+                hide_name: hide_name(&wire.id),
+                attributes: wire.attributes,
+                bits,
+                offset: wire.offset,
+                upto: usize::from(wire.upto),
+                signed: usize::from(wire.signed),
+            },
+        );
+    }
+
+    Ok(Module {
+        attributes: module.attributes,
+        parameter_default_values: module.parameter_default_values,
+        ports: json_ports,
+        cells: json_cells,
+        memories: module
+            .memories
+            .into_iter()
+            .map(|(id, memory)| (unescape(&id), memory))
+            .collect(),
+        netnames,
+    })
+}
+
+/// Parse the RTLIL `text` into a Yosys design.
+pub fn parse(text: &str) -> Result<Design> {
+    // NOTE: Yosys records itself as the creator in the first comment.
+    let creator = text
+        .lines()
+        .next()
+        .and_then(|line| line.strip_prefix("# Generated by "))
+        .map_or_else(
+            || format!("vts {}", env!("CARGO_PKG_VERSION")),
+            str::to_string,
+        );
+    let mut statements = Vec::new();
+    for (i, line) in text.lines().enumerate() {
+        let tokens = tokenize(i + 1, line)?;
+        if !tokens.is_empty() {
+            statements.push(Statement {
+                line: i + 1,
+                tokens: tokens.into_iter().peekable(),
+            });
+        }
+    }
+    let mut parser = Parser {
+        statements: statements.into_iter().peekable(),
+        attributes: FnvHashMap::default(),
+    };
+    let mut modules = Vec::new();
+    while let Some((mut statement, keyword)) = parser.next() {
+        match keyword.as_str() {
+            "autoidx" => {
+                statement.int()?;
+                statement.end()?;
+            }
+            "attribute" => parser.attribute(&mut statement)?,
+            "module" => {
+                let id = statement.id()?;
+                statement.end()?;
+                modules.push((id, parser.module(statement.line)?));
+            }
+            _ => return syntax_error(statement.line, format!(r#"unexpected "{keyword}""#)),
+        }
+    }
+
+    let interfaces: Interfaces = modules
+        .iter()
+        .map(|(id, module)| {
+            let ports = module
+                .wires
+                .iter()
+                .filter_map(|wire| Some((unescape(&wire.id), wire.port.clone()?.1)))
+                .collect();
+            (id.clone(), ports)
+        })
+        .collect();
+    let modules = modules
+        .into_iter()
+        .map(|(id, module)| Ok((unescape(&id), convert(module, &interfaces)?)))
+        .collect::<Result<_>>()?;
+    Ok(Design { creator, modules })
+}
+
+pub fn from_reader<R>(mut reader: R) -> Result<Design>
+where
+    R: Read,
+{
+    let mut text = String::new();
+    reader.read_to_string(&mut text)?;
+    parse(&text)
+}
+
+pub fn from_file<P>(path: P) -> Result<Design>
+where
+    P: AsRef<Path>,
+{
+    parse(&fs::read_to_string(path)?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    macro_rules! example {
+        ($dir:literal / $name:literal) => {
+            (
+                $name,
+                include_str!(concat!("../../../../examples/", $dir, "/", $name, ".rtlil")),
+                include_str!(concat!("../../../../examples/", $dir, "/", $name, ".json")),
+            )
+        };
+    }
+
+    #[test]
+    fn test_examples() {
+        for (name, rtlil, json) in [
+            example!("alu" / "add4"),
+            example!("alu" / "add4_abc"),
+            example!("alu" / "add4_flowmap"),
+            example!("alu" / "add4_simplemap"),
+            example!("alu" / "alu4"),
+            example!("alu" / "alu4_abc"),
+            example!("alu" / "alu4_flowmap"),
+            example!("alu" / "alu4_simplemap"),
+            example!("crc" / "crc16"),
+            example!("crc" / "crc16_flowmap"),
+            example!("crc" / "crc16_simplemap"),
+            example!("crc" / "crc8"),
+            example!("crc" / "crc8_flowmap"),
+            example!("crc" / "crc8_simplemap"),
+            example!("sr" / "sr"),
+            example!("sr" / "sr_abc"),
+            example!("sr" / "sr_flowmap"),
+            example!("sr" / "sr_simplemap"),
+        ] {
+            let design = parse(rtlil).unwrap();
+            let expected: Design = json.parse().unwrap();
+            assert_eq!(design, expected, "{name}");
+        }
+    }
+
+    #[test]
+    fn test_parse() {
+        let design = parse(
+            r#"
+attribute \top 1
+module \top
+  parameter \W 2
+  wire width 2 offset 1 input 1 \a
+  attribute \keep "01"
+  wire output 2 \y
+  wire width 2 \t
+  memory width 8 size 4 $mem
+  cell \sub \u
+    parameter signed \P -1
+    connect \A { 1'1 \a [2] }
+    connect \Y \t [0]
+  end
+  connect \t [1] 1'0
+  connect \y \t [0]
+end
+module \sub
+  wire width 2 input 1 \A
+  wire output 2 \Y
+end
+"#,
+        )
+        .unwrap();
+        assert_eq!(design.creator, format!("vts {}", env!("CARGO_PKG_VERSION")));
+        let top = &design.modules["top"];
+        assert_eq!(top.attributes["top"], format!("{:032b}", 1));
+        assert_eq!(top.parameter_default_values["W"], format!("{:032b}", 2));
+        assert_eq!(top.ports["a"].bits, [2, 3]);
+        assert_eq!(top.ports["a"].offset, 1);
+        assert_eq!(top.ports["y"].bits, [4]);
+        assert_eq!(top.memories["$mem"].size, 4);
+        let cell = &top.cells["u"];
+        assert_eq!(cell.ty, "sub");
+        assert_eq!(cell.parameters["P"], "1".repeat(32));
+        assert_eq!(
+            cell.connections["A"],
+            [SignalBit::Ref(3), SignalBit::Const(ConstBit::_1)]
+        );
+        assert_eq!(cell.connections["Y"], [SignalBit::Ref(4)]);
+        assert_eq!(cell.port_directions["Y"], PortDirection::Output);
+        let t = &top.netnames["t"];
+        assert_eq!(t.bits, [SignalBit::Ref(4), SignalBit::Const(ConstBit::_0)]);
+        assert_eq!(top.netnames["y"].attributes["keep"], "01 ");
+    }
+
+    #[test]
+    fn test_errors() {
+        assert!(matches!(
+            parse("module \\m\n  process $proc\n  end\nend\n"),
+            Err(Error::Unsupported(process)) if process == "process $proc"
+        ));
+        assert!(matches!(
+            parse("module \\m\n  wire \\a\n  connect \\a \\b\nend\n"),
+            Err(Error::Syntax { line: 3, .. })
+        ));
+        assert!(matches!(
+            parse("module \\m\n  wire \\a\n"),
+            Err(Error::Syntax { line: 1, .. })
+        ));
+    }
+}
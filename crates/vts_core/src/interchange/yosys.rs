@@ -15,6 +15,8 @@ use std::io::{BufReader, Read};
 use std::path::Path;
 use std::str::FromStr;
 
+pub mod flatten;
+
 #[derive(Debug, Error)]
 pub enum Error {
     #[error(transparent)]
@@ -99,7 +101,7 @@ pub enum SignalBit {
     Const(ConstBit),
 }
 
-#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+#[derive(Clone, Copy, Debug, Deserialize, PartialEq, Serialize)]
 #[serde(rename_all = "lowercase")]
 pub enum ConstBit {
     #[serde(rename = "0")]
@@ -157,11 +159,17 @@ pub struct NetName {
 
 pub mod constids {
     pub mod parameter_names {
+        pub const ARST_POLARITY: &str = "ARST_POLARITY";
+        pub const ARST_VALUE: &str = "ARST_VALUE";
         pub const A_SIGNED: &str = "A_SIGNED";
         pub const A_WIDTH: &str = "A_WIDTH";
         pub const B_SIGNED: &str = "B_SIGNED";
         pub const B_WIDTH: &str = "B_WIDTH";
+        pub const CLK_POLARITY: &str = "CLK_POLARITY";
+        pub const EN_POLARITY: &str = "EN_POLARITY";
         pub const LUT: &str = "LUT";
+        pub const SRST_POLARITY: &str = "SRST_POLARITY";
+        pub const SRST_VALUE: &str = "SRST_VALUE";
         pub const S_WIDTH: &str = "S_WIDTH";
         pub const WIDTH: &str = "WIDTH";
         pub const Y_WIDTH: &str = "Y_WIDTH";
@@ -171,6 +179,7 @@ pub mod constids {
         pub const ALWAYS_COMB: &str = "always_comb";
         pub const ALWAYS_FF: &str = "always_ff";
         pub const ALWAYS_LATCH: &str = "always_latch";
+        pub const BLACKBOX: &str = "blackbox";
         pub const DYNPORTS: &str = "dynports";
         pub const ENUM_TYPE: &str = "enum_type";
         pub const ENUM_VALUE_00: &str = "enum_value_00";
@@ -181,8 +190,10 @@ pub mod constids {
         pub const ENUM_VALUE_1: &str = "enum_value_1";
         pub const FULL_CASE: &str = "full_case";
         pub const HDLNAME: &str = "hdlname";
+        pub const KEEP_HIERARCHY: &str = "keep_hierarchy";
         pub const SRC: &str = "src";
         pub const TOP: &str = "top";
+        pub const WHITEBOX: &str = "whitebox";
         pub const WIRETYPE: &str = "wiretype";
     }
 
@@ -0,0 +1,503 @@
+//! Hierarchy flattening.
+//!
+//! Every cell whose type is a module of the design is replaced by the cells
+//! and nets of that module, recursively, naming them the way the Yosys
+//! `flatten` pass does: public names are prefixed by the instance name and
+//! private names by `$flatten\<instance>.`. Public names also record their
+//! hierarchical path in the `hdlname` attribute. As in Yosys, instances of
+//! modules with the `blackbox`, `whitebox` or `keep_hierarchy` attribute are
+//! kept as cells. The outer signals of ports sharing a bit, as in a
+//! pass-through, are joined by `$_BUF_` cells.
+//!
+//! References:
+//! - https://github.com/YosysHQ/yosys/blob/1eaf4e0/passes/techmap/flatten.cc
+
+use std::collections::hash_map::Entry;
+
+use fnv::FnvHashMap;
+use thiserror::Error;
+
+use super::constids::attribute_keys::{BLACKBOX, HDLNAME, KEEP_HIERARCHY, TOP, WHITEBOX};
+use super::constids::port_names::{A, Y};
+use super::constids::std_cells::BUF;
+use super::{Cell, Design, Module, PortDirection, SignalBit};
+
+#[derive(Clone, Debug, Error)]
+pub enum Error {
+    #[error("design has no top module")]
+    MissingTop,
+    #[error(r#"design has multiple top modules "{0}" and "{1}""#)]
+    MultipleTops(String, String),
+    #[error(r#"module "{0}" is not defined"#)]
+    MissingModule(String),
+    #[error("module instantiates itself through {}", .0.join(" -> "))]
+    RecursiveModule(Vec<String>),
+    #[error(r#"module "{module}" has no port "{port}""#)]
+    MissingPort { module: String, port: String },
+    #[error(r#"width of port "{port}" of cell "{cell}" does not match its module"#)]
+    WidthMismatch { cell: String, port: String },
+}
+
+pub type Result<T> = std::result::Result<T, Error>;
+
+/// The name of the object `name` of the instance `instance` after flattening.
+fn concat_name(instance: &str, name: &str) -> String {
+    if name.starts_with('$') {
+        let escape = if instance.starts_with('$') { "" } else { "\\" };
+        format!("$flatten{escape}{instance}.{name}")
+    } else {
+        format!("{instance}.{name}")
+    }
+}
+
+/// Whether a constant attribute value is non-zero.
+fn is_set(value: &str) -> bool {
+    value.contains('1')
+}
+
+struct Flattener<'a> {
+    design: &'a Design,
+    module: Module,
+    next_id: usize,
+    stack: Vec<&'a str>,
+}
+
+impl<'a> Flattener<'a> {
+    fn map_bit(&mut self, bits: &mut FnvHashMap<usize, SignalBit>, bit: &SignalBit) -> SignalBit {
+        match bit {
+            SignalBit::Ref(bit) => bits
+                .entry(*bit)
+                .or_insert_with(|| {
+                    self.next_id += 1;
+                    SignalBit::Ref(self.next_id - 1)
+                })
+                .clone(),
+            SignalBit::Const(_) => bit.clone(),
+        }
+    }
+
+    /// Inline the cell `instance` of type `ty`, whose ports are connected to
+    /// `connections`, with `path` the hierarchical name of the instance if it
+    /// is public.
+    fn inline(
+        &mut self,
+        instance: &str,
+        path: Option<&str>,
+        ty: &'a str,
+        connections: &FnvHashMap<String, Vec<SignalBit>>,
+    ) -> Result<()> {
+        let Some((name, module)) = self.design.modules.get_key_value(ty) else {
+            return Err(Error::MissingModule(ty.to_string()));
+        };
+        if let Some(i) = self.stack.iter().position(|parent| *parent == ty) {
+            let mut cycle: Vec<_> = self.stack[i..].iter().map(|m| m.to_string()).collect();
+            cycle.push(ty.to_string());
+            return Err(Error::RecursiveModule(cycle));
+        }
+        self.stack.push(name);
+
+        let mut ports = Vec::with_capacity(connections.len());
+        for (port, signal) in connections {
+            let Some(definition) = module.ports.get(port) else {
+                return Err(Error::MissingPort {
+                    module: ty.to_string(),
+                    port: port.clone(),
+                });
+            };
+            if definition.bits.len() != signal.len() {
+                return Err(Error::WidthMismatch {
+                    cell: instance.to_string(),
+                    port: port.clone(),
+                });
+            }
+            ports.push((port, definition, signal));
+        }
+        // NOTE: A bit shared by several ports, as in `assign y = a`, joins
+        // their outer signals. Inputs are mapped first so the other signals
+        // are driven by a buffer from the input.
+        ports.sort_by_key(|(port, definition, _)| {
+            (definition.direction != PortDirection::Input, *port)
+        });
+        let mut bits = FnvHashMap::default();
+        let mut joined = Vec::new();
+        for (_, definition, signal) in ports {
+            for (bit, signal) in definition.bits.iter().zip(signal) {
+                match bits.entry(*bit) {
+                    Entry::Vacant(entry) => {
+                        entry.insert(signal.clone());
+                    }
+                    Entry::Occupied(entry) if entry.get() != signal => {
+                        joined.push((*bit, entry.get().clone(), signal.clone()));
+                    }
+                    Entry::Occupied(_) => {}
+                }
+            }
+        }
+        for (bit, driver, signal) in joined {
+            if let SignalBit::Const(_) = signal {
+                continue;
+            }
+            let connections = [(A, driver), (Y, signal)]
+                .map(|(port, bit)| (port.to_string(), vec![bit]))
+                .into_iter()
+                .collect();
+            let port_directions = [(A, PortDirection::Input), (Y, PortDirection::Output)]
+                .map(|(port, direction)| (port.to_string(), direction))
+                .into_iter()
+                .collect();
+            let cell = Cell {
+                hide_name: 1,
+                ty: BUF.to_string(),
+                parameters: FnvHashMap::default(),
+                attributes: FnvHashMap::default(),
+                port_directions,
+                connections,
+            };
+            let name = concat_name(instance, &format!("$buf${bit}"));
+            self.module.cells.insert(name, cell);
+        }
+        let hdlname = |name: &str, attributes: &FnvHashMap<String, String>| {
+            let path = path.filter(|_| !name.starts_with('$'))?;
+            let name = attributes.get(HDLNAME).map_or(name, String::as_str);
+            Some(format!("{path} {name}"))
+        };
+
+        // NOTE: Objects are visited by name to number new bits deterministically.
+        let mut cells: Vec<_> = module.cells.iter().collect();
+        cells.sort_by_key(|(name, _)| *name);
+        for (name, cell) in cells {
+            let connections = cell
+                .connections
+                .iter()
+                .map(|(port, signal)| {
+                    let signal = signal
+                        .iter()
+                        .map(|bit| self.map_bit(&mut bits, bit))
+                        .collect();
+                    (port.clone(), signal)
+                })
+                .collect();
+            let flat_name = concat_name(instance, name);
+            let hdlname = hdlname(name, &cell.attributes);
+            if self.design.is_inlined(&cell.ty) {
+                self.inline(&flat_name, hdlname.as_deref(), &cell.ty, &connections)?;
+                continue;
+            }
+            let mut cell = cell.clone();
+            cell.hide_name = usize::from(flat_name.starts_with('$'));
+            cell.connections = connections;
+            cell.attributes
+                .extend(hdlname.map(|hdlname| (HDLNAME.to_string(), hdlname)));
+            self.check_cell(&cell.ty)?;
+            self.module.cells.insert(flat_name, cell);
+        }
+        let mut netnames: Vec<_> = module.netnames.iter().collect();
+        netnames.sort_by_key(|(name, _)| *name);
+        for (name, netname) in netnames {
+            let mut netname = netname.clone();
+            netname.bits = netname
+                .bits
+                .iter()
+                .map(|bit| self.map_bit(&mut bits, bit))
+                .collect();
+            let flat_name = concat_name(instance, name);
+            netname.hide_name = usize::from(flat_name.starts_with('$'));
+            if let Some(hdlname) = hdlname(name, &netname.attributes) {
+                netname.attributes.insert(HDLNAME.to_string(), hdlname);
+            }
+            self.module.netnames.insert(flat_name, netname);
+        }
+        for (name, memory) in &module.memories {
+            let flat_name = concat_name(instance, name);
+            let mut memory = memory.clone();
+            memory.hide_name = usize::from(flat_name.starts_with('$'));
+            self.module.memories.insert(flat_name, memory);
+        }
+
+        self.stack.pop();
+        Ok(())
+    }
+
+    /// Check that a cell of type `ty` is either a module of the design or an
+    /// internal cell.
+    fn check_cell(&self, ty: &str) -> Result<()> {
+        if ty.starts_with('$') || self.design.modules.contains_key(ty) {
+            Ok(())
+        } else {
+            Err(Error::MissingModule(ty.to_string()))
+        }
+    }
+}
+
+impl Design {
+    /// Whether cells of type `ty` are instances of a module of the design
+    /// that is inlined when flattening.
+    fn is_inlined(&self, ty: &str) -> bool {
+        self.modules.get(ty).is_some_and(|module| {
+            [BLACKBOX, WHITEBOX, KEEP_HIERARCHY].iter().all(|key| {
+                !module
+                    .attributes
+                    .get(*key)
+                    .is_some_and(|value| is_set(value))
+            })
+        })
+    }
+
+    /// The name of the top module, the only module with a non-zero `top`
+    /// attribute or else the only module of the design.
+    pub fn top_module(&self) -> Result<&str> {
+        let mut tops = self
+            .modules
+            .iter()
+            .filter(|(_, module)| module.attributes.get(TOP).is_some_and(|top| is_set(top)))
+            .map(|(name, _)| name.as_str());
+        match (tops.next(), tops.next()) {
+            (Some(top), None) => Ok(top),
+            (Some(a), Some(b)) => {
+                let (a, b) = (a.min(b), a.max(b));
+                Err(Error::MultipleTops(a.to_string(), b.to_string()))
+            }
+            (None, _) if self.modules.len() == 1 => {
+                Ok(self.modules.keys().next().expect("design has a module"))
+            }
+            (None, _) => Err(Error::MissingTop),
+        }
+    }
+
+    /// Flatten the module `top`, or the [top module](Self::top_module) of the
+    /// design, inlining every instance of the other modules of the design.
+    pub fn flatten(&self, top: Option<&str>) -> Result<Module> {
+        let top = match top {
+            Some(top) => top,
+            None => self.top_module()?,
+        };
+        let Some((top, module)) = self.modules.get_key_value(top) else {
+            return Err(Error::MissingModule(top.to_string()));
+        };
+        let ports = module.ports.values().flat_map(|port| &port.bits);
+        let cells = module
+            .cells
+            .values()
+            .flat_map(|cell| cell.connections.values().flatten());
+        let netnames = module.netnames.values().flat_map(|netname| &netname.bits);
+        let next_id = ports
+            .copied()
+            .chain(cells.chain(netnames).filter_map(|bit| match bit {
+                SignalBit::Ref(bit) => Some(*bit),
+                SignalBit::Const(_) => None,
+            }))
+            .max()
+            .map_or(2, |id| id + 1);
+
+        let mut instances: Vec<_> = module
+            .cells
+            .iter()
+            .filter(|(_, cell)| self.is_inlined(&cell.ty))
+            .collect();
+        instances.sort_by_key(|(name, _)| *name);
+        let mut flattener = Flattener {
+            design: self,
+            module: Module {
+                cells: FnvHashMap::default(),
+                ..module.clone()
+            },
+            next_id,
+            stack: vec![top],
+        };
+        for (name, cell) in &module.cells {
+            if !self.is_inlined(&cell.ty) {
+                flattener.check_cell(&cell.ty)?;
+                flattener.module.cells.insert(name.clone(), cell.clone());
+            }
+        }
+        for (name, cell) in instances {
+            let path = (!name.starts_with('$'))
+                .then(|| cell.attributes.get(HDLNAME).unwrap_or(name).as_str());
+            flattener.inline(name, path, &cell.ty, &cell.connections)?;
+        }
+        Ok(flattener.module)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::interchange::rtlil;
+    use crate::ir::graph::Graph;
+    use crate::ir::testing::{bits, eval, value};
+
+    /// A full adder built from two half adders.
+    const FULL_ADDER: &str = r#"
+module \half
+  wire input 1 \a
+  wire input 2 \b
+  wire output 3 \s
+  wire output 4 \c
+  cell $_XOR_ $x
+    connect \A \a
+    connect \B \b
+    connect \Y \s
+  end
+  cell $_AND_ \and
+    connect \A \a
+    connect \B \b
+    connect \Y \c
+  end
+end
+attribute \top 1
+module \full
+  wire input 1 \a
+  wire input 2 \b
+  wire input 3 \cin
+  wire width 2 output 4 \y
+  wire \s
+  wire \c0
+  wire \c1
+  cell \half \h0
+    connect \a \a
+    connect \b \b
+    connect \s \s
+    connect \c \c0
+  end
+  cell \half $h1
+    connect \a \s
+    connect \b \cin
+    connect \s \y [0]
+    connect \c \c1
+  end
+  cell $_OR_ $or
+    connect \A \c0
+    connect \B \c1
+    connect \Y \y [1]
+  end
+end
+"#;
+
+    #[test]
+    fn test_flatten() {
+        let design = rtlil::parse(FULL_ADDER).unwrap();
+        assert_eq!(design.top_module().unwrap(), "full");
+        let module = design.flatten(None).unwrap();
+        let mut names: Vec<_> = module.cells.keys().map(String::as_str).collect();
+        names.sort();
+        assert_eq!(
+            names,
+            [
+                "$flatten$h1.$x",
+                "$flatten\\h0.$x",
+                "$h1.and",
+                "$or",
+                "h0.and"
+            ]
+        );
+        assert_eq!(module.cells["h0.and"].attributes[HDLNAME], "h0 and");
+        assert!(!module.cells["$h1.and"].attributes.contains_key(HDLNAME));
+        assert_eq!(module.netnames["h0.s"].attributes[HDLNAME], "h0 s");
+        assert_eq!(module.netnames["h0.s"].bits, module.netnames["s"].bits);
+
+        let graph = Graph::try_from(module).unwrap();
+        for x in 0..8 {
+            let inputs: Vec<_> = bits(x, 3).collect();
+            let sum = inputs.iter().filter(|bit| **bit).count() as u64;
+            assert_eq!(value(&eval(&graph, &inputs)), sum, "{inputs:?}");
+        }
+    }
+
+    #[test]
+    fn test_pass_through() {
+        // NOTE: The output `a` of `pass` sorts before its input `b`.
+        let design = rtlil::parse(
+            r#"
+module \pass
+  wire output 1 \a
+  wire input 2 \b
+  connect \a \b
+end
+attribute \top 1
+module \top
+  wire input 1 \i
+  wire width 2 output 2 \o
+  cell \pass \p0
+    connect \a \o [0]
+    connect \b \i
+  end
+  cell \pass \p1
+    connect \a \o [1]
+    connect \b 1'1
+  end
+end
+"#,
+        )
+        .unwrap();
+        let module = design.flatten(None).unwrap();
+        let mut cells: Vec<_> = module
+            .cells
+            .iter()
+            .map(|(name, cell)| (name.as_str(), cell.ty.as_str()))
+            .collect();
+        cells.sort();
+        assert_eq!(
+            cells,
+            [("$flatten\\p0.$buf$2", BUF), ("$flatten\\p1.$buf$2", BUF)]
+        );
+        let graph = Graph::try_from(module).unwrap();
+        assert_eq!(value(&eval(&graph, &[false])), 0b10);
+        assert_eq!(value(&eval(&graph, &[true])), 0b11);
+    }
+
+    #[test]
+    fn test_keep_hierarchy() {
+        for key in [BLACKBOX, WHITEBOX, KEEP_HIERARCHY] {
+            let mut design = rtlil::parse(FULL_ADDER).unwrap();
+            let half = design.modules.get_mut("half").unwrap();
+            half.attributes.insert(key.to_string(), "1".to_string());
+            let module = design.flatten(None).unwrap();
+            let mut cells: Vec<_> = module
+                .cells
+                .iter()
+                .map(|(name, cell)| (name.as_str(), cell.ty.as_str()))
+                .collect();
+            cells.sort();
+            assert_eq!(
+                cells,
+                [("$h1", "half"), ("$or", "$_OR_"), ("h0", "half")],
+                "{key}"
+            );
+            assert_eq!(module.cells["h0"], design.modules["full"].cells["h0"]);
+        }
+    }
+
+    #[test]
+    fn test_errors() {
+        let mut design = rtlil::parse(FULL_ADDER).unwrap();
+        assert!(matches!(
+            design.flatten(Some("top")),
+            Err(Error::MissingModule(module)) if module == "top"
+        ));
+
+        let mut half = design.modules["half"].clone();
+        half.attributes.insert(TOP.to_string(), "1".to_string());
+        design.modules.insert("half".to_string(), half.clone());
+        assert!(matches!(
+            design.top_module(),
+            Err(Error::MultipleTops(a, b)) if a == "full" && b == "half"
+        ));
+
+        let mut and = half.cells["and"].clone();
+        and.ty = "full".to_string();
+        half.cells.insert("and".to_string(), and.clone());
+        design.modules.insert("half".to_string(), half.clone());
+        assert!(matches!(
+            design.flatten(Some("full")),
+            Err(Error::RecursiveModule(cycle)) if cycle == ["full", "half", "full"]
+        ));
+
+        and.ty = "and2".to_string();
+        half.cells.insert("and".to_string(), and);
+        design.modules.insert("half".to_string(), half);
+        assert!(matches!(
+            design.flatten(Some("full")),
+            Err(Error::MissingModule(module)) if module == "and2"
+        ));
+    }
+}
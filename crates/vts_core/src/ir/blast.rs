@@ -0,0 +1,688 @@
+//! Bit-blasting of coarse-grained Yosys cells.
+//!
+//! Word-level cells, as written by Yosys before `simplemap`, are lowered into
+//! single-bit [AnyOp] nodes. Operands are extended or truncated to the width
+//! of the operation following their `*_SIGNED` parameter, as in Yosys.
+//! Word-level flip-flops and latches are split into a [SeqOp] node per bit.
+//!
+//! Reference: https://yosyshq.readthedocs.io/projects/yosys/en/latest/yosys_internals/formats/cell_library.html
+
+use ustr::Ustr;
+
+use super::graph::{Edge, Graph, Node, NodeData, YosysError};
+use super::ops::{AnyOp, FlipFlop, Latch, Lut, Polarity, Reset, SeqOp};
+use crate::interchange::yosys::{
+    self,
+    constids::{internal_cells, parameter_names},
+};
+
+/// Adds single-bit logic to a [Graph], sharing a single node for each
+/// constant.
+pub(super) struct Builder<'a> {
+    pub(super) graph: &'a mut Graph,
+    /// The cell the added gates are converted from.
+    pub(super) cell: Option<Ustr>,
+    zero: Option<Node>,
+    unit: Option<Node>,
+}
+
+impl<'a> Builder<'a> {
+    pub(super) fn new(graph: &'a mut Graph) -> Self {
+        Self {
+            graph,
+            cell: None,
+            zero: None,
+            unit: None,
+        }
+    }
+
+    pub(super) fn zero(&mut self) -> Node {
+        *self.zero.get_or_insert_with(|| self.graph.add_zero())
+    }
+
+    pub(super) fn unit(&mut self) -> Node {
+        *self.unit.get_or_insert_with(|| self.graph.add_unit())
+    }
+
+    fn constant(&mut self, value: bool) -> Node {
+        if value {
+            self.unit()
+        } else {
+            self.zero()
+        }
+    }
+
+    fn gate(&mut self, op: AnyOp, inputs: &[Node]) -> Node {
+        let mut data = NodeData::new_op(op);
+        data.cell = self.cell;
+        let node = self.graph.add_node(data);
+        for &source in inputs {
+            self.graph.add_edge(Edge { source, sink: node });
+        }
+        node
+    }
+
+    fn not(&mut self, a: Node) -> Node {
+        self.gate(AnyOp::not(), &[a])
+    }
+
+    fn and(&mut self, a: Node, b: Node) -> Node {
+        self.gate(AnyOp::and(), &[a, b])
+    }
+
+    fn or(&mut self, a: Node, b: Node) -> Node {
+        self.gate(AnyOp::or(), &[a, b])
+    }
+
+    fn xor(&mut self, a: Node, b: Node) -> Node {
+        self.gate(AnyOp::xor(), &[a, b])
+    }
+
+    fn xnor(&mut self, a: Node, b: Node) -> Node {
+        let y = self.xor(a, b);
+        self.not(y)
+    }
+
+    /// `s ? b : a`
+    fn mux(&mut self, s: Node, a: Node, b: Node) -> Node {
+        self.gate(AnyOp::mux(), &[a, b, s])
+    }
+
+    /// Reduce `bits` with a balanced tree of `op` gates, or return the
+    /// `identity` of `op` if there are no bits.
+    fn reduce(
+        &mut self,
+        op: fn(&mut Self, Node, Node) -> Node,
+        bits: &[Node],
+        identity: bool,
+    ) -> Node {
+        let mut level = bits.to_vec();
+        while level.len() > 1 {
+            level = level
+                .chunks(2)
+                .map(|chunk| match *chunk {
+                    [a, b] => op(self, a, b),
+                    [a] => a,
+                    _ => unreachable!(),
+                })
+                .collect();
+        }
+        match level.first() {
+            Some(node) => *node,
+            None => self.constant(identity),
+        }
+    }
+
+    /// Extend `bits` to `width` with its sign bit if `signed`, or with zeros
+    /// otherwise, truncating it if it is wider.
+    fn extend(&mut self, bits: &[Node], width: usize, signed: bool) -> Vec<Node> {
+        let mut bits = bits.to_vec();
+        bits.truncate(width);
+        while bits.len() < width {
+            let bit = match bits.last() {
+                Some(msb) if signed => *msb,
+                _ => self.zero(),
+            };
+            bits.push(bit);
+        }
+        bits
+    }
+
+    /// `a + b + carry` of equally wide operands, dropping the carry out.
+    fn add(&mut self, a: &[Node], b: &[Node], mut carry: Option<Node>) -> Vec<Node> {
+        let mut sum = Vec::with_capacity(a.len());
+        for (&a, &b) in a.iter().zip(b) {
+            let half = self.xor(a, b);
+            let Some(c) = carry else {
+                sum.push(half);
+                carry = Some(self.and(a, b));
+                continue;
+            };
+            sum.push(self.xor(half, c));
+            let generate = self.and(a, b);
+            let propagate = self.and(half, c);
+            carry = Some(self.or(generate, propagate));
+        }
+        sum
+    }
+
+    /// `a < b` of equally wide operands.
+    fn less_than(&mut self, a: &[Node], b: &[Node], signed: bool) -> Node {
+        // NOTE: From the least significant bit up, the result is decided by
+        // the most significant differing bit.
+        let mut lt = None;
+        let width = a.len();
+        for (i, (&a, &b)) in a.iter().zip(b).enumerate() {
+            let differ = self.xor(a, b);
+            // NOTE: A differing sign bit makes the negative operand smaller.
+            let bit = if signed && i + 1 == width { a } else { b };
+            lt = Some(match lt {
+                Some(lt) => self.mux(differ, lt, bit),
+                None => self.and(differ, bit),
+            });
+        }
+        lt.unwrap_or_else(|| self.zero())
+    }
+
+    /// Shift `a` by `b` with a barrel shifter, shifting in zeros.
+    fn shift(&mut self, a: &[Node], b: &[Node], left: bool) -> Vec<Node> {
+        let mut bits = a.to_vec();
+        for (k, &s) in b.iter().enumerate() {
+            let distance = 1usize.checked_shl(k as u32).unwrap_or(usize::MAX);
+            bits = (0..bits.len())
+                .map(|i| {
+                    let shifted = if left {
+                        i.checked_sub(distance).map(|j| bits[j])
+                    } else {
+                        i.checked_add(distance).and_then(|j| bits.get(j).copied())
+                    };
+                    let shifted = shifted.unwrap_or_else(|| self.zero());
+                    self.mux(s, bits[i], shifted)
+                })
+                .collect();
+        }
+        bits
+    }
+
+    /// A single bit result extended with zeros to `width`.
+    fn flag(&mut self, bit: Node, width: usize) -> Vec<Node> {
+        self.extend(&[bit], width, false)
+    }
+}
+
+/// The input ports of coarse-grained cells of type `ty`, or [None] if cells
+/// of type `ty` cannot be blasted.
+pub(super) fn cell_inputs(ty: &str) -> Option<&'static [&'static str]> {
+    use internal_cells::*;
+    match ty {
+        NOT | REDUCE_AND | REDUCE_OR | REDUCE_XOR | REDUCE_XNOR | REDUCE_BOOL | LOGIC_NOT | LUT => {
+            Some(&["A"])
+        }
+        AND | OR | XOR | XNOR | ADD | SUB | EQ | NE | LT | LE | GT | GE | SHL | SHR | LOGIC_AND
+        | LOGIC_OR => Some(&["A", "B"]),
+        MUX | PMUX => Some(&["A", "B", "S"]),
+        _ => None,
+    }
+}
+
+fn parameter(name: &str, cell: &yosys::Cell, key: &str) -> Result<usize, YosysError> {
+    let error = |parameter: &str| (name.to_string(), parameter.to_string());
+    let value = cell.parameters.get(key).ok_or_else(|| {
+        let (cell, parameter) = error(key);
+        YosysError::ShouldHaveParameter { cell, parameter }
+    })?;
+    usize::from_str_radix(value, 2).map_err(|_| {
+        let (cell, parameter) = error(key);
+        YosysError::InvalidParameter { cell, parameter }
+    })
+}
+
+/// Bit `i` of the `width` bit constant `value`, written most significant bit
+/// first.
+fn constant_bit(value: &str, width: usize, i: usize) -> Option<bool> {
+    if value.len() != width {
+        return None;
+    }
+    match value.as_bytes()[width - 1 - i] {
+        b'0' => Some(false),
+        b'1' => Some(true),
+        _ => None,
+    }
+}
+
+/// The ports driving the inputs of the [SeqOp]'s of word-level flip-flops and
+/// latches of type `ty`, in order, or [None] if `ty` is not one.
+///
+/// The data input `D` is a word, the other ports single bits.
+pub(super) fn seq_inputs(ty: &str) -> Option<&'static [&'static str]> {
+    use internal_cells::*;
+    match ty {
+        FF => Some(&["D"]),
+        DLATCH => Some(&["D", "EN"]),
+        DFF => Some(&["D", "CLK"]),
+        DFFE => Some(&["D", "CLK", "EN"]),
+        SDFF => Some(&["D", "CLK", "SRST"]),
+        SDFFE => Some(&["D", "CLK", "EN", "SRST"]),
+        ADFF => Some(&["D", "CLK", "ARST"]),
+        ADFFE => Some(&["D", "CLK", "EN", "ARST"]),
+        _ => None,
+    }
+}
+
+/// The [SeqOp] of every bit of the word-level flip-flop or latch `name`, of a
+/// type with [seq_inputs].
+pub(super) fn seq_ops(name: &str, cell: &yosys::Cell) -> Result<Vec<SeqOp>, YosysError> {
+    use internal_cells::*;
+    use parameter_names::*;
+    let param = |key| parameter(name, cell, key);
+    let polarity = |key| {
+        Ok::<_, YosysError>(if param(key)? != 0 {
+            Polarity::Positive
+        } else {
+            Polarity::Negative
+        })
+    };
+    let width = param(WIDTH)?;
+    if cell.ty == FF {
+        return Ok(vec![SeqOp::Ff; width]);
+    }
+    if cell.ty == DLATCH {
+        let latch = Latch {
+            enable: polarity(EN_POLARITY)?,
+            reset: None,
+        };
+        return Ok(vec![SeqOp::Latch(latch); width]);
+    }
+    let clock = polarity(CLK_POLARITY)?;
+    let enable = match cell.ty.as_str() {
+        DFFE | SDFFE | ADFFE => Some(polarity(EN_POLARITY)?),
+        _ => None,
+    };
+    let reset = match cell.ty.as_str() {
+        SDFF | SDFFE => Some((SRST_POLARITY, SRST_VALUE, false)),
+        ADFF | ADFFE => Some((ARST_POLARITY, ARST_VALUE, true)),
+        _ => None,
+    };
+    let mut ops = Vec::with_capacity(width);
+    for i in 0..width {
+        let reset = match reset {
+            Some((polarity_key, value_key, asynchronous)) => {
+                let invalid = || YosysError::InvalidParameter {
+                    cell: name.to_string(),
+                    parameter: value_key.to_string(),
+                };
+                let value = cell.parameters.get(value_key).ok_or_else(|| {
+                    YosysError::ShouldHaveParameter {
+                        cell: name.to_string(),
+                        parameter: value_key.to_string(),
+                    }
+                })?;
+                Some(Reset {
+                    polarity: polarity(polarity_key)?,
+                    value: constant_bit(value, width, i).ok_or_else(invalid)?,
+                    asynchronous,
+                    gated: false,
+                })
+            }
+            None => None,
+        };
+        ops.push(SeqOp::FlipFlop(FlipFlop {
+            clock,
+            enable,
+            reset,
+        }));
+    }
+    Ok(ops)
+}
+
+/// Lower the cell `name` into single-bit logic driven by `inputs`, in the
+/// order of [cell_inputs]. Returns the bits of its output `Y`.
+pub(super) fn blast(
+    builder: &mut Builder,
+    name: &str,
+    cell: &yosys::Cell,
+    inputs: &[Vec<Node>],
+) -> Result<Vec<Node>, YosysError> {
+    use internal_cells::*;
+    use parameter_names::*;
+    let param = |key| parameter(name, cell, key);
+    let check = |port: &str, bits: &[Node], width: usize| {
+        if bits.len() == width {
+            Ok(())
+        } else {
+            Err(YosysError::WidthMismatch {
+                cell: name.to_string(),
+                port: port.to_string(),
+            })
+        }
+    };
+    if cell.ty == internal_cells::LUT {
+        let width = param(WIDTH)?;
+        let a = &inputs[0];
+        check("A", a, width)?;
+        if width > Lut::MAX_INPUTS {
+            return Err(YosysError::Unsupported(format!(
+                "{width}-input look-up tables"
+            )));
+        }
+        let table = cell.parameters.get(parameter_names::LUT).ok_or_else(|| {
+            YosysError::ShouldHaveParameter {
+                cell: name.to_string(),
+                parameter: parameter_names::LUT.to_string(),
+            }
+        })?;
+        let table = u64::from_str_radix(table, 2).map_err(|_| YosysError::InvalidParameter {
+            cell: name.to_string(),
+            parameter: parameter_names::LUT.to_string(),
+        })?;
+        let lut = Lut::new(width, table).expect("look-up table width should be checked");
+        return Ok(vec![builder.gate(AnyOp::from(lut), a)]);
+    }
+    if matches!(cell.ty.as_str(), MUX | PMUX) {
+        let width = param(WIDTH)?;
+        let n_cases = if cell.ty == PMUX { param(S_WIDTH)? } else { 1 };
+        let [a, b, s] = inputs else {
+            unreachable!("multiplexers should have 3 inputs");
+        };
+        check("A", a, width)?;
+        check("B", b, width * n_cases)?;
+        check("S", s, n_cases)?;
+        // NOTE: The select inputs of a `$pmux` are one-hot, so a priority
+        // chain of multiplexers is equivalent.
+        let mut y = a.clone();
+        for (case, &s) in b.chunks(width.max(1)).zip(s) {
+            y = y
+                .iter()
+                .zip(case)
+                .map(|(&a, &b)| builder.mux(s, a, b))
+                .collect();
+        }
+        return Ok(y);
+    }
+
+    let y_width = param(Y_WIDTH)?;
+    let a_signed = param(A_SIGNED)? != 0;
+    let a = &inputs[0];
+    check("A", a, param(A_WIDTH)?)?;
+    let (b, b_signed) = match inputs.get(1) {
+        Some(b) => {
+            check("B", b, param(B_WIDTH)?)?;
+            // NOTE: The shift amount is always unsigned.
+            let signed = !matches!(cell.ty.as_str(), SHL | SHR) && param(B_SIGNED)? != 0;
+            (b.as_slice(), signed)
+        }
+        None => (&[][..], false),
+    };
+    // NOTE: Comparisons are signed only if both operands are.
+    let signed = a_signed && b_signed;
+    let width = a.len().max(b.len());
+    let operands = |builder: &mut Builder| {
+        (
+            builder.extend(a, width, signed),
+            builder.extend(b, width, signed),
+        )
+    };
+    let y = match cell.ty.as_str() {
+        NOT => builder
+            .extend(a, y_width, a_signed)
+            .into_iter()
+            .map(|a| builder.not(a))
+            .collect(),
+        AND | OR | XOR | XNOR => {
+            let op = match cell.ty.as_str() {
+                AND => Builder::and,
+                OR => Builder::or,
+                XOR => Builder::xor,
+                _ => Builder::xnor,
+            };
+            let a = builder.extend(a, y_width, a_signed);
+            let b = builder.extend(b, y_width, b_signed);
+            a.iter().zip(&b).map(|(&a, &b)| op(builder, a, b)).collect()
+        }
+        ADD | SUB => {
+            let a = builder.extend(a, y_width, a_signed);
+            let mut b = builder.extend(b, y_width, b_signed);
+            let mut carry = None;
+            if cell.ty == SUB {
+                // NOTE: `a - b = a + ~b + 1`
+                b = b.into_iter().map(|b| builder.not(b)).collect();
+                carry = Some(builder.unit());
+            }
+            builder.add(&a, &b, carry)
+        }
+        EQ | NE => {
+            let (a, b) = operands(builder);
+            let differ: Vec<_> = a.iter().zip(&b).map(|(&a, &b)| builder.xor(a, b)).collect();
+            let mut y = builder.reduce(Builder::or, &differ, false);
+            if cell.ty == EQ {
+                y = builder.not(y);
+            }
+            builder.flag(y, y_width)
+        }
+        LT | LE | GT | GE => {
+            let (a, b) = operands(builder);
+            let y = match cell.ty.as_str() {
+                LT => builder.less_than(&a, &b, signed),
+                GT => builder.less_than(&b, &a, signed),
+                LE => {
+                    let gt = builder.less_than(&b, &a, signed);
+                    builder.not(gt)
+                }
+                _ => {
+                    let lt = builder.less_than(&a, &b, signed);
+                    builder.not(lt)
+                }
+            };
+            builder.flag(y, y_width)
+        }
+        SHL => {
+            let a = builder.extend(a, y_width, a_signed);
+            builder.shift(&a, b, true)
+        }
+        SHR => {
+            let a = builder.extend(a, y_width.max(a.len()), a_signed);
+            let mut y = builder.shift(&a, b, false);
+            y.truncate(y_width);
+            y
+        }
+        REDUCE_AND => {
+            let y = builder.reduce(Builder::and, a, true);
+            builder.flag(y, y_width)
+        }
+        REDUCE_OR | REDUCE_BOOL => {
+            let y = builder.reduce(Builder::or, a, false);
+            builder.flag(y, y_width)
+        }
+        REDUCE_XOR | REDUCE_XNOR => {
+            let mut y = builder.reduce(Builder::xor, a, false);
+            if cell.ty == REDUCE_XNOR {
+                y = builder.not(y);
+            }
+            builder.flag(y, y_width)
+        }
+        LOGIC_NOT => {
+            let any = builder.reduce(Builder::or, a, false);
+            let y = builder.not(any);
+            builder.flag(y, y_width)
+        }
+        LOGIC_AND | LOGIC_OR => {
+            let a = builder.reduce(Builder::or, a, false);
+            let b = builder.reduce(Builder::or, b, false);
+            let y = if cell.ty == LOGIC_AND {
+                builder.and(a, b)
+            } else {
+                builder.or(a, b)
+            };
+            builder.flag(y, y_width)
+        }
+        ty => unreachable!("cell type {ty} should be supported"),
+    };
+    Ok(y)
+}
+
+#[cfg(test)]
+mod tests {
+    use fnv::FnvHashMap;
+
+    use super::*;
+    use crate::interchange::yosys::{Port, PortDirection, SignalBit};
+    use crate::ir::graph::NodeKind;
+    use crate::ir::testing::{bits, eval, value};
+
+    /// Sign or zero extend the `width` bit `value` to 64 bits.
+    fn extend(value: u64, width: usize, signed: bool) -> u64 {
+        if signed && width > 0 && value >> (width - 1) & 1 == 1 {
+            value | u64::MAX << width
+        } else {
+            value
+        }
+    }
+
+    fn param(value: usize) -> String {
+        format!("{value:032b}")
+    }
+
+    /// A module with inputs `A` and `B` driving the single cell `ty` with
+    /// output `Y`.
+    fn binary_module(ty: &str, widths: [usize; 3], signed: [bool; 2]) -> yosys::Module {
+        let [a_width, b_width, y_width] = widths;
+        let mut next = 2;
+        let mut port = |direction, width| {
+            let bits: Vec<_> = (next..next + width).collect();
+            next += width;
+            Port {
+                direction,
+                bits,
+                offset: 0,
+                upto: 0,
+                signed: 0,
+            }
+        };
+        let a = port(PortDirection::Input, a_width);
+        let b = port(PortDirection::Input, b_width);
+        let y = port(PortDirection::Output, y_width);
+        let signals = |port: &Port| port.bits.iter().copied().map(SignalBit::Ref).collect();
+        let cell = yosys::Cell {
+            hide_name: 1,
+            ty: ty.to_string(),
+            parameters: FnvHashMap::from_iter([
+                ("A_WIDTH".to_string(), param(a_width)),
+                ("B_WIDTH".to_string(), param(b_width)),
+                ("Y_WIDTH".to_string(), param(y_width)),
+                ("A_SIGNED".to_string(), param(signed[0] as usize)),
+                ("B_SIGNED".to_string(), param(signed[1] as usize)),
+            ]),
+            attributes: FnvHashMap::default(),
+            port_directions: FnvHashMap::default(),
+            connections: FnvHashMap::from_iter([
+                ("A".to_string(), signals(&a)),
+                ("B".to_string(), signals(&b)),
+                ("Y".to_string(), signals(&y)),
+            ]),
+        };
+        yosys::Module {
+            attributes: FnvHashMap::default(),
+            parameter_default_values: FnvHashMap::default(),
+            ports: FnvHashMap::from_iter([
+                ("A".to_string(), a),
+                ("B".to_string(), b),
+                ("Y".to_string(), y),
+            ]),
+            cells: FnvHashMap::from_iter([("cell".to_string(), cell)]),
+            memories: FnvHashMap::default(),
+            netnames: FnvHashMap::default(),
+        }
+    }
+
+    /// Check the cell `ty` against `reference` on every input, for every
+    /// signedness.
+    fn check_binary(ty: &str, widths: [usize; 3], reference: fn(i128, i128) -> u64) {
+        let [a_width, b_width, y_width] = widths;
+        for signed in [false, true] {
+            let graph = Graph::try_from(binary_module(ty, widths, [signed; 2])).unwrap();
+            for a in 0..1 << a_width {
+                for b in 0..1 << b_width {
+                    let inputs: Vec<_> = bits(a, a_width).chain(bits(b, b_width)).collect();
+                    let y = value(&eval(&graph, &inputs));
+                    let a = extend(a, a_width, signed) as i64 as i128;
+                    let b = extend(b, b_width, signed) as i64 as i128;
+                    let expected = reference(a, b) & ((1 << y_width) - 1);
+                    assert_eq!(y, expected, "{ty} signed={signed} a={a} b={b}");
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_arithmetic() {
+        check_binary("$add", [3, 2, 4], |a, b| (a + b) as u64);
+        check_binary("$sub", [2, 3, 4], |a, b| (a - b) as u64);
+        check_binary("$and", [3, 2, 3], |a, b| (a & b) as u64);
+        check_binary("$xor", [2, 3, 3], |a, b| (a ^ b) as u64);
+    }
+
+    #[test]
+    fn test_comparison() {
+        check_binary("$eq", [3, 2, 2], |a, b| (a == b) as u64);
+        check_binary("$ne", [3, 2, 1], |a, b| (a != b) as u64);
+        check_binary("$lt", [3, 3, 1], |a, b| (a < b) as u64);
+        check_binary("$lt", [2, 3, 1], |a, b| (a < b) as u64);
+        check_binary("$ge", [3, 2, 1], |a, b| (a >= b) as u64);
+        check_binary("$logic_or", [2, 2, 1], |a, b| (a != 0 || b != 0) as u64);
+    }
+
+    #[test]
+    fn test_shift() {
+        // NOTE: The shift amount is unsigned.
+        fn amount(b: i128) -> u64 {
+            b as u64 & 0b11
+        }
+        check_binary("$shl", [3, 2, 4], |a, b| (a as u64) << amount(b));
+        check_binary("$shr", [3, 2, 4], |a, b| (a as u64 & 0b1111) >> amount(b));
+        check_binary("$shr", [4, 2, 2], |a, b| (a as u64 & 0b1111) >> amount(b));
+    }
+
+    #[test]
+    fn test_lut() {
+        let lut = |width: usize, table: &str| {
+            let mut module = binary_module("$lut", [width, 0, 1], [false; 2]);
+            let cell = module.cells.get_mut("cell").unwrap();
+            cell.parameters.insert("WIDTH".to_string(), param(width));
+            cell.parameters.insert("LUT".to_string(), table.to_string());
+            Graph::try_from(module)
+        };
+        let graph = lut(2, "0110").unwrap();
+        for a in 0..4 {
+            let y = eval(&graph, &bits(a, 2).collect::<Vec<_>>());
+            assert_eq!(y, [a.count_ones() == 1]);
+        }
+        assert!(matches!(
+            lut(2, "01x0"),
+            Err(YosysError::InvalidParameter { .. })
+        ));
+        // NOTE: Wide look-up tables are unsupported whatever their table.
+        assert!(matches!(
+            lut(Lut::MAX_INPUTS + 1, "x"),
+            Err(YosysError::Unsupported(_))
+        ));
+    }
+
+    #[test]
+    fn test_alu4() {
+        let design: yosys::Design = include_str!("../../../../examples/alu/alu4.json")
+            .parse()
+            .unwrap();
+        let module = design.modules["alu4"].clone();
+        let graph = Graph::try_from(module.clone()).unwrap();
+        for node in graph.node_ids() {
+            if let NodeKind::Gate(AnyOp::Const(_)) = graph.node(node).kind {
+                continue;
+            }
+            if let Some(cell) = graph.cell_name(node) {
+                assert!(module.cells.contains_key(cell));
+            } else {
+                assert!(graph.port_bit(node).is_some(), "node {node}");
+            }
+        }
+        assert!(graph
+            .nodes()
+            .all(|node| !matches!(node.kind, NodeKind::Seq(_))));
+        // NOTE: Ports are ordered by name: `A`, `B`, `S`, `V` and `op`.
+        for op in 0..4 {
+            for a in 0..16 {
+                for b in 0..16 {
+                    let inputs: Vec<_> = bits(a, 4).chain(bits(b, 4)).chain(bits(op, 2)).collect();
+                    let outputs = eval(&graph, &inputs);
+                    let (s, v) = match op {
+                        0 => (a + b, (a + b) >> 4),
+                        1 => (a + (!b & 0xf) + 1, (a + (!b & 0xf) + 1) >> 4),
+                        2 => (a & b, 0),
+                        _ => (a | b, 0),
+                    };
+                    assert_eq!(value(&outputs[..4]), s & 0xf, "op={op} a={a} b={b}");
+                    assert_eq!(outputs[4], v == 1, "op={op} a={a} b={b}");
+                }
+            }
+        }
+    }
+}
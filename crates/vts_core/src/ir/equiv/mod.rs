@@ -0,0 +1,454 @@
+//! SAT-based combinational equivalence checking.
+//!
+//! The sources of two graphs are matched like the inputs of a
+//! [comparison](super::sim::compare) and shared by a miter, asserting that a
+//! pair of matched sinks differs. The miter is Tseitin-encoded into the
+//! clauses of a [Solver], hashing and folding AND and XOR gates so that the
+//! parts both graphs have in common collapse before solving. The miter is
+//! unsatisfiable exactly when the graphs are equivalent.
+//!
+//! [NodeKind::Splitter] and [NodeKind::Dff] nodes are buffers, as in
+//! [simulation](super::sim). Nodes holding state are not supported.
+
+pub mod sat;
+
+use fnv::FnvHashMap;
+use thiserror::Error;
+
+use super::graph::{self, Graph, Node, NodeKind};
+use super::ops::{AnyOp, BinaryOp, ComplexOp, ConstOp, Lut, SfqOp, UnaryOp};
+use super::sim::{self, Signature};
+use sat::{Lit, Solver};
+
+#[derive(Clone, Debug, Error)]
+pub enum Error {
+    #[error(transparent)]
+    Graph(#[from] graph::Error),
+    #[error("node {node} should have {expected} inputs, found {found}")]
+    Arity {
+        node: Node,
+        expected: usize,
+        found: usize,
+    },
+    #[error("graphs do not have the same ports ({0})")]
+    PortMismatch(String),
+    #[error("node {0} holds state")]
+    Sequential(Node),
+}
+
+pub type Result<T> = std::result::Result<T, Error>;
+
+/// An assignment of the inputs telling two graphs apart.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Counterexample {
+    pub inputs: Vec<String>,
+    /// The value of every input.
+    pub values: Vec<bool>,
+    /// The outputs that differ.
+    pub outputs: Vec<String>,
+}
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum Equivalence {
+    Equivalent,
+    Counterexample(Counterexample),
+}
+
+/// Tseitin encoding of gates into a [Solver].
+struct Encoder {
+    solver: Solver,
+    unit: Lit,
+    ands: FnvHashMap<(Lit, Lit), Lit>,
+    xors: FnvHashMap<(Lit, Lit), Lit>,
+}
+
+impl Encoder {
+    fn new() -> Self {
+        let mut solver = Solver::new();
+        let unit = solver.new_var();
+        solver.add_clause(&[unit]);
+        Self {
+            solver,
+            unit,
+            ands: FnvHashMap::default(),
+            xors: FnvHashMap::default(),
+        }
+    }
+
+    fn zero(&self) -> Lit {
+        !self.unit
+    }
+
+    fn and(&mut self, a: Lit, b: Lit) -> Lit {
+        let (a, b) = (a.min(b), a.max(b));
+        if a == self.zero() || b == self.zero() || a == !b {
+            return self.zero();
+        }
+        if a == self.unit || a == b {
+            return b;
+        }
+        if b == self.unit {
+            return a;
+        }
+        if let Some(lit) = self.ands.get(&(a, b)) {
+            return *lit;
+        }
+        let lit = self.solver.new_var();
+        self.solver.add_clause(&[!lit, a]);
+        self.solver.add_clause(&[!lit, b]);
+        self.solver.add_clause(&[lit, !a, !b]);
+        self.ands.insert((a, b), lit);
+        lit
+    }
+
+    fn or(&mut self, a: Lit, b: Lit) -> Lit {
+        !self.and(!a, !b)
+    }
+
+    fn xor(&mut self, a: Lit, b: Lit) -> Lit {
+        // NOTE: Hash on positive literals, flipping the output instead.
+        let negated = a.is_negated() != b.is_negated();
+        let (a, b) = (Lit::new(a.var()), Lit::new(b.var()));
+        let (a, b) = (a.min(b), a.max(b));
+        let lit = if a == b {
+            self.zero()
+        } else if a == self.unit {
+            !b
+        } else if let Some(lit) = self.xors.get(&(a, b)) {
+            *lit
+        } else {
+            let lit = self.solver.new_var();
+            self.solver.add_clause(&[!lit, a, b]);
+            self.solver.add_clause(&[!lit, !a, !b]);
+            self.solver.add_clause(&[lit, !a, b]);
+            self.solver.add_clause(&[lit, a, !b]);
+            self.xors.insert((a, b), lit);
+            lit
+        };
+        if negated {
+            !lit
+        } else {
+            lit
+        }
+    }
+
+    fn mux(&mut self, s: Lit, a: Lit, b: Lit) -> Lit {
+        let b = self.and(s, b);
+        let a = self.and(!s, a);
+        self.or(a, b)
+    }
+
+    /// Encode `lut` by one clause per row of its table.
+    fn lut(&mut self, lut: &Lut, inputs: &[Lit]) -> Lit {
+        match lut.n_inputs() {
+            0 => {
+                return if lut.table() & 1 == 1 {
+                    self.unit
+                } else {
+                    self.zero()
+                }
+            }
+            1 => {
+                return match lut.table() {
+                    0b00 => self.zero(),
+                    0b01 => !inputs[0],
+                    0b10 => inputs[0],
+                    _ => self.unit,
+                }
+            }
+            _ => {}
+        }
+        let lit = self.solver.new_var();
+        let mut clause = Vec::with_capacity(inputs.len() + 1);
+        for row in 0..1usize << lut.n_inputs() {
+            clause.clear();
+            clause.extend(inputs.iter().enumerate().map(|(j, input)| {
+                if row >> j & 1 == 1 {
+                    !*input
+                } else {
+                    *input
+                }
+            }));
+            clause.push(if lut.table() >> row & 1 == 1 {
+                lit
+            } else {
+                !lit
+            });
+            self.solver.add_clause(&clause);
+        }
+        lit
+    }
+
+    fn op(&mut self, op: &AnyOp, inputs: &[Lit]) -> Lit {
+        let input = |i: usize| inputs[i];
+        match op {
+            AnyOp::Unary(UnaryOp::Buf) => input(0),
+            AnyOp::Unary(UnaryOp::Not) => !input(0),
+            AnyOp::Binary(op) => {
+                let (a, b) = (input(0), input(1));
+                match op {
+                    BinaryOp::And => self.and(a, b),
+                    BinaryOp::Or => self.or(a, b),
+                    BinaryOp::Xor => self.xor(a, b),
+                    BinaryOp::Nand => !self.and(a, b),
+                    BinaryOp::Nor => !self.or(a, b),
+                    BinaryOp::Xnor => !self.xor(a, b),
+                    BinaryOp::AndNot => self.and(a, !b),
+                    BinaryOp::OrNot => self.or(a, !b),
+                }
+            }
+            AnyOp::Complex(op) => {
+                let ab = match op {
+                    ComplexOp::Aoi3 | ComplexOp::Aoi4 => self.and(input(0), input(1)),
+                    ComplexOp::Oai3 | ComplexOp::Oai4 => self.or(input(0), input(1)),
+                };
+                match op {
+                    ComplexOp::Aoi3 => !self.or(ab, input(2)),
+                    ComplexOp::Oai3 => !self.and(ab, input(2)),
+                    ComplexOp::Aoi4 => {
+                        let cd = self.and(input(2), input(3));
+                        !self.or(ab, cd)
+                    }
+                    ComplexOp::Oai4 => {
+                        let cd = self.or(input(2), input(3));
+                        !self.and(ab, cd)
+                    }
+                }
+            }
+            AnyOp::Lut(lut) => self.lut(lut, inputs),
+            AnyOp::Const(ConstOp::Unit) => self.unit,
+            AnyOp::Const(ConstOp::Zero) => self.zero(),
+            AnyOp::Mux => self.mux(input(2), input(0), input(1)),
+            AnyOp::NMux => !self.mux(input(2), input(0), input(1)),
+        }
+    }
+
+    /// Encode the nodes of `graph`, with its sources driven by `sources`.
+    fn graph(&mut self, graph: &Graph, sources: &FnvHashMap<Node, Lit>) -> Result<Vec<Lit>> {
+        let mut lits = vec![self.zero(); graph.n_nodes()];
+        let mut inputs = Vec::new();
+        for node in graph.topological_order()? {
+            inputs.clear();
+            inputs.extend(graph.fanins(node).iter().map(|fanin| lits[fanin.index()]));
+            let input = |i: usize| inputs[i];
+            lits[node.index()] = match &graph.node(node).kind {
+                NodeKind::Source => sources[&node],
+                NodeKind::Sink | NodeKind::Splitter | NodeKind::Dff => input(0),
+                NodeKind::Gate(op) => self.op(op, &inputs),
+                NodeKind::Sfq(SfqOp::Majority) => {
+                    let ab = self.and(input(0), input(1));
+                    let c = self.or(input(0), input(1));
+                    let c = self.and(c, input(2));
+                    self.or(ab, c)
+                }
+                NodeKind::Sfq(SfqOp::Merger) => self.or(input(0), input(1)),
+                NodeKind::Seq(_) | NodeKind::Sfq(_) => return Err(Error::Sequential(node)),
+            };
+        }
+        Ok(lits)
+    }
+}
+
+fn check_arity(graph: &Graph) -> Result<()> {
+    for node in graph.node_ids() {
+        let expected = sim::n_inputs(&graph.node(node).kind);
+        let found = graph.fanins(node).len();
+        if found != expected {
+            return Err(Error::Arity {
+                node,
+                expected,
+                found,
+            });
+        }
+    }
+    Ok(())
+}
+
+/// Prove that `a` and `b` compute the same function of their inputs, or find
+/// a [Counterexample].
+///
+/// Ports are matched by name and the remaining sources and sinks by node
+/// order. Returns [Error::PortMismatch] if the graphs do not have the same
+/// ports, or [Error::Sequential] if either holds state.
+pub fn check(a: &Graph, b: &Graph) -> Result<Equivalence> {
+    check_arity(a)?;
+    check_arity(b)?;
+    let (a_bits, b_bits) = (Signature::new(a), Signature::new(b));
+    if let Some(mismatch) = a_bits.mismatch(&b_bits) {
+        return Err(Error::PortMismatch(mismatch));
+    }
+
+    let mut encoder = Encoder::new();
+    let inputs: Vec<_> = a_bits
+        .inputs
+        .iter()
+        .map(|_| encoder.solver.new_var())
+        .collect();
+    let sources = |bits: &[(String, Node)]| -> FnvHashMap<Node, Lit> {
+        bits.iter()
+            .zip(&inputs)
+            .map(|((_, node), lit)| (*node, *lit))
+            .collect()
+    };
+    let a_lits = encoder.graph(a, &sources(&a_bits.inputs))?;
+    let b_lits = encoder.graph(b, &sources(&b_bits.inputs))?;
+    let outputs: Vec<_> = a_bits
+        .outputs
+        .iter()
+        .zip(&b_bits.outputs)
+        .map(|((_, x), (_, y))| (a_lits[x.index()], b_lits[y.index()]))
+        .collect();
+    let differences: Vec<_> = outputs.iter().map(|(x, y)| encoder.xor(*x, *y)).collect();
+    let mut solver = encoder.solver;
+    solver.add_clause(&differences);
+    if !solver.solve() {
+        return Ok(Equivalence::Equivalent);
+    }
+    Ok(Equivalence::Counterexample(Counterexample {
+        inputs: a_bits.input_names(),
+        values: inputs.iter().map(|lit| solver.value(*lit)).collect(),
+        outputs: a_bits
+            .output_names()
+            .into_iter()
+            .zip(&outputs)
+            .filter(|(_, (x, y))| solver.value(*x) != solver.value(*y))
+            .map(|(name, _)| name)
+            .collect(),
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::interchange::yosys;
+    use crate::ir::graph::{Edge, NodeData};
+    use crate::ir::sfq::balance::{balance, BalanceMode};
+    use crate::ir::sfq::splitter::{insert_splitters, SplitterConfig};
+    use crate::ir::testing;
+
+    fn load(json: &str, top: &str) -> Graph {
+        let design: yosys::Design = json.parse().unwrap();
+        Graph::try_from(design.modules[top].clone()).unwrap()
+    }
+
+    fn add4() -> Graph {
+        load(
+            include_str!("../../../../../examples/alu/add4_simplemap.json"),
+            "add4",
+        )
+    }
+
+    #[test]
+    fn test_equivalent() {
+        let graph = add4();
+        let flowmap = load(
+            include_str!("../../../../../examples/alu/add4_flowmap.json"),
+            "add4",
+        );
+        assert_eq!(check(&graph, &flowmap).unwrap(), Equivalence::Equivalent);
+
+        let mut sfq = graph.clone();
+        balance(&mut sfq, BalanceMode::Outputs).unwrap();
+        insert_splitters(&mut sfq, &SplitterConfig::default());
+        assert_eq!(check(&sfq, &flowmap).unwrap(), Equivalence::Equivalent);
+
+        for (simplemap, flowmap, top) in [
+            (
+                include_str!("../../../../../examples/alu/alu4_simplemap.json"),
+                include_str!("../../../../../examples/alu/alu4_flowmap.json"),
+                "alu4",
+            ),
+            (
+                include_str!("../../../../../examples/crc/crc16_simplemap.json"),
+                include_str!("../../../../../examples/crc/crc16_flowmap.json"),
+                "crc16_1021",
+            ),
+        ] {
+            let (a, b) = (load(simplemap, top), load(flowmap, top));
+            assert_eq!(check(&a, &b).unwrap(), Equivalence::Equivalent);
+        }
+
+        // NOTE: A majority of A, B and Cin is the carry of a full adder.
+        let mut majority = Graph::default();
+        let [a, b, cin] = ["A", "B", "Cin"].map(|name| majority.add_input(name, 1).next().unwrap());
+        let cout = majority.add_output("Cout", 1).next().unwrap();
+        let node = majority.add_node(NodeData::new_sfq(SfqOp::Majority));
+        majority.add_edges([a, b, cin].map(|source| Edge { source, sink: node }));
+        majority.add_edge(Edge {
+            source: node,
+            sink: cout,
+        });
+        let mut carry = Graph::default();
+        let [a, b, cin] = ["A", "B", "Cin"].map(|name| carry.add_input(name, 1).next().unwrap());
+        let cout = carry.add_output("Cout", 1).next().unwrap();
+        let lut = carry.add_node(NodeData::new_op(Lut::new(3, 0b1110_1000).unwrap()));
+        carry.add_edges([a, b, cin].map(|source| Edge { source, sink: lut }));
+        carry.add_edge(Edge {
+            source: lut,
+            sink: cout,
+        });
+        assert_eq!(check(&majority, &carry).unwrap(), Equivalence::Equivalent);
+    }
+
+    #[test]
+    fn test_counterexample() {
+        let graph = add4();
+        // NOTE: Swap the drivers of S[1] and S[2].
+        let mut mutant = graph.clone();
+        let [s1, s2] = ["S[1]", "S[2]"].map(|name| mutant.find(name).unwrap());
+        let [d1, d2] = [s1, s2].map(|sink| mutant.fanins(sink)[0]);
+        mutant.replace_edge(
+            Edge {
+                source: d1,
+                sink: s1,
+            },
+            d2,
+        );
+        mutant.replace_edge(
+            Edge {
+                source: d2,
+                sink: s2,
+            },
+            d1,
+        );
+        let Equivalence::Counterexample(counterexample) = check(&graph, &mutant).unwrap() else {
+            panic!("graphs should differ");
+        };
+        assert_eq!(counterexample.inputs.len(), 9);
+        assert_eq!(counterexample.outputs, ["S[1]", "S[2]"]);
+        let values = |graph: &Graph| {
+            let mut inputs = vec![false; graph.n_nodes()];
+            for ((_, node), value) in Signature::new(graph)
+                .inputs
+                .iter()
+                .zip(&counterexample.values)
+            {
+                inputs[node.index()] = *value;
+            }
+            let sources: Vec<_> = graph
+                .node_ids()
+                .filter(|node| matches!(graph.node(*node).kind, NodeKind::Source))
+                .map(|node| inputs[node.index()])
+                .collect();
+            testing::eval(graph, &sources)
+        };
+        assert_ne!(values(&graph), values(&mutant));
+    }
+
+    #[test]
+    fn test_errors() {
+        let graph = add4();
+        let sr = load(
+            include_str!("../../../../../examples/sr/sr_simplemap.json"),
+            "sr",
+        );
+        assert!(matches!(
+            check(&graph, &sr),
+            Err(Error::PortMismatch(mismatch)) if mismatch == "inputs"
+        ));
+        assert!(matches!(check(&sr, &sr), Err(Error::Sequential(_))));
+        let mut open = graph.clone();
+        open.add_node(NodeData::new_sink());
+        assert!(matches!(check(&open, &graph), Err(Error::Arity { .. })));
+    }
+}
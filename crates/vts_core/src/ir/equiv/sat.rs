@@ -0,0 +1,416 @@
+//! A conflict-driven clause learning SAT solver.
+//!
+//! Clauses are watched by two literals, conflicts are analysed to their first
+//! unique implication point and decisions follow the activity of variables in
+//! recent conflicts, with saved phases and geometric restarts.
+//!
+//! References:
+//! - N. Eén and N. Sörensson, "An Extensible SAT-solver", SAT 2003.
+
+use std::{fmt, mem, ops};
+
+/// A variable or its negation.
+#[derive(Clone, Copy, Debug, Eq, Hash, Ord, PartialEq, PartialOrd)]
+pub struct Lit(u32);
+
+impl Lit {
+    /// The positive literal of variable `var`.
+    pub fn new(var: usize) -> Self {
+        Self(u32::try_from(var).expect("too many variables") << 1)
+    }
+
+    pub fn var(&self) -> usize {
+        (self.0 >> 1) as usize
+    }
+
+    pub fn is_negated(&self) -> bool {
+        self.0 & 1 == 1
+    }
+
+    fn index(&self) -> usize {
+        self.0 as usize
+    }
+}
+
+impl ops::Not for Lit {
+    type Output = Self;
+
+    fn not(self) -> Self {
+        Self(self.0 ^ 1)
+    }
+}
+
+impl fmt::Display for Lit {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.is_negated() {
+            write!(f, "-")?;
+        }
+        write!(f, "{}", self.var() + 1)
+    }
+}
+
+fn lit_value(assigns: &[Option<bool>], lit: Lit) -> Option<bool> {
+    assigns[lit.var()].map(|value| value != lit.is_negated())
+}
+
+const ACTIVITY_DECAY: f64 = 0.95;
+const FIRST_RESTART: usize = 100;
+
+#[derive(Clone, Debug, Default)]
+pub struct Solver {
+    clauses: Vec<Vec<Lit>>,
+    /// The clauses watching every literal.
+    watches: Vec<Vec<usize>>,
+    assigns: Vec<Option<bool>>,
+    levels: Vec<usize>,
+    reasons: Vec<Option<usize>>,
+    trail: Vec<Lit>,
+    /// The start of every decision level in the trail.
+    trail_lim: Vec<usize>,
+    /// The next literal of the trail to propagate.
+    head: usize,
+    activity: Vec<f64>,
+    increment: f64,
+    phases: Vec<bool>,
+    model: Vec<bool>,
+    unsat: bool,
+}
+
+impl Solver {
+    pub fn new() -> Self {
+        Self {
+            increment: 1.0,
+            ..Self::default()
+        }
+    }
+
+    pub fn n_vars(&self) -> usize {
+        self.assigns.len()
+    }
+
+    /// Add a variable, returning its positive literal.
+    pub fn new_var(&mut self) -> Lit {
+        let lit = Lit::new(self.n_vars());
+        self.watches.extend([Vec::new(), Vec::new()]);
+        self.assigns.push(None);
+        self.levels.push(0);
+        self.reasons.push(None);
+        self.activity.push(0.0);
+        self.phases.push(false);
+        lit
+    }
+
+    /// Add the clause of `lits`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if a literal is not of a variable of the solver.
+    pub fn add_clause(&mut self, lits: &[Lit]) {
+        assert!(lits.iter().all(|lit| lit.var() < self.n_vars()));
+        let mut lits = lits.to_vec();
+        lits.sort();
+        lits.dedup();
+        if self.unsat || lits.windows(2).any(|pair| pair[0] == !pair[1]) {
+            return;
+        }
+        // NOTE: Clauses are only added at decision level 0.
+        if lits
+            .iter()
+            .any(|lit| lit_value(&self.assigns, *lit) == Some(true))
+        {
+            return;
+        }
+        lits.retain(|lit| lit_value(&self.assigns, *lit).is_none());
+        match lits[..] {
+            [] => self.unsat = true,
+            [lit] => {
+                self.enqueue(lit, None);
+                self.unsat = self.propagate().is_some();
+            }
+            _ => {
+                self.attach(lits);
+            }
+        }
+    }
+
+    /// Whether the clauses are satisfiable. The satisfying assignment is
+    /// available from [Solver::value] until the next call.
+    pub fn solve(&mut self) -> bool {
+        if self.unsat || self.propagate().is_some() {
+            self.unsat = true;
+            return false;
+        }
+        let mut restart = FIRST_RESTART;
+        let mut conflicts = 0;
+        loop {
+            if let Some(conflict) = self.propagate() {
+                if self.trail_lim.is_empty() {
+                    self.unsat = true;
+                    return false;
+                }
+                let (learnt, level) = self.analyze(conflict);
+                self.cancel_until(level);
+                let lit = learnt[0];
+                let reason = (learnt.len() > 1).then(|| self.attach(learnt));
+                self.enqueue(lit, reason);
+                self.increment /= ACTIVITY_DECAY;
+                conflicts += 1;
+                if conflicts == restart {
+                    conflicts = 0;
+                    restart += restart / 2;
+                    self.cancel_until(0);
+                }
+            } else if let Some(var) = self.pick() {
+                self.trail_lim.push(self.trail.len());
+                let lit = Lit::new(var);
+                self.enqueue(if self.phases[var] { lit } else { !lit }, None);
+            } else {
+                self.model = self
+                    .assigns
+                    .iter()
+                    .map(|value| *value == Some(true))
+                    .collect();
+                self.cancel_until(0);
+                return true;
+            }
+        }
+    }
+
+    /// The value of `lit` in the last satisfying assignment.
+    pub fn value(&self, lit: Lit) -> bool {
+        self.model[lit.var()] != lit.is_negated()
+    }
+
+    fn attach(&mut self, lits: Vec<Lit>) -> usize {
+        let index = self.clauses.len();
+        self.watches[lits[0].index()].push(index);
+        self.watches[lits[1].index()].push(index);
+        self.clauses.push(lits);
+        index
+    }
+
+    fn enqueue(&mut self, lit: Lit, reason: Option<usize>) {
+        let var = lit.var();
+        self.assigns[var] = Some(!lit.is_negated());
+        self.levels[var] = self.trail_lim.len();
+        self.reasons[var] = reason;
+        self.trail.push(lit);
+    }
+
+    /// Propagate the trail, returning a clause in conflict if any.
+    fn propagate(&mut self) -> Option<usize> {
+        while self.head < self.trail.len() {
+            let falsified = !self.trail[self.head];
+            self.head += 1;
+            let watching = mem::take(&mut self.watches[falsified.index()]);
+            let mut kept = Vec::with_capacity(watching.len());
+            let mut conflict = None;
+            for (i, index) in watching.iter().copied().enumerate() {
+                let clause = &mut self.clauses[index];
+                if clause[0] == falsified {
+                    clause.swap(0, 1);
+                }
+                if lit_value(&self.assigns, clause[0]) == Some(true) {
+                    kept.push(index);
+                    continue;
+                }
+                let replacement =
+                    (2..clause.len()).find(|k| lit_value(&self.assigns, clause[*k]) != Some(false));
+                if let Some(k) = replacement {
+                    clause.swap(1, k);
+                    self.watches[clause[1].index()].push(index);
+                    continue;
+                }
+                kept.push(index);
+                let lit = clause[0];
+                if lit_value(&self.assigns, lit) == Some(false) {
+                    kept.extend(&watching[i + 1..]);
+                    conflict = Some(index);
+                    break;
+                }
+                self.enqueue(lit, Some(index));
+            }
+            self.watches[falsified.index()] = kept;
+            if conflict.is_some() {
+                self.head = self.trail.len();
+                return conflict;
+            }
+        }
+        None
+    }
+
+    /// Learn a clause from `conflict` asserting its first literal once
+    /// backtracked to the returned level.
+    fn analyze(&mut self, conflict: usize) -> (Vec<Lit>, usize) {
+        let level = self.trail_lim.len();
+        let mut seen = vec![false; self.n_vars()];
+        let mut learnt = vec![Lit(0)];
+        let mut pending = 0;
+        let mut index = self.trail.len();
+        let mut clause = conflict;
+        let mut skip = 0;
+        let asserting = loop {
+            for k in skip..self.clauses[clause].len() {
+                let lit = self.clauses[clause][k];
+                let var = lit.var();
+                if seen[var] || self.levels[var] == 0 {
+                    continue;
+                }
+                seen[var] = true;
+                self.bump(var);
+                if self.levels[var] == level {
+                    pending += 1;
+                } else {
+                    learnt.push(lit);
+                }
+            }
+            let lit = loop {
+                index -= 1;
+                if seen[self.trail[index].var()] {
+                    break self.trail[index];
+                }
+            };
+            pending -= 1;
+            if pending == 0 {
+                break lit;
+            }
+            clause = self.reasons[lit.var()].expect("implied literal should have a reason");
+            // NOTE: The implied literal of a reason is its first.
+            skip = 1;
+        };
+        learnt[0] = !asserting;
+        let backtrack = (1..learnt.len())
+            .max_by_key(|k| self.levels[learnt[*k].var()])
+            .map_or(0, |k| {
+                learnt.swap(1, k);
+                self.levels[learnt[1].var()]
+            });
+        (learnt, backtrack)
+    }
+
+    fn bump(&mut self, var: usize) {
+        self.activity[var] += self.increment;
+        if self.activity[var] > 1e100 {
+            for activity in &mut self.activity {
+                *activity *= 1e-100;
+            }
+            self.increment *= 1e-100;
+        }
+    }
+
+    /// The unassigned variable of highest activity, if any.
+    fn pick(&self) -> Option<usize> {
+        (0..self.n_vars())
+            .filter(|var| self.assigns[*var].is_none())
+            .max_by(|a, b| self.activity[*a].total_cmp(&self.activity[*b]))
+    }
+
+    fn cancel_until(&mut self, level: usize) {
+        let Some(start) = self.trail_lim.get(level).copied() else {
+            return;
+        };
+        for lit in self.trail.drain(start..) {
+            let var = lit.var();
+            self.phases[var] = !lit.is_negated();
+            self.assigns[var] = None;
+            self.reasons[var] = None;
+        }
+        self.trail_lim.truncate(level);
+        self.head = self.trail.len();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::{rngs::StdRng, Rng, SeedableRng};
+
+    use super::*;
+
+    fn satisfies(clauses: &[Vec<Lit>], solver: &Solver) -> bool {
+        clauses
+            .iter()
+            .all(|clause| clause.iter().any(|lit| solver.value(*lit)))
+    }
+
+    #[test]
+    fn test_pigeonhole() {
+        // NOTE: 5 pigeons do not fit in 4 holes, but 4 do.
+        for (pigeons, sat) in [(5, false), (4, true)] {
+            let holes = 4;
+            let mut solver = Solver::new();
+            let vars: Vec<Vec<_>> = (0..pigeons)
+                .map(|_| (0..holes).map(|_| solver.new_var()).collect())
+                .collect();
+            let mut clauses = Vec::new();
+            for pigeon in &vars {
+                clauses.push(pigeon.clone());
+            }
+            for (a, first) in vars.iter().enumerate() {
+                for second in &vars[a + 1..] {
+                    for (x, y) in first.iter().zip(second) {
+                        clauses.push(vec![!*x, !*y]);
+                    }
+                }
+            }
+            for clause in &clauses {
+                solver.add_clause(clause);
+            }
+            assert_eq!(solver.solve(), sat);
+            if sat {
+                assert!(satisfies(&clauses, &solver));
+            }
+        }
+    }
+
+    #[test]
+    fn test_random() {
+        let mut rng = StdRng::seed_from_u64(0);
+        let n_vars = 12;
+        for _ in 0..200 {
+            let mut solver = Solver::new();
+            let vars: Vec<_> = (0..n_vars).map(|_| solver.new_var()).collect();
+            let clauses: Vec<Vec<_>> = (0..rng.gen_range(20..70))
+                .map(|_| {
+                    (0..3)
+                        .map(|_| {
+                            let lit = vars[rng.gen_range(0..n_vars)];
+                            if rng.gen() {
+                                !lit
+                            } else {
+                                lit
+                            }
+                        })
+                        .collect()
+                })
+                .collect();
+            for clause in &clauses {
+                solver.add_clause(clause);
+            }
+            let expected = (0..1u32 << n_vars).any(|assignment| {
+                clauses.iter().all(|clause| {
+                    clause
+                        .iter()
+                        .any(|lit| (assignment >> lit.var() & 1 == 1) != lit.is_negated())
+                })
+            });
+            assert_eq!(solver.solve(), expected);
+            if expected {
+                assert!(satisfies(&clauses, &solver));
+            }
+        }
+    }
+
+    #[test]
+    fn test_incremental() {
+        let mut solver = Solver::new();
+        let [a, b] = [solver.new_var(), solver.new_var()];
+        solver.add_clause(&[a, b]);
+        assert!(solver.solve());
+        solver.add_clause(&[!a]);
+        assert!(solver.solve());
+        assert!(solver.value(b));
+        solver.add_clause(&[!b, a]);
+        assert!(!solver.solve());
+        assert_eq!(format!("{} {}", a, !b), "1 -2");
+    }
+}
@@ -0,0 +1,428 @@
+//! Export of an [ir](crate::ir) graph to a Yosys [Design](yosys::Design).
+//!
+//! Every node other than a port or constant is written as a gate-level cell,
+//! driving the net bit of its index offset by 2, as bits 0 and 1 are reserved
+//! by Yosys. SFQ nodes are written as the [sfq_cells] read back by
+//! `Graph: TryFrom<yosys::Module>`.
+
+use fnv::{FnvHashMap, FnvHashSet};
+use thiserror::Error;
+
+use super::graph::{sfq_cells, Graph, Node, NodeKind};
+use super::ops::{
+    AnyOp, BinaryOp, ComplexOp, ConstOp, FlipFlop, Latch, Polarity, SeqOp, SfqOp, UnaryOp,
+};
+use crate::interchange::yosys::{
+    self,
+    constids::{internal_cells, parameter_names},
+    ConstBit, PortDirection, SignalBit,
+};
+
+#[derive(Clone, Debug, Error)]
+pub enum Error {
+    #[error(r#"node {node} should have {expected} inputs, found {found}"#)]
+    Arity {
+        node: Node,
+        expected: usize,
+        found: usize,
+    },
+}
+
+pub type Result<T> = std::result::Result<T, Error>;
+
+fn gate_type(op: AnyOp) -> Option<&'static str> {
+    let ty = match op {
+        AnyOp::Unary(UnaryOp::Buf) => "$_BUF_",
+        AnyOp::Unary(UnaryOp::Not) => "$_NOT_",
+        AnyOp::Binary(op) => match op {
+            BinaryOp::And => "$_AND_",
+            BinaryOp::Or => "$_OR_",
+            BinaryOp::Xor => "$_XOR_",
+            BinaryOp::Nand => "$_NAND_",
+            BinaryOp::Nor => "$_NOR_",
+            BinaryOp::Xnor => "$_XNOR_",
+            BinaryOp::AndNot => "$_ANDNOT_",
+            BinaryOp::OrNot => "$_ORNOT_",
+        },
+        AnyOp::Complex(op) => match op {
+            ComplexOp::Aoi3 => "$_AOI3_",
+            ComplexOp::Oai3 => "$_OAI3_",
+            ComplexOp::Aoi4 => "$_AOI4_",
+            ComplexOp::Oai4 => "$_OAI4_",
+        },
+        AnyOp::Lut(_) => internal_cells::LUT,
+        AnyOp::Const(_) => return None,
+        AnyOp::Mux => "$_MUX_",
+        AnyOp::NMux => "$_NMUX_",
+    };
+    Some(ty)
+}
+
+fn seq_type(op: SeqOp) -> String {
+    let polarity = |polarity| match polarity {
+        Polarity::Positive => 'P',
+        Polarity::Negative => 'N',
+    };
+    match op {
+        SeqOp::FlipFlop(FlipFlop {
+            clock,
+            enable,
+            reset,
+        }) => {
+            let c = polarity(clock);
+            let e = enable.map(polarity);
+            let r = reset.map(|reset| {
+                let s = if reset.asynchronous { "" } else { "S" };
+                // NOTE: A gated reset without an enable is an ordinary
+                // synchronous reset.
+                let g = if reset.gated { "C" } else { "" };
+                (s, g, polarity(reset.polarity), u8::from(reset.value))
+            });
+            match (e, r) {
+                (None, None) => format!("$_DFF_{c}_"),
+                (Some(e), None) => format!("$_DFFE_{c}{e}_"),
+                (None, Some((s, _, r, v))) => format!("$_{s}DFF_{c}{r}{v}_"),
+                (Some(e), Some((s, g, r, v))) => format!("$_{s}DFF{g}E_{c}{r}{v}{e}_"),
+            }
+        }
+        SeqOp::Latch(Latch { enable, reset }) => {
+            let e = polarity(enable);
+            match reset {
+                Some(reset) => {
+                    let (r, v) = (polarity(reset.polarity), u8::from(reset.value));
+                    format!("$_DLATCH_{e}{r}{v}_")
+                }
+                None => format!("$_DLATCH_{e}_"),
+            }
+        }
+        SeqOp::Ff => "$_FF_".to_string(),
+    }
+}
+
+/// The type, input ports and output port of the cell a node of `kind` is
+/// written as, or [None] if it is not written as a cell.
+///
+/// The single input `A` of a [Lut] cell is as wide as the look-up table.
+pub(crate) fn cell_type(
+    kind: &NodeKind,
+) -> Option<(String, &'static [&'static str], &'static str)> {
+    let ty = match kind {
+        NodeKind::Source | NodeKind::Sink => return None,
+        NodeKind::Gate(op) => {
+            let ty = gate_type(*op)?;
+            let inputs: &[_] = match op {
+                AnyOp::Lut(_) => &["A"],
+                AnyOp::Mux | AnyOp::NMux => &["A", "B", "S"],
+                _ => &["A", "B", "C", "D"][..op.arity()],
+            };
+            return Some((ty.to_string(), inputs, "Y"));
+        }
+        NodeKind::Seq(op) => return Some((seq_type(*op), op.inputs(), "Q")),
+        NodeKind::Splitter => sfq_cells::SPLITTER,
+        NodeKind::Dff => sfq_cells::DFF,
+        NodeKind::Sfq(op) => match op {
+            SfqOp::Majority => sfq_cells::MAJORITY,
+            SfqOp::Merger => sfq_cells::MERGER,
+            SfqOp::Ndro => sfq_cells::NDRO,
+            SfqOp::T1 => sfq_cells::T1,
+        },
+    };
+    Some((ty.to_string(), sfq_cells::inputs(kind), "Y"))
+}
+
+/// The net bit driven by `node`.
+fn bit(graph: &Graph, node: Node) -> SignalBit {
+    match graph.node(node).kind {
+        NodeKind::Gate(AnyOp::Const(ConstOp::Zero)) => SignalBit::Const(ConstBit::_0),
+        NodeKind::Gate(AnyOp::Const(ConstOp::Unit)) => SignalBit::Const(ConstBit::_1),
+        _ => SignalBit::Ref(node.index() + 2),
+    }
+}
+
+fn netname(bits: Vec<SignalBit>) -> yosys::NetName {
+    yosys::NetName {
+        hide_name: 0,
+        attributes: FnvHashMap::default(),
+        bits,
+        offset: 0,
+        upto: 0,
+        signed: 0,
+    }
+}
+
+impl TryFrom<&Graph> for yosys::Module {
+    type Error = Error;
+
+    fn try_from(graph: &Graph) -> Result<Self> {
+        let arity = |node: Node, expected: usize| {
+            let found = graph.fanins(node).len();
+            if found == expected {
+                Ok(())
+            } else {
+                Err(Error::Arity {
+                    node,
+                    expected,
+                    found,
+                })
+            }
+        };
+        let mut ports = FnvHashMap::default();
+        let mut netnames = FnvHashMap::default();
+        let mut cells = FnvHashMap::default();
+        let mut in_port = vec![false; graph.n_nodes()];
+        for node in graph.ports().iter().flat_map(|port| port.nodes()) {
+            in_port[node.index()] = true;
+        }
+        let mut add_port = |name: &str, nodes: &[Node]| {
+            let mut direction = PortDirection::Input;
+            let mut bits = Vec::with_capacity(nodes.len());
+            for &node in nodes {
+                let bit = match graph.node(node).kind {
+                    NodeKind::Sink => {
+                        direction = PortDirection::Output;
+                        arity(node, 1)?;
+                        match bit(graph, graph.fanins(node)[0]) {
+                            SignalBit::Ref(bit) => bit,
+                            // NOTE: Port bits cannot be constant, so
+                            // constant outputs are driven through a buffer.
+                            constant => {
+                                let output = node.index() + 2;
+                                let cell = yosys::Cell {
+                                    hide_name: 1,
+                                    ty: "$_BUF_".to_string(),
+                                    parameters: FnvHashMap::default(),
+                                    attributes: FnvHashMap::default(),
+                                    port_directions: FnvHashMap::from_iter([
+                                        ("A".to_string(), PortDirection::Input),
+                                        ("Y".to_string(), PortDirection::Output),
+                                    ]),
+                                    connections: FnvHashMap::from_iter([
+                                        ("A".to_string(), vec![constant]),
+                                        ("Y".to_string(), vec![SignalBit::Ref(output)]),
+                                    ]),
+                                };
+                                cells.insert(format!("$n{node}"), cell);
+                                output
+                            }
+                        }
+                    }
+                    _ => node.index() + 2,
+                };
+                bits.push(bit);
+            }
+            let signals = bits.iter().copied().map(SignalBit::Ref).collect();
+            netnames.insert(name.to_string(), netname(signals));
+            let port = yosys::Port {
+                direction,
+                bits,
+                offset: 0,
+                upto: 0,
+                signed: 0,
+            };
+            ports.insert(name.to_string(), port);
+            Ok(())
+        };
+        for port in graph.ports() {
+            let nodes: Vec<_> = port.nodes().collect();
+            add_port(port.name(), &nodes)?;
+        }
+        // NOTE: Sources and sinks outside of a port get a port of their own.
+        for node in graph.node_ids() {
+            let data = graph.node(node);
+            if matches!(data.kind, NodeKind::Source | NodeKind::Sink) && !in_port[node.index()] {
+                let name = data
+                    .name
+                    .map_or_else(|| format!("n{node}"), |name| name.to_string());
+                add_port(&name, &[node])?;
+            }
+        }
+
+        let mut names = FnvHashSet::default();
+        for node in graph.node_ids() {
+            let data = graph.node(node);
+            let Some((ty, inputs, output)) = cell_type(&data.kind) else {
+                continue;
+            };
+            let mut connections = FnvHashMap::default();
+            let mut port_directions = FnvHashMap::default();
+            let mut parameters = FnvHashMap::default();
+            if let NodeKind::Gate(AnyOp::Lut(lut)) = data.kind {
+                let width = lut.n_inputs();
+                arity(node, width)?;
+                let bits = graph.fanins(node).iter().map(|fanin| bit(graph, *fanin));
+                connections.insert("A".to_string(), bits.collect());
+                port_directions.insert("A".to_string(), PortDirection::Input);
+                let table = format!("{:0size$b}", lut.table(), size = 1 << width);
+                parameters.insert(parameter_names::LUT.to_string(), table);
+                parameters.insert(parameter_names::WIDTH.to_string(), format!("{width:032b}"));
+            } else {
+                arity(node, inputs.len())?;
+                for (port, fanin) in inputs.iter().zip(graph.fanins(node)) {
+                    connections.insert(port.to_string(), vec![bit(graph, *fanin)]);
+                    port_directions.insert(port.to_string(), PortDirection::Input);
+                }
+            }
+            connections.insert(output.to_string(), vec![bit(graph, node)]);
+            port_directions.insert(output.to_string(), PortDirection::Output);
+            let name = match data.name {
+                Some(name) if names.insert(name) => name.to_string(),
+                _ => format!("$n{node}"),
+            };
+            let cell = yosys::Cell {
+                hide_name: usize::from(name.starts_with('$')),
+                ty,
+                parameters,
+                attributes: data.attributes.clone(),
+                port_directions,
+                connections,
+            };
+            cells.insert(name, cell);
+        }
+
+        for (name, drivers) in graph.nets() {
+            if !netnames.contains_key(name) {
+                let bits = drivers.iter().map(|driver| bit(graph, *driver)).collect();
+                netnames.insert(name.to_string(), netname(bits));
+            }
+        }
+        Ok(Self {
+            attributes: FnvHashMap::default(),
+            parameter_default_values: FnvHashMap::default(),
+            ports,
+            cells,
+            memories: FnvHashMap::default(),
+            netnames,
+        })
+    }
+}
+
+/// A [Design](yosys::Design) with `graph` as its single module `name`.
+pub fn to_design(graph: &Graph, name: &str) -> Result<yosys::Design> {
+    let module = yosys::Module::try_from(graph)?;
+    Ok(yosys::Design {
+        creator: format!("vts {}", env!("CARGO_PKG_VERSION")),
+        modules: FnvHashMap::from_iter([(name.to_string(), module)]),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ir::graph::{cell_kind, Edge};
+    use crate::ir::sfq::{balance, splitter};
+
+    fn example(json: &str, module: &str) -> Graph {
+        let design: yosys::Design = json.parse().unwrap();
+        Graph::try_from(design.modules[module].clone()).unwrap()
+    }
+
+    fn round_trip(graph: &Graph) -> Graph {
+        let json = serde_json::to_string(&to_design(graph, "top").unwrap()).unwrap();
+        let design: yosys::Design = json.parse().unwrap();
+        Graph::try_from(design.modules["top"].clone()).unwrap()
+    }
+
+    fn kinds(graph: &Graph) -> Vec<String> {
+        graph
+            .nodes()
+            .map(|node| format!("{:?}", node.kind))
+            .collect()
+    }
+
+    #[test]
+    fn test_round_trip() {
+        for (json, module) in [
+            (
+                include_str!("../../../../examples/alu/add4_simplemap.json"),
+                "add4",
+            ),
+            (
+                include_str!("../../../../examples/sr/sr_simplemap.json"),
+                "sr",
+            ),
+            (
+                include_str!("../../../../examples/alu/add4_flowmap.json"),
+                "add4",
+            ),
+        ] {
+            let graph = example(json, module);
+            let copy = round_trip(&graph);
+            assert_eq!(kinds(&copy), kinds(&graph), "{module}");
+            assert_eq!(
+                copy.edges().collect::<Vec<_>>(),
+                graph.edges().collect::<Vec<_>>(),
+                "{module}"
+            );
+            for node in graph.node_ids() {
+                assert_eq!(copy.node(node).name, graph.node(node).name);
+            }
+        }
+    }
+
+    #[test]
+    fn test_seq_type() {
+        for ty in [
+            "$_DFF_N_",
+            "$_DFFE_PN_",
+            "$_DFF_NP1_",
+            "$_DFFE_PN0P_",
+            "$_SDFF_PP1_",
+            "$_SDFFE_NN0P_",
+            "$_SDFFCE_PN1N_",
+            "$_DLATCH_P_",
+            "$_DLATCH_NP0_",
+            "$_FF_",
+        ] {
+            let Some((NodeKind::Seq(op), ..)) = cell_kind(ty) else {
+                panic!("{ty} should be sequential");
+            };
+            assert_eq!(seq_type(op), ty);
+        }
+    }
+
+    #[test]
+    fn test_sfq() {
+        let mut graph = example(
+            include_str!("../../../../examples/alu/add4_simplemap.json"),
+            "add4",
+        );
+        balance::balance(&mut graph, balance::BalanceMode::Outputs).unwrap();
+        splitter::insert_splitters(&mut graph, &splitter::SplitterConfig::default());
+        let copy = round_trip(&graph);
+        let mut expected = kinds(&graph);
+        let mut kinds = kinds(&copy);
+        expected.sort();
+        kinds.sort();
+        assert_eq!(kinds, expected);
+        assert_eq!(copy.edges().count(), graph.edges().count());
+    }
+
+    #[test]
+    fn test_constant_output() {
+        let mut graph = Graph::default();
+        let y = graph.add_output("y", 1).next().unwrap();
+        let unit = graph.add_unit();
+        graph.add_edge(Edge {
+            source: unit,
+            sink: y,
+        });
+        let module = yosys::Module::try_from(&graph).unwrap();
+        assert_eq!(module.cells.len(), 1);
+        let copy = round_trip(&graph);
+        let driver = copy.fanins(copy.find("y").unwrap())[0];
+        assert!(matches!(
+            copy.node(driver).kind,
+            NodeKind::Gate(AnyOp::Unary(UnaryOp::Buf))
+        ));
+
+        graph.add_sink(1);
+        assert!(matches!(
+            yosys::Module::try_from(&graph),
+            Err(Error::Arity {
+                expected: 1,
+                found: 0,
+                ..
+            })
+        ));
+    }
+}
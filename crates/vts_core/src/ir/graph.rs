@@ -1,8 +1,15 @@
-use std::{collections::HashMap, fmt, slice};
+use std::{
+    cmp::Reverse,
+    collections::{BinaryHeap, HashMap},
+    fmt, iter, mem, slice,
+};
 
+use fnv::FnvHashMap;
 use thiserror::Error;
+use ustr::{ustr, Ustr};
 
-use super::ops::{AnyOp, ConstOp};
+use super::blast::{self, Builder};
+use super::ops::{AnyOp, ComplexOp, ConstOp, FlipFlop, Latch, Polarity, Reset, SeqOp, SfqOp};
 use crate::interchange::yosys::{self, ConstBit, PortDirection, SignalBit};
 
 #[derive(Clone, Copy, Debug, Eq, Hash, Ord, PartialEq, PartialOrd)]
@@ -13,6 +20,10 @@ impl Node {
         Self(id)
     }
 
+    pub fn index(&self) -> usize {
+        self.0
+    }
+
     fn advance(&mut self, count: usize) {
         self.0 += count;
     }
@@ -57,11 +68,19 @@ impl Iterator for NodeRange {
     }
 }
 
+impl ExactSizeIterator for NodeRange {}
+
 #[derive(Clone, Copy, Debug)]
 pub enum NodeKind {
     Source,
     Sink,
     Gate(AnyOp),
+    /// Copies its single input to each of its sinks.
+    Splitter,
+    /// Delays its single input by one clock stage.
+    Dff,
+    Seq(SeqOp),
+    Sfq(SfqOp),
 }
 
 impl<Op> From<Op> for NodeKind
@@ -73,9 +92,28 @@ where
     }
 }
 
+/// The attribute holding the initial value, `0` or `1`, of a sequential node.
+pub const INIT: &str = "init";
+
 #[derive(Clone, Debug)]
 pub struct NodeData {
     pub kind: NodeKind,
+    /// The name the node can be looked up by in its [Graph].
+    pub name: Option<Ustr>,
+    /// The name of the Yosys cell the node was converted from.
+    pub cell: Option<Ustr>,
+    pub attributes: FnvHashMap<String, String>,
+}
+
+impl From<NodeKind> for NodeData {
+    fn from(kind: NodeKind) -> Self {
+        Self {
+            kind,
+            name: None,
+            cell: None,
+            attributes: FnvHashMap::default(),
+        }
+    }
 }
 
 impl NodeData {
@@ -83,21 +121,73 @@ impl NodeData {
     where
         AnyOp: From<Op>,
     {
-        Self {
-            kind: NodeKind::from(op),
-        }
+        Self::from(NodeKind::from(op))
     }
 
     pub fn new_source() -> Self {
-        Self {
-            kind: NodeKind::Source,
-        }
+        Self::from(NodeKind::Source)
     }
 
     pub fn new_sink() -> Self {
-        Self {
-            kind: NodeKind::Sink,
-        }
+        Self::from(NodeKind::Sink)
+    }
+
+    pub fn new_splitter() -> Self {
+        Self::from(NodeKind::Splitter)
+    }
+
+    pub fn new_dff() -> Self {
+        Self::from(NodeKind::Dff)
+    }
+
+    pub fn new_seq(op: SeqOp) -> Self {
+        Self::from(NodeKind::Seq(op))
+    }
+
+    pub fn new_sfq(op: SfqOp) -> Self {
+        Self::from(NodeKind::Sfq(op))
+    }
+
+    pub fn with_name(mut self, name: &str) -> Self {
+        self.name = Some(ustr(name));
+        self
+    }
+
+    pub fn with_cell(mut self, cell: &str) -> Self {
+        self.cell = Some(ustr(cell));
+        self
+    }
+
+    pub fn with_attributes(mut self, attributes: FnvHashMap<String, String>) -> Self {
+        self.attributes = attributes;
+        self
+    }
+}
+
+/// A named range of [NodeKind::Source] or [NodeKind::Sink] nodes, one for
+/// every bit of the port.
+#[derive(Clone, Debug)]
+pub struct Port {
+    name: Ustr,
+    nodes: NodeRange,
+}
+
+impl Port {
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn nodes(&self) -> NodeRange {
+        self.nodes
+    }
+
+    pub fn width(&self) -> usize {
+        self.nodes.len()
+    }
+
+    /// The bit of the port at `node`, if any.
+    pub fn bit(&self, node: Node) -> Option<usize> {
+        (self.nodes.start <= node && node < self.nodes.end).then(|| node.0 - self.nodes.start.0)
     }
 }
 
@@ -111,6 +201,7 @@ pub struct Edge {
 struct NodeEntry {
     data: NodeData,
     sinks: Vec<Node>,
+    fanins: Vec<Node>,
 }
 
 impl From<NodeData> for NodeEntry {
@@ -118,6 +209,7 @@ impl From<NodeData> for NodeEntry {
         Self {
             data,
             sinks: Vec::new(),
+            fanins: Vec::new(),
         }
     }
 }
@@ -125,6 +217,9 @@ impl From<NodeData> for NodeEntry {
 #[derive(Clone, Debug, Default)]
 pub struct Graph {
     entries: Vec<NodeEntry>,
+    names: FnvHashMap<Ustr, Node>,
+    ports: Vec<Port>,
+    nets: FnvHashMap<Ustr, Vec<Node>>,
 }
 
 impl Graph {
@@ -133,18 +228,28 @@ impl Graph {
         Ns: IntoIterator<Item = NodeData>,
         Es: IntoIterator<Item = Edge>,
     {
-        let mut graph = Self {
-            entries: nodes.into_iter().map(NodeEntry::from).collect(),
-        };
+        let mut graph = Self::default();
+        graph.add_nodes(nodes);
         for e in edges.into_iter() {
             graph.add_edge(e);
         }
         graph
     }
 
-    pub fn add_node(&mut self, node: NodeData) -> Node {
+    /// Add `node` to the graph. A named node whose name is taken is renamed
+    /// `name_n`, with the least `n` making its name unique.
+    pub fn add_node(&mut self, mut node: NodeData) -> Node {
+        let id = Node::new(self.entries.len());
+        if let Some(name) = node.name {
+            let name = iter::once(name)
+                .chain((1..).map(|n| ustr(&format!("{name}_{n}"))))
+                .find(|name| !self.names.contains_key(name))
+                .expect("a unique name should exist");
+            node.name = Some(name);
+            self.names.insert(name, id);
+        }
         self.entries.push(NodeEntry::from(node));
-        Node::new(self.entries.len() - 1)
+        id
     }
 
     pub fn add_nodes<Ns>(&mut self, nodes: Ns) -> NodeRange
@@ -167,6 +272,38 @@ impl Graph {
         self.add_nodes((0..width).map(|_| NodeData::new_sink()))
     }
 
+    fn add_port(&mut self, name: &str, width: usize, node: fn() -> NodeData) -> NodeRange {
+        let nodes = self.add_nodes((0..width).map(|bit| match width {
+            1 => node().with_name(name),
+            _ => node().with_name(&format!("{name}[{bit}]")),
+        }));
+        self.ports.push(Port {
+            name: ustr(name),
+            nodes,
+        });
+        nodes
+    }
+
+    /// Add a [Port] of `width` [NodeKind::Source] nodes, named `name[bit]`,
+    /// or `name` if it has a single bit.
+    pub fn add_input(&mut self, name: &str, width: usize) -> NodeRange {
+        self.add_port(name, width, NodeData::new_source)
+    }
+
+    /// Add a [Port] of `width` [NodeKind::Sink] nodes, named like the nodes
+    /// of [Graph::add_input].
+    pub fn add_output(&mut self, name: &str, width: usize) -> NodeRange {
+        self.add_port(name, width, NodeData::new_sink)
+    }
+
+    /// Name the net driven by `drivers`, one for every bit.
+    pub fn add_net(&mut self, name: &str, drivers: Vec<Node>) {
+        for driver in &drivers {
+            self.check_node(*driver);
+        }
+        self.nets.insert(ustr(name), drivers);
+    }
+
     pub fn add_const(&mut self, k: ConstOp) -> Node {
         self.add_node(NodeData::new_op(k))
     }
@@ -176,7 +313,7 @@ impl Graph {
     }
 
     pub fn add_zero(&mut self) -> Node {
-        self.add_const(ConstOp::Unit)
+        self.add_const(ConstOp::Zero)
     }
 
     fn check_node(&self, node: Node) {
@@ -195,6 +332,7 @@ impl Graph {
 
     pub fn add_edge_unchecked(&mut self, edge: Edge) {
         self.entries[edge.source.0].sinks.push(edge.sink);
+        self.entries[edge.sink.0].fanins.push(edge.source);
     }
 
     pub fn add_edges<Es>(&mut self, edges: Es)
@@ -206,6 +344,280 @@ impl Graph {
         }
     }
 
+    /// Move `edge` to be driven by `source` instead, keeping the position of
+    /// the edge among the fan-ins of its sink.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `edge` is not in the graph.
+    pub fn replace_edge(&mut self, edge: Edge, source: Node) {
+        self.check_node(source);
+        let sinks = &mut self.entries[edge.source.0].sinks;
+        let Some(index) = sinks.iter().position(|sink| *sink == edge.sink) else {
+            panic!("edge {} -> {} not in graph", edge.source, edge.sink);
+        };
+        sinks.remove(index);
+        self.entries[source.0].sinks.push(edge.sink);
+        let fanin = self.entries[edge.sink.0]
+            .fanins
+            .iter_mut()
+            .find(|fanin| **fanin == edge.source)
+            .expect("edge should be a fan-in of its sink");
+        *fanin = source;
+    }
+
+    /// Move every edge driven by `node` to be driven by `by` instead, along
+    /// with the nets `node` drives.
+    pub fn replace_uses(&mut self, node: Node, by: Node) {
+        self.check_node(node);
+        self.check_node(by);
+        if node == by {
+            return;
+        }
+        let sinks = mem::take(&mut self.entries[node.0].sinks);
+        for sink in &sinks {
+            for fanin in &mut self.entries[sink.0].fanins {
+                if *fanin == node {
+                    *fanin = by;
+                }
+            }
+        }
+        self.entries[by.0].sinks.extend(sinks);
+        for drivers in self.nets.values_mut() {
+            for driver in drivers.iter_mut().filter(|driver| **driver == node) {
+                *driver = by;
+            }
+        }
+    }
+
+    /// Remove every node `keep` rejects along with its edges, renumbering the
+    /// remaining nodes in order. Nets driven by a removed node are removed too.
+    ///
+    /// Returns the new id of every former node, if kept.
+    ///
+    /// # Panics
+    ///
+    /// Panics if a node of a [Port] is removed.
+    pub fn retain_nodes<F>(&mut self, mut keep: F) -> Vec<Option<Node>>
+    where
+        F: FnMut(Node, &NodeData) -> bool,
+    {
+        let mut n_kept = 0;
+        let ids: Vec<_> = self
+            .node_ids()
+            .map(|node| {
+                keep(node, &self.entries[node.0].data).then(|| {
+                    n_kept += 1;
+                    Node::new(n_kept - 1)
+                })
+            })
+            .collect();
+        let remap = |nodes: &mut Vec<Node>| {
+            nodes.retain(|node| ids[node.0].is_some());
+            for node in nodes.iter_mut() {
+                *node = ids[node.0].unwrap();
+            }
+        };
+        for port in &mut self.ports {
+            assert!(
+                port.nodes().all(|node| ids[node.0].is_some()),
+                r#"nodes of port "{}" should not be removed"#,
+                port.name
+            );
+            let start = Node::new(ids[..port.nodes.start.0].iter().flatten().count());
+            port.nodes = NodeRange {
+                start,
+                end: Node::new(start.0 + port.nodes.len()),
+            };
+        }
+        let entries = mem::take(&mut self.entries);
+        self.entries = entries
+            .into_iter()
+            .zip(&ids)
+            .filter(|(_, id)| id.is_some())
+            .map(|(mut entry, _)| {
+                remap(&mut entry.sinks);
+                remap(&mut entry.fanins);
+                entry
+            })
+            .collect();
+        self.names.retain(|_, node| ids[node.0].is_some());
+        for node in self.names.values_mut() {
+            *node = ids[node.0].unwrap();
+        }
+        self.nets
+            .retain(|_, drivers| drivers.iter().all(|driver| ids[driver.0].is_some()));
+        for drivers in self.nets.values_mut() {
+            remap(drivers);
+        }
+        ids
+    }
+
+    /// Iterate over the nodes in a stable topological order.
+    ///
+    /// Edges into [NodeKind::Seq] nodes do not constrain the order. Nodes
+    /// without a predecessor in common are ordered by their index. The nodes
+    /// of a combinational loop, and the nodes it drives, are never reached.
+    pub fn topological(&self) -> Topological<'_> {
+        let mut n_fanins: Vec<_> = self
+            .entries
+            .iter()
+            .map(|entry| entry.fanins.len())
+            .collect();
+        for (n_fanins, entry) in n_fanins.iter_mut().zip(&self.entries) {
+            if matches!(entry.data.kind, NodeKind::Seq(_)) {
+                *n_fanins = 0;
+            }
+        }
+        let ready = self
+            .node_ids()
+            .filter(|node| n_fanins[node.0] == 0)
+            .map(Reverse)
+            .collect();
+        Topological {
+            graph: self,
+            n_fanins,
+            ready,
+        }
+    }
+
+    /// The nodes in the order of [Graph::topological].
+    ///
+    /// # Errors
+    ///
+    /// Returns [Error::CombinationalLoop] naming one of the loops if the graph
+    /// has any.
+    pub fn topological_order(&self) -> Result<Vec<Node>, Error> {
+        let order: Vec<_> = self.topological().collect();
+        if order.len() == self.entries.len() {
+            return Ok(order);
+        }
+        let mut ordered = vec![false; self.entries.len()];
+        for node in &order {
+            ordered[node.0] = true;
+        }
+        // NOTE: Every node left out of the order has a driver that was left
+        // out too, so walking back through them eventually closes a loop.
+        let mut node = self
+            .node_ids()
+            .find(|node| !ordered[node.0])
+            .expect("a node should be left out of the order");
+        let mut positions = vec![None; self.entries.len()];
+        let mut path = Vec::new();
+        let start = loop {
+            if let Some(start) = positions[node.0] {
+                break start;
+            }
+            positions[node.0] = Some(path.len());
+            path.push(node);
+            node = self.entries[node.0]
+                .fanins
+                .iter()
+                .copied()
+                .find(|fanin| !ordered[fanin.0])
+                .expect("node left out of the order should have a driver left out");
+        };
+        let mut cycle = path.split_off(start);
+        cycle.reverse();
+        let first = (0..cycle.len())
+            .min_by_key(|i| cycle[*i])
+            .unwrap_or_default();
+        cycle.rotate_left(first);
+        Err(Error::CombinationalLoop(cycle))
+    }
+
+    /// Check that the graph has no combinational loop.
+    pub fn check_loops(&self) -> Result<(), Error> {
+        self.topological_order().map(|_| ())
+    }
+
+    /// The logic level of every node, indexed by node.
+    ///
+    /// The level of a [NodeKind::Gate] or [NodeKind::Sfq] node is one more
+    /// than the highest level of its inputs, while other nodes share the level
+    /// of their highest input. [NodeKind::Source], [NodeKind::Seq] and nodes
+    /// without inputs are at level 0.
+    pub fn levels(&self) -> Result<Vec<usize>, Error> {
+        let mut levels = vec![0; self.entries.len()];
+        for node in self.topological_order()? {
+            let entry = &self.entries[node.0];
+            let level = match entry.data.kind {
+                NodeKind::Source | NodeKind::Seq(_) => continue,
+                NodeKind::Gate(_) | NodeKind::Sfq(_) => 1,
+                _ => 0,
+            };
+            let input = entry.fanins.iter().map(|fanin| levels[fanin.0]).max();
+            levels[node.0] = input.map_or(0, |input| input + level);
+        }
+        Ok(levels)
+    }
+
+    /// The highest logic level of the graph.
+    pub fn depth(&self) -> Result<usize, Error> {
+        Ok(self.levels()?.into_iter().max().unwrap_or_default())
+    }
+
+    pub fn node(&self, node: Node) -> &NodeData {
+        self.check_node(node);
+        &self.entries[node.0].data
+    }
+
+    /// The sinks of `node`, once for every edge.
+    pub fn sinks(&self, node: Node) -> &[Node] {
+        self.check_node(node);
+        &self.entries[node.0].sinks
+    }
+
+    /// The drivers of `node`, in input order.
+    pub fn fanins(&self, node: Node) -> &[Node] {
+        self.check_node(node);
+        &self.entries[node.0].fanins
+    }
+
+    /// The node named `name`.
+    pub fn find(&self, name: &str) -> Option<Node> {
+        self.names.get(&ustr(name)).copied()
+    }
+
+    /// The name of the Yosys cell `node` was converted from.
+    pub fn cell_name(&self, node: Node) -> Option<&str> {
+        self.node(node).cell.as_deref()
+    }
+
+    pub fn ports(&self) -> &[Port] {
+        &self.ports
+    }
+
+    /// The port and bit of a [NodeKind::Source] or [NodeKind::Sink] node.
+    pub fn port_bit(&self, node: Node) -> Option<(&Port, usize)> {
+        self.ports
+            .iter()
+            .find_map(|port| Some((port, port.bit(node)?)))
+    }
+
+    /// The drivers of every bit of the net `name`.
+    pub fn net(&self, name: &str) -> Option<&[Node]> {
+        self.nets.get(&ustr(name)).map(Vec::as_slice)
+    }
+
+    /// The named nets, with the drivers of their bits.
+    pub fn nets(&self) -> impl Iterator<Item = (&str, &[Node])> {
+        self.nets
+            .iter()
+            .map(|(name, drivers)| (name.as_str(), drivers.as_slice()))
+    }
+
+    pub fn n_nodes(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn node_ids(&self) -> NodeRange {
+        NodeRange {
+            start: Node::new(0),
+            end: Node::new(self.entries.len()),
+        }
+    }
+
     pub fn nodes(&self) -> Nodes<'_> {
         Nodes {
             iter: self.entries.iter(),
@@ -237,6 +649,31 @@ impl<'a> Iterator for Nodes<'a> {
     }
 }
 
+/// A [Graph] iterator in topological order.
+pub struct Topological<'a> {
+    graph: &'a Graph,
+    n_fanins: Vec<usize>,
+    ready: BinaryHeap<Reverse<Node>>,
+}
+
+impl Iterator for Topological<'_> {
+    type Item = Node;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let Reverse(node) = self.ready.pop()?;
+        for sink in &self.graph.entries[node.0].sinks {
+            if self.n_fanins[sink.0] == 0 {
+                continue;
+            }
+            self.n_fanins[sink.0] -= 1;
+            if self.n_fanins[sink.0] == 0 {
+                self.ready.push(Reverse(*sink));
+            }
+        }
+        Some(node)
+    }
+}
+
 struct CurrentNode<'a> {
     id: Node,
     sinks: slice::Iter<'a, Node>,
@@ -252,40 +689,46 @@ impl<'a> Iterator for Edges<'a> {
     type Item = (Node, Node);
 
     fn next(&mut self) -> Option<Self::Item> {
-        let entries = &mut self.nodes.iter;
-        if self.current.is_none() {
-            self.current = entries.next().map(|entry| {
-                let n = CurrentNode {
+        loop {
+            if self.current.is_none() {
+                let entry = self.nodes.iter.next()?;
+                self.current = Some(CurrentNode {
                     id: self.last_node,
                     sinks: entry.sinks.iter(),
-                };
+                });
                 self.last_node.bump();
-                n
-            });
-        }
-        if let Some(current) = &mut self.current {
-            if let Some(sink) = current.sinks.next() {
-                return Some((current.id, *sink));
-            } else {
+            }
+            if let Some(current) = &mut self.current {
+                if let Some(sink) = current.sinks.next() {
+                    return Some((current.id, *sink));
+                }
                 self.current = None;
             }
         }
-        None
     }
 }
 
+#[derive(Clone, Debug, Error)]
+pub enum Error {
+    #[error(
+        "combinational loop through nodes {}",
+        .0.iter().map(ToString::to_string).collect::<Vec<_>>().join(" -> ")
+    )]
+    CombinationalLoop(Vec<Node>),
+}
+
 #[derive(Clone, Debug, Error)]
 pub enum YosysError {
     #[error(r#""{0}" not supported"#)]
     Unsupported(String),
-    #[error(r#"cell "{0}" should have output Y"#)]
-    ShouldHaveOutput(String),
-    #[error(r#"multi-bit output ports are not supported ("{0}".Y)"#)]
-    MultiBitOutput(String),
-    #[error(r#"expected output port to have single bit ("{0}".Y)"#)]
-    MissingOutput(String),
-    #[error(r#"unexpected const output ("{0}".Y)"#)]
-    ConstOutput(String),
+    #[error(r#"cell "{cell}" should have output "{port}""#)]
+    ShouldHaveOutput { cell: String, port: String },
+    #[error(r#"multi-bit output ports are not supported ("{cell}".{port})"#)]
+    MultiBitOutput { cell: String, port: String },
+    #[error(r#"expected output port to have single bit ("{cell}".{port})"#)]
+    MissingOutput { cell: String, port: String },
+    #[error(r#"unexpected const output ("{cell}".{port})"#)]
+    ConstOutput { cell: String, port: String },
     #[error(r#"cell "{cell}" should have input "{port}""#)]
     ShouldHaveInput { cell: String, port: String },
     #[error(r#"multi-bit input ports are not supported ("{cell}".{port})"#)]
@@ -294,121 +737,372 @@ pub enum YosysError {
     MissingInput { cell: String, port: String },
     #[error(r#"bit {0} out of bounds"#)]
     BitOutOfBounds(usize),
+    #[error(r#"cell "{cell}" should have parameter "{parameter}""#)]
+    ShouldHaveParameter { cell: String, parameter: String },
+    #[error(r#"invalid parameter value ("{cell}".{parameter})"#)]
+    InvalidParameter { cell: String, parameter: String },
+    #[error(r#"port width does not match the cell parameters ("{cell}".{port})"#)]
+    WidthMismatch { cell: String, port: String },
+    #[error(r#"cell "{0}" is part of a combinational loop"#)]
+    CombinationalLoop(String),
+}
+
+/// The [Polarity]'s encoded in the suffix of a sequential cell type, e.g.
+/// `PN` in `$_DFFE_PN_`.
+fn parse_polarities<const N: usize>(ty: &str, prefix: &str) -> Option<[char; N]> {
+    let flags: Vec<_> = ty
+        .strip_prefix(prefix)?
+        .strip_suffix('_')?
+        .chars()
+        .collect();
+    flags.try_into().ok()
+}
+
+fn polarity(flag: char) -> Option<Polarity> {
+    match flag {
+        'P' => Some(Polarity::Positive),
+        'N' => Some(Polarity::Negative),
+        _ => None,
+    }
+}
+
+fn reset(flag: char, value: char, asynchronous: bool) -> Option<Reset> {
+    Some(Reset {
+        polarity: polarity(flag)?,
+        value: match value {
+            '0' => false,
+            '1' => true,
+            _ => return None,
+        },
+        asynchronous,
+        gated: false,
+    })
+}
+
+fn parse_seq_op(ty: &str) -> Option<SeqOp> {
+    let flip_flop = |clock, enable, reset| {
+        Some(SeqOp::FlipFlop(FlipFlop {
+            clock: polarity(clock)?,
+            enable,
+            reset,
+        }))
+    };
+    let latch = |enable, reset| {
+        Some(SeqOp::Latch(Latch {
+            enable: polarity(enable)?,
+            reset,
+        }))
+    };
+    if let Some([c]) = parse_polarities(ty, "$_DFF_") {
+        flip_flop(c, None, None)
+    } else if let Some([c, r, v]) = parse_polarities(ty, "$_DFF_") {
+        flip_flop(c, None, Some(reset(r, v, true)?))
+    } else if let Some([c, e]) = parse_polarities(ty, "$_DFFE_") {
+        flip_flop(c, Some(polarity(e)?), None)
+    } else if let Some([c, r, v, e]) = parse_polarities(ty, "$_DFFE_") {
+        flip_flop(c, Some(polarity(e)?), Some(reset(r, v, true)?))
+    } else if let Some([c, r, v]) = parse_polarities(ty, "$_SDFF_") {
+        flip_flop(c, None, Some(reset(r, v, false)?))
+    } else if let Some([c, r, v, e]) = parse_polarities(ty, "$_SDFFE_") {
+        flip_flop(c, Some(polarity(e)?), Some(reset(r, v, false)?))
+    } else if let Some([c, r, v, e]) = parse_polarities(ty, "$_SDFFCE_") {
+        let reset = Reset {
+            gated: true,
+            ..reset(r, v, false)?
+        };
+        flip_flop(c, Some(polarity(e)?), Some(reset))
+    } else if let Some([e]) = parse_polarities(ty, "$_DLATCH_") {
+        latch(e, None)
+    } else if let Some([e, r, v]) = parse_polarities(ty, "$_DLATCH_") {
+        latch(e, Some(reset(r, v, true)?))
+    } else if ty == "$_FF_" {
+        Some(SeqOp::Ff)
+    } else {
+        None
+    }
+}
+
+/// The [NodeKind], input ports and output port of single-bit cells of type
+/// `ty`.
+pub(crate) fn cell_kind(ty: &str) -> Option<(NodeKind, &'static [&'static str], &'static str)> {
+    let (op, inputs): (_, &[_]) = match ty {
+        "$_BUF_" => (AnyOp::buf(), &["A"]),
+        "$_NOT_" => (AnyOp::not(), &["A"]),
+        "$_AND_" => (AnyOp::and(), &["A", "B"]),
+        "$_NAND_" => (AnyOp::nand(), &["A", "B"]),
+        "$_OR_" => (AnyOp::or(), &["A", "B"]),
+        "$_NOR_" => (AnyOp::nor(), &["A", "B"]),
+        "$_XOR_" => (AnyOp::xor(), &["A", "B"]),
+        "$_XNOR_" => (AnyOp::xnor(), &["A", "B"]),
+        "$_ANDNOT_" => (AnyOp::and_not(), &["A", "B"]),
+        "$_ORNOT_" => (AnyOp::or_not(), &["A", "B"]),
+        "$_AOI3_" => (AnyOp::from(ComplexOp::Aoi3), &["A", "B", "C"]),
+        "$_OAI3_" => (AnyOp::from(ComplexOp::Oai3), &["A", "B", "C"]),
+        "$_AOI4_" => (AnyOp::from(ComplexOp::Aoi4), &["A", "B", "C", "D"]),
+        "$_OAI4_" => (AnyOp::from(ComplexOp::Oai4), &["A", "B", "C", "D"]),
+        "$_MUX_" => (AnyOp::mux(), &["A", "B", "S"]),
+        "$_NMUX_" => (AnyOp::nmux(), &["A", "B", "S"]),
+        _ => {
+            let kind = match ty {
+                sfq_cells::SPLITTER => NodeKind::Splitter,
+                sfq_cells::DFF => NodeKind::Dff,
+                sfq_cells::MAJORITY => NodeKind::Sfq(SfqOp::Majority),
+                sfq_cells::MERGER => NodeKind::Sfq(SfqOp::Merger),
+                sfq_cells::NDRO => NodeKind::Sfq(SfqOp::Ndro),
+                sfq_cells::T1 => NodeKind::Sfq(SfqOp::T1),
+                _ => {
+                    let op = parse_seq_op(ty)?;
+                    return Some((NodeKind::Seq(op), op.inputs(), "Q"));
+                }
+            };
+            return Some((kind, sfq_cells::inputs(&kind), "Y"));
+        }
+    };
+    Some((NodeKind::Gate(op), inputs, "Y"))
+}
+
+/// The types of the cells SFQ nodes are written to Yosys as, with an implicit
+/// clock and the output `Y`.
+pub mod sfq_cells {
+    use super::NodeKind;
+
+    pub const SPLITTER: &str = "sfq_splitter";
+    pub const DFF: &str = "sfq_dff";
+    pub const MAJORITY: &str = "sfq_majority";
+    pub const MERGER: &str = "sfq_merger";
+    pub const NDRO: &str = "sfq_ndro";
+    pub const T1: &str = "sfq_t1";
+
+    /// The input ports of the cell of an SFQ node.
+    pub(crate) fn inputs(kind: &NodeKind) -> &'static [&'static str] {
+        match kind {
+            NodeKind::Sfq(op) => op.inputs(),
+            _ => &["A"],
+        }
+    }
 }
 
+/// The single-bit output `port` of the cell `name`.
+fn output_bit(name: &str, cell: &yosys::Cell, port: &str) -> Result<usize, YosysError> {
+    let bits = cell
+        .connections
+        .get(port)
+        .ok_or_else(|| YosysError::ShouldHaveOutput {
+            cell: cell.ty.clone(),
+            port: port.to_string(),
+        })?;
+    let (cell, port) = (name.to_string(), port.to_string());
+    let mut bits = bits.iter();
+    match (bits.next(), bits.next()) {
+        (Some(SignalBit::Ref(bit)), None) => Ok(*bit),
+        (Some(SignalBit::Const(_)), None) => Err(YosysError::ConstOutput { cell, port }),
+        (Some(_), Some(_)) => Err(YosysError::MultiBitOutput { cell, port }),
+        (None, _) => Err(YosysError::MissingOutput { cell, port }),
+    }
+}
+
+/// The bits connected to the input `port` of `cell`.
+fn input_bits<'a>(cell: &'a yosys::Cell, port: &str) -> Result<&'a [SignalBit], YosysError> {
+    cell.connections
+        .get(port)
+        .map(Vec::as_slice)
+        .ok_or_else(|| YosysError::ShouldHaveInput {
+            cell: cell.ty.clone(),
+            port: port.to_string(),
+        })
+}
+
+/// Convert a Yosys module into a graph of single-bit nodes, bit-blasting
+/// coarse-grained cells.
+///
+/// Undefined (`x`) constant bits are driven by zero, which Yosys allows as
+/// any value may be chosen for them. High-impedance (`z`) bits are not
+/// supported.
 // Reference: https://yosyshq.readthedocs.io/projects/yosys/en/latest/yosys_internals/formats/cell_library.html
 impl TryFrom<yosys::Module> for Graph {
     type Error = YosysError;
 
     fn try_from(module: yosys::Module) -> Result<Self, Self::Error> {
         let mut graph = Self::default();
-        let mut bits_to_nodes = HashMap::with_capacity(module.ports.len());
-        for port in module.ports.values() {
-            let nodes = match port.direction {
-                PortDirection::Input => graph.add_source(port.bits.len()),
-                PortDirection::Output => graph.add_sink(port.bits.len()),
+        let mut builder = Builder::new(&mut graph);
+        let mut drivers = HashMap::with_capacity(module.ports.len());
+        let mut outputs = Vec::new();
+        let mut ports: Vec<_> = module.ports.iter().collect();
+        ports.sort_by_key(|(name, _)| *name);
+        for (name, port) in ports {
+            match port.direction {
+                PortDirection::Input => {
+                    let nodes = builder.graph.add_input(name, port.bits.len());
+                    drivers.extend(port.bits.iter().copied().zip(nodes));
+                }
+                PortDirection::Output => {
+                    let nodes = builder.graph.add_output(name, port.bits.len());
+                    outputs.extend(port.bits.iter().copied().zip(nodes));
+                }
                 PortDirection::InOut => {
                     return Err(YosysError::Unsupported("inout ports".to_string()));
                 }
-            };
-            for (bit, node) in port.bits.iter().zip(nodes) {
-                bits_to_nodes.insert(*bit, node);
             }
         }
-        let known_cells: Vec<_> = module
-            .cells
-            .iter()
-            .filter_map(|(name, cell)| {
-                Some((
-                    name,
-                    cell,
-                    match cell.ty.as_str() {
-                        "$_NOT_" => AnyOp::not(),
-                        "$_AND_" => AnyOp::and(),
-                        "$_OR_" => AnyOp::or(),
-                        "$_XOR_" => AnyOp::xor(),
-                        "$_MUX_" => AnyOp::mux(),
-                        _ => {
-                            return None;
-                        }
-                    },
-                ))
-            })
-            .collect();
-        for (name, cell, op) in known_cells {
-            let id = graph.add_node(match op {
-                AnyOp::Unary(op) => NodeData::new_op(op),
-                AnyOp::Binary(op) => NodeData::new_op(op),
-                AnyOp::Const(op) => NodeData::new_op(op),
-                AnyOp::Mux => NodeData::new_op(op),
-            });
-            let output = cell
-                .connections
-                .get("Y")
-                .ok_or(YosysError::ShouldHaveOutput(cell.ty.clone()))
-                .and_then(|output| {
-                    let mut bits = output.iter();
-                    match (bits.next(), bits.next()) {
-                        (Some(bit), None) => match bit {
-                            SignalBit::Ref(bit) => Ok(*bit),
-                            SignalBit::Const(_) => Err(YosysError::ConstOutput(name.clone())),
-                        },
-                        (Some(_), Some(_)) => Err(YosysError::MultiBitOutput(name.clone())),
-                        (None, _) => Err(YosysError::MissingOutput(name.clone())),
-                    }
-                })?;
-            bits_to_nodes.insert(output, id);
-            let mut add_input = |port: &'static str| -> Result<(), YosysError> {
-                cell.connections
-                    .get(port)
-                    .ok_or(YosysError::ShouldHaveInput {
+        let mut cells: Vec<_> = module.cells.iter().collect();
+        cells.sort_by_key(|(name, _)| *name);
+        // NOTE: Every single-bit cell output is mapped before any input is
+        // connected, as cells may be listed in any order.
+        let mut nodes = Vec::with_capacity(cells.len());
+        let mut coarse = Vec::new();
+        for (name, cell) in cells {
+            // NOTE: The bits of word-level flip-flops drive their outputs
+            // before any cell is blasted, as they break combinational loops.
+            if let Some(inputs) = blast::seq_inputs(&cell.ty) {
+                let ops = blast::seq_ops(name, cell)?;
+                let width_mismatch = |port: &str| YosysError::WidthMismatch {
+                    cell: name.clone(),
+                    port: port.to_string(),
+                };
+                if input_bits(cell, "D")?.len() != ops.len() {
+                    return Err(width_mismatch("D"));
+                }
+                let q = cell
+                    .connections
+                    .get("Q")
+                    .ok_or_else(|| YosysError::ShouldHaveOutput {
                         cell: cell.ty.clone(),
-                        port: port.to_string(),
-                    })
-                    .and_then(|input| {
-                        let mut bits = input.iter();
-                        match (bits.next(), bits.next()) {
-                            (Some(bit), None) => Ok(bit),
-                            (Some(_), Some(_)) => Err(YosysError::MultiBitInput {
-                                cell: name.clone(),
-                                port: port.to_string(),
-                            }),
-                            (None, _) => Err(YosysError::MissingInput {
-                                cell: name.clone(),
-                                port: port.to_string(),
-                            }),
-                        }
-                    })
-                    .and_then(|input| match input {
-                        SignalBit::Ref(bit) => {
-                            graph.add_edge_unchecked(Edge {
-                                source: Node::new(*bit),
-                                sink: id,
-                            });
-                            Ok(())
-                        }
-                        SignalBit::Const(bit) => {
-                            let node = match bit {
-                                ConstBit::Zero => graph.add_zero(),
-                                ConstBit::One => graph.add_unit(),
-                                k => {
-                                    return Err(YosysError::Unsupported(format!("{k} constants")));
-                                }
-                            };
-                            graph.add_edge_unchecked(Edge {
-                                source: node,
-                                sink: id,
-                            });
-                            Ok(())
-                        }
-                    })
+                        port: "Q".to_string(),
+                    })?;
+                if q.len() != ops.len() {
+                    return Err(width_mismatch("Q"));
+                }
+                for (i, (op, bit)) in ops.into_iter().zip(q).enumerate() {
+                    let SignalBit::Ref(bit) = bit else {
+                        return Err(YosysError::ConstOutput {
+                            cell: name.clone(),
+                            port: "Q".to_string(),
+                        });
+                    };
+                    let data = NodeData::new_seq(op)
+                        .with_name(&format!("{name}[{i}]"))
+                        .with_cell(name)
+                        .with_attributes(cell.attributes.clone());
+                    let id = builder.graph.add_node(data);
+                    drivers.insert(*bit, id);
+                    nodes.push((name, cell, inputs, Some(i), id));
+                }
+                continue;
+            }
+            let Some((kind, inputs, output)) = cell_kind(&cell.ty) else {
+                let inputs = blast::cell_inputs(&cell.ty)
+                    .ok_or_else(|| YosysError::Unsupported(format!("cell type {}", cell.ty)))?;
+                coarse.push((name, cell, inputs));
+                continue;
             };
-            add_input("A")?;
-            if matches!(op, AnyOp::Binary(_) | AnyOp::Mux) {
-                add_input("B")?;
+            let data = NodeData::from(kind)
+                .with_name(name)
+                .with_cell(name)
+                .with_attributes(cell.attributes.clone());
+            let id = builder.graph.add_node(data);
+            drivers.insert(output_bit(name, cell, output)?, id);
+            nodes.push((name, cell, inputs, None, id));
+        }
+        let driver = |builder: &mut Builder, drivers: &HashMap<usize, Node>, bit: &SignalBit| {
+            match bit {
+                SignalBit::Ref(bit) => drivers
+                    .get(bit)
+                    .copied()
+                    .ok_or(YosysError::BitOutOfBounds(*bit)),
+                // NOTE: Undefined bits may take any value, so they are driven
+                // by zero rather than rejected.
+                SignalBit::Const(ConstBit::_0 | ConstBit::X) => Ok(builder.zero()),
+                SignalBit::Const(ConstBit::_1) => Ok(builder.unit()),
+                SignalBit::Const(k) => Err(YosysError::Unsupported(format!("{k} constants"))),
             }
-            if matches!(op, AnyOp::Mux) {
-                add_input("S")?;
+        };
+        // NOTE: Coarse-grained cells are blasted once all their inputs are
+        // driven, so the pending bits are the outputs of cells not yet blasted.
+        let mut pending = HashMap::new();
+        for (name, cell, _) in &coarse {
+            for bit in input_bits(cell, "Y")? {
+                match bit {
+                    SignalBit::Ref(bit) => pending.insert(*bit, *name),
+                    SignalBit::Const(_) => {
+                        return Err(YosysError::ConstOutput {
+                            cell: name.to_string(),
+                            port: "Y".to_string(),
+                        })
+                    }
+                };
+            }
+        }
+        while !coarse.is_empty() {
+            let n_coarse = coarse.len();
+            let mut blocked = Vec::new();
+            for (name, cell, ports) in coarse {
+                let mut inputs = Vec::with_capacity(ports.len());
+                for port in ports {
+                    inputs.push(input_bits(cell, port)?);
+                }
+                let is_pending = |bit: &SignalBit| matches!(bit, SignalBit::Ref(bit) if pending.contains_key(bit));
+                if inputs.iter().any(|bits| bits.iter().any(is_pending)) {
+                    blocked.push((name, cell, ports));
+                    continue;
+                }
+                let inputs = inputs
+                    .into_iter()
+                    .map(|bits| {
+                        bits.iter()
+                            .map(|bit| driver(&mut builder, &drivers, bit))
+                            .collect::<Result<Vec<_>, _>>()
+                    })
+                    .collect::<Result<Vec<_>, _>>()?;
+                builder.cell = Some(ustr(name));
+                let y = blast::blast(&mut builder, name, cell, &inputs)?;
+                builder.cell = None;
+                for (bit, node) in input_bits(cell, "Y")?.iter().zip(y) {
+                    if let SignalBit::Ref(bit) = bit {
+                        pending.remove(bit);
+                        drivers.insert(*bit, node);
+                    }
+                }
+            }
+            if blocked.len() == n_coarse {
+                let (name, _, _) = blocked[0];
+                return Err(YosysError::CombinationalLoop(name.clone()));
+            }
+            coarse = blocked;
+        }
+        for (name, cell, inputs, bit, id) in nodes {
+            for &port in inputs {
+                let bits = input_bits(cell, port)?;
+                let (cell, port) = (name.clone(), port.to_string());
+                let input = match (bits, bit) {
+                    // NOTE: Only the data input of a word-level cell is a word.
+                    (bits, Some(i)) if port == "D" => &bits[i],
+                    ([bit], _) => bit,
+                    ([], _) => return Err(YosysError::MissingInput { cell, port }),
+                    _ => return Err(YosysError::MultiBitInput { cell, port }),
+                };
+                let source = driver(&mut builder, &drivers, input)?;
+                builder.graph.add_edge_unchecked(Edge { source, sink: id });
+            }
+        }
+        for (bit, sink) in outputs {
+            let source = driver(&mut builder, &drivers, &SignalBit::Ref(bit))?;
+            builder.graph.add_edge_unchecked(Edge { source, sink });
+        }
+        let mut netnames: Vec<_> = module
+            .netnames
+            .iter()
+            .filter(|(_, net)| net.hide_name == 0)
+            .collect();
+        netnames.sort_by_key(|(name, _)| *name);
+        for (name, net) in netnames {
+            // NOTE: Nets with undriven bits are not named.
+            let bits: Result<Vec<_>, _> = net
+                .bits
+                .iter()
+                .map(|bit| driver(&mut builder, &drivers, bit))
+                .collect();
+            if let Ok(bits) = bits {
+                builder.graph.add_net(name, bits);
             }
         }
         // TODO: validation
@@ -419,7 +1113,9 @@ impl TryFrom<yosys::Module> for Graph {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::interchange::yosys::constids::{internal_cells, parameter_names};
     use crate::ir::ops::BinaryOp;
+    use crate::ir::sim;
 
     macro_rules! binop {
         (and) => {
@@ -565,4 +1261,426 @@ mod tests {
     fn test_new_graph() {
         get_test_graph();
     }
+
+    #[test]
+    fn test_constants() {
+        let mut graph = Graph::default();
+        let zero = graph.add_zero();
+        let unit = graph.add_unit();
+        assert!(matches!(
+            graph.node(zero).kind,
+            NodeKind::Gate(AnyOp::Const(ConstOp::Zero))
+        ));
+        assert!(matches!(
+            graph.node(unit).kind,
+            NodeKind::Gate(AnyOp::Const(ConstOp::Unit))
+        ));
+    }
+
+    #[test]
+    fn test_undefined_bits() {
+        let design: yosys::Design = include_str!("../../../../examples/alu/add4_simplemap.json")
+            .parse()
+            .unwrap();
+        let mut module = design.modules["add4"].clone();
+        let (name, cell) = module
+            .cells
+            .iter_mut()
+            .find(|(_, cell)| cell.connections.contains_key("A"))
+            .unwrap();
+        let name = name.clone();
+        cell.connections
+            .insert("A".to_string(), vec![SignalBit::Const(ConstBit::X)]);
+        let graph = Graph::try_from(module.clone()).unwrap();
+        let node = graph.find(&name).unwrap();
+        assert!(matches!(
+            graph.node(graph.fanins(node)[0]).kind,
+            NodeKind::Gate(AnyOp::Const(ConstOp::Zero))
+        ));
+
+        let cell = module.cells.get_mut(&name).unwrap();
+        cell.connections
+            .insert("A".to_string(), vec![SignalBit::Const(ConstBit::Z)]);
+        assert!(matches!(
+            Graph::try_from(module),
+            Err(YosysError::Unsupported(_))
+        ));
+    }
+
+    #[test]
+    fn test_edges() {
+        // NOTE: Every edge is visited, including the edges from the output port
+        // bit drivers to their sinks.
+        let graph = get_test_graph();
+        assert_eq!(graph.edges().count(), 45);
+        assert!(graph
+            .edges()
+            .all(|(source, sink)| graph.sinks(source).contains(&sink)));
+    }
+
+    #[test]
+    fn test_topological_order() {
+        let graph = get_test_graph();
+        let order = graph.topological_order().unwrap();
+        assert_eq!(order.len(), graph.n_nodes());
+        let mut positions = vec![0; graph.n_nodes()];
+        for (position, node) in order.iter().enumerate() {
+            positions[node.index()] = position;
+        }
+        for (source, sink) in graph.edges() {
+            assert!(positions[source.index()] < positions[sink.index()]);
+        }
+        assert_eq!(graph.topological().collect::<Vec<_>>(), order);
+    }
+
+    #[test]
+    fn test_replace_uses() {
+        let mut graph = Graph::default();
+        let a = graph.add_input("a", 1).next().unwrap();
+        let b = graph.add_input("b", 1).next().unwrap();
+        let y = graph.add_output("y", 1).next().unwrap();
+        let and = graph.add_node(binop!(and));
+        let or = graph.add_node(binop!(or));
+        graph.add_edges([
+            Edge {
+                source: a,
+                sink: and,
+            },
+            Edge {
+                source: a,
+                sink: or,
+            },
+            Edge {
+                source: b,
+                sink: or,
+            },
+            Edge {
+                source: or,
+                sink: y,
+            },
+        ]);
+        graph.add_net("x", vec![a]);
+        graph.replace_uses(a, b);
+        assert!(graph.sinks(a).is_empty());
+        assert_eq!(graph.sinks(b), [or, and, or]);
+        assert_eq!(graph.fanins(or), [b, b]);
+        assert_eq!(graph.net("x"), Some([b].as_slice()));
+    }
+
+    #[test]
+    fn test_retain_nodes() {
+        let mut graph = Graph::default();
+        let a = graph.add_input("a", 2);
+        let dead = graph.add_node(binop!(and).with_name("dead"));
+        let and = graph.add_node(binop!(and).with_name("and"));
+        let y = graph.add_output("y", 1).next().unwrap();
+        for source in a {
+            graph.add_edges([Edge { source, sink: dead }, Edge { source, sink: and }]);
+        }
+        graph.add_edge(Edge {
+            source: and,
+            sink: y,
+        });
+        graph.add_net("dead", vec![dead]);
+        graph.add_net("and", vec![and]);
+        let ids = graph.retain_nodes(|node, _| node != dead);
+        assert_eq!(ids[dead.index()], None);
+        assert_eq!(ids[and.index()], Some(Node::new(2)));
+        assert_eq!(graph.n_nodes(), 4);
+        assert_eq!(graph.edges().count(), 3);
+        assert_eq!(graph.find("dead"), None);
+        let and = graph.find("and").unwrap();
+        assert_eq!(graph.fanins(and), [Node::new(0), Node::new(1)]);
+        assert_eq!(graph.net("and"), Some([and].as_slice()));
+        assert_eq!(graph.net("dead"), None);
+        let y: Vec<_> = graph.ports()[1].nodes().collect();
+        assert_eq!(y, [Node::new(3)]);
+        assert_eq!(graph.find("y"), Some(Node::new(3)));
+    }
+
+    #[test]
+    fn test_levels() {
+        let graph = get_test_graph();
+        let levels = graph.levels().unwrap();
+        for node in graph.node_ids() {
+            let input = graph
+                .fanins(node)
+                .iter()
+                .map(|fanin| levels[fanin.index()])
+                .max();
+            let expected = match graph.node(node).kind {
+                NodeKind::Source => 0,
+                NodeKind::Gate(_) => input.unwrap() + 1,
+                _ => input.unwrap(),
+            };
+            assert_eq!(levels[node.index()], expected, "node {node}");
+        }
+        // NOTE: The carry chain of the adder runs through `Cout`.
+        assert_eq!(graph.depth().unwrap(), levels[29]);
+    }
+
+    #[test]
+    fn test_combinational_loop() {
+        let mut graph = get_test_graph();
+        graph.add_edge(edge!(20:12));
+        let Err(Error::CombinationalLoop(cycle)) = graph.topological_order() else {
+            panic!("graph should have a loop");
+        };
+        assert_eq!(cycle, [12, 15, 16, 18, 20].map(Node::new));
+        assert!(graph.levels().is_err());
+        assert_eq!(
+            graph.check_loops().unwrap_err().to_string(),
+            "combinational loop through nodes 12 -> 15 -> 16 -> 18 -> 20"
+        );
+    }
+
+    #[test]
+    fn test_names() {
+        let design: yosys::Design = include_str!("../../../../examples/alu/add4_simplemap.json")
+            .parse()
+            .unwrap();
+        let module = design.modules["add4"].clone();
+        let graph = Graph::try_from(module.clone()).unwrap();
+        let ports: Vec<_> = graph.ports().iter().map(Port::name).collect();
+        assert_eq!(ports, ["A", "B", "Cin", "Cout", "S"]);
+        let a2 = graph.find("A[2]").unwrap();
+        assert!(matches!(graph.node(a2).kind, NodeKind::Source));
+        let (port, bit) = graph.port_bit(a2).unwrap();
+        assert_eq!((port.name(), bit), ("A", 2));
+        let cout = graph.find("Cout").unwrap();
+        assert!(matches!(graph.node(cout).kind, NodeKind::Sink));
+        assert_eq!(graph.port_bit(cout).unwrap().1, 0);
+        for (name, cell) in &module.cells {
+            let node = graph.find(name).unwrap();
+            assert_eq!(graph.cell_name(node), Some(name.as_str()));
+            assert_eq!(graph.node(node).attributes, cell.attributes);
+        }
+        assert_eq!(graph.cell_name(a2), None);
+        // NOTE: Port nets are driven by their sources, or the drivers of their sinks.
+        let a: Vec<_> = graph.ports()[0].nodes().collect();
+        assert_eq!(graph.net("A"), Some(a.as_slice()));
+        let s = graph.net("S").unwrap();
+        for (driver, sink) in s.iter().zip(graph.ports()[4].nodes()) {
+            assert_eq!(graph.fanins(sink), [*driver]);
+        }
+    }
+
+    #[test]
+    fn test_name_collisions() {
+        let mut graph = Graph::default();
+        let y = graph.add_output("y", 1).start;
+        let cell = graph.add_node(src!().with_name("y"));
+        let other = graph.add_node(src!().with_name("y"));
+        assert_eq!(graph.find("y"), Some(y));
+        assert_eq!(graph.find("y_1"), Some(cell));
+        assert_eq!(graph.find("y_2"), Some(other));
+        assert_eq!(graph.node(cell).name, Some(ustr("y_1")));
+    }
+
+    #[test]
+    fn test_sequential() {
+        let design: yosys::Design = include_str!("../../../../examples/sr/sr_simplemap.json")
+            .parse()
+            .unwrap();
+        let module = design.modules["sr"].clone();
+        let graph = Graph::try_from(module.clone()).unwrap();
+        let clk = graph.node_ids().next().unwrap();
+        let flip_flops: Vec<_> = graph
+            .node_ids()
+            .filter(|node| matches!(graph.node(*node).kind, NodeKind::Seq(_)))
+            .collect();
+        assert_eq!(flip_flops.len(), 4);
+        for node in flip_flops {
+            let NodeKind::Seq(op) = graph.node(node).kind else {
+                unreachable!();
+            };
+            assert_eq!(
+                op,
+                SeqOp::FlipFlop(FlipFlop {
+                    clock: Polarity::Positive,
+                    enable: None,
+                    reset: Some(Reset {
+                        polarity: Polarity::Negative,
+                        value: false,
+                        asynchronous: false,
+                        gated: false,
+                    }),
+                })
+            );
+            assert_eq!(graph.fanins(node).len(), 3);
+            assert_eq!(graph.fanins(node)[1], clk);
+        }
+        // NOTE: The feedback through the flip-flops is not a combinational loop.
+        assert!(graph.check_loops().is_ok());
+
+        let mut module = module;
+        for cell in module.cells.values_mut() {
+            if cell.ty == "$_SDFF_PN0_" {
+                cell.ty = "$_DFFSR_PNN_".to_string();
+            }
+        }
+        assert!(matches!(
+            Graph::try_from(module),
+            Err(YosysError::Unsupported(_))
+        ));
+    }
+
+    #[test]
+    fn test_cell_kind() {
+        for ty in [
+            "$_BUF_",
+            "$_NOT_",
+            "$_AND_",
+            "$_NAND_",
+            "$_OR_",
+            "$_NOR_",
+            "$_XOR_",
+            "$_XNOR_",
+            "$_ANDNOT_",
+            "$_ORNOT_",
+            "$_AOI3_",
+            "$_OAI3_",
+            "$_AOI4_",
+            "$_OAI4_",
+            "$_MUX_",
+            "$_NMUX_",
+        ] {
+            let Some((NodeKind::Gate(op), inputs, "Y")) = cell_kind(ty) else {
+                panic!("{ty} should be a gate");
+            };
+            assert_eq!(op.arity(), inputs.len(), "{ty}");
+        }
+    }
+
+    #[test]
+    fn test_parse_seq_op() {
+        assert_eq!(
+            parse_seq_op("$_SDFFE_NP1N_"),
+            Some(SeqOp::FlipFlop(FlipFlop {
+                clock: Polarity::Negative,
+                enable: Some(Polarity::Negative),
+                reset: Some(Reset {
+                    polarity: Polarity::Positive,
+                    value: true,
+                    asynchronous: false,
+                    gated: false,
+                }),
+            }))
+        );
+        assert_eq!(
+            parse_seq_op("$_SDFFCE_PN0P_"),
+            Some(SeqOp::FlipFlop(FlipFlop {
+                clock: Polarity::Positive,
+                enable: Some(Polarity::Positive),
+                reset: Some(Reset {
+                    polarity: Polarity::Negative,
+                    value: false,
+                    asynchronous: false,
+                    gated: true,
+                }),
+            }))
+        );
+        assert_eq!(
+            parse_seq_op("$_DFF_PN0_"),
+            Some(SeqOp::FlipFlop(FlipFlop {
+                clock: Polarity::Positive,
+                enable: None,
+                reset: Some(Reset {
+                    polarity: Polarity::Negative,
+                    value: false,
+                    asynchronous: true,
+                    gated: false,
+                }),
+            }))
+        );
+        assert_eq!(
+            parse_seq_op("$_DLATCH_N_"),
+            Some(SeqOp::Latch(Latch {
+                enable: Polarity::Negative,
+                reset: None,
+            }))
+        );
+        assert_eq!(
+            parse_seq_op("$_DLATCH_PP1_"),
+            Some(SeqOp::Latch(Latch {
+                enable: Polarity::Positive,
+                reset: Some(Reset {
+                    polarity: Polarity::Positive,
+                    value: true,
+                    asynchronous: true,
+                    gated: false,
+                }),
+            }))
+        );
+        assert_eq!(parse_seq_op("$_DLATCH_PP2_"), None);
+        assert_eq!(parse_seq_op("$_FF_"), Some(SeqOp::Ff));
+        assert_eq!(parse_seq_op("$_DFFE_PX_"), None);
+        assert_eq!(parse_seq_op("$_DFFSR_PNN_"), None);
+    }
+
+    #[test]
+    fn test_word_level_sequential() {
+        let design: yosys::Design = include_str!("../../../../examples/sr/sr.json")
+            .parse()
+            .unwrap();
+        let graph = Graph::try_from(design.modules["sr"].clone()).unwrap();
+        let flip_flops: Vec<_> = graph
+            .node_ids()
+            .filter(|node| matches!(graph.node(*node).kind, NodeKind::Seq(_)))
+            .collect();
+        assert_eq!(flip_flops.len(), 4);
+        let name = "$auto$ff.cc:266:slice$14";
+        assert_eq!(graph.find(&format!("{name}[3]")), Some(flip_flops[3]));
+        assert_eq!(graph.cell_name(flip_flops[0]), Some(name));
+        assert!(graph.check_loops().is_ok());
+
+        let simplemap: yosys::Design = include_str!("../../../../examples/sr/sr_simplemap.json")
+            .parse()
+            .unwrap();
+        let simplemap = Graph::try_from(simplemap.modules["sr"].clone()).unwrap();
+        let config = sim::CompareConfig::default();
+        assert_eq!(sim::compare(&graph, &simplemap, &config).unwrap(), None);
+
+        let mut module = design.modules["sr"].clone();
+        let cell = module.cells.get_mut(name).unwrap();
+        cell.ty = internal_cells::ADFF.to_string();
+        let value = cell.parameters.remove(parameter_names::SRST_VALUE).unwrap();
+        cell.parameters
+            .insert(parameter_names::ARST_VALUE.to_string(), value);
+        let polarity = cell
+            .parameters
+            .remove(parameter_names::SRST_POLARITY)
+            .unwrap();
+        cell.parameters
+            .insert(parameter_names::ARST_POLARITY.to_string(), polarity);
+        let srst = cell.connections.remove("SRST").unwrap();
+        cell.connections.insert("ARST".to_string(), srst);
+        let graph = Graph::try_from(module).unwrap();
+        let NodeKind::Seq(SeqOp::FlipFlop(FlipFlop {
+            reset: Some(reset), ..
+        })) = graph.node(flip_flops[0]).kind
+        else {
+            panic!("flip-flop should have a reset");
+        };
+        assert!(reset.asynchronous);
+    }
+
+    #[test]
+    fn test_from_yosys() {
+        let design: yosys::Design = include_str!("../../../../examples/alu/add4_simplemap.json")
+            .parse()
+            .unwrap();
+        let module = design.modules["add4"].clone();
+        let graph = Graph::try_from(module).unwrap();
+        let count = |f: fn(&NodeKind) -> bool| graph.nodes().filter(|node| f(&node.kind)).count();
+        assert_eq!(count(|kind| matches!(kind, NodeKind::Source)), 9);
+        assert_eq!(count(|kind| matches!(kind, NodeKind::Sink)), 5);
+        assert_eq!(count(|kind| matches!(kind, NodeKind::Gate(_))), 20);
+        assert_eq!(graph.edges().count(), 45);
+        for node in graph.node_ids() {
+            if matches!(graph.node(node).kind, NodeKind::Sink) {
+                assert!(graph.sinks(node).is_empty());
+            }
+        }
+    }
 }
@@ -0,0 +1,716 @@
+//! Cut-based LUT mapping.
+//!
+//! A cut of a gate is a set of nodes every path from the sources to the gate
+//! passes through, and it is `k`-feasible if it has at most `k` nodes. The cuts
+//! of every gate are enumerated by merging the cuts of its inputs, keeping the
+//! best [max_cuts](LutMapConfig::max_cuts) as priority cuts, and every gate of
+//! the cover is implemented by a [Lut] of the function of its best cut.
+//!
+//! [LutMapMode::Depth] selects the cuts of least depth, as FlowMap does, which
+//! is depth-optimal when enough cuts are kept. [LutMapMode::Area] then
+//! recovers area without increasing the depth, first by area flow and then by
+//! exact local area.
+//!
+//! Only [NodeKind::Gate] nodes are mapped, every other node bounds the
+//! mapping. Constants are absorbed into the tables of the LUTs they drive.
+//! Gates of more than `k` inputs have no `k`-feasible cut, so they are first
+//! decomposed by Shannon expansion into gates that fit a LUT.
+//!
+//! References:
+//! - J. Cong and Y. Ding, "FlowMap: An Optimal Technology Mapping Algorithm
+//!   for Delay Optimization in Lookup-Table Based FPGA Designs", 1994.
+//! - A. Mishchenko, S. Cho, S. Chatterjee and R. Brayton, "Combinational and
+//!   Sequential Mapping with Priority Cuts", ICCAD 2007.
+
+use std::cmp::Ordering;
+
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+use ustr::Ustr;
+
+use super::graph::{self, Edge, Graph, Node, NodeData, NodeKind};
+use super::ops::{AnyOp, ConstOp, Lut};
+use super::opt::sweep::sweep;
+
+#[derive(Clone, Debug, Error)]
+pub enum Error {
+    #[error(transparent)]
+    Graph(#[from] graph::Error),
+}
+
+pub type Result<T> = std::result::Result<T, Error>;
+
+#[derive(Clone, Copy, Debug, Default, Deserialize, Eq, PartialEq, Serialize)]
+pub enum LutMapMode {
+    /// Minimise the depth, breaking ties by area flow.
+    Depth,
+    /// Minimise the depth, then recover area.
+    #[default]
+    Area,
+}
+
+#[derive(Clone, Copy, Debug, Deserialize, Serialize)]
+pub struct LutMapConfig {
+    /// The number of inputs of a LUT.
+    pub k: usize,
+    pub mode: LutMapMode,
+    /// The number of cuts kept for every gate.
+    pub max_cuts: usize,
+}
+
+impl LutMapConfig {
+    pub fn new(k: usize) -> Self {
+        Self {
+            k,
+            mode: LutMapMode::default(),
+            max_cuts: 8,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub struct LutMapStats {
+    /// The number of LUTs of the mapping.
+    pub luts: usize,
+    /// The largest number of LUTs between nodes that are not gates.
+    pub depth: usize,
+}
+
+/// The tables of the inputs of a function of [Lut::MAX_INPUTS].
+const VARS: [u64; Lut::MAX_INPUTS] = [
+    0xaaaa_aaaa_aaaa_aaaa,
+    0xcccc_cccc_cccc_cccc,
+    0xf0f0_f0f0_f0f0_f0f0,
+    0xff00_ff00_ff00_ff00,
+    0xffff_0000_ffff_0000,
+    0xffff_ffff_0000_0000,
+];
+
+/// The table of a LUT of one input that passes it through.
+const BUFFER: u64 = 0b10;
+
+/// The bits of a table of `n_inputs`.
+fn mask(n_inputs: usize) -> u64 {
+    u64::MAX >> (64 - (1 << n_inputs))
+}
+
+/// The table over `leaves` of the function `table` of `inputs`, a subset of
+/// `leaves`.
+fn expand(table: u64, inputs: &[Node], leaves: &[Node]) -> u64 {
+    let vars: Vec<_> = inputs
+        .iter()
+        .map(|input| VARS[leaves.binary_search(input).expect("input should be a leaf")])
+        .collect();
+    let lut = Lut::new(inputs.len(), table).expect("cut should fit a LUT");
+    lut.eval_packed(&vars) & mask(leaves.len())
+}
+
+/// The sorted union of `a` and `b`, if it has at most `k` nodes.
+fn merge(a: &[Node], b: &[Node], k: usize) -> Option<Vec<Node>> {
+    let mut merged = Vec::with_capacity(k);
+    let (mut i, mut j) = (0, 0);
+    while i < a.len() || j < b.len() {
+        let next = match (a.get(i), b.get(j)) {
+            (Some(x), Some(y)) if x == y => {
+                i += 1;
+                j += 1;
+                *x
+            }
+            (Some(x), Some(y)) if x < y => {
+                i += 1;
+                *x
+            }
+            (Some(x), None) => {
+                i += 1;
+                *x
+            }
+            (_, Some(y)) => {
+                j += 1;
+                *y
+            }
+            (None, None) => unreachable!(),
+        };
+        if merged.len() == k {
+            return None;
+        }
+        merged.push(next);
+    }
+    Some(merged)
+}
+
+/// Add gates computing the function `table` of `inputs` to `graph`: a [Lut]
+/// if it has at most `k` inputs, and otherwise a multiplexer of 2-input gates
+/// selecting between the cofactors of its last input. Returns the output of
+/// the gates, named `name`.
+fn shannon(graph: &mut Graph, inputs: &[Node], table: u64, k: usize, name: Option<Ustr>) -> Node {
+    let n_inputs = inputs.len();
+    let gate = |graph: &mut Graph, op: AnyOp, fanins: &[Node], name: Option<Ustr>| {
+        let mut data = NodeData::new_op(op);
+        data.name = name;
+        let gate = graph.add_node(data);
+        graph.add_edges(fanins.iter().map(|fanin| Edge {
+            source: *fanin,
+            sink: gate,
+        }));
+        gate
+    };
+    if n_inputs <= k {
+        let lut = Lut::new(n_inputs, table).expect("gate should fit a LUT");
+        return gate(graph, AnyOp::from(lut), inputs, name);
+    }
+    let (rest, select) = (&inputs[..n_inputs - 1], inputs[n_inputs - 1]);
+    let cofactor = |shift: usize| table >> shift & mask(n_inputs - 1);
+    let low = shannon(graph, rest, cofactor(0), k, None);
+    let high = shannon(graph, rest, cofactor(1 << (n_inputs - 1)), k, None);
+    let low = gate(graph, AnyOp::and_not(), &[low, select], None);
+    let high = gate(graph, AnyOp::and(), &[high, select], None);
+    gate(graph, AnyOp::or(), &[low, high], name)
+}
+
+/// Replace every gate of `graph` of more than `k` inputs by [shannon]
+/// expansion.
+fn decompose(graph: &mut Graph, k: usize) {
+    let wide: Vec<_> = graph
+        .node_ids()
+        .filter(|node| {
+            matches!(graph.node(*node).kind, NodeKind::Gate(_)) && graph.fanins(*node).len() > k
+        })
+        .collect();
+    if wide.is_empty() {
+        return;
+    }
+    for node in wide {
+        let NodeKind::Gate(op) = graph.node(node).kind else {
+            unreachable!("only gates are decomposed");
+        };
+        let inputs = graph.fanins(node).to_vec();
+        let table = op.eval_packed(&VARS) & mask(inputs.len());
+        let name = graph.node(node).name;
+        let output = shannon(graph, &inputs, table, k, name);
+        graph.replace_uses(node, output);
+    }
+    sweep(graph);
+}
+
+#[derive(Clone, Debug)]
+struct Cut {
+    /// The sorted leaves of the cut.
+    leaves: Vec<Node>,
+    /// The function of the gate, input `i` being leaf `i`.
+    table: u64,
+    depth: usize,
+    /// The area flow of the cut.
+    area: f64,
+}
+
+impl Cut {
+    /// Whether the cut is implemented without a LUT, by a constant or by its
+    /// leaf.
+    fn is_free(&self) -> bool {
+        self.leaves.is_empty() || (self.leaves.len() == 1 && self.table == BUFFER)
+    }
+}
+
+/// The union of the leaves of a cut of every input so far, with the function
+/// of every input over the leaves of its own cut.
+type Partial = (Vec<Node>, Vec<(Vec<Node>, u64)>);
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+enum Phase {
+    Depth,
+    AreaFlow,
+    ExactArea,
+}
+
+struct Mapper<'a> {
+    graph: &'a Graph,
+    config: LutMapConfig,
+    order: Vec<Node>,
+    /// The cuts of every gate, best first.
+    cuts: Vec<Vec<Cut>>,
+    /// The depth of the best cut of every gate.
+    arrival: Vec<usize>,
+    /// The area flow of every gate, shared between its references.
+    flow: Vec<f64>,
+    /// The references of every node by the cover, or its fanout before there
+    /// is one.
+    refs: Vec<usize>,
+    /// The latest depth of every gate that does not increase the depth of the
+    /// cover.
+    required: Vec<usize>,
+}
+
+impl<'a> Mapper<'a> {
+    fn new(graph: &'a Graph, config: LutMapConfig) -> Result<Self> {
+        let n_nodes = graph.n_nodes();
+        Ok(Self {
+            graph,
+            config,
+            order: graph.topological_order()?,
+            cuts: vec![Vec::new(); n_nodes],
+            arrival: vec![0; n_nodes],
+            flow: vec![0.0; n_nodes],
+            refs: graph
+                .node_ids()
+                .map(|node| graph.sinks(node).len())
+                .collect(),
+            required: vec![usize::MAX; n_nodes],
+        })
+    }
+
+    fn is_gate(&self, node: Node) -> bool {
+        matches!(self.graph.node(node).kind, NodeKind::Gate(_))
+    }
+
+    fn best(&self, node: Node) -> &Cut {
+        &self.cuts[node.index()][0]
+    }
+
+    /// The gates driving a node that is not a gate, once for every edge.
+    fn roots(&self) -> impl Iterator<Item = Node> + '_ {
+        self.graph
+            .node_ids()
+            .filter(|node| !self.is_gate(*node))
+            .flat_map(|node| self.graph.fanins(node))
+            .copied()
+            .filter(|fanin| self.is_gate(*fanin))
+    }
+
+    fn evaluate(&self, leaves: Vec<Node>, table: u64) -> Cut {
+        let mut cut = Cut {
+            leaves,
+            table,
+            depth: 0,
+            area: 0.0,
+        };
+        let lut = usize::from(!cut.is_free());
+        let leaves = cut.leaves.iter().map(|leaf| leaf.index());
+        cut.depth = lut
+            + leaves
+                .clone()
+                .map(|leaf| self.arrival[leaf])
+                .max()
+                .unwrap_or_default();
+        cut.area = lut as f64 + leaves.map(|leaf| self.flow[leaf]).sum::<f64>();
+        cut
+    }
+
+    /// Merge the cuts of the inputs of `node`.
+    fn enumerate(&self, node: Node) -> Vec<Cut> {
+        let NodeKind::Gate(op) = self.graph.node(node).kind else {
+            unreachable!("only gates have cuts");
+        };
+        let k = self.config.k;
+        let mut partials = vec![(Vec::new(), Vec::new())];
+        for fanin in self.graph.fanins(node) {
+            let trivial = (vec![*fanin], BUFFER);
+            let options: Vec<_> = self.cuts[fanin.index()]
+                .iter()
+                .map(|cut| (cut.leaves.clone(), cut.table))
+                .chain([trivial])
+                .collect();
+            let mut next: Vec<Partial> = Vec::new();
+            for (leaves, inputs) in &partials {
+                for (option, table) in &options {
+                    let Some(merged) = merge(leaves, option, k) else {
+                        continue;
+                    };
+                    if next.iter().any(|(other, _)| *other == merged) {
+                        continue;
+                    }
+                    let mut inputs = inputs.clone();
+                    inputs.push((option.clone(), *table));
+                    next.push((merged, inputs));
+                }
+            }
+            partials = next;
+        }
+        let mut cuts: Vec<Cut> = Vec::with_capacity(partials.len());
+        partials.sort_by_key(|(leaves, _)| leaves.len());
+        for (leaves, inputs) in partials {
+            // NOTE: Cuts are merged smallest first, so a cut is dominated by
+            // any kept cut whose leaves it includes.
+            if cuts.iter().any(|cut| {
+                cut.leaves
+                    .iter()
+                    .all(|leaf| leaves.binary_search(leaf).is_ok())
+            }) {
+                continue;
+            }
+            let inputs: Vec<_> = inputs
+                .iter()
+                .map(|(inputs, table)| expand(*table, inputs, &leaves))
+                .collect();
+            let table = op.eval_packed(&inputs) & mask(leaves.len());
+            cuts.push(self.evaluate(leaves, table));
+        }
+        // NOTE: Gates have at most `k` inputs after [decompose], so the cut of
+        // their inputs is always feasible.
+        debug_assert!(!cuts.is_empty());
+        cuts
+    }
+
+    /// Reference the leaves of the best cut of `node`, and the best cuts of
+    /// the leaves it is the first to reference, returning the number of LUTs
+    /// referenced.
+    fn reference(&mut self, node: Node) -> usize {
+        let best = self.best(node);
+        let (leaves, mut area) = (best.leaves.clone(), usize::from(!best.is_free()));
+        for leaf in leaves {
+            if !self.is_gate(leaf) {
+                continue;
+            }
+            self.refs[leaf.index()] += 1;
+            if self.refs[leaf.index()] == 1 {
+                area += self.reference(leaf);
+            }
+        }
+        area
+    }
+
+    /// Undo [Mapper::reference].
+    fn dereference(&mut self, node: Node) -> usize {
+        let best = self.best(node);
+        let (leaves, mut area) = (best.leaves.clone(), usize::from(!best.is_free()));
+        for leaf in leaves {
+            if !self.is_gate(leaf) {
+                continue;
+            }
+            self.refs[leaf.index()] -= 1;
+            if self.refs[leaf.index()] == 0 {
+                area += self.dereference(leaf);
+            }
+        }
+        area
+    }
+
+    /// The number of LUTs `cut` would add to the cover as the best cut of
+    /// `node`, which should not be referenced.
+    fn exact_area(&mut self, node: Node, cut: Cut) -> usize {
+        let best = std::mem::replace(&mut self.cuts[node.index()][0], cut);
+        let area = self.reference(node);
+        self.dereference(node);
+        self.cuts[node.index()][0] = best;
+        area
+    }
+
+    fn compare(&self, phase: Phase, a: &Cut, b: &Cut) -> Ordering {
+        let area = a.area.total_cmp(&b.area);
+        let depth = a.depth.cmp(&b.depth);
+        let size = a.leaves.len().cmp(&b.leaves.len());
+        match phase {
+            Phase::Depth => depth.then(area).then(size),
+            Phase::AreaFlow | Phase::ExactArea => area.then(depth).then(size),
+        }
+    }
+
+    fn run(&mut self, phase: Phase) {
+        for node in self.order.clone() {
+            if !self.is_gate(node) {
+                continue;
+            }
+            let mut cuts = self.enumerate(node);
+            let previous = self.cuts[node.index()]
+                .first()
+                .map(|best| self.evaluate(best.leaves.clone(), best.table));
+            if phase != Phase::Depth {
+                let required = self.required[node.index()];
+                cuts.retain(|cut| cut.depth <= required);
+            }
+            cuts.sort_by(|a, b| self.compare(phase, a, b));
+            cuts.truncate(self.config.max_cuts);
+            // NOTE: The previous best cut meets the required depth of the
+            // gate, so area recovery never increases the depth.
+            if let Some(previous) = previous.filter(|_| phase != Phase::Depth) {
+                match cuts.iter().position(|cut| cut.leaves == previous.leaves) {
+                    Some(index) => cuts[..=index].rotate_right(1),
+                    None => cuts.insert(0, previous),
+                }
+            }
+            if phase == Phase::ExactArea && self.refs[node.index()] > 0 {
+                self.dereference(node);
+                let areas: Vec<_> = cuts
+                    .iter()
+                    .map(|cut| self.exact_area(node, cut.clone()))
+                    .collect();
+                let best = (0..cuts.len()).min_by_key(|index| areas[*index]).unwrap();
+                cuts.swap(0, best);
+                self.cuts[node.index()] = cuts;
+                self.reference(node);
+            } else {
+                self.cuts[node.index()] = cuts;
+            }
+            let best = self.best(node);
+            let refs = self.refs[node.index()].max(1) as f64;
+            (self.arrival[node.index()], self.flow[node.index()]) = (best.depth, best.area / refs);
+        }
+    }
+
+    /// The depth of the cover.
+    fn depth(&self) -> usize {
+        self.roots()
+            .map(|root| self.arrival[root.index()])
+            .max()
+            .unwrap_or_default()
+    }
+
+    /// Count the references of the cover and the required depth of its
+    /// gates.
+    fn update_cover(&mut self) {
+        let depth = self.depth();
+        self.refs.fill(0);
+        self.required.fill(usize::MAX);
+        for root in self.roots().collect::<Vec<_>>() {
+            self.refs[root.index()] += 1;
+            self.required[root.index()] = depth;
+        }
+        for node in self.order.iter().rev() {
+            if self.refs[node.index()] == 0 || !self.is_gate(*node) {
+                continue;
+            }
+            let required = self.required[node.index()].saturating_sub(1);
+            for leaf in &self.cuts[node.index()][0].leaves {
+                self.refs[leaf.index()] += 1;
+                self.required[leaf.index()] = self.required[leaf.index()].min(required);
+            }
+        }
+    }
+}
+
+/// Map the gates of `graph` to LUTs of `k` inputs, removing every gate that
+/// does not drive another kind of node.
+///
+/// # Panics
+///
+/// Panics if `k` is less than 2 or more than [Lut::MAX_INPUTS].
+pub fn map_luts(graph: &mut Graph, config: &LutMapConfig) -> Result<LutMapStats> {
+    assert!(
+        (2..=Lut::MAX_INPUTS).contains(&config.k),
+        "LUTs should have 2 to {} inputs",
+        Lut::MAX_INPUTS
+    );
+    decompose(graph, config.k);
+    let mut mapper = Mapper::new(graph, *config)?;
+    mapper.run(Phase::Depth);
+    mapper.update_cover();
+    if config.mode == LutMapMode::Area {
+        mapper.run(Phase::AreaFlow);
+        mapper.update_cover();
+        mapper.run(Phase::ExactArea);
+        mapper.update_cover();
+    }
+    let depth = mapper.depth();
+    let cover: Vec<_> = mapper
+        .order
+        .iter()
+        .copied()
+        .filter(|node| mapper.is_gate(*node) && mapper.refs[node.index()] > 0)
+        .map(|node| (node, mapper.best(node).clone()))
+        .collect();
+
+    let mut stats = LutMapStats { luts: 0, depth };
+    let mut replacements = Vec::with_capacity(cover.len());
+    for (node, cut) in cover {
+        let replacement = match cut.leaves[..] {
+            [] => graph.add_const(if cut.table & 1 == 1 {
+                ConstOp::Unit
+            } else {
+                ConstOp::Zero
+            }),
+            [leaf] if cut.table == BUFFER => leaf,
+            _ => {
+                let lut = Lut::new(cut.leaves.len(), cut.table).expect("cut should fit a LUT");
+                let mut data = NodeData::new_op(AnyOp::from(lut));
+                data.name = graph.node(node).name;
+                let lut = graph.add_node(data);
+                graph.add_edges(cut.leaves.iter().map(|leaf| Edge {
+                    source: *leaf,
+                    sink: lut,
+                }));
+                stats.luts += 1;
+                lut
+            }
+        };
+        replacements.push((node, replacement));
+    }
+    // NOTE: The LUTs are driven by the gates they replace until the sinks of
+    // every gate are moved, from the last gate to the first.
+    for (node, replacement) in replacements.into_iter().rev() {
+        graph.replace_uses(node, replacement);
+    }
+    sweep(graph);
+    Ok(stats)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::interchange::yosys;
+    use crate::ir::equiv::{check, Equivalence};
+    use crate::ir::sim;
+
+    fn load(json: &str, top: &str) -> Graph {
+        let design: yosys::Design = json.parse().unwrap();
+        Graph::try_from(design.modules[top].clone()).unwrap()
+    }
+
+    fn examples() -> Vec<(Graph, Graph)> {
+        [
+            (
+                include_str!("../../../../examples/alu/add4_simplemap.json"),
+                include_str!("../../../../examples/alu/add4_flowmap.json"),
+                "add4",
+            ),
+            (
+                include_str!("../../../../examples/alu/alu4_simplemap.json"),
+                include_str!("../../../../examples/alu/alu4_flowmap.json"),
+                "alu4",
+            ),
+            (
+                include_str!("../../../../examples/crc/crc16_simplemap.json"),
+                include_str!("../../../../examples/crc/crc16_flowmap.json"),
+                "crc16_1021",
+            ),
+        ]
+        .into_iter()
+        .map(|(simplemap, flowmap, top)| (load(simplemap, top), load(flowmap, top)))
+        .collect()
+    }
+
+    /// Whether every gate of `graph` is a constant or a [Lut] of at most `k`
+    /// inputs.
+    fn fits(graph: &Graph, k: usize) -> bool {
+        graph.nodes().all(|node| match node.kind {
+            NodeKind::Gate(AnyOp::Lut(lut)) => lut.n_inputs() <= k,
+            NodeKind::Gate(AnyOp::Const(_)) => true,
+            NodeKind::Gate(_) => false,
+            _ => true,
+        })
+    }
+
+    fn luts(graph: &Graph) -> usize {
+        graph
+            .nodes()
+            .filter(|node| matches!(node.kind, NodeKind::Gate(AnyOp::Lut(_))))
+            .count()
+    }
+
+    #[test]
+    fn test_helpers() {
+        let mut graph = Graph::default();
+        let [a, b, c] = [0; 3].map(|_| graph.add_unit());
+        assert_eq!(merge(&[a, c], &[b, c], 3), Some(vec![a, b, c]));
+        assert_eq!(merge(&[a, c], &[b], 2), None);
+        // NOTE: `b` over `[a, b]` is the second variable.
+        assert_eq!(expand(0b10, &[b], &[a, b]), 0b1100);
+        assert_eq!(expand(0b0110, &[a, c], &[a, b, c]), 0b0101_1010);
+    }
+
+    #[test]
+    fn test_map_luts() {
+        for (graph, flowmap) in examples() {
+            let mut depths = Vec::new();
+            for mode in [LutMapMode::Depth, LutMapMode::Area] {
+                for k in 2..=Lut::MAX_INPUTS {
+                    let mut mapped = graph.clone();
+                    let config = LutMapConfig {
+                        mode,
+                        ..LutMapConfig::new(k)
+                    };
+                    let stats = map_luts(&mut mapped, &config).unwrap();
+                    assert_eq!(stats.luts, luts(&mapped));
+                    assert_eq!(stats.depth, mapped.depth().unwrap());
+                    assert!(fits(&mapped, k));
+                    assert_eq!(check(&graph, &mapped).unwrap(), Equivalence::Equivalent);
+                    if k == 4 {
+                        // NOTE: The examples were mapped to 4-LUTs by flowmap.
+                        assert!(stats.depth <= flowmap.depth().unwrap());
+                    }
+                    depths.push((mode, k, stats));
+                }
+            }
+            // NOTE: Area recovery keeps the depth and does not add LUTs.
+            let (depth, area) = depths.split_at(depths.len() / 2);
+            for ((_, _, depth), (_, _, area)) in depth.iter().zip(area) {
+                assert_eq!(area.depth, depth.depth);
+                assert!(area.luts <= depth.luts);
+            }
+        }
+    }
+
+    #[test]
+    fn test_constants() {
+        let mut graph = Graph::default();
+        let a = graph.add_input("a", 1).next().unwrap();
+        let y: Vec<_> = graph.add_output("y", 2).collect();
+        let unit = graph.add_unit();
+        let and = graph.add_node(NodeData::new_op(AnyOp::and()));
+        let nand = graph.add_node(NodeData::new_op(AnyOp::nand()));
+        graph.add_edges([
+            Edge {
+                source: a,
+                sink: and,
+            },
+            Edge {
+                source: unit,
+                sink: and,
+            },
+            Edge {
+                source: unit,
+                sink: nand,
+            },
+            Edge {
+                source: unit,
+                sink: nand,
+            },
+            Edge {
+                source: and,
+                sink: y[0],
+            },
+            Edge {
+                source: nand,
+                sink: y[1],
+            },
+        ]);
+        let original = graph.clone();
+        let stats = map_luts(&mut graph, &LutMapConfig::new(4)).unwrap();
+        assert_eq!(stats, LutMapStats { luts: 0, depth: 0 });
+        assert_eq!(graph.fanins(y[0]), [a]);
+        let zero = graph.fanins(y[1])[0];
+        assert!(matches!(
+            graph.node(zero).kind,
+            NodeKind::Gate(AnyOp::Const(ConstOp::Zero))
+        ));
+        assert_eq!(check(&original, &graph).unwrap(), Equivalence::Equivalent);
+    }
+
+    #[test]
+    fn test_wide_gates() {
+        let mut graph = Graph::default();
+        let inputs: Vec<_> = graph.add_input("a", 3).collect();
+        let y = graph.add_output("y", 1).next().unwrap();
+        let mux = graph.add_node(NodeData::new_op(AnyOp::mux()).with_name("mux"));
+        graph.add_edges(inputs.iter().map(|input| Edge {
+            source: *input,
+            sink: mux,
+        }));
+        graph.add_edge(Edge {
+            source: mux,
+            sink: y,
+        });
+        let add4 = load(
+            include_str!("../../../../examples/alu/add4_abc.json"),
+            "add4",
+        );
+        assert!(!fits(&add4, 3));
+        for (graph, k) in [(graph, 2), (add4.clone(), 2), (add4, 3)] {
+            let mut mapped = graph.clone();
+            let stats = map_luts(&mut mapped, &LutMapConfig::new(k)).unwrap();
+            assert!(fits(&mapped, k));
+            assert_eq!(stats.luts, luts(&mapped));
+            assert_eq!(check(&graph, &mapped).unwrap(), Equivalence::Equivalent);
+            let config = sim::CompareConfig::default();
+            assert_eq!(sim::compare(&graph, &mapped, &config).unwrap(), None);
+        }
+    }
+}
@@ -1,2 +1,12 @@
+mod blast;
+pub mod equiv;
+pub mod export;
 pub mod graph;
+pub mod lutmap;
 pub mod ops;
+pub mod opt;
+pub mod sfq;
+pub mod sim;
+#[cfg(test)]
+pub(crate) mod testing;
+pub mod visualize;
@@ -1,30 +1,127 @@
-#[derive(Clone, Copy, Debug)]
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
 pub enum UnaryOp {
+    Buf,
     Not,
 }
 
-#[derive(Clone, Copy, Debug)]
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
 pub enum BinaryOp {
     And,
     Or,
     Xor,
+    Nand,
+    Nor,
+    Xnor,
+    /// `A & ~B`
+    AndNot,
+    /// `A | ~B`
+    OrNot,
 }
 
-#[derive(Clone, Copy, Debug)]
+/// An inverted two-level gate.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum ComplexOp {
+    /// `~((A & B) | C)`
+    Aoi3,
+    /// `~((A | B) & C)`
+    Oai3,
+    /// `~((A & B) | (C & D))`
+    Aoi4,
+    /// `~((A | B) & (C | D))`
+    Oai4,
+}
+
+/// A look-up table of up to [Lut::MAX_INPUTS] inputs.
+///
+/// Bit `i` of the table is the output when every input `j` has the value of
+/// bit `j` of `i`.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub struct Lut {
+    n_inputs: usize,
+    table: u64,
+}
+
+impl Lut {
+    pub const MAX_INPUTS: usize = 6;
+
+    /// A look-up table of `n_inputs`, ignoring the bits of `table` past the
+    /// `2^n_inputs` first, or [None] if there are too many inputs.
+    pub fn new(n_inputs: usize, table: u64) -> Option<Self> {
+        if n_inputs > Self::MAX_INPUTS {
+            return None;
+        }
+        let mask = u64::MAX >> (64 - (1 << n_inputs));
+        Some(Self {
+            n_inputs,
+            table: table & mask,
+        })
+    }
+
+    pub fn n_inputs(&self) -> usize {
+        self.n_inputs
+    }
+
+    pub fn table(&self) -> u64 {
+        self.table
+    }
+
+    pub fn eval(&self, inputs: &[bool]) -> bool {
+        let index = inputs[..self.n_inputs]
+            .iter()
+            .rev()
+            .fold(0, |index, input| index << 1 | usize::from(*input));
+        self.table >> index & 1 == 1
+    }
+
+    /// Evaluate the table on 64 patterns at once, bit `i` of every input
+    /// holding its value in pattern `i`.
+    pub fn eval_packed(&self, inputs: &[u64]) -> u64 {
+        let inputs = &inputs[..self.n_inputs];
+        (0..1usize << self.n_inputs)
+            .filter(|minterm| self.table >> minterm & 1 == 1)
+            .fold(0, |output, minterm| {
+                let term = inputs
+                    .iter()
+                    .enumerate()
+                    .fold(u64::MAX, |term, (j, input)| {
+                        term & if minterm >> j & 1 == 1 {
+                            *input
+                        } else {
+                            !input
+                        }
+                    });
+                output | term
+            })
+    }
+}
+
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
 pub enum ConstOp {
     Unit,
     Zero,
 }
 
-#[derive(Clone, Copy, Debug)]
+/// A combinational operation.
+///
+/// The inputs of a [AnyOp::Mux] are `A`, `B` and the select `S`.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
 pub enum AnyOp {
     Unary(UnaryOp),
     Binary(BinaryOp),
+    Complex(ComplexOp),
+    Lut(Lut),
     Const(ConstOp),
+    /// `S ? B : A`
     Mux,
+    /// `~(S ? B : A)`
+    NMux,
 }
 
 impl AnyOp {
+    pub fn buf() -> Self {
+        Self::from(UnaryOp::Buf)
+    }
+
     pub fn not() -> Self {
         Self::from(UnaryOp::Not)
     }
@@ -41,6 +138,26 @@ impl AnyOp {
         Self::from(BinaryOp::Xor)
     }
 
+    pub fn nand() -> Self {
+        Self::from(BinaryOp::Nand)
+    }
+
+    pub fn nor() -> Self {
+        Self::from(BinaryOp::Nor)
+    }
+
+    pub fn xnor() -> Self {
+        Self::from(BinaryOp::Xnor)
+    }
+
+    pub fn and_not() -> Self {
+        Self::from(BinaryOp::AndNot)
+    }
+
+    pub fn or_not() -> Self {
+        Self::from(BinaryOp::OrNot)
+    }
+
     pub fn unit() -> Self {
         Self::from(ConstOp::Unit)
     }
@@ -52,6 +169,104 @@ impl AnyOp {
     pub fn mux() -> Self {
         Self::Mux
     }
+
+    pub fn nmux() -> Self {
+        Self::NMux
+    }
+
+    /// The number of inputs.
+    pub fn arity(&self) -> usize {
+        match self {
+            Self::Unary(_) => 1,
+            Self::Binary(_) => 2,
+            Self::Complex(ComplexOp::Aoi3 | ComplexOp::Oai3) => 3,
+            Self::Complex(ComplexOp::Aoi4 | ComplexOp::Oai4) => 4,
+            Self::Lut(lut) => lut.n_inputs(),
+            Self::Const(_) => 0,
+            Self::Mux | Self::NMux => 3,
+        }
+    }
+
+    /// Evaluate the operation on `inputs`, in input order.
+    ///
+    /// # Panics
+    ///
+    /// Panics if there are fewer `inputs` than the [arity](Self::arity).
+    pub fn eval(&self, inputs: &[bool]) -> bool {
+        let input = |i: usize| inputs[i];
+        match self {
+            Self::Unary(UnaryOp::Buf) => input(0),
+            Self::Unary(UnaryOp::Not) => !input(0),
+            Self::Binary(op) => {
+                let (a, b) = (input(0), input(1));
+                match op {
+                    BinaryOp::And => a & b,
+                    BinaryOp::Or => a | b,
+                    BinaryOp::Xor => a ^ b,
+                    BinaryOp::Nand => !(a & b),
+                    BinaryOp::Nor => !(a | b),
+                    BinaryOp::Xnor => !(a ^ b),
+                    BinaryOp::AndNot => a & !b,
+                    BinaryOp::OrNot => a | !b,
+                }
+            }
+            Self::Complex(op) => match op {
+                ComplexOp::Aoi3 => !((input(0) & input(1)) | input(2)),
+                ComplexOp::Oai3 => !((input(0) | input(1)) & input(2)),
+                ComplexOp::Aoi4 => !((input(0) & input(1)) | (input(2) & input(3))),
+                ComplexOp::Oai4 => !((input(0) | input(1)) & (input(2) | input(3))),
+            },
+            Self::Lut(lut) => lut.eval(inputs),
+            Self::Const(ConstOp::Unit) => true,
+            Self::Const(ConstOp::Zero) => false,
+            Self::Mux => {
+                if input(2) {
+                    input(1)
+                } else {
+                    input(0)
+                }
+            }
+            Self::NMux => !Self::Mux.eval(inputs),
+        }
+    }
+
+    /// Evaluate the operation on 64 patterns at once, bit `i` of every input
+    /// holding its value in pattern `i`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if there are fewer `inputs` than the [arity](Self::arity).
+    pub fn eval_packed(&self, inputs: &[u64]) -> u64 {
+        let input = |i: usize| inputs[i];
+        match self {
+            Self::Unary(UnaryOp::Buf) => input(0),
+            Self::Unary(UnaryOp::Not) => !input(0),
+            Self::Binary(op) => {
+                let (a, b) = (input(0), input(1));
+                match op {
+                    BinaryOp::And => a & b,
+                    BinaryOp::Or => a | b,
+                    BinaryOp::Xor => a ^ b,
+                    BinaryOp::Nand => !(a & b),
+                    BinaryOp::Nor => !(a | b),
+                    BinaryOp::Xnor => !(a ^ b),
+                    BinaryOp::AndNot => a & !b,
+                    BinaryOp::OrNot => a | !b,
+                }
+            }
+            Self::Complex(op) => match op {
+                ComplexOp::Aoi3 => !((input(0) & input(1)) | input(2)),
+                ComplexOp::Oai3 => !((input(0) | input(1)) & input(2)),
+                ComplexOp::Aoi4 => !((input(0) & input(1)) | (input(2) & input(3))),
+                ComplexOp::Oai4 => !((input(0) | input(1)) & (input(2) | input(3))),
+            },
+            Self::Lut(lut) => lut.eval_packed(inputs),
+            Self::Const(ConstOp::Unit) => u64::MAX,
+            Self::Const(ConstOp::Zero) => 0,
+            Self::Mux => (input(2) & input(1)) | (!input(2) & input(0)),
+            Self::NMux => !Self::Mux.eval_packed(inputs),
+        }
+    }
 }
 
 impl From<UnaryOp> for AnyOp {
@@ -66,8 +281,214 @@ impl From<BinaryOp> for AnyOp {
     }
 }
 
+impl From<ComplexOp> for AnyOp {
+    fn from(op: ComplexOp) -> Self {
+        Self::Complex(op)
+    }
+}
+
+impl From<Lut> for AnyOp {
+    fn from(lut: Lut) -> Self {
+        Self::Lut(lut)
+    }
+}
+
 impl From<ConstOp> for AnyOp {
     fn from(op: ConstOp) -> Self {
         Self::Const(op)
     }
 }
+
+/// A primitive of superconducting SFQ logic.
+///
+/// SFQ logic represents values as the presence of a pulse in a clock period,
+/// rather than as a level. The [NodeKind::Dff](super::graph::NodeKind::Dff) is
+/// the simplest clocked SFQ primitive.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum SfqOp {
+    /// Pulses if at least two of its inputs `A`, `B` and `C` pulsed.
+    Majority,
+    /// A confluence buffer, passing the pulses of both its inputs `A` and `B`.
+    Merger,
+    /// A non-destructive readout cell. It is set by `S` and reset by `R`, and
+    /// pulses on every clock while set.
+    Ndro,
+    /// A toggle flip-flop, flipping its state on every pulse of `T` and
+    /// pulsing on the clock following an odd number of pulses.
+    T1,
+}
+
+impl SfqOp {
+    /// The names of the inputs, in order. The clock of clocked primitives is
+    /// implicit.
+    pub fn inputs(&self) -> &'static [&'static str] {
+        match self {
+            Self::Majority => &["A", "B", "C"],
+            Self::Merger => &["A", "B"],
+            Self::Ndro => &["S", "R"],
+            Self::T1 => &["T"],
+        }
+    }
+
+    /// Whether the primitive is driven by the clock.
+    pub fn is_clocked(&self) -> bool {
+        !matches!(self, Self::Merger)
+    }
+}
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Polarity {
+    Positive,
+    Negative,
+}
+
+/// The reset of a [FlipFlop] or a [Latch].
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct Reset {
+    pub polarity: Polarity,
+    /// The value the element is reset to.
+    pub value: bool,
+    /// Whether the reset acts without waiting for a clock edge.
+    pub asynchronous: bool,
+    /// Whether a synchronous reset only acts while the flip-flop is enabled,
+    /// instead of taking priority over the enable.
+    pub gated: bool,
+}
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct FlipFlop {
+    pub clock: Polarity,
+    pub enable: Option<Polarity>,
+    pub reset: Option<Reset>,
+}
+
+/// A latch transparent while its enable has the given polarity. Its reset is
+/// asynchronous and takes priority over the enable.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct Latch {
+    pub enable: Polarity,
+    pub reset: Option<Reset>,
+}
+
+/// A sequential element.
+///
+/// The inputs of a [SeqOp::FlipFlop] are `D`, the clock `C`, then the enable
+/// `E` and the reset `R` if any. The inputs of a [SeqOp::Latch] are `D`, the
+/// enable `E` and the reset `R` if any, and the only input of a [SeqOp::Ff]
+/// is `D`.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum SeqOp {
+    FlipFlop(FlipFlop),
+    /// A flip-flop on the implicit global clock.
+    Ff,
+    Latch(Latch),
+}
+
+impl SeqOp {
+    /// The names of the inputs, in order.
+    pub fn inputs(&self) -> &'static [&'static str] {
+        match self {
+            Self::FlipFlop(FlipFlop {
+                enable: None,
+                reset: None,
+                ..
+            }) => &["D", "C"],
+            Self::FlipFlop(FlipFlop {
+                enable: Some(_),
+                reset: None,
+                ..
+            }) => &["D", "C", "E"],
+            Self::FlipFlop(FlipFlop {
+                enable: None,
+                reset: Some(_),
+                ..
+            }) => &["D", "C", "R"],
+            Self::FlipFlop(FlipFlop {
+                enable: Some(_),
+                reset: Some(_),
+                ..
+            }) => &["D", "C", "E", "R"],
+            Self::Latch(Latch { reset: None, .. }) => &["D", "E"],
+            Self::Latch(Latch { reset: Some(_), .. }) => &["D", "E", "R"],
+            Self::Ff => &["D"],
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_eval() {
+        for bits in 0..16u8 {
+            let inputs: Vec<_> = (0..4).map(|i| bits >> i & 1 == 1).collect();
+            let [a, b, c, d] = inputs[..] else {
+                unreachable!();
+            };
+            let and = AnyOp::and().eval(&inputs);
+            let or = AnyOp::or().eval(&inputs);
+            let xor = AnyOp::xor().eval(&inputs);
+            assert_eq!(AnyOp::nand().eval(&inputs), !and);
+            assert_eq!(AnyOp::nor().eval(&inputs), !or);
+            assert_eq!(AnyOp::xnor().eval(&inputs), !xor);
+            assert_eq!(AnyOp::and_not().eval(&inputs), a && !b);
+            assert_eq!(AnyOp::or_not().eval(&inputs), a || !b);
+            assert_eq!(AnyOp::from(ComplexOp::Aoi3).eval(&inputs), !(and || c));
+            assert_eq!(AnyOp::from(ComplexOp::Oai3).eval(&inputs), !(or && c));
+            assert_eq!(
+                AnyOp::from(ComplexOp::Aoi4).eval(&inputs),
+                !(and || (c && d))
+            );
+            assert_eq!(
+                AnyOp::from(ComplexOp::Oai4).eval(&inputs),
+                !(or && (c || d))
+            );
+            let lut = Lut::new(3, 0b1110_1000).unwrap();
+            assert_eq!(
+                AnyOp::from(lut).eval(&inputs),
+                u8::from(a) + u8::from(b) + u8::from(c) >= 2
+            );
+            let mux = if c { b } else { a };
+            assert_eq!(AnyOp::mux().eval(&inputs), mux);
+            assert_eq!(AnyOp::nmux().eval(&inputs), !mux);
+        }
+    }
+
+    #[test]
+    fn test_eval_packed() {
+        // NOTE: Lane `p` of input `i` holds bit `i` of pattern `p`.
+        let inputs: Vec<u64> = (0..6)
+            .map(|i| (0..64).fold(0, |word, lane| word | (lane >> i & 1) << lane))
+            .collect();
+        let ops = [
+            AnyOp::buf(),
+            AnyOp::not(),
+            AnyOp::and(),
+            AnyOp::or(),
+            AnyOp::xor(),
+            AnyOp::nand(),
+            AnyOp::nor(),
+            AnyOp::xnor(),
+            AnyOp::and_not(),
+            AnyOp::or_not(),
+            AnyOp::from(ComplexOp::Aoi3),
+            AnyOp::from(ComplexOp::Oai3),
+            AnyOp::from(ComplexOp::Aoi4),
+            AnyOp::from(ComplexOp::Oai4),
+            AnyOp::from(Lut::new(3, 0b1110_1000).unwrap()),
+            AnyOp::from(Lut::new(6, 0x8000_0000_0000_0001).unwrap()),
+            AnyOp::from(ConstOp::Unit),
+            AnyOp::from(ConstOp::Zero),
+            AnyOp::mux(),
+            AnyOp::nmux(),
+        ];
+        for op in ops {
+            let packed = op.eval_packed(&inputs);
+            for lane in 0..64 {
+                let bits: Vec<_> = inputs.iter().map(|word| word >> lane & 1 == 1).collect();
+                assert_eq!(packed >> lane & 1 == 1, op.eval(&bits), "{op:?}");
+            }
+        }
+    }
+}
@@ -0,0 +1,247 @@
+//! Constant propagation.
+//!
+//! Every gate with a [ConstOp] input is replaced by the simplest gate of the
+//! same function of its other inputs: a constant, one of the inputs, an
+//! inverter, a binary gate or a [Lut], dropping the inputs the function no
+//! longer depends on.
+
+use super::Result;
+use crate::ir::graph::{Edge, Graph, Node, NodeData, NodeKind};
+use crate::ir::ops::{AnyOp, BinaryOp, ConstOp, Lut};
+
+const BINARY_OPS: [BinaryOp; 8] = [
+    BinaryOp::And,
+    BinaryOp::Or,
+    BinaryOp::Xor,
+    BinaryOp::Nand,
+    BinaryOp::Nor,
+    BinaryOp::Xnor,
+    BinaryOp::AndNot,
+    BinaryOp::OrNot,
+];
+
+/// The simplest form of a gate, in terms of the indices of its inputs.
+#[derive(Clone, Debug, Eq, PartialEq)]
+enum Simplified {
+    Const(bool),
+    Input(usize),
+    Gate(AnyOp, Vec<usize>),
+}
+
+fn constant(graph: &Graph, node: Node) -> Option<bool> {
+    match graph.node(node).kind {
+        NodeKind::Gate(AnyOp::Const(ConstOp::Unit)) => Some(true),
+        NodeKind::Gate(AnyOp::Const(ConstOp::Zero)) => Some(false),
+        _ => None,
+    }
+}
+
+/// Whether the `table` of `n_inputs` depends on input `input`.
+fn depends_on(table: u64, n_inputs: usize, input: usize) -> bool {
+    (0..1usize << n_inputs)
+        .filter(|row| row >> input & 1 == 0)
+        .any(|row| table >> row & 1 != table >> (row | 1 << input) & 1)
+}
+
+/// The table of `n_inputs` without input `input`, which it should not depend
+/// on.
+fn remove_input(table: u64, n_inputs: usize, input: usize) -> u64 {
+    let low = (1usize << input) - 1;
+    (0..1usize << (n_inputs - 1)).fold(0, |reduced, row| {
+        let row_with = (row & low) | (row & !low) << 1;
+        reduced | (table >> row_with & 1) << row
+    })
+}
+
+fn simplify(op: &AnyOp, values: &[Option<bool>]) -> Simplified {
+    let mut support: Vec<_> = (0..values.len()).filter(|i| values[*i].is_none()).collect();
+    let mut inputs: Vec<_> = values.iter().map(|value| value.unwrap_or(false)).collect();
+    let mut table = (0..1usize << support.len()).fold(0, |table, row| {
+        for (j, i) in support.iter().enumerate() {
+            inputs[*i] = row >> j & 1 == 1;
+        }
+        table | u64::from(op.eval(&inputs)) << row
+    });
+    for j in (0..support.len()).rev() {
+        if !depends_on(table, support.len(), j) {
+            table = remove_input(table, support.len(), j);
+            support.remove(j);
+        }
+    }
+    match support[..] {
+        [] => Simplified::Const(table & 1 == 1),
+        [input] if table == 0b10 => Simplified::Input(input),
+        [_] => Simplified::Gate(AnyOp::not(), support),
+        [a, b] => {
+            let eval = |op: BinaryOp, swap: bool| {
+                (0..4).fold(0, |table, row: u64| {
+                    let (x, y) = (row & 1 == 1, row >> 1 & 1 == 1);
+                    let inputs = if swap { [y, x] } else { [x, y] };
+                    table | u64::from(AnyOp::from(op).eval(&inputs)) << row
+                })
+            };
+            BINARY_OPS
+                .iter()
+                .find_map(|op| {
+                    if eval(*op, false) == table {
+                        Some(Simplified::Gate(AnyOp::from(*op), vec![a, b]))
+                    } else if eval(*op, true) == table {
+                        Some(Simplified::Gate(AnyOp::from(*op), vec![b, a]))
+                    } else {
+                        None
+                    }
+                })
+                .expect("every function of two inputs should be a binary op")
+        }
+        _ => {
+            let lut = Lut::new(support.len(), table).expect("gate should have few inputs");
+            Simplified::Gate(AnyOp::from(lut), support)
+        }
+    }
+}
+
+/// Simplify every gate with a constant input, in topological order.
+///
+/// Returns the number of simplified gates.
+pub fn propagate_constants(graph: &mut Graph) -> Result<usize> {
+    let mut constants: [Option<Node>; 2] = [None, None];
+    let mut rewrites = 0;
+    for node in graph.topological_order()? {
+        let NodeKind::Gate(op) = graph.node(node).kind else {
+            continue;
+        };
+        let fanins = graph.fanins(node).to_vec();
+        let values: Vec<_> = fanins.iter().map(|fanin| constant(graph, *fanin)).collect();
+        if matches!(op, AnyOp::Const(_)) || values.iter().all(Option::is_none) {
+            continue;
+        }
+        let replacement = match simplify(&op, &values) {
+            Simplified::Const(value) => *constants[usize::from(value)].get_or_insert_with(|| {
+                graph.add_const(if value { ConstOp::Unit } else { ConstOp::Zero })
+            }),
+            Simplified::Input(input) => fanins[input],
+            Simplified::Gate(op, inputs) => {
+                let gate = graph.add_node(NodeData::new_op(op));
+                graph.add_edges(inputs.into_iter().map(|input| Edge {
+                    source: fanins[input],
+                    sink: gate,
+                }));
+                gate
+            }
+        };
+        graph.replace_uses(node, replacement);
+        rewrites += 1;
+    }
+    Ok(rewrites)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ir::equiv::{check, Equivalence};
+    use crate::ir::ops::ComplexOp;
+
+    #[test]
+    fn test_simplify() {
+        let (x, zero, unit) = (None, Some(false), Some(true));
+        assert_eq!(simplify(&AnyOp::and(), &[x, unit]), Simplified::Input(0));
+        assert_eq!(
+            simplify(&AnyOp::and(), &[zero, x]),
+            Simplified::Const(false)
+        );
+        assert_eq!(
+            simplify(&AnyOp::xor(), &[unit, x]),
+            Simplified::Gate(AnyOp::not(), vec![1])
+        );
+        assert_eq!(simplify(&AnyOp::mux(), &[x, x, zero]), Simplified::Input(0));
+        assert_eq!(
+            simplify(&AnyOp::from(ComplexOp::Aoi3), &[x, x, zero]),
+            Simplified::Gate(AnyOp::nand(), vec![0, 1])
+        );
+        // NOTE: `~((A | B) & C)` with `A` set is `~C`.
+        assert_eq!(
+            simplify(&AnyOp::from(ComplexOp::Oai3), &[unit, x, x]),
+            Simplified::Gate(AnyOp::not(), vec![2])
+        );
+        assert_eq!(
+            simplify(&AnyOp::from(ComplexOp::Oai4), &[x, zero, x, x]),
+            Simplified::Gate(
+                AnyOp::from(Lut::new(3, 0b0101_0111).unwrap()),
+                vec![0, 2, 3]
+            )
+        );
+        assert_eq!(
+            simplify(&AnyOp::from(ComplexOp::Oai3), &[x, zero, x]),
+            Simplified::Gate(AnyOp::nand(), vec![0, 2])
+        );
+        assert_eq!(remove_input(0b1100, 2, 0), 0b10);
+        assert_eq!(remove_input(0b1010, 2, 1), 0b10);
+    }
+
+    #[test]
+    fn test_propagate_constants() {
+        let mut graph = Graph::default();
+        let a = graph.add_input("a", 1).next().unwrap();
+        let b = graph.add_input("b", 1).next().unwrap();
+        let y: Vec<_> = graph.add_output("y", 3).collect();
+        let zero = graph.add_zero();
+        let or = graph.add_node(NodeData::new_op(AnyOp::or()));
+        let nor = graph.add_node(NodeData::new_op(AnyOp::nor()));
+        let mux = graph.add_node(NodeData::new_op(AnyOp::mux()));
+        graph.add_edges([
+            Edge {
+                source: a,
+                sink: or,
+            },
+            Edge {
+                source: zero,
+                sink: or,
+            },
+            Edge {
+                source: or,
+                sink: y[0],
+            },
+            Edge {
+                source: zero,
+                sink: nor,
+            },
+            Edge {
+                source: zero,
+                sink: nor,
+            },
+            Edge {
+                source: nor,
+                sink: mux,
+            },
+            Edge {
+                source: a,
+                sink: mux,
+            },
+            Edge {
+                source: b,
+                sink: mux,
+            },
+            Edge {
+                source: mux,
+                sink: y[1],
+            },
+            Edge {
+                source: nor,
+                sink: y[2],
+            },
+        ]);
+        let original = graph.clone();
+        // NOTE: The NOR folds to a constant, which turns the MUX into an
+        // OR-NOT of `a` and `b`.
+        assert_eq!(propagate_constants(&mut graph).unwrap(), 3);
+        assert_eq!(graph.fanins(y[0]), [a]);
+        let driver = graph.fanins(y[1])[0];
+        assert!(matches!(
+            graph.node(driver).kind,
+            NodeKind::Gate(AnyOp::Binary(BinaryOp::OrNot))
+        ));
+        assert_eq!(graph.fanins(driver), [a, b]);
+        assert_eq!(constant(&graph, graph.fanins(y[2])[0]), Some(true));
+        assert_eq!(check(&original, &graph).unwrap(), Equivalence::Equivalent);
+    }
+}
@@ -0,0 +1,65 @@
+//! Double-inverter removal.
+
+use super::Result;
+use crate::ir::graph::{Graph, NodeKind};
+use crate::ir::ops::{AnyOp, UnaryOp};
+
+fn is_inverter(kind: &NodeKind) -> bool {
+    matches!(kind, NodeKind::Gate(AnyOp::Unary(UnaryOp::Not)))
+}
+
+/// Bypass every inverter of an inverter, in topological order so that chains
+/// of inverters collapse to at most one.
+///
+/// Returns the number of bypassed inverters.
+pub fn remove_double_inverters(graph: &mut Graph) -> Result<usize> {
+    let mut removed = 0;
+    for node in graph.topological_order()? {
+        if !is_inverter(&graph.node(node).kind) {
+            continue;
+        }
+        let fanin = graph.fanins(node)[0];
+        if is_inverter(&graph.node(fanin).kind) {
+            let source = graph.fanins(fanin)[0];
+            graph.replace_uses(node, source);
+            removed += 1;
+        }
+    }
+    Ok(removed)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ir::graph::{Edge, NodeData};
+
+    #[test]
+    fn test_remove_double_inverters() {
+        let mut graph = Graph::default();
+        let a = graph.add_input("a", 1).next().unwrap();
+        let y: Vec<_> = graph.add_output("y", 2).collect();
+        let mut chain = vec![a];
+        for _ in 0..5 {
+            let not = graph.add_node(NodeData::new_op(AnyOp::not()));
+            graph.add_edge(Edge {
+                source: *chain.last().unwrap(),
+                sink: not,
+            });
+            chain.push(not);
+        }
+        graph.add_edges([
+            Edge {
+                source: chain[4],
+                sink: y[0],
+            },
+            Edge {
+                source: chain[5],
+                sink: y[1],
+            },
+        ]);
+        assert_eq!(remove_double_inverters(&mut graph).unwrap(), 2);
+        assert_eq!(graph.fanins(y[0]), [a]);
+        assert_eq!(graph.fanins(y[1]), [chain[5]]);
+        assert_eq!(graph.fanins(chain[5]), [a]);
+    }
+}
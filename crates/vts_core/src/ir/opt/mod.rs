@@ -0,0 +1,160 @@
+//! Logic optimisation passes.
+//!
+//! Passes other than [Pass::Sweep] only move the sinks of the nodes they
+//! rewrite to an equivalent node, leaving the rewritten nodes for a
+//! [sweep](sweep::sweep) to remove.
+
+pub mod constant;
+pub mod inverter;
+pub mod strash;
+pub mod sweep;
+
+use thiserror::Error;
+
+use super::graph::{self, Graph};
+
+#[derive(Clone, Debug, Error)]
+pub enum Error {
+    #[error(transparent)]
+    Graph(#[from] graph::Error),
+}
+
+pub type Result<T> = std::result::Result<T, Error>;
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Pass {
+    /// See [constant::propagate_constants].
+    ConstantPropagation,
+    /// See [strash::strash].
+    StructuralHashing,
+    /// See [inverter::remove_double_inverters].
+    DoubleInverters,
+    /// See [sweep::sweep].
+    Sweep,
+}
+
+impl Pass {
+    /// Every rewriting pass, followed by a sweep.
+    pub const DEFAULT: [Pass; 4] = [
+        Pass::ConstantPropagation,
+        Pass::DoubleInverters,
+        Pass::StructuralHashing,
+        Pass::Sweep,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::ConstantPropagation => "constant-propagation",
+            Self::StructuralHashing => "strash",
+            Self::DoubleInverters => "double-inverters",
+            Self::Sweep => "sweep",
+        }
+    }
+
+    pub fn run(&self, graph: &mut Graph) -> Result<PassStats> {
+        let nodes_before = graph.n_nodes();
+        let rewrites = match self {
+            Self::ConstantPropagation => constant::propagate_constants(graph)?,
+            Self::StructuralHashing => strash::strash(graph)?,
+            Self::DoubleInverters => inverter::remove_double_inverters(graph)?,
+            Self::Sweep => sweep::sweep(graph),
+        };
+        Ok(PassStats {
+            pass: *self,
+            rewrites,
+            nodes_before,
+            nodes_after: graph.n_nodes(),
+        })
+    }
+}
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct PassStats {
+    pub pass: Pass,
+    /// The number of rewritten nodes, or removed nodes for [Pass::Sweep].
+    pub rewrites: usize,
+    pub nodes_before: usize,
+    pub nodes_after: usize,
+}
+
+/// Run `passes` in order.
+pub fn optimize(graph: &mut Graph, passes: &[Pass]) -> Result<Vec<PassStats>> {
+    passes.iter().map(|pass| pass.run(graph)).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::interchange::yosys;
+    use crate::ir::equiv::{check, Equivalence};
+    use crate::ir::graph::{Edge, NodeData, NodeKind};
+    use crate::ir::ops::AnyOp;
+
+    #[test]
+    fn test_optimize() {
+        let design: yosys::Design = include_str!("../../../../../examples/alu/add4_simplemap.json")
+            .parse()
+            .unwrap();
+        let original = Graph::try_from(design.modules["add4"].clone()).unwrap();
+        let mut graph = original.clone();
+        // NOTE: Drive every sink through a double inverter and an AND with a
+        // constant, and leave a duplicate of the first inverter behind.
+        let unit = graph.add_unit();
+        let sinks: Vec<_> = graph
+            .node_ids()
+            .filter(|node| matches!(graph.node(*node).kind, NodeKind::Sink))
+            .collect();
+        for sink in sinks {
+            let driver = graph.fanins(sink)[0];
+            let mut node = driver;
+            for _ in 0..2 {
+                let not = graph.add_node(NodeData::new_op(AnyOp::not()));
+                graph.add_edge(Edge {
+                    source: node,
+                    sink: not,
+                });
+                node = not;
+            }
+            let and = graph.add_node(NodeData::new_op(AnyOp::and()));
+            graph.add_edges([
+                Edge {
+                    source: unit,
+                    sink: and,
+                },
+                Edge {
+                    source: node,
+                    sink: and,
+                },
+            ]);
+            graph.replace_edge(
+                Edge {
+                    source: driver,
+                    sink,
+                },
+                and,
+            );
+            let dead = graph.add_node(NodeData::new_op(AnyOp::not()));
+            graph.add_edge(Edge {
+                source: driver,
+                sink: dead,
+            });
+        }
+        let stats = optimize(&mut graph, &Pass::DEFAULT).unwrap();
+        let rewrites: Vec<_> = stats
+            .iter()
+            .map(|stats| (stats.pass, stats.rewrites))
+            .collect();
+        assert_eq!(
+            rewrites,
+            [
+                (Pass::ConstantPropagation, 5),
+                (Pass::DoubleInverters, 5),
+                (Pass::StructuralHashing, 5),
+                (Pass::Sweep, 1 + 5 * 4),
+            ]
+        );
+        assert_eq!(stats[3].nodes_after, original.n_nodes());
+        assert_eq!(check(&original, &graph).unwrap(), Equivalence::Equivalent);
+        assert_eq!(Pass::Sweep.name(), "sweep");
+    }
+}
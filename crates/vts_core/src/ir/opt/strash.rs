@@ -0,0 +1,129 @@
+//! Structural hashing.
+//!
+//! As in an and-inverter graph, gates of the same operation on the same
+//! inputs are merged into the first of them. The inputs of commutative binary
+//! gates are hashed in node order.
+
+use fnv::FnvHashMap;
+
+use super::Result;
+use crate::ir::graph::{Graph, Node, NodeKind};
+use crate::ir::ops::{AnyOp, BinaryOp};
+
+fn is_commutative(op: &AnyOp) -> bool {
+    matches!(
+        op,
+        AnyOp::Binary(
+            BinaryOp::And
+                | BinaryOp::Or
+                | BinaryOp::Xor
+                | BinaryOp::Nand
+                | BinaryOp::Nor
+                | BinaryOp::Xnor
+        )
+    )
+}
+
+/// Merge duplicate gates, in topological order so that gates become
+/// duplicates once their inputs are merged.
+///
+/// Returns the number of merged gates.
+pub fn strash(graph: &mut Graph) -> Result<usize> {
+    let mut gates = FnvHashMap::<(AnyOp, Vec<Node>), Node>::default();
+    let mut merged = 0;
+    for node in graph.topological_order()? {
+        let NodeKind::Gate(op) = graph.node(node).kind else {
+            continue;
+        };
+        let mut fanins = graph.fanins(node).to_vec();
+        if is_commutative(&op) {
+            fanins.sort();
+        }
+        let key = (op, fanins);
+        if let Some(gate) = gates.get(&key) {
+            graph.replace_uses(node, *gate);
+            merged += 1;
+        } else {
+            gates.insert(key, node);
+        }
+    }
+    Ok(merged)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ir::graph::{Edge, NodeData};
+
+    #[test]
+    fn test_strash() {
+        let mut graph = Graph::default();
+        let a = graph.add_input("a", 1).next().unwrap();
+        let b = graph.add_input("b", 1).next().unwrap();
+        let y: Vec<_> = graph.add_output("y", 2).collect();
+        let units = [graph.add_unit(), graph.add_unit()];
+        let ands: Vec<_> = (0..2)
+            .map(|_| graph.add_node(NodeData::new_op(AnyOp::and())))
+            .collect();
+        let and_not = graph.add_node(NodeData::new_op(AnyOp::and_not()));
+        let xors: Vec<_> = (0..2)
+            .map(|_| graph.add_node(NodeData::new_op(AnyOp::xor())))
+            .collect();
+        graph.add_edges([
+            Edge {
+                source: a,
+                sink: ands[0],
+            },
+            Edge {
+                source: b,
+                sink: ands[0],
+            },
+            Edge {
+                source: b,
+                sink: ands[1],
+            },
+            Edge {
+                source: a,
+                sink: ands[1],
+            },
+            Edge {
+                source: b,
+                sink: and_not,
+            },
+            Edge {
+                source: a,
+                sink: and_not,
+            },
+            Edge {
+                source: ands[0],
+                sink: xors[0],
+            },
+            Edge {
+                source: units[0],
+                sink: xors[0],
+            },
+            Edge {
+                source: ands[1],
+                sink: xors[1],
+            },
+            Edge {
+                source: units[1],
+                sink: xors[1],
+            },
+            Edge {
+                source: xors[0],
+                sink: y[0],
+            },
+            Edge {
+                source: xors[1],
+                sink: y[1],
+            },
+        ]);
+        // NOTE: The second constant, AND and then XOR are merged, but not the
+        // AND-NOT of swapped inputs.
+        assert_eq!(strash(&mut graph).unwrap(), 3);
+        assert_eq!(graph.fanins(y[1]), [xors[0]]);
+        assert_eq!(graph.fanins(xors[0]), [ands[0], units[0]]);
+        assert!(graph.sinks(ands[1]).is_empty());
+    }
+}
@@ -0,0 +1,76 @@
+//! Dead-node removal.
+
+use crate::ir::graph::{Graph, NodeKind};
+
+/// Remove every node that does not reach a [NodeKind::Sink], other than the
+/// [NodeKind::Source] nodes.
+///
+/// Returns the number of removed nodes.
+pub fn sweep(graph: &mut Graph) -> usize {
+    let mut live = vec![false; graph.n_nodes()];
+    let mut stack: Vec<_> = graph
+        .node_ids()
+        .filter(|node| matches!(graph.node(*node).kind, NodeKind::Sink))
+        .collect();
+    while let Some(node) = stack.pop() {
+        if live[node.index()] {
+            continue;
+        }
+        live[node.index()] = true;
+        stack.extend(graph.fanins(node));
+    }
+    let n_nodes = graph.n_nodes();
+    graph.retain_nodes(|node, data| live[node.index()] || matches!(data.kind, NodeKind::Source));
+    n_nodes - graph.n_nodes()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ir::graph::{Edge, NodeData};
+    use crate::ir::ops::{AnyOp, SeqOp};
+
+    #[test]
+    fn test_sweep() {
+        let mut graph = Graph::default();
+        let a = graph.add_input("a", 1).next().unwrap();
+        let unused = graph.add_input("unused", 1).next().unwrap();
+        let dead = graph.add_node(NodeData::new_op(AnyOp::and()).with_name("dead"));
+        let ff = graph.add_node(NodeData::new_seq(SeqOp::Ff).with_name("ff"));
+        let xor = graph.add_node(NodeData::new_op(AnyOp::xor()));
+        let y = graph.add_output("y", 1).next().unwrap();
+        graph.add_edges([
+            Edge {
+                source: a,
+                sink: dead,
+            },
+            Edge {
+                source: unused,
+                sink: dead,
+            },
+            Edge {
+                source: a,
+                sink: xor,
+            },
+            Edge {
+                source: ff,
+                sink: xor,
+            },
+            Edge {
+                source: xor,
+                sink: ff,
+            },
+            Edge {
+                source: ff,
+                sink: y,
+            },
+        ]);
+        assert_eq!(sweep(&mut graph), 1);
+        assert_eq!(graph.n_nodes(), 5);
+        assert_eq!(graph.find("dead"), None);
+        let ff = graph.find("ff").unwrap();
+        assert_eq!(graph.fanins(graph.find("y").unwrap()), [ff]);
+        assert!(graph.sinks(unused).is_empty());
+        assert_eq!(sweep(&mut graph), 0);
+    }
+}
@@ -0,0 +1,481 @@
+//! Path balancing.
+//!
+//! Clocked SFQ gates consume their inputs on every clock pulse, so every
+//! input of a gate has to arrive in the same clock stage. Every node is
+//! assigned a clock stage and every edge spanning more than one stage is
+//! delayed by a chain of [NodeKind::Dff] nodes. The chains of a driver are
+//! shared between its sinks, so balancing should be run before
+//! [splitter](super::splitter) insertion.
+//!
+//! [NodeKind::Splitter], [NodeKind::Sink] and merger
+//! ([SfqOp::Merger](crate::ir::ops::SfqOp::Merger)) nodes are not clocked and
+//! do not consume a stage. The inputs of [NodeKind::Seq] nodes are not
+//! balanced.
+//!
+//! The stages minimising the number of DFF's without increasing the depth
+//! are a linear program over difference constraints, with a variable bounding
+//! the chain of every driver as in register minimisation with fanout sharing.
+//! Its dual is a minimum-cost flow, which is solved by successive shortest
+//! paths, and the optimal stages are the potentials of the final residual
+//! graph.
+//!
+//! References:
+//! - C. E. Leiserson and J. B. Saxe, "Retiming Synchronous Circuitry", 1991.
+
+use std::cmp::Reverse;
+use std::collections::{BTreeMap, BinaryHeap, VecDeque};
+
+use thiserror::Error;
+
+use crate::ir::graph::{self, Edge, Graph, Node, NodeData, NodeKind};
+
+#[derive(Clone, Debug, Error)]
+pub enum Error {
+    #[error(transparent)]
+    Graph(#[from] graph::Error),
+}
+
+pub type Result<T> = std::result::Result<T, Error>;
+
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub enum BalanceMode {
+    /// Balance the inputs of every clocked node.
+    #[default]
+    Inputs,
+    /// Also delay every sink to the stage of the deepest sink.
+    Outputs,
+}
+
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub struct BalanceStats {
+    /// The number of inserted DFF's.
+    pub dffs: usize,
+    /// The stage of the deepest node.
+    pub depth: usize,
+}
+
+/// Whether `kind` consumes a clock stage.
+pub fn is_clocked(kind: &NodeKind) -> bool {
+    match kind {
+        NodeKind::Gate(_) | NodeKind::Dff => true,
+        NodeKind::Sfq(op) => op.is_clocked(),
+        _ => false,
+    }
+}
+
+/// The earliest clock stage of every node, indexed by node.
+///
+/// [NodeKind::Source]'s and nodes without inputs are in stage 0.
+pub fn stages(graph: &Graph) -> Result<Vec<usize>> {
+    let order = graph.topological_order()?;
+    let mut stages = vec![0; graph.n_nodes()];
+    for node in order {
+        for &sink in graph.sinks(node) {
+            if matches!(graph.node(sink).kind, NodeKind::Seq(_)) {
+                continue;
+            }
+            let stage = stages[node.index()] + usize::from(is_clocked(&graph.node(sink).kind));
+            stages[sink.index()] = stages[sink.index()].max(stage);
+        }
+    }
+    Ok(stages)
+}
+
+/// An arc of the residual graph of [min_cost_potentials].
+struct Arc {
+    to: usize,
+    cost: i64,
+    capacity: i64,
+    /// The index of the reverse arc among the arcs of `to`.
+    reverse: usize,
+}
+
+/// The values `x` minimising the sum of `weights[i] * x[i]` subject to
+/// `x[v] - x[u] >= w` for every `(u, v, w)` of `constraints`, which should be
+/// feasible, with the weights summing to 0 and every constraint bounded.
+///
+/// The dual is a flow of cost `-w` along every constraint, with node `i`
+/// receiving `weights[i]` more than it sends. The optimum is the negated
+/// potentials of the flow of least cost.
+fn min_cost_potentials(weights: &[i64], constraints: &[(usize, usize, i64)]) -> Vec<i64> {
+    let n = weights.len();
+    let unbounded = i64::MAX / 4;
+    let mut arcs: Vec<Vec<Arc>> = (0..n).map(|_| Vec::new()).collect();
+    for &(u, v, w) in constraints {
+        let (forward, reverse) = (arcs[u].len(), arcs[v].len() + usize::from(u == v));
+        arcs[u].push(Arc {
+            to: v,
+            cost: -w,
+            capacity: unbounded,
+            reverse,
+        });
+        arcs[v].push(Arc {
+            to: u,
+            cost: w,
+            capacity: 0,
+            reverse: forward,
+        });
+    }
+    // NOTE: Feasible constraints have no cycle of negative cost, so the
+    // initial potentials are shortest distances from a virtual root.
+    let mut potentials = vec![0; n];
+    let mut queue: VecDeque<_> = (0..n).collect();
+    let mut queued = vec![true; n];
+    while let Some(u) = queue.pop_front() {
+        queued[u] = false;
+        for arc in arcs[u].iter().filter(|arc| arc.capacity > 0) {
+            if potentials[u] + arc.cost < potentials[arc.to] {
+                potentials[arc.to] = potentials[u] + arc.cost;
+                if !queued[arc.to] {
+                    queued[arc.to] = true;
+                    queue.push_back(arc.to);
+                }
+            }
+        }
+    }
+    let mut excess: Vec<_> = weights.iter().map(|weight| -weight).collect();
+    loop {
+        let mut distances = vec![i64::MAX; n];
+        let mut parents = vec![None; n];
+        let mut heap = BinaryHeap::new();
+        for (u, _) in excess.iter().enumerate().filter(|(_, excess)| **excess > 0) {
+            distances[u] = 0;
+            heap.push(Reverse((0, u)));
+        }
+        if heap.is_empty() {
+            break;
+        }
+        let mut target = None;
+        while let Some(Reverse((distance, u))) = heap.pop() {
+            if distance > distances[u] {
+                continue;
+            }
+            if excess[u] < 0 {
+                target = Some(u);
+                break;
+            }
+            for (i, arc) in arcs[u].iter().enumerate() {
+                let reduced = arc.cost + potentials[u] - potentials[arc.to];
+                if arc.capacity > 0 && distance + reduced < distances[arc.to] {
+                    distances[arc.to] = distance + reduced;
+                    parents[arc.to] = Some((u, i));
+                    heap.push(Reverse((distances[arc.to], arc.to)));
+                }
+            }
+        }
+        let target = target.expect("constraints should be bounded");
+        for (potential, distance) in potentials.iter_mut().zip(&distances) {
+            *potential += (*distance).min(distances[target]);
+        }
+        // NOTE: Supplies and demands are units, so every path carries one.
+        let mut v = target;
+        while let Some((u, i)) = parents[v] {
+            arcs[u][i].capacity -= 1;
+            let (to, reverse) = (arcs[u][i].to, arcs[u][i].reverse);
+            arcs[to][reverse].capacity += 1;
+            v = u;
+        }
+        excess[v] -= 1;
+        excess[target] += 1;
+    }
+    potentials.into_iter().map(|potential| -potential).collect()
+}
+
+/// The clock stage of every node, indexed by node, that minimises the number
+/// of DFF's needed to balance `graph` without increasing its depth.
+///
+/// [NodeKind::Source]'s and [NodeKind::Seq] nodes are in stage 0.
+pub fn min_stages(graph: &Graph, mode: BalanceMode) -> Result<Vec<usize>> {
+    let depth = stages(graph)?.into_iter().max().unwrap_or_default() as i64;
+    let n = graph.n_nodes();
+    // NOTE: Variable `i` is the stage of node `i`, `n + i` bounds the chain of
+    // node `i` and `2 * n` is the stage 0.
+    let zero = 2 * n;
+    let mut weights = vec![0; 2 * n + 1];
+    let mut constraints = Vec::new();
+    for node in graph.node_ids() {
+        let i = node.index();
+        let (earliest, latest) = match graph.node(node).kind {
+            NodeKind::Source | NodeKind::Seq(_) => (0, 0),
+            NodeKind::Sink if mode == BalanceMode::Outputs => (depth, depth),
+            _ => (0, depth),
+        };
+        constraints.push((zero, i, earliest));
+        constraints.push((i, zero, -latest));
+    }
+    for (source, sink) in graph.edges() {
+        let kind = &graph.node(sink).kind;
+        let w = match (kind, mode) {
+            (NodeKind::Seq(_), _) | (NodeKind::Sink, BalanceMode::Inputs) => continue,
+            _ => i64::from(is_clocked(kind)),
+        };
+        let (u, v, chain) = (source.index(), sink.index(), n + source.index());
+        constraints.push((u, v, w));
+        constraints.push((v, chain, -w));
+        if weights[chain] == 0 {
+            // NOTE: The chain of `u` is its length, `x[chain] - x[u]`.
+            weights[chain] = 1;
+            weights[u] -= 1;
+            constraints.push((u, chain, 0));
+        }
+    }
+    let x = min_cost_potentials(&weights, &constraints);
+    Ok((0..n)
+        .map(|i| usize::try_from(x[i] - x[zero]).expect("stages should not be negative"))
+        .collect())
+}
+
+/// The delay needed on every edge of `graph` to balance it, given the
+/// `stages` of its nodes.
+pub(super) fn edge_delays(
+    graph: &Graph,
+    stages: &[usize],
+    mode: BalanceMode,
+) -> Vec<(Edge, usize)> {
+    let depth = stages.iter().copied().max().unwrap_or_default();
+    let mut delays = Vec::new();
+    for (source, sink) in graph.edges() {
+        let kind = &graph.node(sink).kind;
+        let arrival = match (kind, mode) {
+            (NodeKind::Seq(_), _) => continue,
+            (NodeKind::Sink, BalanceMode::Outputs) => depth,
+            (NodeKind::Sink, BalanceMode::Inputs) => stages[source.index()],
+            _ => stages[sink.index()] - usize::from(is_clocked(kind)),
+        };
+        let delay = arrival - stages[source.index()];
+        if delay > 0 {
+            delays.push((Edge { source, sink }, delay));
+        }
+    }
+    delays
+}
+
+/// The number of DFF's needed to balance every driver, given the `delays` of
+/// its edges, sharing a single chain between the sinks of a driver.
+pub(super) fn count_dffs(delays: &[(Edge, usize)]) -> usize {
+    let mut chains = BTreeMap::<Node, usize>::new();
+    for (edge, delay) in delays {
+        let length = chains.entry(edge.source).or_default();
+        *length = (*length).max(*delay);
+    }
+    chains.values().sum()
+}
+
+/// Insert the minimum number of DFF's balancing `graph`, under the
+/// [min_stages] of its nodes.
+pub fn balance(graph: &mut Graph, mode: BalanceMode) -> Result<BalanceStats> {
+    let stages = min_stages(graph, mode)?;
+    let delays = edge_delays(graph, &stages, mode);
+    let stats = BalanceStats {
+        dffs: count_dffs(&delays),
+        depth: stages.iter().copied().max().unwrap_or_default(),
+    };
+    let mut chains = BTreeMap::<Node, Vec<Node>>::new();
+    for (edge, delay) in delays {
+        let chain = chains.entry(edge.source).or_default();
+        while chain.len() < delay {
+            let dff = graph.add_node(NodeData::new_dff());
+            let source = chain.last().copied().unwrap_or(edge.source);
+            graph.add_edge(Edge { source, sink: dff });
+            chain.push(dff);
+        }
+        graph.replace_edge(edge, chain[delay - 1]);
+    }
+    Ok(stats)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::interchange::yosys;
+    use crate::ir::ops::{AnyOp, BinaryOp};
+
+    /// `y = (a & b) & c`, `z = c`.
+    fn unbalanced_graph() -> Graph {
+        let mut graph = Graph::default();
+        let sources: Vec<_> = graph.add_source(3).collect();
+        let and0 = graph.add_node(NodeData::new_op(BinaryOp::And));
+        let and1 = graph.add_node(NodeData::new_op(BinaryOp::And));
+        let sinks: Vec<_> = graph.add_sink(2).collect();
+        graph.add_edges([
+            Edge {
+                source: sources[0],
+                sink: and0,
+            },
+            Edge {
+                source: sources[1],
+                sink: and0,
+            },
+            Edge {
+                source: and0,
+                sink: and1,
+            },
+            Edge {
+                source: sources[2],
+                sink: and1,
+            },
+            Edge {
+                source: and1,
+                sink: sinks[0],
+            },
+            Edge {
+                source: sources[2],
+                sink: sinks[1],
+            },
+        ]);
+        graph
+    }
+
+    /// `y = !!a & b`, `z = !!a & !b`, where balancing the earliest stages
+    /// delays both `b` and `!b`.
+    fn fanout_graph() -> Graph {
+        let mut graph = Graph::default();
+        let [a, b] = [0; 2].map(|_| graph.add_source(1).next().unwrap());
+        let [not0, not1, not2] = [0; 3].map(|_| graph.add_node(NodeData::new_op(AnyOp::not())));
+        let [and0, and1] = [0; 2].map(|_| graph.add_node(NodeData::new_op(AnyOp::and())));
+        let [y, z] = [0; 2].map(|_| graph.add_sink(1).next().unwrap());
+        graph.add_edges(
+            [
+                (a, not0),
+                (not0, not1),
+                (not1, and0),
+                (b, and0),
+                (b, not2),
+                (not1, and1),
+                (not2, and1),
+                (and0, y),
+                (and1, z),
+            ]
+            .map(|(source, sink)| Edge { source, sink }),
+        );
+        graph
+    }
+
+    /// The least number of DFF's over every feasible assignment of stages.
+    fn brute_force(graph: &Graph, mode: BalanceMode) -> usize {
+        let depth = stages(graph).unwrap().into_iter().max().unwrap();
+        let free: Vec<_> = graph
+            .node_ids()
+            .filter(|node| match graph.node(*node).kind {
+                NodeKind::Source | NodeKind::Seq(_) => false,
+                NodeKind::Sink => mode == BalanceMode::Inputs,
+                _ => true,
+            })
+            .collect();
+        let mut best = usize::MAX;
+        for mut assignment in 0..(depth + 1).pow(free.len() as u32) {
+            let mut stages: Vec<_> = graph
+                .nodes()
+                .map(|node| match node.kind {
+                    NodeKind::Sink => depth,
+                    _ => 0,
+                })
+                .collect();
+            for node in &free {
+                stages[node.index()] = assignment % (depth + 1);
+                assignment /= depth + 1;
+            }
+            let feasible = graph.edges().all(|(source, sink)| {
+                let kind = &graph.node(sink).kind;
+                match (kind, mode) {
+                    (NodeKind::Seq(_), _) | (NodeKind::Sink, BalanceMode::Inputs) => true,
+                    _ => {
+                        stages[sink.index()]
+                            >= stages[source.index()] + usize::from(is_clocked(kind))
+                    }
+                }
+            });
+            if feasible {
+                best = best.min(count_dffs(&edge_delays(graph, &stages, mode)));
+            }
+        }
+        best
+    }
+
+    fn check_balanced(graph: &Graph, mode: BalanceMode) {
+        // NOTE: Gates without inputs, such as constants, may be balanced in a
+        // later stage than the earliest.
+        let stages = min_stages(graph, mode).unwrap();
+        assert!(edge_delays(graph, &stages, mode).is_empty());
+    }
+
+    #[test]
+    fn test_balance_inputs() {
+        let mut graph = unbalanced_graph();
+        let stats = balance(&mut graph, BalanceMode::Inputs).unwrap();
+        assert_eq!(stats, BalanceStats { dffs: 1, depth: 2 });
+        check_balanced(&graph, BalanceMode::Inputs);
+        let dffs = graph
+            .nodes()
+            .filter(|node| matches!(node.kind, NodeKind::Dff))
+            .count();
+        assert_eq!(dffs, 1);
+    }
+
+    #[test]
+    fn test_balance_outputs() {
+        let mut graph = unbalanced_graph();
+        let stats = balance(&mut graph, BalanceMode::Outputs).unwrap();
+        // NOTE: `c` shares its first DFF between `and1` and `z`.
+        assert_eq!(stats, BalanceStats { dffs: 2, depth: 2 });
+        check_balanced(&graph, BalanceMode::Outputs);
+        assert_eq!(graph.n_nodes(), 7 + 2);
+    }
+
+    #[test]
+    fn test_combinational_loop() {
+        let mut graph = unbalanced_graph();
+        let mut nodes = graph.node_ids().skip(3);
+        let (and0, and1) = (nodes.next().unwrap(), nodes.next().unwrap());
+        graph.add_edge(Edge {
+            source: and1,
+            sink: and0,
+        });
+        assert!(matches!(
+            balance(&mut graph, BalanceMode::Inputs),
+            Err(Error::Graph(graph::Error::CombinationalLoop(cycle))) if cycle == [and0, and1]
+        ));
+    }
+
+    #[test]
+    fn test_min_stages() {
+        let graph = fanout_graph();
+        let asap = stages(&graph).unwrap();
+        let delays = edge_delays(&graph, &asap, BalanceMode::Inputs);
+        assert_eq!(count_dffs(&delays), 3);
+        let mut balanced = graph.clone();
+        let stats = balance(&mut balanced, BalanceMode::Inputs).unwrap();
+        // NOTE: `!b` is computed late, sharing the chain of `b`.
+        assert_eq!(stats, BalanceStats { dffs: 2, depth: 3 });
+        check_balanced(&balanced, BalanceMode::Inputs);
+
+        for graph in [unbalanced_graph(), fanout_graph()] {
+            for mode in [BalanceMode::Inputs, BalanceMode::Outputs] {
+                let stats = balance(&mut graph.clone(), mode).unwrap();
+                assert_eq!(stats.dffs, brute_force(&graph, mode), "{mode:?}");
+            }
+        }
+
+        for (json, top) in [
+            (
+                include_str!("../../../../../examples/alu/alu4_simplemap.json"),
+                "alu4",
+            ),
+            (
+                include_str!("../../../../../examples/crc/crc16_simplemap.json"),
+                "crc16_1021",
+            ),
+        ] {
+            let design: yosys::Design = json.parse().unwrap();
+            let graph = Graph::try_from(design.modules[top].clone()).unwrap();
+            for mode in [BalanceMode::Inputs, BalanceMode::Outputs] {
+                let asap = stages(&graph).unwrap();
+                let asap = count_dffs(&edge_delays(&graph, &asap, mode));
+                let mut balanced = graph.clone();
+                let stats = balance(&mut balanced, mode).unwrap();
+                assert!(stats.dffs <= asap, "{top} {mode:?}");
+                check_balanced(&balanced, mode);
+            }
+        }
+    }
+}
@@ -0,0 +1,292 @@
+//! Multi-phase clocking.
+//!
+//! With `n`-phase clocking every clock cycle is split into `n` phases and
+//! every clocked node is assigned the absolute phase in which it fires. A node
+//! has to fire in a later phase than its inputs, but an input remains valid
+//! for a whole cycle, so only edges spanning more than `n` phases have to be
+//! delayed by DFF's, one for every further `n` phases. Single-phase clocking
+//! is the special case `n = 1` of [balance](super::balance).
+
+use super::balance::{self, is_clocked, BalanceMode, Result};
+use crate::ir::graph::{Edge, Graph, Node, NodeKind};
+
+/// How clocked nodes are assigned their phase.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub enum PhaseStrategy {
+    /// Every node fires in the earliest phase its inputs allow.
+    #[default]
+    Asap,
+    /// Every node fires in the latest phase its sinks allow, without
+    /// increasing the depth of the graph.
+    Alap,
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct ClockingConfig {
+    /// The number of phases per clock cycle.
+    pub n_phases: usize,
+    pub strategy: PhaseStrategy,
+    pub mode: BalanceMode,
+}
+
+impl ClockingConfig {
+    pub fn new(n_phases: usize) -> Self {
+        Self {
+            n_phases,
+            strategy: PhaseStrategy::default(),
+            mode: BalanceMode::default(),
+        }
+    }
+}
+
+/// The phase of every node of a graph.
+#[derive(Clone, Debug)]
+pub struct PhaseAssignment {
+    n_phases: usize,
+    phases: Vec<usize>,
+}
+
+impl PhaseAssignment {
+    pub fn n_phases(&self) -> usize {
+        self.n_phases
+    }
+
+    /// The absolute phase of `node`, counted from the first phase of the
+    /// first cycle.
+    pub fn phase(&self, node: Node) -> usize {
+        self.phases[node.index()]
+    }
+
+    /// The phase of `node` within its clock cycle.
+    pub fn cycle_phase(&self, node: Node) -> usize {
+        self.phase(node) % self.n_phases
+    }
+
+    /// The clock cycle in which `node` fires.
+    pub fn cycle(&self, node: Node) -> usize {
+        self.phase(node) / self.n_phases
+    }
+
+    /// The phase of the latest node.
+    pub fn depth(&self) -> usize {
+        self.phases.iter().copied().max().unwrap_or_default()
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub struct ClockingStats {
+    pub n_phases: usize,
+    /// The phase of the latest node.
+    pub depth: usize,
+    /// The number of DFF's needed to balance the graph.
+    pub dffs: usize,
+}
+
+/// Assign every clocked node of `graph` a phase.
+///
+/// # Panics
+///
+/// Panics if the configured number of phases is 0.
+pub fn assign_phases(graph: &Graph, config: &ClockingConfig) -> Result<PhaseAssignment> {
+    assert!(config.n_phases > 0, "clocking should have at least 1 phase");
+    // NOTE: The earliest phases are the single-phase stages.
+    let mut phases = balance::stages(graph)?;
+    if config.strategy == PhaseStrategy::Alap {
+        let depth = phases.iter().copied().max().unwrap_or_default();
+        let order = graph
+            .topological_order()
+            .expect("graph should not have a loop");
+        let mut latest = vec![depth; graph.n_nodes()];
+        for &node in order.iter().rev() {
+            for &sink in graph.sinks(node) {
+                let bound = match graph.node(sink).kind {
+                    NodeKind::Seq(_) => continue,
+                    NodeKind::Sink if config.mode == BalanceMode::Inputs => continue,
+                    NodeKind::Sink => depth,
+                    ref kind => latest[sink.index()] - usize::from(is_clocked(kind)),
+                };
+                latest[node.index()] = latest[node.index()].min(bound);
+            }
+        }
+        // NOTE: As in [balance::stages], sequential nodes break loops and are
+        // in phase 0, and other unclocked nodes share the phase of their
+        // latest input.
+        for node in order {
+            phases[node.index()] = match graph.node(node).kind {
+                NodeKind::Source | NodeKind::Seq(_) => 0,
+                ref kind if is_clocked(kind) => latest[node.index()],
+                _ => graph
+                    .fanins(node)
+                    .iter()
+                    .map(|fanin| phases[fanin.index()])
+                    .max()
+                    .unwrap_or_default(),
+            };
+        }
+    }
+    Ok(PhaseAssignment {
+        n_phases: config.n_phases,
+        phases,
+    })
+}
+
+/// The number of DFF's needed to balance `graph` under the phase `assignment`.
+pub fn count_dffs(graph: &Graph, assignment: &PhaseAssignment, mode: BalanceMode) -> usize {
+    let n = assignment.n_phases;
+    let depth = assignment.depth();
+    let mut delays = Vec::new();
+    for (source, sink) in graph.edges() {
+        let kind = &graph.node(sink).kind;
+        let phase = match (kind, mode) {
+            (NodeKind::Seq(_), _) => continue,
+            // NOTE: Outputs are read as if by a clocked node following the latest node.
+            (NodeKind::Sink, BalanceMode::Outputs) => depth + 1,
+            (kind, _) if !is_clocked(kind) => continue,
+            _ => assignment.phase(sink),
+        };
+        let gap = phase - assignment.phase(source);
+        let delay = gap.div_ceil(n).saturating_sub(1);
+        if delay > 0 {
+            delays.push((Edge { source, sink }, delay));
+        }
+    }
+    balance::count_dffs(&delays)
+}
+
+/// Assign phases to the nodes of `graph` and count the DFF's needed to balance it.
+pub fn clocking_stats(graph: &Graph, config: &ClockingConfig) -> Result<ClockingStats> {
+    let assignment = assign_phases(graph, config)?;
+    Ok(ClockingStats {
+        n_phases: config.n_phases,
+        depth: assignment.depth(),
+        dffs: count_dffs(graph, &assignment, config.mode),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::interchange::yosys;
+    use crate::ir::graph::NodeData;
+    use crate::ir::ops::{AnyOp, SeqOp};
+    use crate::ir::sfq::balance::balance;
+
+    fn example(json: &str, module: &str) -> Graph {
+        let design: yosys::Design = json.parse().unwrap();
+        Graph::try_from(design.modules[module].clone()).unwrap()
+    }
+
+    #[test]
+    fn test_single_phase() {
+        let graph = example(
+            include_str!("../../../../../examples/alu/add4_simplemap.json"),
+            "add4",
+        );
+        for mode in [BalanceMode::Inputs, BalanceMode::Outputs] {
+            let config = ClockingConfig {
+                mode,
+                ..ClockingConfig::new(1)
+            };
+            let stats = clocking_stats(&graph, &config).unwrap();
+            let balanced = balance(&mut graph.clone(), mode).unwrap();
+            assert_eq!(stats.dffs, balanced.dffs);
+            assert_eq!(stats.depth, balanced.depth);
+        }
+    }
+
+    #[test]
+    fn test_multi_phase() {
+        for (json, module) in [
+            (
+                include_str!("../../../../../examples/alu/add4_simplemap.json"),
+                "add4",
+            ),
+            (
+                include_str!("../../../../../examples/crc/crc16_simplemap.json"),
+                "crc16_1021",
+            ),
+        ] {
+            let graph = example(json, module);
+            let mut previous = usize::MAX;
+            for n_phases in [1, 2, 4] {
+                let config = ClockingConfig {
+                    mode: BalanceMode::Outputs,
+                    ..ClockingConfig::new(n_phases)
+                };
+                let stats = clocking_stats(&graph, &config).unwrap();
+                assert!(stats.dffs <= previous, "{module} with {n_phases} phases");
+                previous = stats.dffs;
+                let alap = clocking_stats(
+                    &graph,
+                    &ClockingConfig {
+                        strategy: PhaseStrategy::Alap,
+                        ..config
+                    },
+                )
+                .unwrap();
+                assert_eq!(alap.depth, stats.depth);
+            }
+            let stats = clocking_stats(&graph, &ClockingConfig::new(usize::MAX)).unwrap();
+            assert_eq!(stats.dffs, 0);
+        }
+    }
+
+    #[test]
+    fn test_sequential() {
+        // NOTE: `ff` feeds back the last of a chain of 3 gates to the first.
+        let mut graph = Graph::default();
+        let a = graph.add_input("a", 1).next().unwrap();
+        let y = graph.add_output("y", 1).next().unwrap();
+        let ff = graph.add_node(NodeData::new_seq(SeqOp::Ff));
+        let gates: Vec<_> = (0..3)
+            .map(|_| graph.add_node(NodeData::new_op(AnyOp::and())))
+            .collect();
+        graph.add_edges(
+            [
+                (a, gates[0]),
+                (ff, gates[0]),
+                (gates[0], gates[1]),
+                (a, gates[1]),
+                (gates[1], gates[2]),
+                (a, gates[2]),
+                (gates[2], ff),
+                (gates[2], y),
+            ]
+            .map(|(source, sink)| Edge { source, sink }),
+        );
+        for strategy in [PhaseStrategy::Asap, PhaseStrategy::Alap] {
+            for mode in [BalanceMode::Inputs, BalanceMode::Outputs] {
+                let config = ClockingConfig {
+                    strategy,
+                    mode,
+                    ..ClockingConfig::new(1)
+                };
+                let assignment = assign_phases(&graph, &config).unwrap();
+                assert_eq!(assignment.phase(ff), 0, "{strategy:?}");
+                assert_eq!(assignment.depth(), 3, "{strategy:?}");
+                let dffs = count_dffs(&graph, &assignment, mode);
+                if strategy == PhaseStrategy::Asap {
+                    assert_eq!(dffs, balance(&mut graph.clone(), mode).unwrap().dffs);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_cycle_phase() {
+        let graph = example(
+            include_str!("../../../../../examples/alu/add4_simplemap.json"),
+            "add4",
+        );
+        let assignment = assign_phases(&graph, &ClockingConfig::new(4)).unwrap();
+        let node = graph
+            .node_ids()
+            .max_by_key(|node| assignment.phase(*node))
+            .unwrap();
+        assert_eq!(assignment.phase(node), assignment.depth());
+        assert_eq!(
+            assignment.cycle(node) * 4 + assignment.cycle_phase(node),
+            assignment.depth()
+        );
+    }
+}
@@ -0,0 +1,5 @@
+//! Passes legalising an [ir](crate::ir) graph for superconducting SFQ logic.
+
+pub mod balance;
+pub mod clocking;
+pub mod splitter;
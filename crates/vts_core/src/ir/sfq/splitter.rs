@@ -0,0 +1,257 @@
+//! Splitter insertion.
+//!
+//! An SFQ gate output can only drive a single input, so the fanout of every
+//! node driving more than one sink is rewritten into a tree of
+//! [NodeKind::Splitter] nodes, each driving at most `fanout` sinks.
+
+use crate::ir::graph::{Edge, Graph, Node, NodeData, NodeKind};
+
+/// The shape of a splitter tree.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub enum TreeShape {
+    /// Splitters are arranged level by level, minimising the depth of the tree.
+    #[default]
+    Balanced,
+    /// Every splitter drives the next splitter of a chain, minimising the
+    /// depth of the first sinks.
+    Chain,
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct SplitterConfig {
+    /// The number of sinks of a single splitter.
+    pub fanout: usize,
+    pub shape: TreeShape,
+}
+
+impl Default for SplitterConfig {
+    fn default() -> Self {
+        Self {
+            fanout: 2,
+            shape: TreeShape::default(),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub struct SplitterStats {
+    /// The number of inserted splitters.
+    pub splitters: usize,
+    /// The largest number of splitters between a node and one of its sinks.
+    pub max_depth: usize,
+}
+
+/// Insert splitter trees after every node, other than a splitter, with more
+/// than one sink.
+///
+/// # Panics
+///
+/// Panics if the configured `fanout` is less than 2.
+pub fn insert_splitters(graph: &mut Graph, config: &SplitterConfig) -> SplitterStats {
+    assert!(config.fanout >= 2, "splitter fanout should be at least 2");
+    let mut stats = SplitterStats::default();
+    for node in graph.node_ids() {
+        if matches!(graph.node(node).kind, NodeKind::Splitter) || graph.sinks(node).len() < 2 {
+            continue;
+        }
+        let sinks: Vec<_> = graph
+            .sinks(node)
+            .iter()
+            .map(|sink| TreeNode::Sink(*sink))
+            .collect();
+        let mut tree = Tree {
+            graph: &mut *graph,
+            source: node,
+            stats: &mut stats,
+        };
+        let (root, depth) = match config.shape {
+            TreeShape::Balanced => tree.balanced(sinks, config.fanout),
+            TreeShape::Chain => tree.chain(sinks, config.fanout),
+        };
+        graph.add_edge(Edge {
+            source: node,
+            sink: root,
+        });
+        stats.max_depth = stats.max_depth.max(depth);
+    }
+    stats
+}
+
+/// A child of a splitter in a tree.
+#[derive(Clone, Copy, Debug)]
+enum TreeNode {
+    /// One of the sinks of the root of the tree.
+    Sink(Node),
+    Splitter(Node),
+}
+
+/// A splitter tree driven by `source`.
+struct Tree<'a> {
+    graph: &'a mut Graph,
+    source: Node,
+    stats: &'a mut SplitterStats,
+}
+
+impl Tree<'_> {
+    /// Add a splitter driving `children`, moving the edges from the source to
+    /// its sinks onto the splitter.
+    fn add_splitter(&mut self, children: &[TreeNode]) -> TreeNode {
+        let splitter = self.graph.add_node(NodeData::new_splitter());
+        for child in children {
+            match *child {
+                TreeNode::Sink(sink) => {
+                    let edge = Edge {
+                        source: self.source,
+                        sink,
+                    };
+                    self.graph.replace_edge(edge, splitter);
+                }
+                TreeNode::Splitter(sink) => self.graph.add_edge(Edge {
+                    source: splitter,
+                    sink,
+                }),
+            }
+        }
+        self.stats.splitters += 1;
+        TreeNode::Splitter(splitter)
+    }
+
+    /// Build a tree bottom-up, grouping every `fanout` nodes of a level under
+    /// a splitter of the next level. Returns the root and depth of the tree.
+    fn balanced(&mut self, sinks: Vec<TreeNode>, fanout: usize) -> (Node, usize) {
+        let mut level = sinks;
+        let mut depth = 0;
+        while level.len() > 1 {
+            level = level
+                .chunks(fanout)
+                .map(|chunk| match chunk {
+                    [node] => *node,
+                    _ => self.add_splitter(chunk),
+                })
+                .collect();
+            depth += 1;
+        }
+        match level[0] {
+            TreeNode::Splitter(root) => (root, depth),
+            TreeNode::Sink(_) => unreachable!("tree should have more than one sink"),
+        }
+    }
+
+    /// Build a chain of splitters, each driving `fanout - 1` sinks and the next
+    /// splitter. Returns the root and depth of the chain.
+    fn chain(&mut self, sinks: Vec<TreeNode>, fanout: usize) -> (Node, usize) {
+        // NOTE: The chain is built from its last splitter up.
+        let mut chunks: Vec<_> = sinks.chunks(fanout - 1).map(<[_]>::to_vec).collect();
+        let mut last = chunks.pop().expect("node should have sinks");
+        if last.len() == 1 {
+            let previous = chunks.pop().expect("node should have more than one sink");
+            last = [previous, last].concat();
+        }
+        let mut next = self.add_splitter(&last);
+        let mut depth = 1;
+        while let Some(mut chunk) = chunks.pop() {
+            chunk.push(next);
+            next = self.add_splitter(&chunk);
+            depth += 1;
+        }
+        match next {
+            TreeNode::Splitter(root) => (root, depth),
+            TreeNode::Sink(_) => unreachable!("chain should start with a splitter"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ir::ops::BinaryOp;
+
+    /// A source driving both inputs of an AND gate and `n_sinks` sinks.
+    fn fanout_graph(n_sinks: usize) -> Graph {
+        let mut graph = Graph::default();
+        let source = graph.add_source(1).next().unwrap();
+        let and = graph.add_node(NodeData::new_op(BinaryOp::And));
+        graph.add_edges([Edge { source, sink: and }, Edge { source, sink: and }]);
+        for sink in graph.add_sink(n_sinks) {
+            graph.add_edge(Edge { source, sink });
+        }
+        graph
+    }
+
+    fn check_fanout(graph: &Graph, fanout: usize) {
+        for node in graph.node_ids() {
+            let limit = match graph.node(node).kind {
+                NodeKind::Splitter => fanout,
+                _ => 1,
+            };
+            assert!(graph.sinks(node).len() <= limit, "node {node} fanout");
+        }
+        for (source, sink) in graph.edges() {
+            assert!(graph.fanins(sink).contains(&source));
+        }
+    }
+
+    /// The non-splitter nodes reached from `node` through splitters.
+    fn leaves(graph: &Graph, node: Node) -> Vec<Node> {
+        let mut nodes = Vec::new();
+        for &sink in graph.sinks(node) {
+            match graph.node(sink).kind {
+                NodeKind::Splitter => nodes.extend(leaves(graph, sink)),
+                _ => nodes.push(sink),
+            }
+        }
+        nodes.sort();
+        nodes
+    }
+
+    #[test]
+    fn test_balanced() {
+        let mut graph = fanout_graph(3);
+        let source = graph.node_ids().next().unwrap();
+        let expected = leaves(&graph, source);
+        let stats = insert_splitters(&mut graph, &SplitterConfig::default());
+        // NOTE: 5 sinks need 4 splitters in 3 levels.
+        assert_eq!(
+            stats,
+            SplitterStats {
+                splitters: 4,
+                max_depth: 3
+            }
+        );
+        check_fanout(&graph, 2);
+        assert_eq!(leaves(&graph, source), expected);
+
+        let mut graph = fanout_graph(3);
+        let config = SplitterConfig {
+            fanout: 3,
+            ..SplitterConfig::default()
+        };
+        let stats = insert_splitters(&mut graph, &config);
+        assert_eq!(
+            stats,
+            SplitterStats {
+                splitters: 3,
+                max_depth: 2
+            }
+        );
+        check_fanout(&graph, 3);
+    }
+
+    #[test]
+    fn test_chain() {
+        for (fanout, splitters) in [(2, 4), (3, 2), (5, 1)] {
+            let mut graph = fanout_graph(3);
+            let source = graph.node_ids().next().unwrap();
+            let expected = leaves(&graph, source);
+            let config = SplitterConfig {
+                fanout,
+                shape: TreeShape::Chain,
+            };
+            let stats = insert_splitters(&mut graph, &config);
+            assert_eq!(stats.splitters, splitters);
+            assert_eq!(stats.max_depth, splitters);
+            check_fanout(&graph, fanout);
+            assert_eq!(leaves(&graph, source), expected);
+        }
+    }
+}
@@ -0,0 +1,913 @@
+//! Bit-parallel logic simulation.
+//!
+//! Every node holds a 64-bit word, bit `i` of which is its value in pattern
+//! `i`, so 64 patterns are simulated at once. A cycle evaluates the graph in
+//! topological order, then clocks every flip-flop once. Latches and
+//! asynchronous resets act within the evaluation, as soon as their inputs
+//! change.
+//!
+//! Clock stages are abstracted away: [NodeKind::Dff] nodes are buffers and
+//! clocked SFQ gates settle within the cycle they are driven in, so a graph
+//! simulates the same before and after [balancing](super::sfq::balance) and
+//! [splitter](super::sfq::splitter) insertion. Only [NodeKind::Seq] nodes and
+//! the [Ndro](SfqOp::Ndro) and [T1](SfqOp::T1) primitives hold state across
+//! cycles. [Simulator::cycle] ignores clock inputs, every cycle is a clock
+//! edge, while [Simulator::simulate] clocks flip-flops on the edges of their
+//! clock input.
+
+pub mod vcd;
+
+use rand::{rngs::StdRng, Rng, SeedableRng};
+use thiserror::Error;
+
+use super::graph::{self, Graph, Node, NodeKind, INIT};
+use super::ops::{FlipFlop, Latch, Polarity, SeqOp, SfqOp};
+use vcd::{Sample, Signal, Waveform};
+
+#[derive(Debug, Error)]
+pub enum Error {
+    #[error(transparent)]
+    Graph(#[from] graph::Error),
+    #[error("node {node} should have {expected} inputs, found {found}")]
+    Arity {
+        node: Node,
+        expected: usize,
+        found: usize,
+    },
+    #[error("graphs do not have the same ports ({0})")]
+    PortMismatch(String),
+    #[error(r#"signal "{0}" missing from waveform"#)]
+    MissingSignal(String),
+    #[error(transparent)]
+    Vcd(#[from] vcd::Error),
+}
+
+pub type Result<T> = std::result::Result<T, Error>;
+
+fn active(polarity: Polarity, word: u64) -> u64 {
+    match polarity {
+        Polarity::Positive => word,
+        Polarity::Negative => !word,
+    }
+}
+
+fn fill(bit: bool) -> u64 {
+    if bit {
+        u64::MAX
+    } else {
+        0
+    }
+}
+
+/// Select `then` in the patterns of `condition`, and `other` elsewhere.
+fn select(condition: u64, then: u64, other: u64) -> u64 {
+    (condition & then) | (!condition & other)
+}
+
+/// The number of inputs of a node of `kind`.
+pub(crate) fn n_inputs(kind: &NodeKind) -> usize {
+    match kind {
+        NodeKind::Source => 0,
+        NodeKind::Sink | NodeKind::Splitter | NodeKind::Dff => 1,
+        NodeKind::Gate(op) => op.arity(),
+        NodeKind::Seq(op) => op.inputs().len(),
+        NodeKind::Sfq(op) => op.inputs().len(),
+    }
+}
+
+fn holds_state(kind: &NodeKind) -> bool {
+    matches!(
+        kind,
+        NodeKind::Seq(_) | NodeKind::Sfq(SfqOp::Ndro | SfqOp::T1)
+    )
+}
+
+/// Whether a node of `kind` changes state as soon as its inputs do, i.e. a
+/// latch or a flip-flop with an asynchronous reset.
+fn is_transparent(kind: &NodeKind) -> bool {
+    match kind {
+        NodeKind::Seq(SeqOp::Latch(_)) => true,
+        NodeKind::Seq(SeqOp::FlipFlop(FlipFlop { reset, .. })) => {
+            reset.is_some_and(|reset| reset.asynchronous)
+        }
+        _ => false,
+    }
+}
+
+pub struct Simulator<'a> {
+    graph: &'a Graph,
+    order: Vec<Node>,
+    values: Vec<u64>,
+    state: Vec<u64>,
+    sources: Vec<Node>,
+    sinks: Vec<Node>,
+    /// The nodes [is_transparent] is true of.
+    transparent: Vec<Node>,
+}
+
+impl<'a> Simulator<'a> {
+    /// Prepare the simulation of `graph`, with every sequential element in
+    /// its initial state.
+    ///
+    /// Returns [Error::Arity] if a node has the wrong number of inputs, or
+    /// [Error::Graph] if `graph` has a combinational loop.
+    pub fn new(graph: &'a Graph) -> Result<Self> {
+        for node in graph.node_ids() {
+            let expected = n_inputs(&graph.node(node).kind);
+            let found = graph.fanins(node).len();
+            if found != expected {
+                return Err(Error::Arity {
+                    node,
+                    expected,
+                    found,
+                });
+            }
+        }
+        let order = graph.topological_order()?;
+        let kind = |node: &Node| graph.node(*node).kind;
+        let sources = graph
+            .node_ids()
+            .filter(|node| matches!(kind(node), NodeKind::Source))
+            .collect();
+        let sinks = graph
+            .node_ids()
+            .filter(|node| matches!(kind(node), NodeKind::Sink))
+            .collect();
+        let transparent = graph
+            .node_ids()
+            .filter(|node| is_transparent(&kind(node)))
+            .collect();
+        let mut simulator = Self {
+            graph,
+            order,
+            values: vec![0; graph.n_nodes()],
+            state: vec![0; graph.n_nodes()],
+            sources,
+            sinks,
+            transparent,
+        };
+        simulator.reset();
+        Ok(simulator)
+    }
+
+    pub fn graph(&self) -> &'a Graph {
+        self.graph
+    }
+
+    /// The [NodeKind::Source] nodes, in node order.
+    pub fn sources(&self) -> &[Node] {
+        &self.sources
+    }
+
+    /// The [NodeKind::Sink] nodes, in node order.
+    pub fn sinks(&self) -> &[Node] {
+        &self.sinks
+    }
+
+    /// Return every sequential element to the value of its [INIT] attribute,
+    /// or 0 if it has none.
+    pub fn reset(&mut self) {
+        for node in self.graph.node_ids() {
+            let data = self.graph.node(node);
+            self.state[node.index()] = match data.kind {
+                NodeKind::Seq(_) => fill(data.attributes.get(INIT).is_some_and(|init| init == "1")),
+                _ => 0,
+            };
+        }
+    }
+
+    /// Drive the [NodeKind::Source] `node` with `word` from the next
+    /// [Simulator::eval] on.
+    pub fn set(&mut self, node: Node, word: u64) {
+        self.values[node.index()] = word;
+    }
+
+    /// The value of `node` as of the last [Simulator::eval].
+    pub fn value(&self, node: Node) -> u64 {
+        self.values[node.index()]
+    }
+
+    /// Evaluate every node given the values of the sources and the state of
+    /// the sequential elements.
+    ///
+    /// Enabled latches pass their input through and active asynchronous
+    /// resets take effect, so the graph is evaluated again until they settle,
+    /// at most once more for each of them.
+    pub fn eval(&mut self) {
+        self.propagate();
+        for _ in 0..self.transparent.len() {
+            if !self.settle() {
+                break;
+            }
+            self.propagate();
+        }
+    }
+
+    /// Update the state of the [transparent](is_transparent) elements on the
+    /// values of the last [Simulator::propagate], returning whether any
+    /// changed.
+    fn settle(&mut self) -> bool {
+        let mut changed = false;
+        for &node in &self.transparent {
+            let input = |i: usize| self.values[self.graph.fanins(node)[i].index()];
+            let state = self.state[node.index()];
+            let next = match self.graph.node(node).kind {
+                NodeKind::Seq(SeqOp::Latch(Latch { enable, reset })) => {
+                    let next = select(active(enable, input(1)), input(0), state);
+                    match reset {
+                        Some(reset) => {
+                            select(active(reset.polarity, input(2)), fill(reset.value), next)
+                        }
+                        None => next,
+                    }
+                }
+                NodeKind::Seq(SeqOp::FlipFlop(FlipFlop {
+                    reset: Some(reset), ..
+                })) => {
+                    let r = input(self.graph.fanins(node).len() - 1);
+                    select(active(reset.polarity, r), fill(reset.value), state)
+                }
+                _ => unreachable!("node {node} should be transparent"),
+            };
+            changed |= next != state;
+            self.state[node.index()] = next;
+        }
+        changed
+    }
+
+    /// Evaluate every node once in topological order.
+    fn propagate(&mut self) {
+        let mut inputs = Vec::new();
+        for node in &self.order {
+            let index = node.index();
+            inputs.clear();
+            inputs.extend(
+                self.graph
+                    .fanins(*node)
+                    .iter()
+                    .map(|fanin| self.values[fanin.index()]),
+            );
+            let kind = &self.graph.node(*node).kind;
+            self.values[index] = match kind {
+                NodeKind::Source => continue,
+                NodeKind::Sink | NodeKind::Splitter | NodeKind::Dff => inputs[0],
+                NodeKind::Gate(op) => op.eval_packed(&inputs),
+                NodeKind::Sfq(SfqOp::Majority) => {
+                    (inputs[0] & inputs[1]) | (inputs[0] & inputs[2]) | (inputs[1] & inputs[2])
+                }
+                NodeKind::Sfq(SfqOp::Merger) => inputs[0] | inputs[1],
+                _ if holds_state(kind) => self.state[index],
+                _ => unreachable!("unexpected node {kind:?}"),
+            };
+        }
+    }
+
+    /// Clock every flip-flop and clocked SFQ primitive on the values of the
+    /// last [Simulator::eval]. Latches only change state in
+    /// [Simulator::eval].
+    pub fn step(&mut self) {
+        for node in self.graph.node_ids() {
+            if let Some(next) = self.next_state(node, &self.values) {
+                self.state[node.index()] = next;
+            }
+        }
+    }
+
+    /// Clock every flip-flop on the active edges of its clock input between
+    /// the `previous` values and those of the last [Simulator::eval],
+    /// sampling its other inputs before the edge. The other clocked elements
+    /// are clocked on every call.
+    fn clock_edges(&mut self, previous: &[u64]) {
+        for node in self.graph.node_ids() {
+            let Some(next) = self.next_state(node, previous) else {
+                continue;
+            };
+            let edges = match self.graph.node(node).kind {
+                NodeKind::Seq(SeqOp::FlipFlop(FlipFlop { clock, .. })) => {
+                    let c = self.graph.fanins(node)[1].index();
+                    let (before, after) = (previous[c], self.values[c]);
+                    match clock {
+                        Polarity::Positive => !before & after,
+                        Polarity::Negative => before & !after,
+                    }
+                }
+                _ => u64::MAX,
+            };
+            let state = &mut self.state[node.index()];
+            *state = select(edges, next, *state);
+        }
+    }
+
+    /// The state of the clocked element `node` after a clock edge, given the
+    /// `values` of the nodes before it.
+    fn next_state(&self, node: Node, values: &[u64]) -> Option<u64> {
+        let input = |i: usize| values[self.graph.fanins(node)[i].index()];
+        let state = self.state[node.index()];
+        let next = match self.graph.node(node).kind {
+            NodeKind::Seq(SeqOp::Ff) => input(0),
+            NodeKind::Seq(SeqOp::FlipFlop(FlipFlop { enable, reset, .. })) => {
+                let mut next = input(0);
+                let mut enabled = u64::MAX;
+                let mut i = 2;
+                if let Some(polarity) = enable {
+                    enabled = active(polarity, input(i));
+                    next = select(enabled, next, state);
+                    i += 1;
+                }
+                if let Some(reset) = reset {
+                    let mut r = active(reset.polarity, input(i));
+                    if reset.gated {
+                        r &= enabled;
+                    }
+                    next = select(r, fill(reset.value), next);
+                }
+                next
+            }
+            NodeKind::Sfq(SfqOp::Ndro) => (state | input(0)) & !input(1),
+            NodeKind::Sfq(SfqOp::T1) => state ^ input(0),
+            _ => return None,
+        };
+        Some(next)
+    }
+
+    /// Simulate a cycle with the sources driven by `inputs`, returning the
+    /// values of the sinks.
+    ///
+    /// # Panics
+    ///
+    /// Panics if there is not a word of `inputs` for every source.
+    pub fn cycle(&mut self, inputs: &[u64]) -> Vec<u64> {
+        assert_eq!(inputs.len(), self.sources.len(), "one word per source");
+        for (source, word) in self.sources.iter().zip(inputs) {
+            self.values[source.index()] = *word;
+        }
+        self.eval();
+        let outputs = self.sinks.iter().map(|sink| self.value(*sink)).collect();
+        self.step();
+        outputs
+    }
+
+    /// Simulate every sample of `stimulus`, driving every input port with the
+    /// signal of the same name. The result holds the input and output ports
+    /// of the graph at the times of `stimulus`.
+    ///
+    /// Unlike [Simulator::cycle], flip-flops are only clocked on the active
+    /// edges of their clock input between samples, on the values of their
+    /// other inputs in the previous sample. [SeqOp::Ff] nodes and clocked SFQ
+    /// primitives, whose clock is implicit, are clocked at every sample but
+    /// the first.
+    ///
+    /// Returns [Error::MissingSignal] if an input port has no signal of its
+    /// width, or [vcd::Error::TooWide] if a port is wider than a sample value.
+    pub fn simulate(&mut self, stimulus: &Waveform) -> Result<Waveform> {
+        let (inputs, outputs) = port_signals(self.graph);
+        if let Some((signal, _)) = inputs
+            .iter()
+            .chain(&outputs)
+            .find(|(signal, _)| signal.width > u64::BITS as usize)
+        {
+            return Err(Error::Vcd(vcd::Error::TooWide {
+                name: signal.name.clone(),
+                width: signal.width,
+            }));
+        }
+        let indices = inputs
+            .iter()
+            .map(|(signal, _)| {
+                stimulus
+                    .signals
+                    .iter()
+                    .position(|other| other == signal)
+                    .ok_or_else(|| Error::MissingSignal(signal.name.clone()))
+            })
+            .collect::<Result<Vec<_>>>()?;
+        let signals = inputs.iter().chain(&outputs);
+        let mut waveform = Waveform {
+            signals: signals.clone().map(|(signal, _)| signal.clone()).collect(),
+            samples: Vec::new(),
+        };
+        self.reset();
+        let mut previous: Option<Vec<u64>> = None;
+        for sample in &stimulus.samples {
+            for ((_, nodes), index) in inputs.iter().zip(&indices) {
+                for (bit, node) in nodes.iter().enumerate() {
+                    self.set(*node, fill(sample.values[*index] >> bit & 1 == 1));
+                }
+            }
+            self.eval();
+            if let Some(previous) = &previous {
+                self.clock_edges(previous);
+                self.eval();
+            }
+            let values = signals
+                .clone()
+                .map(|(_, nodes)| {
+                    nodes.iter().enumerate().fold(0, |value, (bit, node)| {
+                        value | (self.value(*node) & 1) << bit
+                    })
+                })
+                .collect();
+            waveform.samples.push(Sample {
+                time: sample.time,
+                values,
+            });
+            previous = Some(self.values.clone());
+        }
+        Ok(waveform)
+    }
+}
+
+/// Ports as signals, with their nodes.
+type PortSignals = Vec<(Signal, Vec<Node>)>;
+
+/// The input and output ports of `graph`.
+fn port_signals(graph: &Graph) -> (PortSignals, PortSignals) {
+    let (mut inputs, mut outputs) = (Vec::new(), Vec::new());
+    for port in graph.ports() {
+        let signal = Signal {
+            name: port.name().to_string(),
+            width: port.width(),
+        };
+        let nodes: Vec<_> = port.nodes().collect();
+        match nodes.first().map(|node| graph.node(*node).kind) {
+            Some(NodeKind::Source) => inputs.push((signal, nodes)),
+            Some(NodeKind::Sink) => outputs.push((signal, nodes)),
+            _ => {}
+        }
+    }
+    (inputs, outputs)
+}
+
+/// The bits of the input or output ports of a graph, sorted by port name,
+/// followed by the sources or sinks outside of any port in node order.
+fn signature(
+    graph: &Graph,
+    kind: fn(&NodeKind) -> bool,
+    ports: PortSignals,
+) -> Vec<(String, Node)> {
+    let mut ports = ports;
+    ports.sort_by(|(a, _), (b, _)| a.name.cmp(&b.name));
+    let mut bits = Vec::new();
+    for (signal, nodes) in ports {
+        for (bit, node) in nodes.into_iter().enumerate() {
+            let name = match signal.width {
+                1 => signal.name.clone(),
+                _ => format!("{}[{bit}]", signal.name),
+            };
+            bits.push((name, node));
+        }
+    }
+    for node in graph.node_ids() {
+        if kind(&graph.node(node).kind) && graph.port_bit(node).is_none() {
+            let name = graph
+                .node(node)
+                .name
+                .map_or_else(|| format!("${node}"), |name| name.to_string());
+            bits.push((name, node));
+        }
+    }
+    bits
+}
+
+/// The named input and output bits two graphs are matched by.
+pub(crate) struct Signature {
+    pub(crate) inputs: Vec<(String, Node)>,
+    pub(crate) outputs: Vec<(String, Node)>,
+}
+
+impl Signature {
+    pub(crate) fn new(graph: &Graph) -> Self {
+        let (inputs, outputs) = port_signals(graph);
+        Self {
+            inputs: signature(graph, |kind| matches!(kind, NodeKind::Source), inputs),
+            outputs: signature(graph, |kind| matches!(kind, NodeKind::Sink), outputs),
+        }
+    }
+
+    pub(crate) fn input_names(&self) -> Vec<String> {
+        self.inputs.iter().map(|(name, _)| name.clone()).collect()
+    }
+
+    pub(crate) fn output_names(&self) -> Vec<String> {
+        self.outputs.iter().map(|(name, _)| name.clone()).collect()
+    }
+
+    /// Describe how the bits of `other` differ, if they do.
+    pub(crate) fn mismatch(&self, other: &Self) -> Option<String> {
+        if self.input_names() != other.input_names() {
+            Some("inputs".to_string())
+        } else if self.output_names() != other.output_names() {
+            Some("outputs".to_string())
+        } else {
+            None
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct CompareConfig {
+    /// The number of times the graphs are reset and run on 64 random
+    /// patterns.
+    pub n_rounds: usize,
+    /// The number of cycles of every round.
+    pub n_cycles: usize,
+    pub seed: u64,
+}
+
+impl Default for CompareConfig {
+    fn default() -> Self {
+        Self {
+            n_rounds: 16,
+            n_cycles: 16,
+            seed: 0,
+        }
+    }
+}
+
+/// A pattern telling two graphs apart.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Mismatch {
+    /// The cycle of the round the outputs differ in.
+    pub cycle: usize,
+    /// The first output bit that differs.
+    pub output: String,
+    /// The input bits, in the order of every cycle of `stimulus`.
+    pub inputs: Vec<String>,
+    /// The inputs of every cycle of the round, up to and including `cycle`.
+    pub stimulus: Vec<Vec<bool>>,
+}
+
+/// Simulate `a` and `b` on the same random inputs, returning the first
+/// [Mismatch] of their outputs, if any.
+///
+/// Ports are matched by name and the remaining sources and sinks by node
+/// order. Returns [Error::PortMismatch] if the graphs do not have the same
+/// ports.
+pub fn compare(a: &Graph, b: &Graph, config: &CompareConfig) -> Result<Option<Mismatch>> {
+    let (a_bits, b_bits) = (Signature::new(a), Signature::new(b));
+    if let Some(mismatch) = a_bits.mismatch(&b_bits) {
+        return Err(Error::PortMismatch(mismatch));
+    }
+    let (inputs, outputs) = (a_bits.input_names(), a_bits.output_names());
+    let (a_inputs, a_outputs) = (a_bits.inputs, a_bits.outputs);
+    let (b_inputs, b_outputs) = (b_bits.inputs, b_bits.outputs);
+    let mut a = Simulator::new(a)?;
+    let mut b = Simulator::new(b)?;
+
+    let mut rng = StdRng::seed_from_u64(config.seed);
+    for _ in 0..config.n_rounds {
+        a.reset();
+        b.reset();
+        let mut stimulus: Vec<Vec<u64>> = Vec::new();
+        for cycle in 0..config.n_cycles {
+            let words: Vec<u64> = inputs.iter().map(|_| rng.gen()).collect();
+            for (i, word) in words.iter().enumerate() {
+                a.set(a_inputs[i].1, *word);
+                b.set(b_inputs[i].1, *word);
+            }
+            stimulus.push(words);
+            a.eval();
+            b.eval();
+            let difference = a_outputs
+                .iter()
+                .zip(&b_outputs)
+                .enumerate()
+                .map(|(i, ((_, x), (_, y)))| (i, a.value(*x) ^ b.value(*y)))
+                .filter(|(_, difference)| *difference != 0)
+                .min_by_key(|(_, difference)| difference.trailing_zeros());
+            if let Some((output, difference)) = difference {
+                let lane = difference.trailing_zeros();
+                return Ok(Some(Mismatch {
+                    cycle,
+                    output: outputs[output].clone(),
+                    inputs,
+                    stimulus: stimulus
+                        .iter()
+                        .map(|words| words.iter().map(|word| word >> lane & 1 == 1).collect())
+                        .collect(),
+                }));
+            }
+            a.step();
+            b.step();
+        }
+    }
+    Ok(None)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::interchange::yosys;
+    use crate::ir::graph::{Edge, NodeData};
+    use crate::ir::ops::Reset;
+    use crate::ir::sfq::balance::{balance, BalanceMode};
+    use crate::ir::sfq::splitter::{insert_splitters, SplitterConfig};
+    use crate::ir::testing;
+
+    fn load(json: &str, top: &str) -> Graph {
+        let design: yosys::Design = json.parse().unwrap();
+        Graph::try_from(design.modules[top].clone()).unwrap()
+    }
+
+    fn add4() -> Graph {
+        load(
+            include_str!("../../../../../examples/alu/add4_simplemap.json"),
+            "add4",
+        )
+    }
+
+    #[test]
+    fn test_packed() {
+        let graph = add4();
+        let mut simulator = Simulator::new(&graph).unwrap();
+        let n_sources = simulator.sources().len();
+        assert_eq!(n_sources, 9);
+        // NOTE: Pattern `p` of all 512 is in lane `p % 64` of chunk `p / 64`.
+        for chunk in 0..8 {
+            let inputs: Vec<u64> = (0..n_sources)
+                .map(|i| {
+                    (0..64).fold(0, |word, lane| {
+                        word | ((chunk * 64 + lane) >> i & 1) << lane
+                    })
+                })
+                .collect();
+            let outputs = simulator.cycle(&inputs);
+            for lane in 0..64 {
+                let pattern: Vec<_> = testing::bits(chunk * 64 + lane, n_sources).collect();
+                let expected = testing::eval(&graph, &pattern);
+                let found: Vec<_> = outputs.iter().map(|word| word >> lane & 1 == 1).collect();
+                assert_eq!(found, expected);
+            }
+        }
+    }
+
+    #[test]
+    fn test_sequential() {
+        let graph = load(
+            include_str!("../../../../../examples/sr/sr_simplemap.json"),
+            "sr",
+        );
+        let mut simulator = Simulator::new(&graph).unwrap();
+        let find = |name: &str| graph.find(name).unwrap();
+        let shift_out: Vec<_> = (0..4)
+            .map(|bit| find(&format!("shift_out[{bit}]")))
+            .collect();
+        let mut cycle = |reset: bool, op: bool, shift_in: bool| {
+            simulator.set(find("reset"), fill(reset));
+            simulator.set(find("op"), fill(op));
+            simulator.set(find("shift_in"), fill(shift_in));
+            simulator.eval();
+            simulator.step();
+            simulator.eval();
+            let bits: Vec<_> = shift_out
+                .iter()
+                .map(|node| simulator.value(*node) & 1 == 1)
+                .collect();
+            testing::value(&bits)
+        };
+        // NOTE: The reset is active-low and op 0 shifts left.
+        assert_eq!(cycle(true, false, true), 0b0001);
+        assert_eq!(cycle(true, false, true), 0b0011);
+        assert_eq!(cycle(true, false, false), 0b0110);
+        assert_eq!(cycle(true, true, true), 0b1011);
+        assert_eq!(cycle(true, true, false), 0b0101);
+        assert_eq!(cycle(false, true, true), 0b0000);
+    }
+
+    #[test]
+    fn test_transparent() {
+        let mut graph = Graph::default();
+        let [d, e, r] = [0; 3].map(|_| graph.add_node(NodeData::new_source()));
+        // NOTE: Two latches in a row, then a flip-flop reset asynchronously.
+        let latch = SeqOp::Latch(Latch {
+            enable: Polarity::Positive,
+            reset: None,
+        });
+        let latch0 = graph.add_node(NodeData::new_seq(latch));
+        let latch1 = graph.add_node(NodeData::new_seq(latch));
+        let ff = graph.add_node(NodeData::new_seq(SeqOp::FlipFlop(FlipFlop {
+            clock: Polarity::Positive,
+            enable: None,
+            reset: Some(Reset {
+                polarity: Polarity::Positive,
+                value: true,
+                asynchronous: true,
+                gated: false,
+            }),
+        })));
+        for (source, sink) in [
+            (d, latch0),
+            (e, latch0),
+            (latch0, latch1),
+            (e, latch1),
+            (latch1, ff),
+            (e, ff),
+            (r, ff),
+        ] {
+            graph.add_edge(Edge { source, sink });
+        }
+        let mut simulator = Simulator::new(&graph).unwrap();
+        let mut eval = |values: [bool; 3]| {
+            for (node, value) in [d, e, r].into_iter().zip(values) {
+                simulator.set(node, fill(value));
+            }
+            simulator.eval();
+            [latch1, ff].map(|node| simulator.value(node) & 1 == 1)
+        };
+        assert_eq!(eval([true, true, false]), [true, false]);
+        assert_eq!(eval([false, false, false]), [true, false]);
+        assert_eq!(eval([false, false, true]), [true, true]);
+        assert_eq!(eval([false, true, false]), [false, true]);
+    }
+
+    #[test]
+    fn test_reset_priority() {
+        let mut graph = Graph::default();
+        let [d, c, e, r] = [0; 4].map(|_| graph.add_node(NodeData::new_source()));
+        let flip_flop = |gated| {
+            SeqOp::FlipFlop(FlipFlop {
+                clock: Polarity::Positive,
+                enable: Some(Polarity::Positive),
+                reset: Some(Reset {
+                    polarity: Polarity::Positive,
+                    value: false,
+                    asynchronous: false,
+                    gated,
+                }),
+            })
+        };
+        // NOTE: `$_SDFFE_PP0P_`, `$_SDFFCE_PP0P_` and `$_DLATCH_PP0_`.
+        let sdffe = graph.add_node(NodeData::new_seq(flip_flop(false)));
+        let sdffce = graph.add_node(NodeData::new_seq(flip_flop(true)));
+        let latch = graph.add_node(NodeData::new_seq(SeqOp::Latch(Latch {
+            enable: Polarity::Positive,
+            reset: Some(Reset {
+                polarity: Polarity::Positive,
+                value: false,
+                asynchronous: true,
+                gated: false,
+            }),
+        })));
+        for sink in [sdffe, sdffce] {
+            graph.add_edges([d, c, e, r].map(|source| Edge { source, sink }));
+        }
+        graph.add_edges([d, e, r].map(|source| Edge {
+            source,
+            sink: latch,
+        }));
+        let mut simulator = Simulator::new(&graph).unwrap();
+        let mut cycle = |values: [bool; 3]| {
+            for (node, value) in [d, e, r].into_iter().zip(values) {
+                simulator.set(node, fill(value));
+            }
+            simulator.eval();
+            let latched = simulator.value(latch) & 1 == 1;
+            simulator.step();
+            simulator.eval();
+            let flip_flops = [sdffe, sdffce].map(|node| simulator.value(node) & 1 == 1);
+            (flip_flops, latched)
+        };
+        assert_eq!(cycle([true, true, false]), ([true, true], true));
+        // NOTE: The gated reset waits for the enable.
+        assert_eq!(cycle([true, false, true]), ([false, true], false));
+        assert_eq!(cycle([true, true, true]), ([false, false], false));
+        assert_eq!(cycle([true, false, false]), ([false, false], false));
+    }
+
+    #[test]
+    fn test_compare() {
+        let config = CompareConfig::default();
+        let graph = add4();
+        let flowmap = load(
+            include_str!("../../../../../examples/alu/add4_flowmap.json"),
+            "add4",
+        );
+        assert_eq!(compare(&graph, &flowmap, &config).unwrap(), None);
+
+        let mut sfq = graph.clone();
+        balance(&mut sfq, BalanceMode::Outputs).unwrap();
+        insert_splitters(&mut sfq, &SplitterConfig::default());
+        assert_eq!(compare(&graph, &sfq, &config).unwrap(), None);
+
+        let sr = load(
+            include_str!("../../../../../examples/sr/sr_simplemap.json"),
+            "sr",
+        );
+        let sr_flowmap = load(
+            include_str!("../../../../../examples/sr/sr_flowmap.json"),
+            "sr",
+        );
+        assert_eq!(compare(&sr, &sr_flowmap, &config).unwrap(), None);
+
+        // NOTE: Drive S[0] straight from A[0].
+        let mut mutant = graph.clone();
+        let s0 = mutant.find("S[0]").unwrap();
+        let driver = mutant.fanins(s0)[0];
+        let a0 = mutant.find("A[0]").unwrap();
+        mutant.replace_edge(
+            Edge {
+                source: driver,
+                sink: s0,
+            },
+            a0,
+        );
+        let mismatch = compare(&graph, &mutant, &config).unwrap().unwrap();
+        assert_eq!(mismatch.output, "S[0]");
+        assert_eq!(mismatch.cycle, 0);
+        let inputs = &mismatch.stimulus[0];
+        let input = |name: &str| inputs[mismatch.inputs.iter().position(|n| n == name).unwrap()];
+        // NOTE: S[0] = A[0] ^ B[0] ^ Cin differs from A[0] exactly when B[0] ^ Cin.
+        assert!(input("B[0]") ^ input("Cin"));
+
+        assert!(matches!(
+            compare(&graph, &sr, &config),
+            Err(Error::PortMismatch(_))
+        ));
+    }
+
+    #[test]
+    fn test_simulate() {
+        let graph = add4();
+        let signal = |name: &str, width| Signal {
+            name: name.to_string(),
+            width,
+        };
+        let stimulus = Waveform {
+            signals: vec![signal("A", 4), signal("B", 4), signal("Cin", 1)],
+            samples: vec![
+                Sample {
+                    time: 0,
+                    values: vec![3, 4, 0],
+                },
+                Sample {
+                    time: 10,
+                    values: vec![15, 1, 1],
+                },
+            ],
+        };
+        let mut simulator = Simulator::new(&graph).unwrap();
+        let waveform = simulator.simulate(&stimulus).unwrap();
+        let names: Vec<_> = waveform.signals.iter().map(|s| s.name.as_str()).collect();
+        assert_eq!(names, ["A", "B", "Cin", "Cout", "S"]);
+        assert_eq!(waveform.samples[0].values, [3, 4, 0, 0, 7]);
+        assert_eq!(waveform.samples[1].time, 10);
+        assert_eq!(waveform.samples[1].values, [15, 1, 1, 1, 1]);
+
+        let mut stimulus = stimulus;
+        stimulus.signals[2] = signal("C", 1);
+        assert!(matches!(
+            simulator.simulate(&stimulus),
+            Err(Error::MissingSignal(name)) if name == "Cin"
+        ));
+
+        let mut wide = Graph::default();
+        let a = wide.add_input("a", 65);
+        let y = wide.add_output("y", 65);
+        for (source, sink) in a.zip(y) {
+            wide.add_edge(Edge { source, sink });
+        }
+        let mut simulator = Simulator::new(&wide).unwrap();
+        assert!(matches!(
+            simulator.simulate(&stimulus),
+            Err(Error::Vcd(vcd::Error::TooWide { name, width: 65 })) if name == "a"
+        ));
+    }
+
+    #[test]
+    fn test_simulate_clock() {
+        let graph = load(
+            include_str!("../../../../../examples/sr/sr_simplemap.json"),
+            "sr",
+        );
+        let signals = ["clk", "op", "reset", "shift_in"].map(|name| Signal {
+            name: name.to_string(),
+            width: 1,
+        });
+        // NOTE: The reset is synchronous and active-low, and op 0 shifts left.
+        let inputs = [
+            [0, 0, 1, 1],
+            [1, 0, 1, 1],
+            [1, 0, 1, 1],
+            [0, 0, 1, 1],
+            [1, 0, 1, 0],
+            [0, 0, 0, 0],
+            [1, 0, 0, 0],
+        ];
+        let stimulus = Waveform {
+            signals: signals.to_vec(),
+            samples: inputs
+                .iter()
+                .enumerate()
+                .map(|(time, values)| Sample {
+                    time: time as u64 * 5,
+                    values: values.to_vec(),
+                })
+                .collect(),
+        };
+        let mut simulator = Simulator::new(&graph).unwrap();
+        let waveform = simulator.simulate(&stimulus).unwrap();
+        let shift_out: Vec<_> = waveform.samples.iter().map(|s| s.values[4]).collect();
+        // NOTE: Inputs changing with the clock are sampled before the edge.
+        assert_eq!(shift_out, [0, 1, 1, 1, 0b11, 0b11, 0]);
+    }
+}
@@ -0,0 +1,344 @@
+//! Value Change Dump serialization.
+//!
+//! Signals are named by their reference, without their scope, and hold at most
+//! 64 bits. `x` and `z` bits are read as 0 and real values are not supported.
+//!
+//! References:
+//! - IEEE 1364-2005, section 18
+
+use std::fs;
+use std::io::{self, Read, Write};
+use std::path::Path;
+
+use fnv::FnvHashMap;
+use thiserror::Error;
+
+#[derive(Debug, Error)]
+pub enum Error {
+    #[error(transparent)]
+    IO(#[from] io::Error),
+    #[error("{0}")]
+    Syntax(String),
+    #[error(r#"identifier "{0}" is not declared"#)]
+    UndeclaredIdentifier(String),
+    #[error(r#"signal "{name}" has {width} bits, at most 64 are supported"#)]
+    TooWide { name: String, width: usize },
+    #[error(r#""{0}" not supported"#)]
+    Unsupported(String),
+}
+
+pub type Result<T> = std::result::Result<T, Error>;
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Signal {
+    pub name: String,
+    pub width: usize,
+}
+
+/// The values of every signal at a point in time.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Sample {
+    pub time: u64,
+    /// The value of every signal, least significant bit first.
+    pub values: Vec<u64>,
+}
+
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct Waveform {
+    pub signals: Vec<Signal>,
+    pub samples: Vec<Sample>,
+}
+
+fn syntax_error<T>(message: impl Into<String>) -> Result<T> {
+    Err(Error::Syntax(message.into()))
+}
+
+/// Parse a binary vector, reading `x` and `z` as 0.
+fn parse_vector(vector: &str) -> Result<u64> {
+    vector.chars().try_fold(0u64, |value, bit| {
+        let bit = match bit.to_ascii_lowercase() {
+            '1' => 1,
+            '0' | 'x' | 'z' => 0,
+            _ => return syntax_error(format!(r#"invalid vector "{vector}""#)),
+        };
+        Ok(value << 1 | bit)
+    })
+}
+
+/// Set the signals of `id` to `value`.
+fn change(
+    ids: &FnvHashMap<&str, Vec<usize>>,
+    signals: &[Signal],
+    values: &mut [u64],
+    id: &str,
+    value: u64,
+) -> Result<()> {
+    let Some(indices) = ids.get(id) else {
+        return Err(Error::UndeclaredIdentifier(id.to_string()));
+    };
+    for index in indices {
+        let width = signals[*index].width;
+        values[*index] = if width < 64 {
+            value & ((1 << width) - 1)
+        } else {
+            value
+        };
+    }
+    Ok(())
+}
+
+pub fn parse(vcd: &str) -> Result<Waveform> {
+    let mut tokens = vcd.split_whitespace();
+    let mut waveform = Waveform::default();
+    let mut ids = FnvHashMap::<&str, Vec<usize>>::default();
+    let mut values = Vec::new();
+    let mut time = None;
+    let mut declarations = true;
+    while let Some(token) = tokens.next() {
+        if declarations {
+            match token {
+                "$var" => {
+                    let mut fields = Vec::new();
+                    for token in tokens.by_ref() {
+                        if token == "$end" {
+                            break;
+                        }
+                        fields.push(token);
+                    }
+                    let [_, width, id, name, ..] = fields[..] else {
+                        return syntax_error(format!("invalid $var {}", fields.join(" ")));
+                    };
+                    let Ok(width) = width.parse() else {
+                        return syntax_error(format!(r#"invalid width "{width}""#));
+                    };
+                    let name = name.split('[').next().unwrap_or(name).to_string();
+                    if width > 64 {
+                        return Err(Error::TooWide { name, width });
+                    }
+                    ids.entry(id).or_default().push(waveform.signals.len());
+                    waveform.signals.push(Signal { name, width });
+                }
+                "$enddefinitions" => {
+                    declarations = false;
+                    values = vec![0; waveform.signals.len()];
+                    tokens.find(|token| *token == "$end");
+                }
+                _ if token.starts_with('$') => {
+                    tokens.find(|token| *token == "$end");
+                }
+                _ => return syntax_error(format!(r#"unexpected "{token}" in declarations"#)),
+            }
+            continue;
+        }
+        match token.chars().next() {
+            Some('#') => {
+                let Ok(next) = token[1..].parse() else {
+                    return syntax_error(format!(r#"invalid time "{token}""#));
+                };
+                if let Some(time) = time.replace(next) {
+                    waveform.samples.push(Sample {
+                        time,
+                        values: values.clone(),
+                    });
+                }
+            }
+            Some('b' | 'B') => {
+                let value = parse_vector(&token[1..])?;
+                let Some(id) = tokens.next() else {
+                    return syntax_error(format!(r#"missing identifier after "{token}""#));
+                };
+                change(&ids, &waveform.signals, &mut values, id, value)?;
+            }
+            Some('0' | '1' | 'x' | 'X' | 'z' | 'Z') => {
+                let value = parse_vector(&token[..1])?;
+                change(&ids, &waveform.signals, &mut values, &token[1..], value)?;
+            }
+            Some('r' | 'R') => return Err(Error::Unsupported("real value".to_string())),
+            // NOTE: `$dumpvars` and friends only group changes.
+            Some('$') => {}
+            _ => return syntax_error(format!(r#"unexpected "{token}""#)),
+        }
+    }
+    if let Some(time) = time {
+        waveform.samples.push(Sample { time, values });
+    }
+    Ok(waveform)
+}
+
+pub fn from_reader<R>(mut reader: R) -> Result<Waveform>
+where
+    R: Read,
+{
+    let mut vcd = String::new();
+    reader.read_to_string(&mut vcd)?;
+    parse(&vcd)
+}
+
+pub fn from_file<P>(path: P) -> Result<Waveform>
+where
+    P: AsRef<Path>,
+{
+    from_reader(fs::File::open(path)?)
+}
+
+/// The identifier of signal `index`, in the printable ASCII characters.
+fn identifier(index: usize) -> String {
+    let mut id = String::new();
+    let mut index = index;
+    loop {
+        id.push(char::from(b'!' + (index % 94) as u8));
+        index /= 94;
+        if index == 0 {
+            break id;
+        }
+        index -= 1;
+    }
+}
+
+fn write_value<W>(writer: &mut W, signal: &Signal, id: &str, value: u64) -> io::Result<()>
+where
+    W: Write,
+{
+    match signal.width {
+        1 => writeln!(writer, "{}{id}", value & 1),
+        width => {
+            let bits: String = (0..width)
+                .rev()
+                .map(|bit| if value >> bit & 1 == 1 { '1' } else { '0' })
+                .collect();
+            writeln!(writer, "b{bits} {id}")
+        }
+    }
+}
+
+/// Write `waveform` in a single `scope`, only dumping the values that change
+/// between samples.
+pub fn write<W>(waveform: &Waveform, scope: &str, mut writer: W) -> io::Result<()>
+where
+    W: Write,
+{
+    let ids: Vec<_> = (0..waveform.signals.len()).map(identifier).collect();
+    writeln!(writer, "$timescale 1ns $end")?;
+    writeln!(writer, "$scope module {scope} $end")?;
+    for (signal, id) in waveform.signals.iter().zip(&ids) {
+        let range = match signal.width {
+            1 => String::new(),
+            width => format!(" [{}:0]", width - 1),
+        };
+        writeln!(
+            writer,
+            "$var wire {} {id} {}{range} $end",
+            signal.width, signal.name
+        )?;
+    }
+    writeln!(writer, "$upscope $end")?;
+    writeln!(writer, "$enddefinitions $end")?;
+    let mut previous: Option<&[u64]> = None;
+    for sample in &waveform.samples {
+        writeln!(writer, "#{}", sample.time)?;
+        if previous.is_none() {
+            writeln!(writer, "$dumpvars")?;
+        }
+        for (i, value) in sample.values.iter().enumerate() {
+            if previous.is_none_or(|previous| previous[i] != *value) {
+                write_value(&mut writer, &waveform.signals[i], &ids[i], *value)?;
+            }
+        }
+        if previous.is_none() {
+            writeln!(writer, "$end")?;
+        }
+        previous = Some(&sample.values);
+    }
+    Ok(())
+}
+
+pub fn to_string(waveform: &Waveform, scope: &str) -> String {
+    let mut vcd = Vec::new();
+    write(waveform, scope, &mut vcd).expect("writing to a Vec should not fail");
+    String::from_utf8(vcd).expect("VCD should be ASCII")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse() {
+        let vcd = "
+            $date today $end
+            $timescale 1ps $end
+            $scope module top $end
+            $var wire 1 ! clk $end
+            $var reg 4 \" count [3:0] $end
+            $scope module inner $end
+            $var wire 4 \" value $end
+            $upscope $end
+            $upscope $end
+            $enddefinitions $end
+            $dumpvars
+            0!
+            bx \"
+            $end
+            #0
+            #5
+            1!
+            b101 \"
+            #10
+            0!
+            b1z11 \"
+        ";
+        let waveform = parse(vcd).unwrap();
+        let names: Vec<_> = waveform.signals.iter().map(|s| s.name.as_str()).collect();
+        assert_eq!(names, ["clk", "count", "value"]);
+        assert_eq!(waveform.signals[1].width, 4);
+        let samples: Vec<_> = waveform
+            .samples
+            .iter()
+            .map(|sample| (sample.time, sample.values.clone()))
+            .collect();
+        assert_eq!(
+            samples,
+            [
+                (0, vec![0, 0, 0]),
+                (5, vec![1, 5, 5]),
+                (10, vec![0, 0b1011, 0b1011])
+            ]
+        );
+
+        assert!(matches!(
+            parse("$enddefinitions $end #0 1!"),
+            Err(Error::UndeclaredIdentifier(id)) if id == "!"
+        ));
+        assert!(matches!(
+            parse("$var wire 65 ! wide $end"),
+            Err(Error::TooWide { width: 65, .. })
+        ));
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let waveform = Waveform {
+            signals: vec![
+                Signal {
+                    name: "a".to_string(),
+                    width: 1,
+                },
+                Signal {
+                    name: "b".to_string(),
+                    width: 64,
+                },
+            ],
+            samples: (0..100)
+                .map(|i| Sample {
+                    time: i * 10,
+                    values: vec![i % 3 % 2, i.wrapping_mul(0x9e37_79b9_7f4a_7c15)],
+                })
+                .collect(),
+        };
+        let vcd = to_string(&waveform, "top");
+        assert!(vcd.contains("$var wire 64 \" b [63:0] $end"));
+        assert_eq!(parse(&vcd).unwrap(), waveform);
+        assert_eq!(identifier(93), "~");
+        assert_eq!(identifier(94), "!!");
+    }
+}
@@ -0,0 +1,42 @@
+//! Helpers shared by the tests of the [ir](crate::ir) passes and formats.
+
+use super::graph::{Graph, NodeKind};
+
+/// Evaluate the sinks of `graph` given the values of its sources, both in
+/// node order. Sequential nodes hold 0.
+pub(crate) fn eval(graph: &Graph, inputs: &[bool]) -> Vec<bool> {
+    let mut values = vec![false; graph.n_nodes()];
+    let mut inputs = inputs.iter();
+    let mut outputs = Vec::new();
+    for node in graph.topological_order().unwrap() {
+        let fanins: Vec<_> = graph
+            .fanins(node)
+            .iter()
+            .map(|fanin| values[fanin.index()])
+            .collect();
+        values[node.index()] = match graph.node(node).kind {
+            NodeKind::Source => *inputs.next().unwrap(),
+            NodeKind::Sink => {
+                outputs.push((node, fanins[0]));
+                fanins[0]
+            }
+            NodeKind::Gate(op) => op.eval(&fanins),
+            NodeKind::Seq(_) => false,
+            ref kind => panic!("unexpected node {kind:?}"),
+        };
+    }
+    outputs.sort();
+    outputs.into_iter().map(|(_, value)| value).collect()
+}
+
+/// The bits of `value`, least significant first.
+pub(crate) fn bits(value: u64, width: usize) -> impl Iterator<Item = bool> {
+    (0..width).map(move |i| value >> i & 1 == 1)
+}
+
+/// The value of `bits`, least significant first.
+pub(crate) fn value(bits: &[bool]) -> u64 {
+    bits.iter()
+        .rev()
+        .fold(0, |value, bit| value << 1 | u64::from(*bit))
+}
@@ -0,0 +1,130 @@
+//! Graphviz DOT export.
+
+use std::io::{self, Write};
+
+use super::{color, label, levels, VisualizeConfig, HIGHLIGHT};
+use crate::ir::graph::Graph;
+
+/// Quote `text` as a DOT string.
+fn quote(text: &str) -> String {
+    let mut quoted = String::with_capacity(text.len() + 2);
+    quoted.push('"');
+    for c in text.chars() {
+        match c {
+            '"' | '\\' => {
+                quoted.push('\\');
+                quoted.push(c);
+            }
+            '\n' => quoted.push_str("\\n"),
+            _ => quoted.push(c),
+        }
+    }
+    quoted.push('"');
+    quoted
+}
+
+/// Write `graph` to `writer` as the DOT digraph `name`, from left to right.
+pub fn write<W>(
+    graph: &Graph,
+    name: &str,
+    config: &VisualizeConfig,
+    mut writer: W,
+) -> io::Result<()>
+where
+    W: Write,
+{
+    writeln!(writer, "digraph {} {{", quote(name))?;
+    writeln!(writer, "  rankdir=LR;")?;
+    writeln!(writer, "  node [shape=box, style=filled];")?;
+    for node in graph.node_ids() {
+        let kind = &graph.node(node).kind;
+        write!(
+            writer,
+            "  n{} [label={}, fillcolor={}",
+            node,
+            quote(&label(graph, node)),
+            quote(color(kind))
+        )?;
+        if config.highlight.contains(&node) {
+            write!(writer, ", color={}, penwidth=3", quote(HIGHLIGHT))?;
+        }
+        writeln!(writer, "];")?;
+    }
+    if let Some(levels) = levels(graph) {
+        let depth = levels.iter().copied().max().unwrap_or_default();
+        let mut ranks = vec![Vec::new(); depth + 1];
+        for node in graph.node_ids() {
+            ranks[levels[node.index()]].push(node);
+        }
+        for rank in ranks.iter().filter(|rank| !rank.is_empty()) {
+            write!(writer, "  {{ rank=same;")?;
+            for node in rank {
+                write!(writer, " n{node};")?;
+            }
+            writeln!(writer, " }}")?;
+        }
+    }
+    for (source, sink) in graph.edges() {
+        write!(writer, "  n{source} -> n{sink}")?;
+        if config.highlight.contains(&source) && config.highlight.contains(&sink) {
+            write!(writer, " [color={}, penwidth=3]", quote(HIGHLIGHT))?;
+        }
+        writeln!(writer, ";")?;
+    }
+    writeln!(writer, "}}")
+}
+
+/// The DOT digraph `name` of `graph`.
+pub fn to_string(graph: &Graph, name: &str, config: &VisualizeConfig) -> String {
+    let mut bytes = Vec::new();
+    write(graph, name, config, &mut bytes).expect("writing to a Vec should not fail");
+    String::from_utf8(bytes).expect("DOT should be UTF-8")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ir::graph::{Edge, NodeData};
+    use crate::ir::ops::AnyOp;
+
+    #[test]
+    fn test_write() {
+        let mut graph = Graph::default();
+        let a: Vec<_> = graph.add_input("a", 2).collect();
+        let and = graph.add_node(NodeData::new_op(AnyOp::and()).with_name("x\"y"));
+        let y = graph.add_output("y", 1).next().unwrap();
+        graph.add_edges([
+            Edge {
+                source: a[0],
+                sink: and,
+            },
+            Edge {
+                source: a[1],
+                sink: and,
+            },
+            Edge {
+                source: and,
+                sink: y,
+            },
+        ]);
+        let config = VisualizeConfig::default().with_highlight([a[1], and]);
+        let dot = to_string(&graph, "top", &config);
+        assert_eq!(
+            dot,
+            r##"digraph "top" {
+  rankdir=LR;
+  node [shape=box, style=filled];
+  n0 [label="a[0]\nsource", fillcolor="#a6d96a"];
+  n1 [label="a[1]\nsource", fillcolor="#a6d96a", color="#d7191c", penwidth=3];
+  n2 [label="x\"y\nAnd", fillcolor="#abd9e9", color="#d7191c", penwidth=3];
+  n3 [label="y\nsink", fillcolor="#fdae61"];
+  { rank=same; n0; n1; }
+  { rank=same; n2; n3; }
+  n0 -> n2;
+  n1 -> n2 [color="#d7191c", penwidth=3];
+  n2 -> n3;
+}
+"##
+        );
+    }
+}
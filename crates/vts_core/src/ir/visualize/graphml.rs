@@ -0,0 +1,141 @@
+//! GraphML export.
+//!
+//! Every node has the `label`, `kind` and `color` attributes, its `level` if
+//! the graph is ranked, and a `highlight` attribute, leaving the layout to the
+//! viewer.
+
+use std::io::{self, Write};
+
+use super::{color, describe, label, levels, VisualizeConfig};
+use crate::ir::graph::Graph;
+
+/// The attributes of nodes, as `(id, type, default)`.
+const KEYS: [(&str, &str, Option<&str>); 5] = [
+    ("label", "string", None),
+    ("kind", "string", None),
+    ("color", "string", None),
+    ("level", "int", None),
+    ("highlight", "boolean", Some("false")),
+];
+
+/// Escape `text` for XML character data and attribute values.
+fn escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&apos;"),
+            _ => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+/// Write `graph` to `writer` as the GraphML graph `name`.
+pub fn write<W>(
+    graph: &Graph,
+    name: &str,
+    config: &VisualizeConfig,
+    mut writer: W,
+) -> io::Result<()>
+where
+    W: Write,
+{
+    writeln!(writer, r#"<?xml version="1.0" encoding="UTF-8"?>"#)?;
+    writeln!(
+        writer,
+        r#"<graphml xmlns="http://graphml.graphdrawing.org/xmlns">"#
+    )?;
+    for (id, ty, default) in KEYS {
+        let key = format!(r#"<key id="{id}" for="node" attr.name="{id}" attr.type="{ty}""#);
+        match default {
+            Some(default) => writeln!(writer, "  {key}><default>{default}</default></key>")?,
+            None => writeln!(writer, "  {key}/>")?,
+        }
+    }
+    writeln!(
+        writer,
+        r#"  <graph id="{}" edgedefault="directed">"#,
+        escape(name)
+    )?;
+    let levels = levels(graph);
+    for node in graph.node_ids() {
+        let kind = &graph.node(node).kind;
+        writeln!(writer, r#"    <node id="n{node}">"#)?;
+        let mut data = vec![
+            ("label", escape(&label(graph, node))),
+            ("kind", escape(&describe(kind))),
+            ("color", color(kind).to_string()),
+        ];
+        if let Some(levels) = &levels {
+            data.push(("level", levels[node.index()].to_string()));
+        }
+        if config.highlight.contains(&node) {
+            data.push(("highlight", "true".to_string()));
+        }
+        for (key, value) in data {
+            writeln!(writer, r#"      <data key="{key}">{value}</data>"#)?;
+        }
+        writeln!(writer, "    </node>")?;
+    }
+    for (index, (source, sink)) in graph.edges().enumerate() {
+        writeln!(
+            writer,
+            r#"    <edge id="e{index}" source="n{source}" target="n{sink}"/>"#
+        )?;
+    }
+    writeln!(writer, "  </graph>")?;
+    writeln!(writer, "</graphml>")
+}
+
+/// The GraphML graph `name` of `graph`.
+pub fn to_string(graph: &Graph, name: &str, config: &VisualizeConfig) -> String {
+    let mut bytes = Vec::new();
+    write(graph, name, config, &mut bytes).expect("writing to a Vec should not fail");
+    String::from_utf8(bytes).expect("GraphML should be UTF-8")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ir::graph::{Edge, NodeData};
+    use crate::ir::ops::AnyOp;
+
+    #[test]
+    fn test_write() {
+        let mut graph = Graph::default();
+        let a = graph.add_input("a", 1).next().unwrap();
+        let not = graph.add_node(NodeData::new_op(AnyOp::not()).with_name("<n>"));
+        let y = graph.add_output("y", 1).next().unwrap();
+        graph.add_edges([
+            Edge {
+                source: a,
+                sink: not,
+            },
+            Edge {
+                source: not,
+                sink: y,
+            },
+        ]);
+        let config = VisualizeConfig::default().with_highlight([not]);
+        let graphml = to_string(&graph, "top", &config);
+        assert!(graphml.contains(
+            r#"    <node id="n1">
+      <data key="label"><n>
+Not</data>
+      <data key="kind">Not</data>
+      <data key="color">#abd9e9</data>
+      <data key="level">1</data>
+      <data key="highlight">true</data>
+    </node>"#
+                .replace("<n>", "&lt;n&gt;")
+                .as_str()
+        ));
+        assert!(graphml.contains(r#"<edge id="e1" source="n1" target="n2"/>"#));
+        assert_eq!(graphml.matches("<node ").count(), 3);
+        assert!(graphml.ends_with("  </graph>\n</graphml>\n"));
+    }
+}
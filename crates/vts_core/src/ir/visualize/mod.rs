@@ -0,0 +1,147 @@
+//! Visualisation of IR graphs as Graphviz [DOT](dot) and [GraphML](graphml).
+//!
+//! Nodes are coloured by their [NodeKind], ranked by their
+//! [logic level](Graph::levels) and labelled with their name, or with the port
+//! bit they are, when they have one. Graphs with a combinational loop are not
+//! ranked.
+
+pub mod dot;
+pub mod graphml;
+
+use fnv::FnvHashSet;
+
+use super::graph::{self, Graph, Node, NodeKind};
+use super::ops::{AnyOp, ConstOp, SeqOp};
+
+#[derive(Clone, Debug, Default)]
+pub struct VisualizeConfig {
+    /// The nodes to highlight, such as a [critical path](critical_path).
+    pub highlight: FnvHashSet<Node>,
+}
+
+impl VisualizeConfig {
+    pub fn with_highlight<Ns>(mut self, nodes: Ns) -> Self
+    where
+        Ns: IntoIterator<Item = Node>,
+    {
+        self.highlight.extend(nodes);
+        self
+    }
+}
+
+/// The colour of the nodes of `kind`.
+pub fn color(kind: &NodeKind) -> &'static str {
+    match kind {
+        NodeKind::Source => "#a6d96a",
+        NodeKind::Sink => "#fdae61",
+        NodeKind::Gate(AnyOp::Const(_)) => "#d9d9d9",
+        NodeKind::Gate(_) => "#abd9e9",
+        NodeKind::Splitter => "#fee08b",
+        NodeKind::Dff => "#f1b6da",
+        NodeKind::Seq(_) => "#c2a5cf",
+        NodeKind::Sfq(_) => "#80cdc1",
+    }
+}
+
+/// The colour of the outline of highlighted nodes.
+pub const HIGHLIGHT: &str = "#d7191c";
+
+/// A short description of `kind`.
+pub fn describe(kind: &NodeKind) -> String {
+    match kind {
+        NodeKind::Source => "source".to_string(),
+        NodeKind::Sink => "sink".to_string(),
+        NodeKind::Gate(AnyOp::Unary(op)) => format!("{op:?}"),
+        NodeKind::Gate(AnyOp::Binary(op)) => format!("{op:?}"),
+        NodeKind::Gate(AnyOp::Complex(op)) => format!("{op:?}"),
+        NodeKind::Gate(AnyOp::Lut(lut)) => {
+            format!("LUT{} {:#x}", lut.n_inputs(), lut.table())
+        }
+        NodeKind::Gate(AnyOp::Const(ConstOp::Unit)) => "1".to_string(),
+        NodeKind::Gate(AnyOp::Const(ConstOp::Zero)) => "0".to_string(),
+        NodeKind::Gate(AnyOp::Mux) => "Mux".to_string(),
+        NodeKind::Gate(AnyOp::NMux) => "NMux".to_string(),
+        NodeKind::Splitter => "Splitter".to_string(),
+        NodeKind::Dff => "Dff".to_string(),
+        NodeKind::Seq(SeqOp::FlipFlop(_)) => "FlipFlop".to_string(),
+        NodeKind::Seq(op) => format!("{op:?}"),
+        NodeKind::Sfq(op) => format!("{op:?}"),
+    }
+}
+
+/// The name of `node`, or the port bit it is, if any.
+pub fn name(graph: &Graph, node: Node) -> Option<String> {
+    if let Some((port, bit)) = graph.port_bit(node) {
+        return Some(if port.width() == 1 {
+            port.name().to_string()
+        } else {
+            format!("{}[{bit}]", port.name())
+        });
+    }
+    graph.node(node).name.map(|name| name.to_string())
+}
+
+/// The label of `node`, its name above a description of its kind.
+pub fn label(graph: &Graph, node: Node) -> String {
+    let kind = describe(&graph.node(node).kind);
+    match name(graph, node) {
+        Some(name) => format!("{name}\n{kind}"),
+        None => kind,
+    }
+}
+
+/// The logic levels of the nodes of `graph`, unless it has a combinational
+/// loop.
+fn levels(graph: &Graph) -> Option<Vec<usize>> {
+    graph.levels().ok()
+}
+
+/// A path of the highest logic level, from a [NodeKind::Source] or
+/// [NodeKind::Seq] node, or a node without inputs, to a node of the
+/// [depth](Graph::depth) of `graph`.
+pub fn critical_path(graph: &Graph) -> Result<Vec<Node>, graph::Error> {
+    let levels = graph.levels()?;
+    let Some(mut node) = graph.node_ids().max_by_key(|node| levels[node.index()]) else {
+        return Ok(Vec::new());
+    };
+    let mut path = vec![node];
+    while !matches!(graph.node(node).kind, NodeKind::Source | NodeKind::Seq(_)) {
+        let Some(fanin) = graph
+            .fanins(node)
+            .iter()
+            .rev()
+            .max_by_key(|fanin| levels[fanin.index()])
+        else {
+            break;
+        };
+        node = *fanin;
+        path.push(node);
+    }
+    path.reverse();
+    Ok(path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::interchange::yosys;
+
+    #[test]
+    fn test_critical_path() {
+        let design: yosys::Design = include_str!("../../../../../examples/alu/add4_simplemap.json")
+            .parse()
+            .unwrap();
+        let graph = Graph::try_from(design.modules["add4"].clone()).unwrap();
+        let path = critical_path(&graph).unwrap();
+        let levels = graph.levels().unwrap();
+        assert!(matches!(graph.node(path[0]).kind, NodeKind::Source));
+        assert_eq!(levels[path.last().unwrap().index()], graph.depth().unwrap());
+        for pair in path.windows(2) {
+            assert!(graph.fanins(pair[1]).contains(&pair[0]));
+        }
+        assert!(path
+            .iter()
+            .any(|node| label(&graph, *node).starts_with("B[0]\n")
+                || label(&graph, *node).starts_with("A[0]\n")));
+    }
+}
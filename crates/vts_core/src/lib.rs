@@ -1,7 +1,10 @@
 pub mod arch1;
 pub mod design;
 pub mod device;
+pub mod flow;
 pub mod interchange;
+pub mod ir;
+pub mod pack;
 pub mod place;
 pub mod route;
 pub mod synth;
@@ -9,4 +12,23 @@ pub mod synth;
 use place::Placer;
 use route::Router;
 
-pub trait Flow<PS: place::State, P: Placer<PS>, RS: route::State, R: Router<RS>> {}
+/// A flow driving a design through a sequence of [Stage](flow::Stage)'s.
+pub trait Flow<PS: place::State, P: Placer<PS>, RS: route::State, R: Router<RS>> {
+    type Err;
+
+    /// The next stage to be run.
+    fn stage(&self) -> flow::Stage;
+
+    /// Run the next stage.
+    ///
+    /// Returns the stage following it.
+    fn run_stage(&mut self) -> Result<flow::Stage, Self::Err>;
+
+    /// Run every remaining stage.
+    fn run(&mut self) -> Result<(), Self::Err> {
+        while self.stage() != flow::Stage::Done {
+            self.run_stage()?;
+        }
+        Ok(())
+    }
+}
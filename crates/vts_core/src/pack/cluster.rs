@@ -0,0 +1,825 @@
+//! Clustering of LUT and latch cells into instances of an [arch1](crate::arch1)
+//! tile component.
+//!
+//! The tile is unrolled into a graph with a node for every port pin of every
+//! instance and an edge for every pin-to-pin connection, expanded like the
+//! [generator](crate::device::generator) does. Every instance of a
+//! [ComponentClass] is a slot hosting a single cell, with its cell pins bound
+//! to the slot pins of the matching [PortClass] in order.
+//!
+//! Cells are packed greedily: a cluster is seeded with the first unpacked cell
+//! and filled with the cells sharing the most nets with it, then with a few
+//! unrelated cells, as long as every net of the cluster can still be routed
+//! through the graph without two nets sharing a node. Nets of high fanout,
+//! such as clocks, are ignored when counting shared nets, so every step tries
+//! a number of cells bounded by the size of the tile. A net is routed from its
+//! driver slot pin, or from a tile input pin if it is driven outside the
+//! cluster, to every sink slot pin and, if it has sinks outside the cluster,
+//! to a tile output pin.
+//!
+//! Cells without a class are packed into their own block, like the
+//! [CellPacker](super::CellPacker).
+
+use std::cmp::Reverse;
+use std::collections::{BTreeSet, VecDeque};
+
+use fnv::{FnvHashMap, FnvHashSet};
+use serde::{Deserialize, Serialize};
+use ustr::Ustr;
+
+use super::{Error, Packer, Result};
+use crate::arch1::{
+    connection::ComponentRefs, module::ComponentRefId, port::PortPins, Component, ComponentClass,
+    Module, PortClass, PortKind,
+};
+use crate::design::database::{self as design, Cell, CellId, NetId};
+use crate::device::database::Database;
+use crate::device::generator::{self, connection_pairs, find_tile, indexed};
+use crate::device::resources::PinDirection;
+use crate::interchange::yosys::constids::{internal_cells, std_cells};
+use crate::place::netlist::{BlockId, BlockPin, Netlist};
+
+/// Nets with this many sinks, such as clocks and resets, do not attract cells
+/// to a cluster.
+const MAX_ATTRACTION_FANOUT: usize = 32;
+
+/// A cluster of cells packed into a single instance of the tile component.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct Cluster {
+    block: BlockId,
+    cells: Vec<(CellId, String)>,
+}
+
+impl Cluster {
+    /// The [Block](crate::place::netlist::Block) of the cluster.
+    pub fn block(&self) -> BlockId {
+        self.block
+    }
+
+    /// The cells of the cluster and the paths of their slots, relative to the
+    /// tile, e.g. `lut[1]`.
+    pub fn cells(&self) -> impl Iterator<Item = (CellId, &str)> + '_ {
+        self.cells.iter().map(|(cell, slot)| (*cell, slot.as_str()))
+    }
+}
+
+/// Packs LUT and latch cells into clusters of the tile component of an
+/// architecture.
+///
+/// The packer owns its architecture, so it can be saved in a
+/// [Checkpoint](crate::flow::Checkpoint).
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct ClusterPacker {
+    arch: Module,
+    tile: Option<String>,
+    clusters: Vec<Cluster>,
+}
+
+impl ClusterPacker {
+    /// Create a packer for the only component of `arch` not referenced by any
+    /// other component.
+    pub fn new(arch: Module) -> Self {
+        Self {
+            arch,
+            tile: None,
+            clusters: Vec::new(),
+        }
+    }
+
+    /// Pack into the component named `tile` instead.
+    pub fn with_tile(mut self, tile: &str) -> Self {
+        self.tile = Some(tile.to_string());
+        self
+    }
+
+    /// The clusters of the last packed design.
+    pub fn clusters(&self) -> &[Cluster] {
+        &self.clusters
+    }
+}
+
+impl Packer for ClusterPacker {
+    fn pack(&mut self, device: &Database, design: &mut design::Database) -> Result<Netlist> {
+        design.bind_buckets(device)?;
+        let tile = find_tile(&self.arch, self.tile.as_deref())?;
+        let tile_bucket = device
+            .find_bucket(tile.name())
+            .ok_or_else(|| Error::UndefinedBucket(tile.name().to_string()))?;
+        let graph = ClusterGraph::new(&self.arch, tile)?;
+
+        let cells: Vec<CellId> = design
+            .cells()
+            .filter(|(_, cell)| cell.class().is_some())
+            .map(|(id, _)| id)
+            .collect();
+        let order: FnvHashMap<CellId, usize> = cells
+            .iter()
+            .enumerate()
+            .map(|(i, cell)| (*cell, i))
+            .collect();
+        let mut unpacked: BTreeSet<usize> = (0..cells.len()).collect();
+        let mut clusters = Vec::new();
+        while let Some(seed) = unpacked.pop_first() {
+            let seed = cells[seed];
+            let (mut members, mut routing) = graph
+                .try_add(design, &[], seed)
+                .ok_or_else(|| Error::UnpackableCell(design.cell(seed).name().to_string()))?;
+            // NOTE: The attraction of the unpacked cells to the cluster is
+            // updated as cells are added, so every step only visits the nets
+            // of the added cell.
+            let mut attraction = Attraction::default();
+            attraction.add(design, &order, &unpacked, seed);
+            while members.len() < graph.slots.len() {
+                // NOTE: Only the unpacked cells sharing a net with the cluster are
+                // candidates, the most attracted first.
+                let mut candidates: Vec<_> = attraction
+                    .gains
+                    .iter()
+                    .map(|(&i, &attraction)| (i, attraction))
+                    .collect();
+                candidates.sort_by_key(|&(i, attraction)| (Reverse(attraction), i));
+                // NOTE: Fill the cluster with a bounded number of unrelated cells,
+                // so every step tries a bounded number of cells.
+                let unrelated = unpacked.iter().copied().take(graph.slots.len());
+                let Some((i, (next_members, next_routing))) = candidates
+                    .into_iter()
+                    .map(|(i, _)| i)
+                    .chain(unrelated)
+                    .find_map(|i| {
+                        graph
+                            .try_add(design, &members, cells[i])
+                            .map(|packed| (i, packed))
+                    })
+                else {
+                    break;
+                };
+                unpacked.remove(&i);
+                attraction.gains.remove(&i);
+                attraction.add(design, &order, &unpacked, cells[i]);
+                members = next_members;
+                routing = next_routing;
+            }
+            clusters.push((members, routing));
+        }
+
+        let mut netlist = Netlist::new();
+        let mut blocks = FnvHashMap::default();
+        let mut cell_clusters = FnvHashMap::default();
+        self.clusters.clear();
+        for (k, (members, _)) in clusters.iter().enumerate() {
+            let (seed, _) = members[0];
+            let block = netlist.add_block(design.cell(seed).name(), tile_bucket);
+            let mut cells = Vec::new();
+            for &(cell, slot) in members {
+                netlist.add_cell(block, cell);
+                blocks.insert(cell, block);
+                cell_clusters.insert(cell, k);
+                cells.push((cell, graph.slots[slot].path.clone()));
+            }
+            self.clusters.push(Cluster { block, cells });
+        }
+        for (id, cell) in design.cells() {
+            if cell.class().is_none() {
+                let bucket = cell.bucket().expect("cell should be bound to a bucket");
+                let block = netlist.add_block(cell.name(), bucket);
+                netlist.add_cell(block, id);
+                blocks.insert(id, block);
+            }
+        }
+
+        for (id, net) in design.nets() {
+            let Some(driver) = net.driver() else {
+                continue;
+            };
+            let driver_cluster = cell_clusters.get(&driver.cell).copied();
+            let driver = match driver_cluster {
+                Some(k) => match clusters[k].1[&id].output {
+                    Some(pin) => BlockPin::new(blocks[&driver.cell], &pin),
+                    // NOTE: The net does not leave its cluster.
+                    None => continue,
+                },
+                None => BlockPin::new(blocks[&driver.cell], &driver.pin),
+            };
+            let mut sink_clusters = FnvHashSet::default();
+            let mut sinks = Vec::new();
+            for sink in net.sinks() {
+                match cell_clusters.get(&sink.cell).copied() {
+                    Some(k) if Some(k) == driver_cluster => {}
+                    Some(k) => {
+                        if sink_clusters.insert(k) {
+                            let pin = clusters[k].1[&id]
+                                .input
+                                .expect("net should enter its sink clusters");
+                            sinks.push(BlockPin::new(blocks[&sink.cell], &pin));
+                        }
+                    }
+                    None => sinks.push(BlockPin::new(blocks[&sink.cell], &sink.pin)),
+                }
+            }
+            if !sinks.is_empty() {
+                let net = netlist.add_net(net.name(), driver, sinks);
+                netlist.bind_net(net, id);
+            }
+        }
+        Ok(netlist)
+    }
+}
+
+/// The attraction of unpacked cells to a cluster.
+#[derive(Default)]
+struct Attraction {
+    /// The nets of the cluster counted so far.
+    nets: FnvHashSet<NetId>,
+    /// The number of pins every unpacked cell, by index, has on the counted
+    /// nets.
+    gains: FnvHashMap<usize, usize>,
+}
+
+impl Attraction {
+    /// Count the nets of `cell`, added to the cluster, that have fewer than
+    /// [MAX_ATTRACTION_FANOUT] sinks.
+    fn add(
+        &mut self,
+        design: &design::Database,
+        order: &FnvHashMap<CellId, usize>,
+        unpacked: &BTreeSet<usize>,
+        cell: CellId,
+    ) {
+        for id in design.cell(cell).pins().iter().filter_map(|pin| pin.net()) {
+            let net = design.net(id);
+            if net.sinks().len() >= MAX_ATTRACTION_FANOUT || !self.nets.insert(id) {
+                continue;
+            }
+            for pin in net.driver().iter().chain(net.sinks()) {
+                if let Some(&i) = order.get(&pin.cell) {
+                    if unpacked.contains(&i) {
+                        *self.gains.entry(i).or_default() += 1;
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// An instance of a [ComponentClass] within the tile.
+struct Slot {
+    path: String,
+    class: ComponentClass,
+    /// The nodes of the classified port pins, in port order.
+    pins: Vec<(PortClass, usize)>,
+}
+
+/// The tile pins used by a net routed through a cluster.
+#[derive(Clone, Copy, Debug, Default)]
+struct ClusterPins {
+    input: Option<Ustr>,
+    output: Option<Ustr>,
+}
+
+type Members = Vec<(CellId, usize)>;
+
+type Routing = FnvHashMap<NetId, ClusterPins>;
+
+/// The unrolled interconnect of the tile component.
+#[derive(Default)]
+struct ClusterGraph {
+    edges: Vec<Vec<usize>>,
+    slots: Vec<Slot>,
+    inputs: Vec<usize>,
+    outputs: Vec<usize>,
+    tile_pins: FnvHashMap<usize, Ustr>,
+}
+
+/// The nodes of the port pins of a single component instance.
+type InstancePins = FnvHashMap<(String, u32), usize>;
+
+fn child_path(path: &str, name: &str) -> String {
+    if path.is_empty() {
+        name.to_string()
+    } else {
+        format!("{path}.{name}")
+    }
+}
+
+/// The name of the clock pin of cells of type `ty`, if any.
+fn clock_pin(ty: &str) -> Option<&'static str> {
+    match ty {
+        internal_cells::FF | std_cells::FF => None,
+        internal_cells::DLATCH => Some("EN"),
+        std_cells::DLATCH_P | std_cells::DLATCH_N => Some("E"),
+        _ if ty.starts_with("$_") => Some("C"),
+        _ => Some("CLK"),
+    }
+}
+
+/// The [PortClass] of the slot pin hosting `pin` of `cell`.
+///
+/// Only the clock, data and output pins of latches have a slot pin, cells
+/// with an enable, set or reset pin cannot be hosted.
+fn pin_class(cell: &Cell, pin: &design::CellPin) -> Option<PortClass> {
+    Some(match (cell.class()?, pin.direction()) {
+        (ComponentClass::Lut, PinDirection::Input) => PortClass::LutIn,
+        (ComponentClass::Lut, PinDirection::Output) => PortClass::LutOut,
+        (ComponentClass::Latch, PinDirection::Input)
+            if Some(pin.name()) == clock_pin(cell.ty()) =>
+        {
+            PortClass::Clock
+        }
+        (ComponentClass::Latch, PinDirection::Input)
+            if pin.name() == "D" || pin.name().starts_with("D[") =>
+        {
+            PortClass::LatchIn
+        }
+        (ComponentClass::Latch, PinDirection::Input) => return None,
+        (ComponentClass::Latch, PinDirection::Output) => PortClass::LatchOut,
+    })
+}
+
+impl ClusterGraph {
+    fn new(arch: &Module, tile: Component<'_>) -> Result<Self> {
+        let mut graph = Self::default();
+        let pins = graph.add_instance(arch, tile, "")?;
+        for port in tile.ports() {
+            for i in 0..port.n_pins() {
+                let node = pins[&(port.name().to_string(), i)];
+                match port.kind() {
+                    PortKind::Input => graph.inputs.push(node),
+                    PortKind::Output => graph.outputs.push(node),
+                }
+                let name = indexed(port.name(), i, port.n_pins());
+                graph.tile_pins.insert(node, Ustr::from(&name));
+            }
+        }
+        Ok(graph)
+    }
+
+    /// Unroll an instance of `component` named `path`, returning the nodes of its ports.
+    fn add_instance(
+        &mut self,
+        arch: &Module,
+        component: Component<'_>,
+        path: &str,
+    ) -> Result<InstancePins> {
+        let mut pins = InstancePins::default();
+        for port in component.ports() {
+            for i in 0..port.n_pins() {
+                self.edges.push(Vec::new());
+                pins.insert((port.name().to_string(), i), self.edges.len() - 1);
+            }
+        }
+        if let Some(class) = component.class() {
+            let mut slot_pins = Vec::new();
+            for port in component.ports() {
+                if let Some(class) = port.class() {
+                    for i in 0..port.n_pins() {
+                        slot_pins.push((class, pins[&(port.name().to_string(), i)]));
+                    }
+                }
+            }
+            self.slots.push(Slot {
+                path: path.to_string(),
+                class,
+                pins: slot_pins,
+            });
+        }
+        let mut children = FnvHashMap::<ComponentRefId, Vec<InstancePins>>::default();
+        for reference in component.references() {
+            let n_instances = reference.n_instances();
+            let instances = (0..n_instances)
+                .map(|k| {
+                    let name = indexed(reference.alias_or_name(), k, n_instances);
+                    self.add_instance(arch, reference.component(), &child_path(path, &name))
+                })
+                .collect::<Result<Vec<_>>>()?;
+            children.insert(reference.unbind(), instances);
+        }
+        for connection in component.connections() {
+            let sources = connection_nodes(
+                arch,
+                &pins,
+                &children,
+                connection.source_component(),
+                connection.source_pins(),
+            );
+            let sinks = connection_nodes(
+                arch,
+                &pins,
+                &children,
+                connection.sink_component(),
+                connection.sink_pins(),
+            );
+            let pairs = connection_pairs(connection.kind(), sources.len(), sinks.len())
+                .ok_or_else(|| generator::Error::WidthMismatch {
+                    connection: connection.source_name_or_default().to_string(),
+                    sources: sources.len(),
+                    sinks: sinks.len(),
+                })?;
+            for (j, k) in pairs {
+                self.edges[sources[j]].push(sinks[k]);
+            }
+        }
+        Ok(pins)
+    }
+
+    /// The nets and directions of the pins of `cell`, bound to the nodes of
+    /// `slot`. Returns [None] if the slot cannot host the cell.
+    fn bind(&self, cell: &Cell, slot: usize) -> Option<Vec<(NetId, usize, PinDirection)>> {
+        let slot = &self.slots[slot];
+        if cell.class() != Some(slot.class) {
+            return None;
+        }
+        let mut bound = Vec::new();
+        for (i, pin) in cell.pins().iter().enumerate() {
+            let class = pin_class(cell, pin)?;
+            // NOTE: The `n`-th cell pin of a class is bound to the `n`-th slot pin of that class.
+            let n = cell.pins()[..i]
+                .iter()
+                .filter(|other| pin_class(cell, other) == Some(class))
+                .count();
+            let (_, node) = slot
+                .pins
+                .iter()
+                .filter(|(other, _)| *other == class)
+                .nth(n)?;
+            if let Some(net) = pin.net() {
+                bound.push((net, *node, pin.direction()));
+            }
+        }
+        Some(bound)
+    }
+
+    /// Add `cell` to the first free slot of a cluster of `members` keeping the
+    /// cluster routable.
+    fn try_add(
+        &self,
+        design: &design::Database,
+        members: &[(CellId, usize)],
+        cell: CellId,
+    ) -> Option<(Members, Routing)> {
+        (0..self.slots.len())
+            .filter(|slot| members.iter().all(|(_, other)| other != slot))
+            .find_map(|slot| {
+                let mut members = members.to_vec();
+                members.push((cell, slot));
+                let routing = self.route(design, &members)?;
+                Some((members, routing))
+            })
+    }
+
+    /// Route every net of a cluster of `members`, returning the tile pins used
+    /// by each net.
+    fn route(&self, design: &design::Database, members: &[(CellId, usize)]) -> Option<Routing> {
+        let mut owners = vec![None; self.edges.len()];
+        let mut nets = Vec::new();
+        let mut drivers = FnvHashMap::default();
+        let mut sinks = FnvHashMap::<NetId, Vec<usize>>::default();
+        for &(cell, slot) in members {
+            for (net, node, direction) in self.bind(design.cell(cell), slot)? {
+                owners[node] = Some(net);
+                if !nets.contains(&net) {
+                    nets.push(net);
+                }
+                match direction {
+                    PinDirection::Input => sinks.entry(net).or_default().push(node),
+                    PinDirection::Output => {
+                        drivers.insert(net, node);
+                    }
+                }
+            }
+        }
+        let cells: FnvHashSet<_> = members.iter().map(|(cell, _)| *cell).collect();
+        let mut routing = Routing::default();
+        for net in nets {
+            let mut pins = ClusterPins::default();
+            let mut tree: Vec<usize> = drivers.get(&net).copied().into_iter().collect();
+            for &sink in sinks.get(&net).into_iter().flatten() {
+                let path = if tree.is_empty() {
+                    let path = self.find_path(&owners, net, &self.inputs, |node| node == sink)?;
+                    pins.input = Some(self.tile_pins[&path[0]]);
+                    path
+                } else {
+                    self.find_path(&owners, net, &tree, |node| node == sink)?
+                };
+                for node in path {
+                    owners[node] = Some(net);
+                    tree.push(node);
+                }
+            }
+            let has_external_sinks = design
+                .net(net)
+                .sinks()
+                .iter()
+                .any(|pin| !cells.contains(&pin.cell));
+            if drivers.contains_key(&net) && has_external_sinks {
+                let path =
+                    self.find_path(&owners, net, &tree, |node| self.outputs.contains(&node))?;
+                let output = *path.last().expect("path should not be empty");
+                pins.output = Some(self.tile_pins[&output]);
+                for node in path {
+                    owners[node] = Some(net);
+                }
+            }
+            routing.insert(net, pins);
+        }
+        Some(routing)
+    }
+
+    /// Find the shortest path from any of `sources` to a node satisfying
+    /// `is_target`, using only nodes free or owned by `net`.
+    fn find_path(
+        &self,
+        owners: &[Option<NetId>],
+        net: NetId,
+        sources: &[usize],
+        is_target: impl Fn(usize) -> bool,
+    ) -> Option<Vec<usize>> {
+        let usable = |node: usize| owners[node].is_none_or(|owner| owner == net);
+        let mut parents = vec![None; self.edges.len()];
+        let mut visited = vec![false; self.edges.len()];
+        let mut queue = VecDeque::new();
+        for &source in sources {
+            if usable(source) && !visited[source] {
+                visited[source] = true;
+                queue.push_back(source);
+            }
+        }
+        while let Some(node) = queue.pop_front() {
+            if is_target(node) {
+                let mut path = vec![node];
+                while let Some(parent) = parents[*path.last().unwrap()] {
+                    path.push(parent);
+                }
+                path.reverse();
+                return Some(path);
+            }
+            for &next in &self.edges[node] {
+                if !visited[next] && usable(next) {
+                    visited[next] = true;
+                    parents[next] = Some(node);
+                    queue.push_back(next);
+                }
+            }
+        }
+        None
+    }
+}
+
+fn connection_nodes(
+    arch: &Module,
+    pins: &InstancePins,
+    children: &FnvHashMap<ComponentRefId, Vec<InstancePins>>,
+    references: Option<&ComponentRefs>,
+    port_pins: &PortPins,
+) -> Vec<usize> {
+    let port = port_pins.port(arch);
+    let range = port_pins.range(arch);
+    match references {
+        Some(references) => references
+            .range(arch)
+            .flat_map(|k| {
+                let instance = &children[&references.id()][k as usize];
+                range
+                    .clone()
+                    .map(move |i| instance[&(port.name().to_string(), i)])
+            })
+            .collect(),
+        None => range.map(|i| pins[&(port.name().to_string(), i)]).collect(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::iter;
+
+    use super::*;
+    use crate::arch1::yaml;
+    use crate::design::database::{INPAD, INPAD_PIN, OUTPAD, OUTPAD_PIN};
+    use crate::device::generator::{generate, DeviceConfig};
+    use crate::flow::{Checkpoint, FlowDriver, Stage};
+    use crate::place::anneal::{AnnealConfig, AnnealState, AnnealingPlacer};
+    use crate::route::pathfinder::{PathFinderConfig, PathFinderRouter, PathFinderState};
+    use crate::Flow;
+
+    const ARCH: &str = r#"
+name: test
+components:
+  lut:
+    class: LUT
+    ports:
+      in: { kind: INPUT, n_pins: 2, class: LUT_IN }
+      out: { kind: OUTPUT, class: LUT_OUT }
+  clb:
+    ports:
+      I: { kind: INPUT, n_pins: 4 }
+      O: { kind: OUTPUT, n_pins: 2 }
+    references:
+      - { component: lut, n_instances: 2 }
+    connections:
+      - kind: COMPLETE
+        source: { port: I }
+        sink: { reference: lut, port: in }
+      - kind: DIRECT
+        source: { reference: lut, port: out }
+        sink: { port: O }
+"#;
+
+    /// [ARCH] with the second LUT input fed back from the cluster outputs.
+    fn feedback_arch() -> String {
+        ARCH.replace(
+            "sink: { reference: lut, port: in }",
+            r#"sink: { reference: lut, port: in, port_end: 1 }
+      - kind: COMPLETE
+        source: { port: O }
+        sink: { reference: lut, port: in, port_start: 1 }"#,
+        )
+    }
+
+    /// A design of LUTs with their inputs driven by `inputs`, where `0` is a
+    /// primary input and `k` the output of the `k - 1`-th LUT. The last LUT
+    /// drives a primary output.
+    fn design(inputs: &[&[usize]]) -> design::Database {
+        let mut design = design::Database::new("top");
+        let mut nets: Vec<_> = (0..=inputs.len())
+            .map(|k| design.add_net(&format!("n{k}")).unwrap())
+            .collect();
+        let mut pads = Vec::new();
+        for (k, lut_inputs) in inputs.iter().enumerate() {
+            let cell = design
+                .add_cell(&format!("lut{k}"), internal_cells::LUT)
+                .unwrap();
+            for (i, &input) in lut_inputs.iter().enumerate() {
+                let pin = format!("A[{i}]");
+                design.add_pin(cell, &pin, PinDirection::Input);
+                if input == 0 {
+                    let net = design.add_net(&format!("in{k}_{i}")).unwrap();
+                    pads.push(net);
+                    design.connect(cell, &pin, net).unwrap();
+                } else {
+                    design.connect(cell, &pin, nets[input]).unwrap();
+                }
+            }
+            design.add_pin(cell, "Y", PinDirection::Output);
+            design.connect(cell, "Y", nets[k + 1]).unwrap();
+        }
+        for net in pads {
+            let name = design.net(net).name().to_string();
+            let pad = design.add_cell(&name, INPAD).unwrap();
+            design.add_pin(pad, INPAD_PIN, PinDirection::Output);
+            design.connect(pad, INPAD_PIN, net).unwrap();
+        }
+        let pad = design.add_cell("out", OUTPAD).unwrap();
+        design.add_pin(pad, OUTPAD_PIN, PinDirection::Input);
+        design
+            .connect(pad, OUTPAD_PIN, nets.pop().unwrap())
+            .unwrap();
+        design
+    }
+
+    fn pack(arch: &str, design: &mut design::Database) -> (Netlist, Vec<Cluster>) {
+        let arch = yaml::from_str(arch).unwrap();
+        let device = generate(&arch, &DeviceConfig::new(2, 2)).unwrap();
+        let mut packer = ClusterPacker::new(arch);
+        let netlist = packer.pack(&device, design).unwrap();
+        (netlist, packer.clusters().to_vec())
+    }
+
+    #[test]
+    fn test_pack_connectivity() {
+        // NOTE: Without feedback, a LUT output cannot reach a LUT input of
+        // the same cluster.
+        let mut chain = design(&[&[0], &[0, 1]]);
+        let (netlist, clusters) = pack(ARCH, &mut chain);
+        assert_eq!(clusters.len(), 2);
+        assert_eq!(netlist.n_blocks(), 2 + 3);
+        let lut0 = netlist.find_block("lut0").unwrap();
+        let (_, n1) = netlist.nets().find(|(_, net)| net.name() == "n1").unwrap();
+        assert_eq!(n1.driver(), BlockPin::new(lut0, "O[0]"));
+
+        let mut chain = design(&[&[0], &[0, 1]]);
+        let (netlist, clusters) = pack(&feedback_arch(), &mut chain);
+        assert_eq!(clusters.len(), 1);
+        let slots: Vec<_> = clusters[0].cells().map(|(_, slot)| slot).collect();
+        assert_eq!(slots, ["lut[0]", "lut[1]"]);
+        // NOTE: The net between the LUTs is routed within the cluster.
+        assert!(netlist.nets().all(|(_, net)| net.name() != "n1"));
+        assert_eq!(netlist.n_nets(), 3);
+    }
+
+    #[test]
+    fn test_attraction() {
+        for n_sinks in [1, MAX_ATTRACTION_FANOUT] {
+            // NOTE: `lut0` drives every other LUT.
+            let mut inputs = vec![&[0][..]];
+            inputs.extend(iter::repeat_n(&[1][..], n_sinks));
+            let design = design(&inputs);
+            let cells: Vec<_> = (0..=n_sinks)
+                .map(|k| design.find_cell(&format!("lut{k}")).unwrap())
+                .collect();
+            let order = cells
+                .iter()
+                .enumerate()
+                .map(|(i, cell)| (*cell, i))
+                .collect();
+            let unpacked = (1..=n_sinks).collect();
+            let mut attraction = Attraction::default();
+            attraction.add(&design, &order, &unpacked, cells[0]);
+            let expected: FnvHashMap<_, _> = if n_sinks < MAX_ATTRACTION_FANOUT {
+                (1..=n_sinks).map(|i| (i, 1)).collect()
+            } else {
+                FnvHashMap::default()
+            };
+            assert_eq!(attraction.gains, expected, "{n_sinks} sinks");
+        }
+    }
+
+    #[test]
+    fn test_pack_capacity() {
+        let mut luts = design(&[&[0, 0], &[0, 0], &[0, 0]]);
+        let (_, clusters) = pack(ARCH, &mut luts);
+        // NOTE: 2 LUTs per cluster, each using 2 of the 4 tile inputs.
+        let sizes: Vec<_> = clusters
+            .iter()
+            .map(|cluster| cluster.cells().count())
+            .collect();
+        assert_eq!(sizes, [2, 1]);
+
+        let arch = ARCH.replace("n_pins: 4", "n_pins: 3");
+        let mut luts = design(&[&[0, 0], &[0, 0]]);
+        let (_, clusters) = pack(&arch, &mut luts);
+        assert_eq!(clusters.len(), 2);
+    }
+
+    #[test]
+    fn test_flow() {
+        let arch = yaml::from_str(&feedback_arch()).unwrap();
+        let device = generate(&arch, &DeviceConfig::new(2, 2)).unwrap();
+        let mut flow = FlowDriver::new(
+            &device,
+            design(&[&[0], &[0, 1], &[2]]),
+            AnnealingPlacer::new(AnnealConfig::default()).unwrap(),
+            PathFinderRouter::new(PathFinderConfig::default()),
+        )
+        .with_packer(ClusterPacker::new(arch));
+        flow.run().unwrap();
+        let netlist = flow.netlist().unwrap();
+        assert_eq!(netlist.n_blocks(), 2 + 3);
+        let placement = flow.place_state().unwrap().placement();
+        assert!(placement.is_legal(&device, netlist));
+        assert!(flow.route_state().unwrap().is_done());
+
+        // NOTE: The resumed flow repacks with the saved cluster packer.
+        let json = serde_json::to_string(&flow.checkpoint()).unwrap();
+        let checkpoint: Checkpoint<AnnealState, PathFinderState, ClusterPacker> =
+            serde_json::from_str(&json).unwrap();
+        let mut resumed = FlowDriver::resume(
+            &device,
+            AnnealingPlacer::new(AnnealConfig::default()).unwrap(),
+            PathFinderRouter::new(PathFinderConfig::default()),
+            checkpoint,
+        );
+        resumed.rewind(Stage::Pack);
+        resumed.run().unwrap();
+        assert_eq!(resumed.netlist().unwrap().n_blocks(), 2 + 3);
+        assert_eq!(resumed.packer().clusters().len(), 2);
+    }
+
+    #[test]
+    fn test_unpackable() {
+        let arch = yaml::from_str(ARCH).unwrap();
+        let device = generate(&arch, &DeviceConfig::new(1, 1)).unwrap();
+        let mut luts = design(&[&[0, 0, 0]]);
+        assert!(matches!(
+            ClusterPacker::new(arch).pack(&device, &mut luts),
+            Err(Error::UnpackableCell(_))
+        ));
+    }
+
+    #[test]
+    fn test_pin_class() {
+        let mut design = design::Database::new("top");
+        for (ty, pins) in [
+            (std_cells::DFF_P, ["C", "D", "Q"]),
+            (internal_cells::DFF, ["CLK", "D", "Q"]),
+            (std_cells::DFF_N, ["E", "D", "Q"]),
+        ] {
+            let cell = design.add_cell(ty, ty).unwrap();
+            for pin in pins {
+                let direction = match pin {
+                    "Q" => PinDirection::Output,
+                    _ => PinDirection::Input,
+                };
+                design.add_pin(cell, pin, direction);
+            }
+            let cell = design.cell(cell);
+            let classes: Vec<_> = cell.pins().iter().map(|pin| pin_class(cell, pin)).collect();
+            let clock = match ty {
+                std_cells::DFF_N => None,
+                _ => Some(PortClass::Clock),
+            };
+            assert_eq!(
+                classes,
+                [clock, Some(PortClass::LatchIn), Some(PortClass::LatchOut)]
+            );
+        }
+    }
+}
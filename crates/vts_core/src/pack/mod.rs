@@ -0,0 +1,70 @@
+//! Packing of a design [Database](design::Database) into the
+//! [Block](crate::place::netlist::Block)'s of a placement [Netlist].
+
+pub mod cluster;
+
+use fnv::FnvHashMap;
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+use crate::design::database::{self as design, PinRef};
+use crate::device::database::Database;
+use crate::device::generator;
+use crate::place::netlist::{BlockPin, Netlist};
+
+#[derive(Debug, Error)]
+pub enum Error {
+    #[error(transparent)]
+    Design(#[from] design::Error),
+    #[error(transparent)]
+    Arch(#[from] generator::Error),
+    #[error(r#"device has no bucket "{0}""#)]
+    UndefinedBucket(String),
+    #[error(r#"cell "{0}" does not fit in an empty cluster"#)]
+    UnpackableCell(String),
+}
+
+pub type Result<T> = std::result::Result<T, Error>;
+
+/// A packing algorithm implementation.
+pub trait Packer {
+    /// Pack the cells of `design` into the blocks of a [Netlist] placeable on
+    /// `device`.
+    fn pack(&mut self, device: &Database, design: &mut design::Database) -> Result<Netlist>;
+}
+
+/// Packs every cell into its own block.
+#[derive(Clone, Copy, Debug, Default, Deserialize, Serialize)]
+pub struct CellPacker;
+
+impl Packer for CellPacker {
+    /// Bind every cell of `design` to a [BelBucket](crate::device::resources::BelBucket)
+    /// of `device` and pack it into its own block.
+    ///
+    /// Every net with a driver and at least one sink becomes a net of the packed
+    /// netlist, with pins named after the cell pins.
+    fn pack(&mut self, device: &Database, design: &mut design::Database) -> Result<Netlist> {
+        design.bind_buckets(device)?;
+        let mut netlist = Netlist::new();
+        let mut blocks = FnvHashMap::default();
+        for (id, cell) in design.cells() {
+            let bucket = cell.bucket().expect("cell should be bound to a bucket");
+            let block = netlist.add_block(cell.name(), bucket);
+            netlist.add_cell(block, id);
+            blocks.insert(id, block);
+        }
+        let block_pin = |pin: PinRef| BlockPin::new(blocks[&pin.cell], &pin.pin);
+        for (id, net) in design.nets() {
+            let Some(driver) = net.driver() else {
+                continue;
+            };
+            if net.sinks().is_empty() {
+                continue;
+            }
+            let sinks = net.sinks().iter().map(|pin| block_pin(*pin));
+            let net = netlist.add_net(net.name(), block_pin(driver), sinks);
+            netlist.bind_net(net, id);
+        }
+        Ok(netlist)
+    }
+}
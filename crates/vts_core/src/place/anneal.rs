@@ -0,0 +1,613 @@
+//! Simulated-annealing placement.
+//!
+//! References:
+//! - V. Betz and J. Rose, "VPR: A new packing, placement and routing tool for FPGA research",
+//!   FPL 1997.
+//! - https://github.com/verilog-to-routing/vtr-verilog-to-routing/blob/8d4a9b5/vpr/src/place/place.cpp
+
+use fnv::FnvHashSet;
+use rand::{rngs::StdRng, Rng, SeedableRng};
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+use super::{
+    netlist::{BlockId, Net, NetId, Netlist},
+    placement::Placement,
+    Placer, State,
+};
+use crate::device::database::{BelBucketId, BelId, Database};
+
+#[derive(Debug, Error)]
+pub enum Error {
+    #[error(r#"no free bel left for block "{0}""#)]
+    NoFreeBel(String),
+    #[error(r#"block "{0}" is fixed to a bel of another bucket"#)]
+    IllegalFixedBel(String),
+    #[error(r#"block "{block}" is fixed to a bel already used by "{other}""#)]
+    FixedBelOccupied { block: String, other: String },
+    #[error("placement is incomplete or illegal")]
+    IllegalPlacement,
+    #[error("geometric cooling factor should be in (0, 1), got {0}")]
+    InvalidCoolingFactor(f64),
+    #[error("linear cooling step should be positive, got {0}")]
+    InvalidCoolingStep(f64),
+}
+
+pub type Result<T> = std::result::Result<T, Error>;
+
+/// How the temperature is lowered after every annealing step.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum Schedule {
+    /// VPR's adaptive schedule, which cools slowest while a moderate fraction of
+    /// moves is accepted.
+    #[default]
+    Adaptive,
+    /// `T' = alpha * T`, with `0 < alpha < 1`.
+    Geometric { alpha: f64 },
+    /// `T' = T - delta`, with `delta > 0`.
+    Linear { delta: f64 },
+}
+
+impl Schedule {
+    fn next_temperature(&self, temperature: f64, acceptance_rate: f64) -> f64 {
+        match *self {
+            Self::Adaptive => {
+                let alpha = if acceptance_rate > 0.96 {
+                    0.5
+                } else if acceptance_rate > 0.8 {
+                    0.9
+                } else if acceptance_rate > 0.15 {
+                    0.95
+                } else {
+                    0.8
+                };
+                alpha * temperature
+            }
+            Self::Geometric { alpha } => alpha * temperature,
+            Self::Linear { delta } => (temperature - delta).max(0.0),
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct AnnealConfig {
+    pub schedule: Schedule,
+    /// The starting temperature. Estimated from the cost spread of random moves
+    /// when `None`.
+    pub initial_temperature: Option<f64>,
+    /// Scales the number of moves per temperature, `inner_num * n_blocks^(4/3)`.
+    pub inner_num: f64,
+    /// Annealing stops once `T < exit_epsilon * cost / n_nets`.
+    pub exit_epsilon: f64,
+    /// Stop after this many temperature steps, regardless of the temperature.
+    pub max_steps: Option<usize>,
+    pub seed: u64,
+}
+
+impl Default for AnnealConfig {
+    fn default() -> Self {
+        Self {
+            schedule: Schedule::default(),
+            initial_temperature: None,
+            inner_num: 1.0,
+            exit_epsilon: 0.005,
+            max_steps: None,
+            seed: 1,
+        }
+    }
+}
+
+/// Statistics of a single annealing step.
+#[derive(Clone, Copy, Debug, Deserialize, PartialEq, Serialize)]
+pub struct StepStats {
+    pub temperature: f64,
+    pub moves: usize,
+    pub accepted: usize,
+    pub cost: f64,
+    pub range_limit: f64,
+}
+
+impl StepStats {
+    pub fn acceptance_rate(&self) -> f64 {
+        if self.moves == 0 {
+            0.0
+        } else {
+            self.accepted as f64 / self.moves as f64
+        }
+    }
+}
+
+// VPR's correction for the bounding box underestimating the wirelength of
+// nets with many terminals.
+const CROSSING_COUNT: [f64; 50] = [
+    1.0, 1.0, 1.0, 1.0828, 1.1536, 1.2206, 1.2823, 1.3385, 1.3991, 1.4493, 1.4974, 1.5455, 1.5937,
+    1.6418, 1.6899, 1.7304, 1.7709, 1.8114, 1.8519, 1.8924, 1.9288, 1.9652, 2.0015, 2.0379, 2.0743,
+    2.1061, 2.1379, 2.1698, 2.2016, 2.2334, 2.2646, 2.2958, 2.3271, 2.3583, 2.3895, 2.4187, 2.4479,
+    2.4772, 2.5064, 2.5356, 2.5610, 2.5864, 2.6117, 2.6371, 2.6625, 2.6887, 2.7148, 2.7410, 2.7671,
+    2.7933,
+];
+
+fn crossing_count(n_terminals: usize) -> f64 {
+    if n_terminals <= CROSSING_COUNT.len() {
+        CROSSING_COUNT[n_terminals.saturating_sub(1)]
+    } else {
+        2.7933 + 0.02616 * (n_terminals - CROSSING_COUNT.len()) as f64
+    }
+}
+
+fn net_cost(device: &Database, placement: &Placement, net: &Net) -> f64 {
+    let mut pins = net.pins().map(|pin| {
+        let bel = placement.bel(pin.block).expect("block should be placed");
+        device.bel(bel).location()
+    });
+    let first = pins.next().expect("net should have a driver");
+    let (mut x_min, mut x_max, mut y_min, mut y_max) = (first.x, first.x, first.y, first.y);
+    for location in pins {
+        x_min = x_min.min(location.x);
+        x_max = x_max.max(location.x);
+        y_min = y_min.min(location.y);
+        y_max = y_max.max(location.y);
+    }
+    let half_perimeter = (x_max - x_min + 1) + (y_max - y_min + 1);
+    crossing_count(net.sinks().len() + 1) * half_perimeter as f64
+}
+
+/// The `state` of an [AnnealingPlacer].
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct AnnealState {
+    netlist: Netlist,
+    placement: Placement,
+    net_costs: Vec<f64>,
+    cost: f64,
+    temperature: Option<f64>,
+    range_limit: f64,
+    max_range_limit: f64,
+    history: Vec<StepStats>,
+    done: bool,
+}
+
+impl State for AnnealState {}
+
+impl AnnealState {
+    /// Create the initial state with every block placed on the first free
+    /// [Bel](crate::device::resources::Bel) of its bucket.
+    pub fn new(device: &Database, netlist: Netlist) -> Result<Self> {
+        let mut placement = Placement::new(netlist.n_blocks());
+        for (id, block) in netlist.blocks() {
+            if let Some(bel) = block.fixed() {
+                if device.bel(bel).bucket() != block.bucket() {
+                    return Err(Error::IllegalFixedBel(block.name().to_string()));
+                }
+                if let Some(other) = placement.place(id, bel) {
+                    return Err(Error::FixedBelOccupied {
+                        block: block.name().to_string(),
+                        other: netlist.block(other).name().to_string(),
+                    });
+                }
+            }
+        }
+        let mut next_free = fnv::FnvHashMap::<BelBucketId, usize>::default();
+        for (id, block) in netlist.blocks() {
+            if block.fixed().is_some() {
+                continue;
+            }
+            let bels = device.bucket(block.bucket()).bels();
+            let next = next_free.entry(block.bucket()).or_default();
+            while *next < bels.len() && placement.block(bels[*next]).is_some() {
+                *next += 1;
+            }
+            let bel = bels
+                .get(*next)
+                .ok_or_else(|| Error::NoFreeBel(block.name().to_string()))?;
+            placement.place(id, *bel);
+        }
+        Ok(Self::with_placement(device, netlist, placement))
+    }
+
+    /// Create the initial state from an existing complete placement.
+    pub fn with_placement(device: &Database, netlist: Netlist, placement: Placement) -> Self {
+        let net_costs: Vec<_> = netlist
+            .nets()
+            .map(|(_, net)| net_cost(device, &placement, net))
+            .collect();
+        let cost = net_costs.iter().sum();
+        let max_range_limit = device.bels().fold(1, |extent, (_, bel)| {
+            let location = bel.location();
+            extent.max(location.x + 1).max(location.y + 1)
+        }) as f64;
+        Self {
+            netlist,
+            placement,
+            net_costs,
+            cost,
+            temperature: None,
+            range_limit: max_range_limit,
+            max_range_limit,
+            history: Vec::new(),
+            done: false,
+        }
+    }
+
+    pub fn netlist(&self) -> &Netlist {
+        &self.netlist
+    }
+
+    pub fn placement(&self) -> &Placement {
+        &self.placement
+    }
+
+    pub fn into_placement(self) -> Placement {
+        self.placement
+    }
+
+    /// The total half-perimeter wirelength cost of the placement.
+    pub fn cost(&self) -> f64 {
+        self.cost
+    }
+
+    /// The temperature of the next step, `None` before the first step.
+    pub fn temperature(&self) -> Option<f64> {
+        self.temperature
+    }
+
+    /// Statistics of every step taken so far.
+    pub fn history(&self) -> &[StepStats] {
+        &self.history
+    }
+
+    pub fn is_done(&self) -> bool {
+        self.done
+    }
+
+    fn movable_blocks(&self, device: &Database) -> Vec<BlockId> {
+        self.netlist
+            .blocks()
+            .filter(|(_, block)| {
+                block.fixed().is_none() && device.bucket(block.bucket()).bels().len() > 1
+            })
+            .map(|(id, _)| id)
+            .collect()
+    }
+
+    fn should_exit(&self, config: &AnnealConfig, temperature: f64) -> bool {
+        let n_nets = self.netlist.n_nets().max(1) as f64;
+        temperature <= 0.0
+            || temperature < config.exit_epsilon * self.cost / n_nets
+            || config
+                .max_steps
+                .is_some_and(|max_steps| self.history.len() + 1 >= max_steps)
+    }
+}
+
+enum Move {
+    Swap(BlockId, BlockId),
+    Relocate(BlockId, BelId),
+}
+
+/// A VPR-style simulated-annealing placer minimising half-perimeter wirelength.
+///
+/// Every call to [Placer::step] runs the moves of a single temperature and
+/// then cools according to the configured [Schedule]. Once the exit criterion
+/// is met, a final greedy step is taken and the state is marked as done.
+pub struct AnnealingPlacer {
+    config: AnnealConfig,
+    rng: StdRng,
+    initial_temperature: f64,
+}
+
+impl AnnealingPlacer {
+    /// A placer annealing with `config`, or an error if its [Schedule] does
+    /// not cool down.
+    pub fn new(config: AnnealConfig) -> Result<Self> {
+        match config.schedule {
+            Schedule::Adaptive => {}
+            Schedule::Geometric { alpha } => {
+                if alpha.is_nan() || alpha <= 0.0 || alpha >= 1.0 {
+                    return Err(Error::InvalidCoolingFactor(alpha));
+                }
+            }
+            Schedule::Linear { delta } => {
+                if delta.is_nan() || delta <= 0.0 {
+                    return Err(Error::InvalidCoolingStep(delta));
+                }
+            }
+        }
+        let rng = StdRng::seed_from_u64(config.seed);
+        Ok(Self {
+            config,
+            rng,
+            initial_temperature: 0.0,
+        })
+    }
+
+    pub fn config(&self) -> &AnnealConfig {
+        &self.config
+    }
+
+    fn propose(
+        &mut self,
+        device: &Database,
+        state: &AnnealState,
+        movable: &[BlockId],
+    ) -> Option<Move> {
+        const MAX_TRIES: usize = 8;
+
+        let block = movable[self.rng.gen_range(0..movable.len())];
+        let from = state.placement.bel(block).expect("block should be placed");
+        let origin = device.bel(from).location();
+        let bels = device.bucket(state.netlist.block(block).bucket()).bels();
+        let range_limit = state.range_limit.round().max(1.0) as u32;
+        let to = (0..MAX_TRIES).find_map(|_| {
+            let bel = bels[self.rng.gen_range(0..bels.len())];
+            let location = device.bel(bel).location();
+            (bel != from
+                && location.x.abs_diff(origin.x) <= range_limit
+                && location.y.abs_diff(origin.y) <= range_limit)
+                .then_some(bel)
+        })?;
+        match state.placement.block(to) {
+            Some(other) if state.netlist.block(other).fixed().is_some() => None,
+            Some(other) => Some(Move::Swap(block, other)),
+            None => Some(Move::Relocate(block, to)),
+        }
+    }
+
+    /// Try a single move, returning whether it was accepted.
+    fn try_move(
+        &mut self,
+        device: &Database,
+        state: &mut AnnealState,
+        movable: &[BlockId],
+        temperature: Option<f64>,
+    ) -> bool {
+        let Some(proposed) = self.propose(device, state, movable) else {
+            return false;
+        };
+        let (block, undo) = match proposed {
+            Move::Swap(block, other) => {
+                state.placement.swap(block, other);
+                (block, Move::Swap(block, other))
+            }
+            Move::Relocate(block, to) => {
+                let from = state.placement.bel(block).expect("block should be placed");
+                state.placement.place(block, to);
+                (block, Move::Relocate(block, from))
+            }
+        };
+        let mut affected: Vec<NetId> = state.netlist.block_nets(block).to_vec();
+        if let Move::Swap(_, other) = undo {
+            let seen: FnvHashSet<_> = affected.iter().copied().collect();
+            affected.extend(
+                state
+                    .netlist
+                    .block_nets(other)
+                    .iter()
+                    .filter(|net| !seen.contains(net)),
+            );
+        }
+        let new_costs: Vec<_> = affected
+            .iter()
+            .map(|net| net_cost(device, &state.placement, state.netlist.net(*net)))
+            .collect();
+        let delta: f64 = affected
+            .iter()
+            .zip(&new_costs)
+            .map(|(net, cost)| cost - state.net_costs[net.index()])
+            .sum();
+        let accept = delta <= 0.0
+            || match temperature {
+                None => true,
+                Some(temperature) if temperature > 0.0 => {
+                    self.rng.gen::<f64>() < (-delta / temperature).exp()
+                }
+                Some(_) => false,
+            };
+        if accept {
+            for (net, cost) in affected.iter().zip(new_costs) {
+                state.net_costs[net.index()] = cost;
+            }
+            state.cost += delta;
+        } else {
+            match undo {
+                Move::Swap(block, other) => state.placement.swap(block, other),
+                Move::Relocate(block, from) => {
+                    state.placement.place(block, from);
+                }
+            }
+        }
+        accept
+    }
+
+    fn estimate_initial_temperature(&mut self, device: &Database, mut state: AnnealState) -> f64 {
+        let movable = state.movable_blocks(device);
+        if movable.is_empty() {
+            return 0.0;
+        }
+        let costs: Vec<_> = (0..state.netlist.n_blocks())
+            .map(|_| {
+                self.try_move(device, &mut state, &movable, None);
+                state.cost
+            })
+            .collect();
+        let n = costs.len() as f64;
+        let mean = costs.iter().sum::<f64>() / n;
+        let variance = costs.iter().map(|cost| (cost - mean).powi(2)).sum::<f64>() / n;
+        20.0 * variance.sqrt()
+    }
+}
+
+impl Placer<AnnealState> for AnnealingPlacer {
+    type Ctx = Database;
+    type Err = Error;
+
+    fn setup(&mut self, device: &Self::Ctx, state: AnnealState) -> Result<()> {
+        if !state.placement.is_legal(device, &state.netlist) {
+            return Err(Error::IllegalPlacement);
+        }
+        self.rng = StdRng::seed_from_u64(self.config.seed);
+        self.initial_temperature = match self.config.initial_temperature {
+            Some(temperature) => temperature,
+            None => self.estimate_initial_temperature(device, state),
+        };
+        Ok(())
+    }
+
+    fn step(&mut self, device: &Self::Ctx, mut state: AnnealState) -> Result<AnnealState> {
+        if state.done {
+            return Ok(state);
+        }
+        let temperature = *state.temperature.get_or_insert(self.initial_temperature);
+        let quench = state.should_exit(&self.config, temperature);
+        let temperature = if quench { 0.0 } else { temperature };
+        let movable = state.movable_blocks(device);
+        let n_moves = if movable.is_empty() {
+            0
+        } else {
+            let n_blocks = state.netlist.n_blocks() as f64;
+            (self.config.inner_num * n_blocks.powf(4.0 / 3.0))
+                .ceil()
+                .max(1.0) as usize
+        };
+        let accepted = (0..n_moves)
+            .filter(|_| self.try_move(device, &mut state, &movable, Some(temperature)))
+            .count();
+        // NOTE: Recompute the total to keep rounding errors from accumulating.
+        state.cost = state.net_costs.iter().sum();
+        let stats = StepStats {
+            temperature,
+            moves: n_moves,
+            accepted,
+            cost: state.cost,
+            range_limit: state.range_limit,
+        };
+        let acceptance_rate = stats.acceptance_rate();
+        state.history.push(stats);
+        state.range_limit =
+            (state.range_limit * (1.0 - 0.44 + acceptance_rate)).clamp(1.0, state.max_range_limit);
+        state.temperature = Some(
+            self.config
+                .schedule
+                .next_temperature(temperature, acceptance_rate),
+        );
+        state.done = quench;
+        Ok(state)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::device::resources::{Bel, BelBucket, Location};
+    use crate::place::netlist::BlockPin;
+
+    fn grid_device(width: u32, height: u32) -> (Database, BelBucketId) {
+        let mut device = Database::new();
+        let bucket = device.add_bucket(BelBucket::new("LUT", ["$lut"])).unwrap();
+        for x in 0..width {
+            for y in 0..height {
+                let name = format!("x{x}y{y}");
+                device
+                    .add_bel(Bel::new(&name, Location::new(x, y), bucket))
+                    .unwrap();
+            }
+        }
+        (device, bucket)
+    }
+
+    fn chain_netlist(bucket: BelBucketId, length: usize) -> Netlist {
+        let mut netlist = Netlist::new();
+        let blocks: Vec<_> = (0..length)
+            .map(|i| netlist.add_block(&format!("b{i}"), bucket))
+            .collect();
+        for (i, pair) in blocks.windows(2).enumerate() {
+            netlist.add_net(
+                &format!("n{i}"),
+                BlockPin::new(pair[0], "Y"),
+                [BlockPin::new(pair[1], "A")],
+            );
+        }
+        netlist
+    }
+
+    #[test]
+    fn test_initial_placement() {
+        let (device, bucket) = grid_device(2, 2);
+        let state = AnnealState::new(&device, chain_netlist(bucket, 4)).unwrap();
+        assert!(state.placement().is_complete());
+        assert!(state.placement().is_legal(&device, state.netlist()));
+        assert!(matches!(
+            AnnealState::new(&device, chain_netlist(bucket, 5)),
+            Err(Error::NoFreeBel(_))
+        ));
+    }
+
+    #[test]
+    fn test_anneal_reduces_wirelength() {
+        let (device, bucket) = grid_device(6, 6);
+        let mut netlist = chain_netlist(bucket, 20);
+        // Scatter the chain to give the annealer something to do.
+        let mut placement = Placement::new(netlist.n_blocks());
+        let bels = device.bucket(bucket).bels();
+        for (i, (block, _)) in netlist.blocks().enumerate() {
+            placement.place(block, bels[(i * 7) % bels.len()]);
+        }
+        let fixed = netlist.find_block("b0").unwrap();
+        netlist.fix_block(fixed, placement.bel(fixed).unwrap());
+        let mut state = AnnealState::with_placement(&device, netlist, placement);
+        let initial_cost = state.cost();
+        let mut placer = AnnealingPlacer::new(AnnealConfig::default()).unwrap();
+        placer.setup(&device, state.clone()).unwrap();
+        while !state.is_done() {
+            state = placer.step(&device, state).unwrap();
+        }
+        assert!(state.placement().is_legal(&device, state.netlist()));
+        assert!(state.cost() < initial_cost);
+        let temperatures: Vec<_> = state.history().iter().map(|s| s.temperature).collect();
+        assert!(temperatures.windows(2).all(|t| t[1] <= t[0]));
+        assert_eq!(*temperatures.last().unwrap(), 0.0);
+    }
+
+    #[test]
+    fn test_schedules() {
+        let (device, bucket) = grid_device(4, 4);
+        for schedule in [
+            Schedule::Geometric { alpha: 0.5 },
+            Schedule::Linear { delta: 2.0 },
+        ] {
+            let mut state = AnnealState::new(&device, chain_netlist(bucket, 8)).unwrap();
+            let mut placer = AnnealingPlacer::new(AnnealConfig {
+                schedule,
+                initial_temperature: Some(4.0),
+                ..AnnealConfig::default()
+            })
+            .unwrap();
+            placer.setup(&device, state.clone()).unwrap();
+            state = placer.step(&device, state).unwrap();
+            assert_eq!(state.history()[0].temperature, 4.0);
+            assert_eq!(state.temperature(), Some(2.0));
+        }
+    }
+
+    #[test]
+    fn test_invalid_schedule() {
+        let placer = |schedule| {
+            AnnealingPlacer::new(AnnealConfig {
+                schedule,
+                ..AnnealConfig::default()
+            })
+        };
+        assert!(matches!(
+            placer(Schedule::Geometric { alpha: 1.0 }),
+            Err(Error::InvalidCoolingFactor(_))
+        ));
+        assert!(matches!(
+            placer(Schedule::Geometric { alpha: f64::NAN }),
+            Err(Error::InvalidCoolingFactor(_))
+        ));
+        assert!(matches!(
+            placer(Schedule::Linear { delta: 0.0 }),
+            Err(Error::InvalidCoolingStep(_))
+        ));
+        assert!(placer(Schedule::Linear { delta: 0.5 }).is_ok());
+    }
+}
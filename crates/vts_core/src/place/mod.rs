@@ -1,3 +1,7 @@
+pub mod anneal;
+pub mod netlist;
+pub mod placement;
+
 /// A `state` of the placer.
 pub trait State {}
 
@@ -7,10 +11,10 @@ pub trait Placer<I: State> {
     type Err;
 
     /// Setup the placement algorithm.
-    fn setup<'cx>(&mut self, ctx: &'cx Self::Ctx, state: I) -> Result<(), Self::Err>;
+    fn setup(&mut self, ctx: &Self::Ctx, state: I) -> Result<(), Self::Err>;
 
     /// Advance the placement algorithm.
     ///
     /// Returns the next `state` of the placer.
-    fn step<'cx>(&mut self, ctx: &'cx Self::Ctx, state: I) -> Result<I, Self::Err>;
+    fn step(&mut self, ctx: &Self::Ctx, state: I) -> Result<I, Self::Err>;
 }
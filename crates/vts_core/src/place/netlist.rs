@@ -0,0 +1,251 @@
+use std::fmt;
+use std::slice;
+
+use serde::{Deserialize, Serialize};
+use ustr::{ustr, Ustr};
+
+use crate::design::database::{CellId, NetId as DesignNetId};
+use crate::device::database::{BelBucketId, BelId};
+
+#[derive(Clone, Copy, Debug, Deserialize, Eq, Hash, Ord, PartialEq, PartialOrd, Serialize)]
+pub struct BlockId(usize);
+
+impl BlockId {
+    pub(crate) fn new(index: usize) -> Self {
+        Self(index)
+    }
+
+    pub fn index(&self) -> usize {
+        self.0
+    }
+}
+
+impl fmt::Display for BlockId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+#[derive(Clone, Copy, Debug, Deserialize, Eq, Hash, Ord, PartialEq, PartialOrd, Serialize)]
+pub struct NetId(usize);
+
+impl NetId {
+    pub fn index(&self) -> usize {
+        self.0
+    }
+}
+
+/// A placeable instance of a packed netlist.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct Block {
+    name: Ustr,
+    bucket: BelBucketId,
+    fixed: Option<BelId>,
+    cells: Vec<CellId>,
+}
+
+impl Block {
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// The [BelBucket](crate::device::resources::BelBucket) of [Bel](crate::device::resources::Bel)'s
+    /// this block may be placed on.
+    pub fn bucket(&self) -> BelBucketId {
+        self.bucket
+    }
+
+    /// The [Bel](crate::device::resources::Bel) this block is locked to, if any.
+    pub fn fixed(&self) -> Option<BelId> {
+        self.fixed
+    }
+
+    /// The design [Cell](crate::design::database::Cell)'s packed into this block.
+    pub fn cells(&self) -> &[CellId] {
+        &self.cells
+    }
+}
+
+/// A pin on a [Block].
+#[derive(Clone, Copy, Debug, Deserialize, Eq, Hash, PartialEq, Serialize)]
+pub struct BlockPin {
+    pub block: BlockId,
+    pub pin: Ustr,
+}
+
+impl BlockPin {
+    pub fn new(block: BlockId, pin: &str) -> Self {
+        Self {
+            block,
+            pin: ustr(pin),
+        }
+    }
+}
+
+/// A connection from a driving [BlockPin] to one or more sink [BlockPin]'s.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct Net {
+    name: Ustr,
+    driver: BlockPin,
+    sinks: Vec<BlockPin>,
+    design_net: Option<DesignNetId>,
+}
+
+impl Net {
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// The net of the [design](crate::design::database::Database) this net
+    /// implements, if any.
+    pub fn design_net(&self) -> Option<DesignNetId> {
+        self.design_net
+    }
+
+    pub fn driver(&self) -> BlockPin {
+        self.driver
+    }
+
+    pub fn sinks(&self) -> &[BlockPin] {
+        &self.sinks
+    }
+
+    /// All the pins connected by the net, starting with the driver.
+    pub fn pins(&self) -> impl Iterator<Item = BlockPin> + '_ {
+        [self.driver].into_iter().chain(self.sinks.iter().copied())
+    }
+}
+
+/// A packed netlist of [Block]'s connected by [Net]'s.
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+pub struct Netlist {
+    blocks: Vec<Block>,
+    nets: Vec<Net>,
+    block_nets: Vec<Vec<NetId>>,
+}
+
+impl Netlist {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn add_block(&mut self, name: &str, bucket: BelBucketId) -> BlockId {
+        self.blocks.push(Block {
+            name: ustr(name),
+            bucket,
+            fixed: None,
+            cells: Vec::new(),
+        });
+        self.block_nets.push(Vec::new());
+        BlockId(self.blocks.len() - 1)
+    }
+
+    /// Lock `block` to `bel`.
+    pub fn fix_block(&mut self, block: BlockId, bel: BelId) {
+        self.blocks[block.0].fixed = Some(bel);
+    }
+
+    /// Pack `cell` into `block`.
+    pub fn add_cell(&mut self, block: BlockId, cell: CellId) {
+        self.blocks[block.0].cells.push(cell);
+    }
+
+    pub fn add_net<S>(&mut self, name: &str, driver: BlockPin, sinks: S) -> NetId
+    where
+        S: IntoIterator<Item = BlockPin>,
+    {
+        let id = NetId(self.nets.len());
+        let net = Net {
+            name: ustr(name),
+            driver,
+            sinks: sinks.into_iter().collect(),
+            design_net: None,
+        };
+        for pin in net.pins() {
+            let nets = &mut self.block_nets[pin.block.0];
+            if nets.last() != Some(&id) {
+                nets.push(id);
+            }
+        }
+        self.nets.push(net);
+        id
+    }
+
+    /// Bind `net` to the net of the [design](crate::design::database::Database)
+    /// it implements.
+    pub fn bind_net(&mut self, net: NetId, design_net: DesignNetId) {
+        self.nets[net.0].design_net = Some(design_net);
+    }
+
+    pub fn block(&self, block: BlockId) -> &Block {
+        &self.blocks[block.0]
+    }
+
+    pub fn net(&self, net: NetId) -> &Net {
+        &self.nets[net.0]
+    }
+
+    pub fn blocks(&self) -> Blocks<'_> {
+        Blocks {
+            iter: self.blocks.iter().enumerate(),
+        }
+    }
+
+    pub fn nets(&self) -> Nets<'_> {
+        Nets {
+            iter: self.nets.iter().enumerate(),
+        }
+    }
+
+    pub fn n_blocks(&self) -> usize {
+        self.blocks.len()
+    }
+
+    pub fn n_nets(&self) -> usize {
+        self.nets.len()
+    }
+
+    /// The [Net]'s connected to `block`.
+    pub fn block_nets(&self, block: BlockId) -> &[NetId] {
+        &self.block_nets[block.0]
+    }
+
+    pub fn find_block(&self, name: &str) -> Option<BlockId> {
+        self.blocks
+            .iter()
+            .position(|block| block.name == name)
+            .map(BlockId)
+    }
+}
+
+pub struct Blocks<'a> {
+    iter: std::iter::Enumerate<slice::Iter<'a, Block>>,
+}
+
+impl<'a> Iterator for Blocks<'a> {
+    type Item = (BlockId, &'a Block);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.iter.next().map(|(i, block)| (BlockId(i), block))
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.iter.size_hint()
+    }
+}
+
+pub struct Nets<'a> {
+    iter: std::iter::Enumerate<slice::Iter<'a, Net>>,
+}
+
+impl<'a> Iterator for Nets<'a> {
+    type Item = (NetId, &'a Net);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.iter.next().map(|(i, net)| (NetId(i), net))
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.iter.size_hint()
+    }
+}
@@ -0,0 +1,104 @@
+use fnv::FnvHashMap;
+use serde::{Deserialize, Serialize};
+
+use super::netlist::{BlockId, Netlist};
+use crate::device::database::{BelId, Database};
+
+/// An assignment of [Block](super::netlist::Block)'s to [Bel](crate::device::resources::Bel)'s.
+// NOTE: Only the bel of every block is serialized, the blocks on every bel are
+// rebuilt from it.
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+#[serde(from = "Vec<Option<BelId>>", into = "Vec<Option<BelId>>")]
+pub struct Placement {
+    bels: Vec<Option<BelId>>,
+    blocks: FnvHashMap<BelId, BlockId>,
+}
+
+impl Placement {
+    /// Create an empty placement for `n_blocks` blocks.
+    pub fn new(n_blocks: usize) -> Self {
+        Self {
+            bels: vec![None; n_blocks],
+            blocks: FnvHashMap::default(),
+        }
+    }
+
+    /// The [Bel](crate::device::resources::Bel) `block` is placed on.
+    pub fn bel(&self, block: BlockId) -> Option<BelId> {
+        self.bels[block.index()]
+    }
+
+    /// The block placed on `bel`.
+    pub fn block(&self, bel: BelId) -> Option<BlockId> {
+        self.blocks.get(&bel).copied()
+    }
+
+    /// Place `block` on `bel`, replacing any previous location of `block`.
+    ///
+    /// Returns the block previously placed on `bel`, which is left unplaced.
+    pub fn place(&mut self, block: BlockId, bel: BelId) -> Option<BlockId> {
+        self.unplace(block);
+        let previous = self.blocks.insert(bel, block);
+        if let Some(previous) = previous {
+            self.bels[previous.index()] = None;
+        }
+        self.bels[block.index()] = Some(bel);
+        previous
+    }
+
+    /// Remove `block` from its [Bel](crate::device::resources::Bel).
+    pub fn unplace(&mut self, block: BlockId) -> Option<BelId> {
+        let bel = self.bels[block.index()].take();
+        if let Some(bel) = bel {
+            self.blocks.remove(&bel);
+        }
+        bel
+    }
+
+    /// Exchange the locations of two placed blocks.
+    pub fn swap(&mut self, a: BlockId, b: BlockId) {
+        let bel_a = self.bels[a.index()].expect("block should be placed");
+        let bel_b = self.bels[b.index()].expect("block should be placed");
+        self.bels.swap(a.index(), b.index());
+        self.blocks.insert(bel_a, b);
+        self.blocks.insert(bel_b, a);
+    }
+
+    pub fn is_complete(&self) -> bool {
+        self.bels.iter().all(Option::is_some)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (BlockId, BelId)> + '_ {
+        self.bels
+            .iter()
+            .enumerate()
+            .filter_map(|(block, bel)| bel.map(|bel| (BlockId::new(block), bel)))
+    }
+
+    /// Check that every block is placed on a [Bel](crate::device::resources::Bel) of its bucket.
+    pub fn is_legal(&self, device: &Database, netlist: &Netlist) -> bool {
+        netlist.blocks().all(|(id, block)| {
+            self.bel(id).is_some_and(|bel| {
+                device.bel(bel).bucket() == block.bucket()
+                    && block.fixed().is_none_or(|fixed| fixed == bel)
+            })
+        })
+    }
+}
+
+impl From<Vec<Option<BelId>>> for Placement {
+    fn from(bels: Vec<Option<BelId>>) -> Self {
+        let blocks = bels
+            .iter()
+            .enumerate()
+            .filter_map(|(index, bel)| Some(((*bel)?, BlockId::new(index))))
+            .collect();
+        Self { bels, blocks }
+    }
+}
+
+impl From<Placement> for Vec<Option<BelId>> {
+    fn from(placement: Placement) -> Self {
+        placement.bels
+    }
+}
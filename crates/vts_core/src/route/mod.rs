@@ -1,3 +1,5 @@
+pub mod pathfinder;
+
 /// A `state` of the router.
 pub trait State {}
 
@@ -7,10 +9,10 @@ pub trait Router<I: State> {
     type Err;
 
     /// Setup the routing algorithm.
-    fn setup<'cx>(&mut self, ctx: &'cx Self::Ctx, state: I) -> Result<(), Self::Err>;
+    fn setup(&mut self, ctx: &Self::Ctx, state: I) -> Result<(), Self::Err>;
 
     /// Advance the routing algorithm.
     ///
     /// Returns the next `state` of the router.
-    fn step<'cx>(&mut self, ctx: &'cx Self::Ctx, state: I) -> Result<I, Self::Err>;
+    fn step(&mut self, ctx: &Self::Ctx, state: I) -> Result<I, Self::Err>;
 }
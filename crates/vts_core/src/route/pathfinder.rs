@@ -0,0 +1,579 @@
+//! PathFinder negotiated-congestion routing.
+//!
+//! As in VPR, the cost of reaching a wire blends its congestion cost with the
+//! delay of the pip driving it, weighted by the criticality of the net.
+//!
+//! References:
+//! - L. McMurchie and C. Ebeling, "PathFinder: A negotiation-based performance-driven router for
+//!   FPGAs", FPGA 1995.
+//! - https://github.com/verilog-to-routing/vtr-verilog-to-routing/blob/8d4a9b5/vpr/src/route/route_common.cpp
+
+use std::cmp::{Ordering, Reverse};
+use std::collections::BinaryHeap;
+
+use fnv::FnvHashSet;
+use serde::{Deserialize, Serialize};
+use slotmap::SecondaryMap;
+use thiserror::Error;
+use ustr::{ustr, Ustr};
+
+use super::{Router, State};
+use crate::device::database::{Database, PipId, WireId};
+
+#[derive(Debug, Error)]
+pub enum Error {
+    #[error(r#"net "{0}" references a wire that is not in the device"#)]
+    UndefinedWire(String),
+    #[error(r#"no path from the source of net "{net}" to sink wire "{sink}""#)]
+    Unroutable { net: String, sink: String },
+    /// The routing did not converge, `state` holds the partial routing of the
+    /// last iteration.
+    #[error("{overused} wires are still overused after {iterations} iterations")]
+    Congested {
+        iterations: usize,
+        overused: usize,
+        state: Box<PathFinderState>,
+    },
+}
+
+pub type Result<T> = std::result::Result<T, Error>;
+
+#[derive(Clone, Debug)]
+pub struct PathFinderConfig {
+    /// The present congestion factor of the first iteration.
+    pub initial_present_factor: f64,
+    /// The present congestion factor is multiplied by this after every iteration.
+    pub present_factor_mult: f64,
+    /// Scales the overuse added to the historical congestion cost after every iteration.
+    pub history_factor: f64,
+    /// The base cost of using a wire.
+    pub base_cost: f64,
+    /// The cost of a picosecond of pip delay, relative to the base cost.
+    pub delay_cost: f64,
+    /// Give up if the routing has not converged after this many iterations.
+    pub max_iterations: usize,
+}
+
+impl Default for PathFinderConfig {
+    fn default() -> Self {
+        Self {
+            initial_present_factor: 0.5,
+            present_factor_mult: 1.3,
+            history_factor: 1.0,
+            base_cost: 1.0,
+            delay_cost: 0.01,
+            max_iterations: 50,
+        }
+    }
+}
+
+/// A net to be routed from a source [Wire](crate::device::resources::Wire)
+/// to one or more sink wires.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct RouteNet {
+    name: Ustr,
+    source: WireId,
+    sinks: Vec<WireId>,
+    criticality: f64,
+}
+
+impl RouteNet {
+    pub fn new<S>(name: &str, source: WireId, sinks: S) -> Self
+    where
+        S: IntoIterator<Item = WireId>,
+    {
+        Self {
+            name: ustr(name),
+            source,
+            sinks: sinks.into_iter().collect(),
+            criticality: 0.0,
+        }
+    }
+
+    /// Set the timing criticality of the net, from `0` to route for congestion
+    /// only, to `1` to route for delay only.
+    ///
+    /// Values outside `[0, 1]` are clamped, and NaN is treated as `0`.
+    pub fn with_criticality(mut self, criticality: f64) -> Self {
+        self.criticality = if criticality.is_nan() {
+            0.0
+        } else {
+            criticality.clamp(0.0, 1.0)
+        };
+        self
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn source(&self) -> WireId {
+        self.source
+    }
+
+    pub fn sinks(&self) -> &[WireId] {
+        &self.sinks
+    }
+
+    pub fn criticality(&self) -> f64 {
+        self.criticality
+    }
+}
+
+/// The routing tree of a single [RouteNet].
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+pub struct Route {
+    // NOTE: Every wire is stored with the pip driving it, `None` for the source.
+    nodes: Vec<(WireId, Option<PipId>)>,
+}
+
+impl Route {
+    /// The wires used by the route, starting with the source.
+    pub fn wires(&self) -> impl Iterator<Item = WireId> + '_ {
+        self.nodes.iter().map(|(wire, _)| *wire)
+    }
+
+    /// The pips used by the route.
+    pub fn pips(&self) -> impl Iterator<Item = PipId> + '_ {
+        self.nodes.iter().filter_map(|(_, pip)| *pip)
+    }
+
+    /// The pip driving `wire`, if `wire` is part of the route.
+    pub fn driver(&self, wire: WireId) -> Option<Option<PipId>> {
+        self.nodes
+            .iter()
+            .find(|(other, _)| *other == wire)
+            .map(|(_, pip)| *pip)
+    }
+
+    /// The sum of the pip delays from the source to `wire`, if `wire` is part
+    /// of the route.
+    pub fn delay(&self, device: &Database, mut wire: WireId) -> Option<f64> {
+        let mut delay = 0.0;
+        while let Some(pip) = self.driver(wire)? {
+            delay += device.pip(pip).delay();
+            wire = device.pip(pip).source();
+        }
+        Some(delay)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.nodes.is_empty()
+    }
+}
+
+/// Statistics of a single routing iteration.
+#[derive(Clone, Copy, Debug, Deserialize, PartialEq, Serialize)]
+pub struct IterationStats {
+    pub iteration: usize,
+    pub rerouted_nets: usize,
+    pub overused_wires: usize,
+    pub total_overuse: usize,
+    /// The total number of pips used by all routes.
+    pub wirelength: usize,
+}
+
+/// The `state` of a [PathFinderRouter].
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct PathFinderState {
+    nets: Vec<RouteNet>,
+    routes: Vec<Route>,
+    occupancy: SecondaryMap<WireId, usize>,
+    history_cost: SecondaryMap<WireId, f64>,
+    present_factor: Option<f64>,
+    history: Vec<IterationStats>,
+    done: bool,
+}
+
+impl State for PathFinderState {}
+
+impl PathFinderState {
+    pub fn new<N>(nets: N) -> Self
+    where
+        N: IntoIterator<Item = RouteNet>,
+    {
+        let nets: Vec<_> = nets.into_iter().collect();
+        let routes = vec![Route::default(); nets.len()];
+        Self {
+            nets,
+            routes,
+            occupancy: SecondaryMap::new(),
+            history_cost: SecondaryMap::new(),
+            present_factor: None,
+            history: Vec::new(),
+            done: false,
+        }
+    }
+
+    pub fn nets(&self) -> &[RouteNet] {
+        &self.nets
+    }
+
+    /// The route of the `index`-th net.
+    pub fn route(&self, index: usize) -> &Route {
+        &self.routes[index]
+    }
+
+    /// Every net together with its route.
+    pub fn routes(&self) -> impl Iterator<Item = (&RouteNet, &Route)> + '_ {
+        self.nets.iter().zip(&self.routes)
+    }
+
+    /// The number of nets currently using `wire`.
+    pub fn occupancy(&self, wire: WireId) -> usize {
+        self.occupancy.get(wire).copied().unwrap_or_default()
+    }
+
+    /// The wires used by more than one net.
+    pub fn overused_wires(&self) -> impl Iterator<Item = WireId> + '_ {
+        self.occupancy
+            .iter()
+            .filter(|(_, occupancy)| **occupancy > 1)
+            .map(|(wire, _)| wire)
+    }
+
+    /// The sum of the overuse of every wire.
+    pub fn total_overuse(&self) -> usize {
+        self.occupancy
+            .values()
+            .map(|occupancy| occupancy.saturating_sub(1))
+            .sum()
+    }
+
+    /// Statistics of every iteration taken so far.
+    pub fn history(&self) -> &[IterationStats] {
+        &self.history
+    }
+
+    /// Whether every net is routed without overuse.
+    pub fn is_done(&self) -> bool {
+        self.done
+    }
+
+    fn rip_up(&mut self, index: usize) {
+        for wire in self.routes[index].wires() {
+            if let Some(occupancy) = self.occupancy.get_mut(wire) {
+                *occupancy -= 1;
+            }
+        }
+        self.routes[index] = Route::default();
+    }
+
+    fn commit(&mut self, index: usize, route: Route) {
+        for wire in route.wires() {
+            *self.occupancy.entry(wire).unwrap().or_default() += 1;
+        }
+        self.routes[index] = route;
+    }
+
+    fn is_congested(&self, index: usize) -> bool {
+        self.routes[index]
+            .wires()
+            .any(|wire| self.occupancy(wire) > 1)
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+struct Cost(f64);
+
+impl Eq for Cost {}
+
+impl PartialOrd for Cost {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Cost {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.0.total_cmp(&other.0)
+    }
+}
+
+/// A PathFinder router negotiating congestion over the wire and pip graph of
+/// a [Database].
+///
+/// Every call to [Router::step] is a single routing iteration. The first
+/// iteration routes every net, later iterations rip up and reroute the nets
+/// using overused wires.
+pub struct PathFinderRouter {
+    config: PathFinderConfig,
+}
+
+impl PathFinderRouter {
+    pub fn new(config: PathFinderConfig) -> Self {
+        Self { config }
+    }
+
+    pub fn config(&self) -> &PathFinderConfig {
+        &self.config
+    }
+
+    fn wire_cost(&self, state: &PathFinderState, wire: WireId, present_factor: f64) -> f64 {
+        let history_cost = state.history_cost.get(wire).copied().unwrap_or_default();
+        // NOTE: Every wire has a capacity of one, so any existing user of the wire
+        // results in overuse.
+        let present_cost = 1.0 + present_factor * state.occupancy(wire) as f64;
+        (self.config.base_cost + history_cost) * present_cost
+    }
+
+    fn route_net(
+        &self,
+        device: &Database,
+        state: &PathFinderState,
+        net: &RouteNet,
+        present_factor: f64,
+    ) -> Result<Route> {
+        let mut route = Route {
+            nodes: vec![(net.source, None)],
+        };
+        let mut in_tree = FnvHashSet::from_iter([net.source]);
+        for &sink in &net.sinks {
+            if in_tree.contains(&sink) {
+                continue;
+            }
+            let mut best = SecondaryMap::<WireId, (f64, Option<PipId>)>::new();
+            let mut heap = BinaryHeap::new();
+            for wire in route.wires() {
+                best.insert(wire, (0.0, None));
+                heap.push(Reverse((Cost(0.0), wire)));
+            }
+            let mut found = false;
+            while let Some(Reverse((Cost(cost), wire))) = heap.pop() {
+                if wire == sink {
+                    found = true;
+                    break;
+                }
+                if cost > best[wire].0 {
+                    continue;
+                }
+                for &pip in device.wire(wire).downhill() {
+                    let next = device.pip(pip).sink();
+                    let congestion_cost = self.wire_cost(state, next, present_factor);
+                    let delay_cost = self.config.delay_cost * device.pip(pip).delay();
+                    let next_cost = cost
+                        + (1.0 - net.criticality) * congestion_cost
+                        + net.criticality * delay_cost;
+                    if best.get(next).is_none_or(|(other, _)| next_cost < *other) {
+                        best.insert(next, (next_cost, Some(pip)));
+                        heap.push(Reverse((Cost(next_cost), next)));
+                    }
+                }
+            }
+            if !found {
+                return Err(Error::Unroutable {
+                    net: net.name.to_string(),
+                    sink: device.wire(sink).name().to_string(),
+                });
+            }
+            let mut path = Vec::new();
+            let mut wire = sink;
+            while !in_tree.contains(&wire) {
+                let pip = best[wire].1.expect("wire should be reached through a pip");
+                path.push((wire, Some(pip)));
+                wire = device.pip(pip).source();
+            }
+            for (wire, pip) in path.into_iter().rev() {
+                in_tree.insert(wire);
+                route.nodes.push((wire, pip));
+            }
+        }
+        Ok(route)
+    }
+}
+
+impl Router<PathFinderState> for PathFinderRouter {
+    type Ctx = Database;
+    type Err = Error;
+
+    fn setup(&mut self, device: &Self::Ctx, state: PathFinderState) -> Result<()> {
+        for net in &state.nets {
+            let mut wires = [net.source].into_iter().chain(net.sinks.iter().copied());
+            if !wires.all(|wire| device.contains_wire(wire)) {
+                return Err(Error::UndefinedWire(net.name.to_string()));
+            }
+        }
+        Ok(())
+    }
+
+    fn step(&mut self, device: &Self::Ctx, mut state: PathFinderState) -> Result<PathFinderState> {
+        if state.done {
+            return Ok(state);
+        }
+        let present_factor = *state
+            .present_factor
+            .get_or_insert(self.config.initial_present_factor);
+        let first = state.history.is_empty();
+        let reroute: Vec<_> = (0..state.nets.len())
+            .filter(|&index| first || state.is_congested(index))
+            .collect();
+        for &index in &reroute {
+            state.rip_up(index);
+            let route = self.route_net(device, &state, &state.nets[index], present_factor)?;
+            state.commit(index, route);
+        }
+        let overused: Vec<_> = state.overused_wires().collect();
+        for &wire in &overused {
+            let overuse = state.occupancy(wire) - 1;
+            *state.history_cost.entry(wire).unwrap().or_default() +=
+                self.config.history_factor * overuse as f64;
+        }
+        let stats = IterationStats {
+            iteration: state.history.len(),
+            rerouted_nets: reroute.len(),
+            overused_wires: overused.len(),
+            total_overuse: state.total_overuse(),
+            wirelength: state.routes.iter().map(|route| route.pips().count()).sum(),
+        };
+        state.history.push(stats);
+        state.present_factor = Some(present_factor * self.config.present_factor_mult);
+        state.done = stats.overused_wires == 0;
+        if !state.done && state.history.len() >= self.config.max_iterations {
+            return Err(Error::Congested {
+                iterations: state.history.len(),
+                overused: stats.overused_wires,
+                state: Box::new(state),
+            });
+        }
+        Ok(state)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::device::resources::{Pip, Wire};
+
+    fn connect(device: &mut Database, source: WireId, sink: WireId) -> PipId {
+        let name = format!(
+            "{}->{}",
+            device.wire(source).name(),
+            device.wire(sink).name()
+        );
+        device.add_pip(Pip::new(&name, source, sink)).unwrap()
+    }
+
+    /// Two nets that both prefer the shared wire `m`, with a longer detour each.
+    fn contested_device() -> (Database, Vec<RouteNet>) {
+        let mut device = Database::new();
+        let [s0, s1, t0, t1, m, a0, a1, b0, b1] =
+            ["s0", "s1", "t0", "t1", "m", "a0", "a1", "b0", "b1"]
+                .map(|name| device.add_wire(Wire::new(name)).unwrap());
+        for (source, sink) in [
+            (s0, m),
+            (s1, m),
+            (m, t0),
+            (m, t1),
+            (s0, a0),
+            (a0, a1),
+            (a1, t0),
+            (s1, b0),
+            (b0, b1),
+            (b1, t1),
+        ] {
+            connect(&mut device, source, sink);
+        }
+        let nets = vec![RouteNet::new("n0", s0, [t0]), RouteNet::new("n1", s1, [t1])];
+        (device, nets)
+    }
+
+    #[test]
+    fn test_route_tree() {
+        let mut device = Database::new();
+        let [s, m, t0, t1] =
+            ["s", "m", "t0", "t1"].map(|name| device.add_wire(Wire::new(name)).unwrap());
+        let pips =
+            [(s, m), (m, t0), (m, t1)].map(|(source, sink)| connect(&mut device, source, sink));
+        let mut router = PathFinderRouter::new(PathFinderConfig::default());
+        let state = PathFinderState::new([RouteNet::new("n", s, [t0, t1])]);
+        router.setup(&device, state.clone()).unwrap();
+        let state = router.step(&device, state).unwrap();
+        assert!(state.is_done());
+        let route = state.route(0);
+        assert_eq!(route.wires().collect::<Vec<_>>(), [s, m, t0, t1]);
+        assert_eq!(route.pips().collect::<Vec<_>>(), pips);
+        assert_eq!(route.driver(t1), Some(Some(pips[2])));
+        assert_eq!(state.history()[0].wirelength, 3);
+    }
+
+    #[test]
+    fn test_criticality() {
+        // NOTE: `s -> t` is a single slow pip, `s -> a -> b -> t` three fast ones.
+        let mut device = Database::new();
+        let [s, a, b, t] =
+            ["s", "a", "b", "t"].map(|name| device.add_wire(Wire::new(name)).unwrap());
+        device
+            .add_pip(Pip::new("s->t", s, t).with_delay(1000.0))
+            .unwrap();
+        for (source, sink) in [(s, a), (a, b), (b, t)] {
+            let name = format!(
+                "{}->{}",
+                device.wire(source).name(),
+                device.wire(sink).name()
+            );
+            device
+                .add_pip(Pip::new(&name, source, sink).with_delay(10.0))
+                .unwrap();
+        }
+        let mut router = PathFinderRouter::new(PathFinderConfig::default());
+        for (criticality, pips, delay) in [(0.0, 1, 1000.0), (0.9, 3, 30.0)] {
+            let net = RouteNet::new("n", s, [t]).with_criticality(criticality);
+            let state = router.step(&device, PathFinderState::new([net])).unwrap();
+            assert_eq!(state.route(0).pips().count(), pips);
+            assert_eq!(state.route(0).delay(&device, t), Some(delay));
+        }
+        for (criticality, clamped) in [(-1.0, 0.0), (2.0, 1.0), (f64::NAN, 0.0)] {
+            let net = RouteNet::new("n", s, [t]).with_criticality(criticality);
+            assert_eq!(net.criticality(), clamped);
+        }
+    }
+
+    #[test]
+    fn test_negotiate_congestion() {
+        let (device, nets) = contested_device();
+        let mut router = PathFinderRouter::new(PathFinderConfig::default());
+        let mut state = PathFinderState::new(nets);
+        router.setup(&device, state.clone()).unwrap();
+        state = router.step(&device, state).unwrap();
+        assert!(!state.is_done());
+        assert_eq!(state.history()[0].total_overuse, 1);
+        while !state.is_done() {
+            state = router.step(&device, state).unwrap();
+        }
+        assert_eq!(state.total_overuse(), 0);
+        assert!(state.history().last().unwrap().rerouted_nets > 0);
+        let used: Vec<_> = state
+            .routes()
+            .flat_map(|(_, route)| route.wires())
+            .collect();
+        let unique: FnvHashSet<_> = used.iter().collect();
+        assert_eq!(used.len(), unique.len());
+    }
+
+    #[test]
+    fn test_errors() {
+        let (device, nets) = contested_device();
+        let mut router = PathFinderRouter::new(PathFinderConfig {
+            max_iterations: 1,
+            ..PathFinderConfig::default()
+        });
+        let state = PathFinderState::new(nets.clone());
+        let Err(Error::Congested {
+            iterations: 1,
+            overused: 1,
+            state,
+        }) = router.step(&device, state)
+        else {
+            panic!("routing should not converge in one iteration");
+        };
+        assert_eq!(state.total_overuse(), 1);
+        assert!(state.routes().all(|(_, route)| !route.is_empty()));
+        let mut router = PathFinderRouter::new(PathFinderConfig::default());
+        let state =
+            PathFinderState::new([RouteNet::new("n", nets[0].sinks()[0], [nets[0].source()])]);
+        assert!(matches!(
+            router.step(&device, state),
+            Err(Error::Unroutable { .. })
+        ));
+    }
+}
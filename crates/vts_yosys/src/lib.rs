@@ -156,8 +156,10 @@ macro_rules! impl_backend {
 
 #[derive(Clone, Copy, Debug, Eq, Hash, Ord, PartialEq, PartialOrd)]
 pub enum FileFormat {
+    Aiger,
     Blif,
     Json,
+    Rtlil,
     SV,
     Verilog,
 }
@@ -168,8 +170,10 @@ impl FileFormat {
             .extension()
             .ok_or(Error::UnknownFormat(filename.to_path_buf()))?;
         Ok(match extension.to_string_lossy().as_ref() {
+            "aag" | "aig" => Self::Aiger,
             "blif" => Self::Blif,
             "json" => Self::Json,
+            "il" | "rtlil" => Self::Rtlil,
             "sv" => Self::SV,
             "v" => Self::Verilog,
             _ => {
@@ -198,9 +202,12 @@ impl Command {
     impl_command!(input read_verilog);
     impl_command!(input read_sv);
     impl_command!(input read_blif);
+    impl_command!(input read_rtlil);
+    impl_command!(input read_aiger);
 
     impl_command!(output write_blif);
     impl_command!(output write_json);
+    impl_command!(output write_rtlil);
 
     impl_command!(flatten);
     impl_command!(opt);
@@ -208,15 +215,22 @@ impl Command {
     impl_command!(proc);
     impl_command!("design -reset" as reset_design);
     impl_command!(simplemap);
+    impl_command!(techmap);
 
     pub fn execute(&mut self, yosys: &Yosys) -> Result<()> {
         let input_filename = self.input_filename.as_ref().ok_or(Error::MissingInput)?;
         let input_format = FileFormat::guess(input_filename)?;
         // TODO(rikus): Check and report yosys errors.
         match input_format {
+            FileFormat::Aiger => {
+                impl_frontend!(yosys => input_filename : "aiger");
+            }
             FileFormat::Blif => {
                 impl_frontend!(yosys => input_filename : "blif");
             }
+            FileFormat::Rtlil => {
+                impl_frontend!(yosys => input_filename : "rtlil");
+            }
             FileFormat::SV => {
                 impl_frontend!(yosys => input_filename : "verilog -sv");
             }
@@ -239,6 +253,9 @@ impl Command {
                 FileFormat::Json => {
                     impl_backend!(yosys => output_filename : "json");
                 }
+                FileFormat::Rtlil => {
+                    impl_backend!(yosys => output_filename : "rtlil");
+                }
                 _ => {
                     return Err(Error::UnsupportedOutput(output_filename.to_path_buf()));
                 }